@@ -0,0 +1,19 @@
+//! Builds the images used by the test suite of dockertest, see `dockertest::selftest_images`.
+
+use dockertest::selftest_images;
+
+#[tokio::main]
+async fn main() {
+    let dockerfiles = concat!(env!("CARGO_MANIFEST_DIR"), "/dockerfiles");
+    match selftest_images::build_from_env(dockerfiles).await {
+        Ok(images) => {
+            for image in images {
+                println!("built {}", image);
+            }
+        }
+        Err(e) => {
+            eprintln!("failed to build the test images: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
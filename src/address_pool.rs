@@ -0,0 +1,136 @@
+//! Fallback subnets for networks created while the address pools of the daemon are exhausted.
+
+use bollard::errors::Error;
+use rand::Rng;
+
+use std::net::Ipv4Addr;
+
+/// The number of fallback subnets tried before giving up.
+const MAX_ATTEMPTS: usize = 16;
+
+/// The message of the docker daemon when none of its address pools has a free subnet.
+const EXHAUSTED_MESSAGES: [&str; 2] = [
+    "could not find an available, non-overlapping IPv4 address pool",
+    "all predefined address pools have been fully subnetted",
+];
+
+/// An address range to allocate network subnets from, see
+/// [DockerTest::with_network_address_pool](crate::DockerTest::with_network_address_pool).
+#[derive(Clone, Debug)]
+pub(crate) struct AddressPool {
+    /// The range in CIDR notation, e.g., `10.210.0.0/16`.
+    base: String,
+    /// The prefix length of each allocated subnet.
+    size: u8,
+}
+
+impl AddressPool {
+    pub(crate) fn new(base: String, size: u8) -> AddressPool {
+        AddressPool { base, size }
+    }
+
+    /// The subnets to try in order, starting at a random subnet of the pool to avoid parallel
+    /// tests contending for the same subnets.
+    pub(crate) fn candidates(&self) -> Result<Vec<String>, String> {
+        let (address, prefix) = self
+            .base
+            .split_once('/')
+            .ok_or_else(|| format!("address pool `{}` is not in CIDR notation", self.base))?;
+        let address: Ipv4Addr = address
+            .parse()
+            .map_err(|_| format!("address pool `{}` has an invalid address", self.base))?;
+        let prefix: u8 = prefix
+            .parse()
+            .ok()
+            .filter(|p| *p <= 32)
+            .ok_or_else(|| format!("address pool `{}` has an invalid prefix", self.base))?;
+        if self.size < prefix || self.size > 30 {
+            return Err(format!(
+                "subnet size /{} must be within address pool `{}`, and at most /30",
+                self.size, self.base
+            ));
+        }
+
+        let network = u32::from(address) & mask(prefix);
+        let count = 1u64 << (self.size - prefix);
+        let start = rand::thread_rng().gen_range(0..count);
+        Ok((0..count.min(MAX_ATTEMPTS as u64))
+            .map(|i| {
+                let index = (start + i) % count;
+                let subnet = network | ((index as u32) << (32 - self.size));
+                format!("{}/{}", Ipv4Addr::from(subnet), self.size)
+            })
+            .collect())
+    }
+}
+
+fn mask(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0)
+}
+
+/// Whether network creation failed as the daemon has no free subnet left in its address pools.
+pub(crate) fn is_exhausted(e: &Error) -> bool {
+    let message = e.to_string();
+    EXHAUSTED_MESSAGES.iter().any(|m| message.contains(m))
+}
+
+/// Whether network creation failed as the requested subnet is already in use.
+pub(crate) fn is_overlapping(e: &Error) -> bool {
+    e.to_string().contains("overlaps")
+}
+
+/// Explain how to resolve address pool exhaustion.
+pub(crate) fn exhausted_explanation(pool_configured: bool) -> &'static str {
+    if pool_configured {
+        "the docker daemon has exhausted its address pools, and every subnet tried from the \
+         address pool of the test is in use. Remove unused networks with `docker network prune`, \
+         or provide a larger address pool"
+    } else {
+        "the docker daemon has exhausted its address pools. Remove unused networks with \
+         `docker network prune`, configure `default-address-pools` of the docker daemon, \
+         share networks between tests through `Network::Singular` or `Network::Shared`, or \
+         provide fallback subnets through `DockerTest::with_network_address_pool`"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AddressPool;
+
+    // Tests that subnets are allocated within the address pool
+    #[test]
+    fn test_candidates_within_pool() {
+        let pool = AddressPool::new("10.210.0.0/16".to_string(), 24);
+        let candidates = pool.candidates().unwrap();
+
+        assert_eq!(candidates.len(), 16);
+        for candidate in candidates {
+            assert!(candidate.starts_with("10.210."), "{}", candidate);
+            assert!(candidate.ends_with(".0/24"), "{}", candidate);
+        }
+    }
+
+    // Tests that a pool of a single subnet yields that subnet
+    #[test]
+    fn test_candidates_single_subnet() {
+        let pool = AddressPool::new("172.30.5.7/24".to_string(), 24);
+        assert_eq!(
+            pool.candidates().unwrap(),
+            vec!["172.30.5.0/24".to_string()]
+        );
+    }
+
+    // Tests that malformed address pools are rejected
+    #[test]
+    fn test_candidates_malformed_pool() {
+        assert!(AddressPool::new("10.210.0.0".to_string(), 24)
+            .candidates()
+            .is_err());
+        assert!(AddressPool::new("10.210.0.0/33".to_string(), 24)
+            .candidates()
+            .is_err());
+        assert!(AddressPool::new("10.210.0.0/16".to_string(), 8)
+            .candidates()
+            .is_err());
+    }
+}
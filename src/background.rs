@@ -0,0 +1,136 @@
+//! Teardown of test environments in the background, see [DockerTest::with_background_teardown].
+//!
+//! Teardowns are executed on a process-wide runtime, as the runtime of the test itself may be
+//! dropped as soon as the test returns. Other background tasks, such as refilling warm pools, are
+//! executed likewise. The process awaits the pending tasks as it exits, see [finish_suite].
+//!
+//! [DockerTest::with_background_teardown]: crate::DockerTest::with_background_teardown
+
+use crate::suite_summary;
+
+use futures::Future;
+use lazy_static::lazy_static;
+use tokio::runtime::{Builder, Runtime};
+use tracing::{event, Level};
+
+use std::io::Write;
+use std::os::raw::c_int;
+use std::sync::{Condvar, Mutex, Once};
+use std::time::Duration;
+
+/// How long the process awaits the pending background tasks as it exits.
+const EXIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    static ref RUNTIME: Runtime = Builder::new_multi_thread()
+        .worker_threads(2)
+        .thread_name("dockertest-teardown")
+        .enable_all()
+        .build()
+        .expect("failed to create the background teardown runtime");
+    /// The number of teardowns that have not yet completed.
+    static ref PENDING: (Mutex<usize>, Condvar) = (Mutex::new(0), Condvar::new());
+}
+
+static REGISTER_EXIT_HOOK: Once = Once::new();
+
+extern "C" {
+    /// Registers a function invoked as the process exits, provided by the C runtime the standard
+    /// library links against.
+    fn atexit(hook: extern "C" fn()) -> c_int;
+}
+
+/// Decrements the pending teardowns once dropped, also if the teardown panics.
+struct PendingGuard;
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        let (pending, completed) = &*PENDING;
+        let mut pending = pending.lock().unwrap_or_else(|e| e.into_inner());
+        *pending -= 1;
+        completed.notify_all();
+    }
+}
+
+/// Execute the teardown in the background, see [finish_suite].
+pub(crate) fn spawn_teardown<F>(teardown: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    spawn_background(teardown)
+}
+
+/// Execute the task in the background, see [finish_suite].
+pub(crate) fn spawn_background<F>(task: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    register_exit_hook();
+    *PENDING.0.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+    let guard = PendingGuard;
+    RUNTIME.spawn(async move {
+        let _guard = guard;
+        task.await;
+    });
+}
+
+/// Await the pending background tasks, and print the summary of the suite, through
+/// [finish_suite] as the process exits.
+pub(crate) fn register_exit_hook() {
+    REGISTER_EXIT_HOOK.call_once(|| {
+        // SAFETY: The hook does not unwind, and only accesses statics that are never dropped.
+        if unsafe { atexit(exit_hook) } != 0 {
+            event!(
+                Level::WARN,
+                "unable to await background teardowns, or print the suite summary, at process exit"
+            );
+        }
+    });
+}
+
+/// Awaits the pending background tasks, and prints the summary of the suite, as the process
+/// exits.
+///
+/// The hook only waits for the tasks to complete, which are driven by the worker threads of the
+/// background runtime, still running as the process exits.
+extern "C" fn exit_hook() {
+    // The hook may not unwind into the C runtime.
+    let _ = std::panic::catch_unwind(|| {
+        let remaining = finish_suite(EXIT_TIMEOUT);
+        if remaining > 0 {
+            let _ = writeln!(
+                std::io::stderr(),
+                "dockertest: gave up waiting for {} background teardowns at exit",
+                remaining
+            );
+        }
+    });
+}
+
+/// Await the teardowns and other tasks executed in the background by the tests of the process
+/// for at most `timeout`, and print the summary of the suite if any test opted in through
+/// [DockerTest::with_suite_summary], returning the number of tasks still pending.
+///
+/// The process invokes this as it exits, awaiting the tasks for at most a minute, and the summary
+/// is printed at most once. The
+/// containers of a teardown cut short are left behind, until removed by the janitor of
+/// [DockerTest::with_janitor], or found through [discover](crate::discover). A custom test
+/// harness, i.e., `harness = false`, may invoke this at the end of its `main` function to bound
+/// the wait otherwise. This blocks the calling thread.
+///
+/// [DockerTest::with_suite_summary]: crate::DockerTest::with_suite_summary
+/// [DockerTest::with_janitor]: crate::DockerTest::with_janitor
+pub fn finish_suite(timeout: Duration) -> usize {
+    let remaining = {
+        let (pending, completed) = &*PENDING;
+        let pending = pending.lock().unwrap_or_else(|e| e.into_inner());
+        let (pending, _) = completed
+            .wait_timeout_while(pending, timeout, |pending| *pending > 0)
+            .unwrap_or_else(|e| e.into_inner());
+        *pending
+    };
+
+    // Teardowns still pending may leak resources the summary does not account for.
+    suite_summary::print();
+    remaining
+}
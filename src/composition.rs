@@ -1,9 +1,22 @@
 //! Represent a concrete instance of an Image, before it is ran as a Container.
 
-use crate::container::{CreatedContainer, PendingContainer};
-use crate::image::Image;
-use crate::static_container::STATIC_CONTAINERS;
+use crate::container::{CreatedContainer, PendingContainer, RunningContainer};
+use crate::dockertest::{sanitize_name, Defaults};
+use crate::faketime;
+use crate::image::{self, Image};
+use crate::overrides::{self, Overrides};
+use crate::performance;
+use crate::reset::Resettable;
+use crate::retry::{daemon_error, is_conflict, is_not_found, RetryPolicy};
+use crate::secrets::{self, Redactions};
+use crate::shared_environment;
+use crate::smart_defaults;
+use crate::static_container::{add_to_network, STATIC_CONTAINERS};
+use crate::template;
+use crate::throttle;
+use crate::utils::stable_hash;
 use crate::waitfor::{NoWait, WaitFor};
+use crate::warm_pool;
 use crate::{DockerTestError, Network};
 
 use bollard::{
@@ -11,15 +24,29 @@ use bollard::{
         Config, CreateContainerOptions, InspectContainerOptions, NetworkingConfig,
         RemoveContainerOptions,
     },
-    models::HostConfig,
-    service::{EndpointSettings, PortBinding},
+    models::{HostConfig, HostConfigIsolationEnum, Mount, MountTypeEnum},
+    service::{EndpointIpamConfig, EndpointSettings, PortBinding},
     Docker,
 };
 
-use futures::future::TryFutureExt;
-use std::collections::HashMap;
+use futures::future::{BoxFuture, Future, FutureExt, TryFutureExt};
+use secrecy::{ExposeSecret, Secret};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::OsString;
+use std::sync::Arc;
 use tracing::{event, trace, Level};
 
+/// The longest container name that is resolvable on the network of the test, as the docker DNS
+/// server does not resolve names longer than a single DNS label.
+const MAX_CONTAINER_NAME_LENGTH: usize = 63;
+
+/// The host port that instructs the docker daemon to assign a random free host port.
+const EPHEMERAL_HOST_PORT: &str = "0";
+
+/// Replaced by the index of the replica within environment variable values, see
+/// [Composition::with_replicas].
+const REPLICA_PLACEHOLDER: &str = "{replica}";
+
 /// Specifies the starting policy of a container specification.
 ///
 /// - [StartPolicy::Strict] policy will enforce that the container is started in the order
@@ -28,14 +55,311 @@ use tracing::{event, trace, Level};
 ///     all container specifications with a relaxed policy will be started concurrently.
 ///     These are all started asynchrously started before the strict policy containers
 ///     are started sequentially.
+/// - [StartPolicy::Group] policy will start the containers of a group concurrently, once all
+///   containers of the previous group are ready. The groups are started in order after the
+///   strict policy containers, while the relaxed policy containers are still starting.
 ///
 /// [DockerTest]: crate::DockerTest
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum StartPolicy {
     /// Concurrently start the Container with other Relaxed instances.
     Relaxed,
     /// Start Containers' sequentially in the order added to DockerTest.
     Strict,
+    /// Concurrently start the Container with the other members of the named group, after all
+    /// groups of a lower order are ready, e.g., an infrastructure group before a middleware group
+    /// before an application group. Groups of the same order are started in the order of their
+    /// names.
+    Group(String, u32),
+}
+
+/// Specifies how to handle an existing container with the same name as the container to create,
+/// e.g., one left behind by an aborted test.
+///
+/// Only applies to containers whose lifecycle is managed by the test body.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExistingContainerPolicy {
+    /// Fail the test, leaving the existing container untouched.
+    Fail,
+    /// Forcefully remove the existing container, and create a new one.
+    #[default]
+    Remove,
+    /// Use the existing container as is, starting it if it is not running.
+    ///
+    /// The configuration of the container is not compared with that of the existing
+    /// container. Instead, an existing container created by an environment of another
+    /// definition, as told by the [environment hash], is replaced. An existing container not
+    /// created by dockertest, i.e., lacking the `dockertest.environment` label, is left untouched
+    /// and fails the test. The reused container is neither stopped nor removed when the test
+    /// terminates, such that later tests may reuse it.
+    ///
+    /// [environment hash]: crate::DockerTest::environment_hash
+    Reuse,
+}
+
+/// Specifies the isolation technology of a Windows container.
+///
+/// Only supported by docker daemons executing Windows containers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Isolation {
+    /// The default isolation of the docker daemon.
+    Default,
+    /// Share the kernel of the host, which requires the image to match the version of the host.
+    Process,
+    /// Run the container within a lightweight Hyper-V virtual machine.
+    HyperV,
+}
+
+impl From<Isolation> for HostConfigIsolationEnum {
+    fn from(isolation: Isolation) -> HostConfigIsolationEnum {
+        match isolation {
+            Isolation::Default => HostConfigIsolationEnum::DEFAULT,
+            Isolation::Process => HostConfigIsolationEnum::PROCESS,
+            Isolation::HyperV => HostConfigIsolationEnum::HYPERV,
+        }
+    }
+}
+
+/// The options of a bind mount, see [Composition::bind_mount_with].
+///
+/// The default options mount the host path writable, with the default propagation of the docker
+/// daemon, and relabel it for the exclusive use of the container under SELinux.
+///
+/// ```rust
+/// # use dockertest::{BindMountOptions, MountPropagation};
+/// let options = BindMountOptions::new()
+///     .with_read_only(true)
+///     .with_propagation(MountPropagation::RShared);
+/// assert!(options.read_only);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct BindMountOptions {
+    /// Whether the host path is mounted read-only.
+    pub read_only: bool,
+    /// How mounts below the mounted path propagate between the host and the container, the
+    /// default propagation of the docker daemon if `None`.
+    pub propagation: Option<MountPropagation>,
+    /// How the host path is relabeled for SELinux, not relabeled if `None`.
+    ///
+    /// Hosts enforcing SELinux, e.g., Fedora or RHEL, deny the container access to a host path
+    /// that is not relabeled. Relabeling system directories, e.g., `/usr`, breaks the host.
+    pub selinux_relabel: Option<SelinuxRelabel>,
+}
+
+impl Default for BindMountOptions {
+    fn default() -> Self {
+        BindMountOptions {
+            read_only: false,
+            propagation: None,
+            selinux_relabel: Some(SelinuxRelabel::Private),
+        }
+    }
+}
+
+impl BindMountOptions {
+    /// The default options, see [BindMountOptions].
+    pub fn new() -> BindMountOptions {
+        BindMountOptions::default()
+    }
+
+    /// Set whether the host path is mounted read-only.
+    pub fn with_read_only(self, read_only: bool) -> Self {
+        Self { read_only, ..self }
+    }
+
+    /// Set how mounts below the mounted path propagate between the host and the container.
+    pub fn with_propagation(self, propagation: MountPropagation) -> Self {
+        Self {
+            propagation: Some(propagation),
+            ..self
+        }
+    }
+
+    /// Set how the host path is relabeled for SELinux, or `None` to leave it as is.
+    pub fn with_selinux_relabel(self, selinux_relabel: Option<SelinuxRelabel>) -> Self {
+        Self {
+            selinux_relabel,
+            ..self
+        }
+    }
+}
+
+/// The propagation of mounts below a bind mounted path, see the corresponding
+/// [docker reference].
+///
+/// [docker reference]: https://docs.docker.com/storage/bind-mounts/#configure-bind-propagation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MountPropagation {
+    /// Mounts do not propagate in either direction.
+    Private,
+    /// Like [MountPropagation::Private], for every nested mount point as well.
+    RPrivate,
+    /// Mounts propagate in both directions.
+    Shared,
+    /// Like [MountPropagation::Shared], for every nested mount point as well.
+    RShared,
+    /// Mounts of the host propagate to the container, but not the other way around.
+    Slave,
+    /// Like [MountPropagation::Slave], for every nested mount point as well.
+    RSlave,
+}
+
+impl MountPropagation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MountPropagation::Private => "private",
+            MountPropagation::RPrivate => "rprivate",
+            MountPropagation::Shared => "shared",
+            MountPropagation::RShared => "rshared",
+            MountPropagation::Slave => "slave",
+            MountPropagation::RSlave => "rslave",
+        }
+    }
+}
+
+/// How a bind mounted host path is relabeled for SELinux, see the corresponding
+/// [docker reference].
+///
+/// [docker reference]: https://docs.docker.com/storage/bind-mounts/#configure-the-selinux-label
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SelinuxRelabel {
+    /// The `z` relabeling, such that the path is shared by multiple containers.
+    Shared,
+    /// The `Z` relabeling, such that the path is private to the container.
+    Private,
+}
+
+/// A callback invoked with the [RunningContainer] right after its [WaitFor] condition is
+/// fulfilled, see [Composition::on_started].
+#[derive(Clone)]
+pub(crate) struct OnStarted(
+    Arc<dyn Fn(RunningContainer) -> BoxFuture<'static, Result<(), DockerTestError>> + Send + Sync>,
+);
+
+impl OnStarted {
+    pub(crate) async fn invoke(&self, container: RunningContainer) -> Result<(), DockerTestError> {
+        (self.0)(container).await
+    }
+}
+
+impl std::fmt::Debug for OnStarted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("OnStarted")
+    }
+}
+
+/// A hook mutating the raw configuration of the container right before it is created, see
+/// [Composition::with_raw_config_hook].
+#[derive(Clone)]
+pub(crate) struct RawConfigHook(Arc<RawConfigFn>);
+
+type RawConfigFn = dyn Fn(&mut Config<String>, &mut HostConfig) + Send + Sync;
+
+impl RawConfigHook {
+    pub(crate) fn invoke(&self, config: &mut Config<String>, host_config: &mut HostConfig) {
+        (self.0)(config, host_config)
+    }
+}
+
+impl std::fmt::Debug for RawConfigHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RawConfigHook")
+    }
+}
+
+/// The configuration a container is created with, after defaults, container names, named
+/// volumes, injected environment variables and placeholders have been resolved.
+///
+/// Obtained through [DockerTest::resolve] to assert on the environment a test builds, without a
+/// docker daemon.
+///
+/// [DockerTest::resolve]: crate::DockerTest::resolve
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedConfig {
+    name: String,
+    handle: String,
+    image: String,
+    env: HashMap<String, String>,
+    cmd: Vec<String>,
+    binds: Vec<String>,
+    tmpfs: Vec<String>,
+    port_bindings: Vec<(String, String)>,
+    publish_all_ports: bool,
+    privileged: bool,
+    network: Option<String>,
+    aliases: Vec<String>,
+    labels: HashMap<String, String>,
+}
+
+impl ResolvedConfig {
+    /// The final name of the container, including the namespace and unique suffix.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The handle of the container within the test body.
+    pub fn handle(&self) -> &str {
+        &self.handle
+    }
+
+    /// The reference of the image of the container, e.g., `postgres:latest`.
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+
+    /// The environment variables of the container, including injected container names and host
+    /// ports, and substituted placeholders.
+    pub fn env(&self) -> &HashMap<String, String> {
+        &self.env
+    }
+
+    /// The command of the container, empty to use that of the image.
+    pub fn cmd(&self) -> &[String] {
+        &self.cmd
+    }
+
+    /// The bind mounts and named volumes of the container, in the `SOURCE:PATH` form of the
+    /// docker daemon. Named volumes include the unique suffix of the test.
+    pub fn binds(&self) -> &[String] {
+        &self.binds
+    }
+
+    /// The paths within the container mounted as a tmpfs.
+    pub fn tmpfs(&self) -> &[String] {
+        &self.tmpfs
+    }
+
+    /// The container ports and the host ports they are published on.
+    pub fn port_bindings(&self) -> &[(String, String)] {
+        &self.port_bindings
+    }
+
+    /// Whether all exposed ports are published on an ephemeral host port.
+    pub fn publish_all_ports(&self) -> bool {
+        self.publish_all_ports
+    }
+
+    /// Whether the container is privileged.
+    pub fn privileged(&self) -> bool {
+        self.privileged
+    }
+
+    /// The network the container is attached to, if known prior to the test setup.
+    pub fn network(&self) -> Option<&str> {
+        self.network.as_deref()
+    }
+
+    /// The network aliases of the container.
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    /// The labels of the container.
+    pub fn labels(&self) -> &HashMap<String, String> {
+        &self.labels
+    }
 }
 
 /// Specifies who is responsible for managing a static container.
@@ -71,6 +395,9 @@ pub enum StaticManagementPolicy {
 }
 
 /// Specifies how should dockertest should handle log output from this container.
+///
+/// Output forwarded to the dockertest process is subject to the output capture of the test
+/// harness, such that it is only shown for failed tests, unless run with `--nocapture`.
 #[derive(Clone, Debug)]
 pub enum LogAction {
     /// Forward all outputs to their respective output sources of the dockertest process.
@@ -99,6 +426,7 @@ pub enum LogSource {
 
 /// Specifies when [LogAction] is applicable.
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub enum LogPolicy {
     /// [LogAction] is always applicable.
     Always,
@@ -106,6 +434,17 @@ pub enum LogPolicy {
     OnError,
     /// [LogAction] is applicable only if a startup error occures.
     OnStartupError,
+    /// [LogAction] is applied continuously from the moment the container starts, instead of
+    /// once the test completes.
+    ///
+    /// With [LogAction::ForwardToFile], the logs are written as they are produced, such that
+    /// they survive the test process being killed, and long running tests do not read the
+    /// entire log at once during teardown. Output forwarded to the dockertest process is
+    /// buffered instead, and forwarded once the test is torn down, such that it is captured
+    /// along with the output of the test. With
+    /// [DockerTest::with_graceful_stop](crate::DockerTest::with_graceful_stop), teardown awaits
+    /// the logs until the container is stopped, such that its shutdown output is included.
+    Follow,
 }
 
 /// Specifies how dockertest should handle logging output from this specific container.
@@ -164,7 +503,9 @@ pub struct Composition {
     pub(crate) container_name: String,
 
     /// A trait object holding the implementation that indicate container readiness.
-    wait: Box<dyn WaitFor>,
+    ///
+    /// If not configured, the default of [crate::DockerTest] is used, falling back to [NoWait].
+    wait: Option<Box<dyn WaitFor>>,
 
     /// The environmentable variables that will be passed to the container.
     pub(crate) env: HashMap<String, String>,
@@ -189,16 +530,44 @@ pub struct Composition {
     pub(crate) final_named_volume_names: Vec<String>,
 
     /// Bind mounts associated with this composition, are in the form of:
-    /// - `HOST_PATH:CONTAINER_PATH`
+    /// - "(HOST_PATH,CONTAINER_PATH,OPTIONS)"
     ///
     /// NOTE: As bind mounts do not outlive the container they are mounted in they do not need to
     /// be cleaned up.
-    bind_mounts: Vec<String>,
+    pub(crate) bind_mounts: Vec<(String, String, BindMountOptions)>,
+
+    /// The user and group of the container owning the bind mounted host paths, if configured.
+    /// Only applied when the docker daemon executes containers within a user namespace.
+    pub(crate) bind_mount_owner: Option<(u32, u32)>,
+
+    /// Named pipes of the Windows host mounted into the container, are in the form of:
+    /// - "(HOST_PIPE,CONTAINER_PIPE)"
+    pub(crate) named_pipes: Vec<(String, String)>,
+
+    /// The isolation technology of a Windows container, if configured.
+    pub(crate) isolation: Option<Isolation>,
+
+    /// The paths within the container mounted as a tmpfs.
+    tmpfs: Vec<String>,
+
+    /// Whether to apply the speed knobs of a recognized database image.
+    performance_mode: bool,
+
+    /// The offset in seconds of the clock of the container faked through libfaketime, if any.
+    pub(crate) faked_time: Option<i64>,
 
     /// All user specified container name injections as environment variables.
     /// Tuple contains (handle, env).
     pub(crate) inject_container_name_env: Vec<(String, String)>,
 
+    /// All user specified network alias injections as environment variables.
+    /// Tuple contains (handle, env).
+    pub(crate) inject_network_alias_env: Vec<(String, String)>,
+
+    /// All user specified host port injections as environment variables.
+    /// Tuple contains (handle, exported port, env).
+    pub(crate) inject_host_port_env: Vec<(String, u32, String)>,
+
     /// Port mapping (used for Windows-compatibility)
     port: Vec<(String, String)>,
 
@@ -217,6 +586,10 @@ pub struct Composition {
     /// Logging options for this specific container.
     pub(crate) log_options: Option<LogOptions>,
 
+    /// Whether the log options have been explicitly configured for this container,
+    /// in which case the default log options of [crate::DockerTest] does not apply.
+    log_options_configured: bool,
+
     /// Whether this composition should be started in privileged mode.
     /// Privileged mode is required for some images, such as the `docker:dind` image.
     /// See https://docs.docker.com/engine/reference/run/#runtime-privilege-and-linux-capabilities
@@ -227,6 +600,68 @@ pub struct Composition {
     /// NOTE: This is only supported on Docker API 1.25 and above.
     /// NOTE: This is only supported on Docker Engine 1.13 and above.
     pub(crate) privileged: bool,
+
+    /// The parent cgroup of the container, if configured.
+    pub(crate) cgroup_parent: Option<String>,
+
+    /// Whether the container joins the user namespace of the host, as required for privileged
+    /// containers of a docker daemon remapping the users of containers.
+    pub(crate) userns_host: bool,
+
+    /// Whether the docker daemon executes the container within a user namespace, in which only
+    /// the `net.*` sysctls of the network namespace may be set.
+    pub(crate) user_namespaced: bool,
+
+    /// How to retry creating and starting the container, provided by [crate::DockerTest].
+    pub(crate) retry_policy: RetryPolicy,
+
+    /// How to handle an existing container with the same name.
+    pub(crate) existing_container_policy: ExistingContainerPolicy,
+
+    /// Whether the existing container policy has been explicitly configured for this container,
+    /// in which case the `DOCKERTEST_EXISTING_CONTAINERS` override does not apply.
+    existing_container_policy_configured: bool,
+
+    /// The labels of the container.
+    pub(crate) labels: HashMap<String, String>,
+
+    /// The name of the container within a shared environment, see
+    /// [crate::DockerTest::with_shared_environment].
+    shared_container_name: Option<String>,
+
+    /// The number of containers to keep created ahead of time, if pooled.
+    warm_pool: Option<usize>,
+
+    /// How to reset the state of the container before each test body, if at all.
+    reset: Option<Box<dyn Resettable>>,
+
+    /// The callback invoked once the container is started, if any.
+    on_started: Option<OnStarted>,
+
+    /// The hook mutating the raw configuration of the container before it is created, if any.
+    raw_config_hook: Option<RawConfigHook>,
+
+    /// The number of replicas to create of this composition, if replicated.
+    replicas: Option<usize>,
+
+    /// The handle of the replicated composition this composition is a replica of, if any.
+    pub(crate) replica_of: Option<String>,
+
+    /// Whether the container is expected to exit during the test, e.g., a one-shot job.
+    pub(crate) expect_exit: bool,
+
+    /// The secrets written to the container before it is started, by name.
+    pub(crate) secrets: Vec<(String, Secret<String>)>,
+
+    /// The prefixes of the environment variables of the test process passed through to the
+    /// container.
+    pub(crate) passthrough_env: Vec<String>,
+
+    /// The names of the environment variables whose values are redacted from diagnostics.
+    pub(crate) sensitive_env: HashSet<String>,
+
+    /// The address of the container on the network, if pinned instead of allocated at start.
+    pub(crate) ipv4_address: Option<String>,
 }
 
 impl Composition {
@@ -244,19 +679,47 @@ impl Composition {
             network_aliases: None,
             image: Image::with_repository(&copy),
             container_name: copy.replace('/', "-"),
-            wait: Box::new(NoWait {}),
+            wait: None,
             env: HashMap::new(),
             cmd: Vec::new(),
             start_policy: StartPolicy::Relaxed,
             bind_mounts: Vec::new(),
+            bind_mount_owner: None,
+            named_pipes: Vec::new(),
+            isolation: None,
+            tmpfs: Vec::new(),
+            performance_mode: false,
+            faked_time: None,
             named_volumes: Vec::new(),
             inject_container_name_env: Vec::new(),
+            inject_network_alias_env: Vec::new(),
+            inject_host_port_env: Vec::new(),
             final_named_volume_names: Vec::new(),
             port: Vec::new(),
             publish_all_ports: false,
             management: None,
             log_options: Some(LogOptions::default()),
+            log_options_configured: false,
             privileged: false,
+            userns_host: false,
+            user_namespaced: false,
+            cgroup_parent: None,
+            retry_policy: RetryPolicy::default(),
+            existing_container_policy: ExistingContainerPolicy::default(),
+            existing_container_policy_configured: false,
+            labels: HashMap::new(),
+            shared_container_name: None,
+            warm_pool: None,
+            reset: None,
+            on_started: None,
+            raw_config_hook: None,
+            replicas: None,
+            replica_of: None,
+            expect_exit: false,
+            secrets: Vec::new(),
+            passthrough_env: Vec::new(),
+            sensitive_env: HashSet::new(),
+            ipv4_address: None,
         }
     }
 
@@ -270,19 +733,47 @@ impl Composition {
             network_aliases: None,
             container_name: image.repository().to_string().replace('/', "-"),
             image,
-            wait: Box::new(NoWait {}),
+            wait: None,
             env: HashMap::new(),
             cmd: Vec::new(),
             start_policy: StartPolicy::Relaxed,
             bind_mounts: Vec::new(),
+            bind_mount_owner: None,
+            named_pipes: Vec::new(),
+            isolation: None,
+            tmpfs: Vec::new(),
+            performance_mode: false,
+            faked_time: None,
             named_volumes: Vec::new(),
             inject_container_name_env: Vec::new(),
+            inject_network_alias_env: Vec::new(),
+            inject_host_port_env: Vec::new(),
             final_named_volume_names: Vec::new(),
             port: Vec::new(),
             publish_all_ports: false,
             management: None,
             log_options: Some(LogOptions::default()),
+            log_options_configured: false,
             privileged: false,
+            userns_host: false,
+            user_namespaced: false,
+            cgroup_parent: None,
+            retry_policy: RetryPolicy::default(),
+            existing_container_policy: ExistingContainerPolicy::default(),
+            existing_container_policy_configured: false,
+            labels: HashMap::new(),
+            shared_container_name: None,
+            warm_pool: None,
+            reset: None,
+            on_started: None,
+            raw_config_hook: None,
+            replicas: None,
+            replica_of: None,
+            expect_exit: false,
+            secrets: Vec::new(),
+            passthrough_env: Vec::new(),
+            sensitive_env: HashSet::new(),
+            ipv4_address: None,
         }
     }
 
@@ -296,6 +787,35 @@ impl Composition {
         }
     }
 
+    /// Sets the [ExistingContainerPolicy] for this [Composition].
+    ///
+    /// Defaults to [removing](ExistingContainerPolicy::Remove) the existing container.
+    pub fn with_existing_container_policy(self, policy: ExistingContainerPolicy) -> Composition {
+        Composition {
+            existing_container_policy: policy,
+            existing_container_policy_configured: true,
+            ..self
+        }
+    }
+
+    /// Take the container from a pool of `size` containers created ahead of time, see
+    /// [crate::TestBodySpecification::set_warm_pool].
+    pub fn with_warm_pool(self, size: usize) -> Composition {
+        Composition {
+            warm_pool: Some(size),
+            ..self
+        }
+    }
+
+    /// Offset the clock of the container by `offset` seconds through libfaketime, see
+    /// [crate::TestBodySpecification::set_faked_time].
+    pub fn with_faked_time(self, offset: i64) -> Composition {
+        Composition {
+            faked_time: Some(offset),
+            ..self
+        }
+    }
+
     /// Assigns the full set of environmental variables available for the [RunningContainer].
     ///
     /// Each key in the map should be the environmental variable name
@@ -333,6 +853,41 @@ impl Composition {
         self
     }
 
+    /// Publishes the exported tcp `port` on a random free host port, assigned by the docker
+    /// daemon when the container starts.
+    ///
+    /// The assigned host port can be found via [crate::container::RunningContainer::host_port].
+    pub fn publish_port(&mut self, port: u32) -> &mut Composition {
+        self.port
+            .push((format!("{}/tcp", port), EPHEMERAL_HOST_PORT.to_string()));
+        self
+    }
+
+    /// The host port mapped to the exported tcp `port`, if known prior to starting the container.
+    pub(crate) fn mapped_host_port(&self, port: u32) -> Option<&str> {
+        let exported = format!("{}/tcp", port);
+        self.port
+            .iter()
+            .rev()
+            .find(|(e, _)| *e == exported)
+            .map(|(_, host)| host.as_str())
+            .filter(|host| *host != EPHEMERAL_HOST_PORT)
+    }
+
+    /// The explicitly mapped host ports, paired with the exported port they are mapped to.
+    ///
+    /// Only the last mapping of each exported port applies, and ephemeral host ports are omitted.
+    pub(crate) fn mapped_host_ports(&self) -> Vec<(&str, &str)> {
+        let mut mappings: Vec<(&str, &str)> = Vec::new();
+        for (exported, host) in self.port.iter() {
+            mappings.retain(|(e, _)| e != exported);
+            if host != EPHEMERAL_HOST_PORT {
+                mappings.push((exported.as_str(), host.as_str()));
+            }
+        }
+        mappings
+    }
+
     /// Allocates an ephemeral host port for all of the container's exposed ports.
     ///
     /// Mapped host ports can be found via [crate::container::RunningContainer::host_port] method.
@@ -360,6 +915,19 @@ impl Composition {
         }
     }
 
+    /// Places the container within the parent cgroup `path`, e.g., the cgroup of a CI job such
+    /// that runaway containers are kept within the resource quota of the job.
+    ///
+    /// The path is interpreted by the cgroup driver of the docker daemon: a path such as
+    /// `/ci/job-42` with the `cgroupfs` driver, or a slice such as `ci-job42.slice` with the
+    /// `systemd` driver. Only supported for Linux containers.
+    pub fn with_cgroup_parent<T: ToString>(self, path: T) -> Composition {
+        Composition {
+            cgroup_parent: Some(path.to_string()),
+            ..self
+        }
+    }
+
     /// Sets network aliases for this `Composition`.
     pub fn with_alias(self, aliases: Vec<String>) -> Composition {
         Composition {
@@ -377,13 +945,121 @@ impl Composition {
         self
     }
 
+    /// The network aliases of this `Composition`.
+    pub(crate) fn network_aliases(&self) -> &[String] {
+        self.network_aliases.as_deref().unwrap_or_default()
+    }
+
     /// Sets the `WaitFor` trait object for this `Composition`.
     ///
     /// The default `WaitFor` implementation used is [RunningWait].
     ///
     /// [RunningWait]: crate::waitfor::RunningWait
     pub fn with_wait_for(self, wait: Box<dyn WaitFor>) -> Composition {
-        Composition { wait, ..self }
+        Composition {
+            wait: Some(wait),
+            ..self
+        }
+    }
+
+    /// Sets the [Resettable] trait object for this `Composition`, resetting the state of the
+    /// container before each test body.
+    pub fn with_reset(self, reset: Box<dyn Resettable>) -> Composition {
+        Composition {
+            reset: Some(reset),
+            ..self
+        }
+    }
+
+    /// Sets the callback invoked with the [RunningContainer] right after its [WaitFor] condition
+    /// is fulfilled, before other containers may be ready.
+    ///
+    /// This allows wiring the container as part of the startup, e.g., creating the topics of a
+    /// Kafka broker, rather than at the top of every test body. A failing callback fails the
+    /// startup of the container. The ip address and host ports of the container are only
+    /// resolved once every container is ready, hence the callback interacts with the container
+    /// through the docker daemon, e.g., through [RunningContainer::exec].
+    pub fn on_started<F, Fut>(&mut self, callback: F) -> &mut Composition
+    where
+        F: Fn(RunningContainer) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), DockerTestError>> + Send + 'static,
+    {
+        self.on_started = Some(OnStarted(Arc::new(move |container| {
+            callback(container).boxed()
+        })));
+        self
+    }
+
+    /// Sets the hook mutating the raw [Config] and [HostConfig] of the container right before it
+    /// is created, after dockertest has applied its own configuration.
+    ///
+    /// This is an escape hatch to set any option of the docker daemon that dockertest does not
+    /// wrap. The hook can override the configuration of dockertest, e.g., the labels or network
+    /// the container is cleaned up and reached through, which may break the test environment.
+    ///
+    /// As the hook cannot be compared, validation rejects it for containers that are taken from a
+    /// warm pool, shared with other test binaries, or reuse existing containers, as these would
+    /// be handed containers created without the hook.
+    pub fn with_raw_config_hook<F>(self, hook: F) -> Composition
+    where
+        F: Fn(&mut Config<String>, &mut HostConfig) + Send + Sync + 'static,
+    {
+        Composition {
+            raw_config_hook: Some(RawConfigHook(Arc::new(hook))),
+            ..self
+        }
+    }
+
+    /// Creates `replicas` containers of this `Composition`, e.g., the brokers of a cluster.
+    ///
+    /// Each replica is identified by the handle suffixed with its index, counting from zero,
+    /// e.g., `kafka-0` and `kafka-1`. Every `{replica}` within the values of the environment
+    /// variables is replaced by the index of the replica. Network aliases are shared by the
+    /// replicas, such that the alias resolves to all of them.
+    pub fn with_replicas(self, replicas: usize) -> Composition {
+        Composition {
+            replicas: Some(replicas),
+            ..self
+        }
+    }
+
+    /// Expand this composition into its replicas, or itself if not replicated.
+    pub(crate) fn into_replicas(self) -> Vec<Composition> {
+        let replicas = match self.replicas {
+            Some(replicas) => replicas,
+            None => return vec![self],
+        };
+
+        let handle = self.handle();
+        (0..replicas)
+            .map(|index| {
+                let mut replica = self.clone();
+                replica.replicas = None;
+                replica.replica_of = Some(handle.clone());
+                replica.user_provided_container_name = Some(format!("{}-{}", handle, index));
+                for value in replica.env.values_mut() {
+                    *value = value.replace(REPLICA_PLACEHOLDER, &index.to_string());
+                }
+                replica
+            })
+            .collect()
+    }
+
+    /// The number of replicas configured, if replicated.
+    pub(crate) fn replicas(&self) -> Option<usize> {
+        self.replicas
+    }
+
+    /// Marks the container as expected to exit during the test, e.g., a one-shot job migrating
+    /// a database.
+    ///
+    /// The liveness check of [crate::DockerTest::with_liveness_check] considers every other
+    /// container a long-running dependency, failing the test if it exited or restarted. An
+    /// expected exit only fails the test if the container exited unsuccessfully. Containers
+    /// awaited through [ExitedWait](crate::waitfor::ExitedWait) are expected to exit.
+    pub fn expect_exit(&mut self) -> &mut Composition {
+        self.expect_exit = true;
+        self
     }
 
     /// Sets log options for this `Composition`.
@@ -392,12 +1068,19 @@ impl Composition {
     pub fn with_log_options(self, log_options: Option<LogOptions>) -> Composition {
         Composition {
             log_options,
+            log_options_configured: true,
             ..self
         }
     }
 
     /// Sets the environment variable to the given value.
     ///
+    /// The value may reference other containers of the test through placeholders, which are
+    /// substituted before the container is created:
+    /// - `{handle:NAME}` is replaced by the generated container name of handle `NAME`.
+    /// - `{host_port:NAME:PORT}` is replaced by the host port mapped to the container port
+    ///   `PORT` of handle `NAME`, see [inject_host_port](Composition::inject_host_port).
+    ///
     /// NOTE: if [with_env] is called after a call to [env], all values added by [env] will be overwritten.
     ///
     /// [env]: Composition::env
@@ -407,6 +1090,19 @@ impl Composition {
         self
     }
 
+    /// Pass every environment variable of the test process whose name starts with `prefix`
+    /// through to the container, e.g., `AWS_` for credentials provided by CI.
+    ///
+    /// The variables are read when the container is created, and never replace the environment
+    /// variables configured through [env], [with_env] or the defaults of [crate::DockerTest].
+    ///
+    /// [env]: Composition::env
+    /// [with_env]: Composition::with_env
+    pub fn passthrough_env<T: ToString>(&mut self, prefix: T) -> &mut Composition {
+        self.passthrough_env.push(prefix.to_string());
+        self
+    }
+
     /// Appends the command string to the current command vector.
     ///
     /// If no entries in the command vector is provided to the [Composition],
@@ -440,23 +1136,81 @@ impl Composition {
     /// host to the container.
     /// Use named volumes if you want to share data between containers.
     /// The `host_path` can either point to a directory or a file that MUST exist on the local host.
-    /// `path_in_container` has to be an absolute path.
+    /// `path_in_container` has to be an absolute path, e.g., `/data` or `C:\data` for Windows
+    /// containers.
     pub fn bind_mount<T: ToString, S: ToString>(
         &mut self,
         host_path: T,
         path_in_container: S,
     ) -> &mut Composition {
-        // The ':Z' is needed due to permission issues, see
-        // https://stackoverflow.com/questions/24288616/permission-denied-on-accessing-host-directory-in-docker
-        // for more details
-        self.bind_mounts.push(format!(
-            "{}:{}:Z",
+        self.bind_mount_with(host_path, path_in_container, BindMountOptions::default())
+    }
+
+    /// Adds the given bind mount to the Composition with the provided options, e.g., to mount
+    /// the host path read-only, with a mount propagation or a different SELinux relabeling.
+    ///
+    /// Only the read-only option applies to Windows containers.
+    pub fn bind_mount_with<T: ToString, S: ToString>(
+        &mut self,
+        host_path: T,
+        path_in_container: S,
+        options: BindMountOptions,
+    ) -> &mut Composition {
+        self.bind_mounts.push((
             host_path.to_string(),
-            path_in_container.to_string()
+            path_in_container.to_string(),
+            options,
         ));
         self
     }
 
+    /// Sets the user and group of the container that owns the host paths bind mounted into it.
+    ///
+    /// A rootless docker daemon, or a daemon remapping the users of containers through
+    /// `userns-remap`, maps the users of the container to unprivileged users of the host, which
+    /// are unable to write to the bind mounted host paths. With such a daemon, the owner of the
+    /// bind mounted host paths is recursively changed through a helper container before the
+    /// container is created, such that `uid` and `gid` within the container own them. With other
+    /// daemons, the bind mounts are left as is.
+    ///
+    /// The owner of the host paths is changed permanently, it is not restored once the test
+    /// terminates. Read-only bind mounts are left as is.
+    pub fn bind_mount_owner(&mut self, uid: u32, gid: u32) -> &mut Composition {
+        self.bind_mount_owner = Some((uid, gid));
+        self
+    }
+
+    /// Mounts the named pipe `host_pipe` of the Windows host into the Windows container as
+    /// `container_pipe`, e.g., `\\.\pipe\docker_engine` to use the docker daemon from within
+    /// the container.
+    pub fn named_pipe<T: ToString, S: ToString>(
+        &mut self,
+        host_pipe: T,
+        container_pipe: S,
+    ) -> &mut Composition {
+        self.named_pipes
+            .push((host_pipe.to_string(), container_pipe.to_string()));
+        self
+    }
+
+    /// Applies well-known speed knobs of recognized database images, trading durability for
+    /// speed, as the data of the container is discarded along with it anyway.
+    ///
+    /// For `postgres`, `fsync`, `synchronous_commit` and `full_page_writes` are disabled. For
+    /// `mysql` and `mariadb`, the doublewrite buffer is skipped, and the logs are not flushed on
+    /// every commit. The data directory is mounted as a tmpfs, unless a volume is mounted there.
+    /// Other images are left as is.
+    pub fn ephemeral_performance_mode(&mut self) -> &mut Composition {
+        self.performance_mode = true;
+        self
+    }
+
+    /// Sets the isolation technology of the Windows container.
+    pub fn isolation(&mut self, isolation: Isolation) -> &mut Composition {
+        self.isolation = Some(isolation);
+        self
+    }
+
     /// Inject the generated container name identified by `handle` into
     /// this Composition environment variable `env`.
     ///
@@ -478,6 +1232,53 @@ impl Composition {
         self
     }
 
+    /// Inject the first network alias of the container identified by `handle` into this
+    /// Composition environment variable `env`.
+    ///
+    /// Unlike the generated container name injected by
+    /// [inject_container_name](Composition::inject_container_name), the alias is stable across
+    /// test runs, such that connection strings built from it remain readable in logs and
+    /// debuggers. The container identified by `handle` must have a network alias, see
+    /// [alias](Composition::alias).
+    ///
+    /// Unlike the generated container name, the alias is not unique to the test. On a network
+    /// shared with other tests, i.e., any but [Network::Isolated], the alias resolves to the
+    /// containers of every test executing in parallel with the same alias, which is reported as
+    /// a warning.
+    pub fn inject_network_alias<T: ToString, E: ToString>(
+        &mut self,
+        handle: T,
+        env: E,
+    ) -> &mut Composition {
+        self.inject_network_alias_env
+            .push((handle.to_string(), env.to_string()));
+        self
+    }
+
+    /// The first network alias of the container, if any.
+    pub(crate) fn network_alias(&self) -> Option<&str> {
+        self.network_aliases().first().map(|alias| alias.as_str())
+    }
+
+    /// Inject the host port published for the exported tcp `port` of the container identified
+    /// by `handle` into this Composition environment variable `env`.
+    ///
+    /// If the exported port of the referenced container is not explicitly mapped through
+    /// [port_map](Composition::port_map), it is published on a host port assigned by the docker
+    /// daemon. This container is then only created once the referenced container has started,
+    /// regardless of its [StartPolicy]. This allows containers that communicate through the host
+    /// to be wired together without hardcoding host ports.
+    pub fn inject_host_port<T: ToString, E: ToString>(
+        &mut self,
+        handle: T,
+        port: u32,
+        env: E,
+    ) -> &mut Composition {
+        self.inject_host_port_env
+            .push((handle.to_string(), port, env.to_string()));
+        self
+    }
+
     /// Defines this as a static container which will will only be cleaned up after the full test
     /// binary has executed.
     /// If the static container is used across multiple tests in the same test binary, Dockertest can only guarantee that
@@ -492,23 +1293,42 @@ impl Composition {
     pub fn static_container(&mut self, management: StaticManagementPolicy) -> &mut Composition {
         let management = match management {
             StaticManagementPolicy::External | StaticManagementPolicy::Internal => management,
-            StaticManagementPolicy::Dynamic => match std::env::var("DOCKERTEST_DYNAMIC") {
-                Ok(val) => match val.as_str() {
-                    "EXTERNAL" => StaticManagementPolicy::External,
-                    "INTERNAL" => StaticManagementPolicy::Internal,
-                    "DYNAMIC" => StaticManagementPolicy::Dynamic,
-                    _ => {
-                        event!(Level::WARN, "DOCKERTEST_DYNAMIC environment variable set to unknown value, defaulting to Dynamic policy");
-                        StaticManagementPolicy::Dynamic
-                    }
-                },
-                Err(_) => management,
-            },
+            StaticManagementPolicy::Dynamic => {
+                overrides::dynamic_management_policy().unwrap_or(management)
+            }
         };
         self.management = Some(management);
         self
     }
 
+    /// Provide the secret `value` to the container as the file `/run/secrets/{name}`, instead of
+    /// through its environment.
+    ///
+    /// The file is written before the container is started, and is readable by every user of the
+    /// container. The value is redacted from the container logs forwarded by dockertest.
+    pub fn secret<T: ToString, S: ToString>(&mut self, name: T, value: S) -> &mut Composition {
+        let name = name.to_string();
+        let value = Secret::new(value.to_string());
+        match self.secrets.iter_mut().find(|(n, _)| *n == name) {
+            Some(secret) => secret.1 = value,
+            None => self.secrets.push((name, value)),
+        }
+        self
+    }
+
+    /// Mark the environment variables named `keys` as sensitive, e.g., credentials, redacting
+    /// their values from the diagnostics of dockertest: tracing events, startup and validation
+    /// errors, and the container logs forwarded by dockertest.
+    ///
+    /// This applies to the values configured on the container, by the defaults of the test, and
+    /// passed through from the test process alike. The values remain visible to `docker inspect`,
+    /// see [secret](Composition::secret) to hide them.
+    pub fn mark_env_sensitive<T: ToString>(&mut self, keys: &[T]) -> &mut Composition {
+        self.sensitive_env
+            .extend(keys.iter().map(|key| key.to_string()));
+        self
+    }
+
     /// Should this container be started with priviledged mode enabled?
     /// This is required for some containers to run correctly.
     /// See https://docs.docker.com/engine/reference/run/#runtime-privilege-and-linux-capabilities
@@ -518,13 +1338,107 @@ impl Composition {
         self
     }
 
+    /// Apply the defaults configured on [crate::DockerTest] to every option not explicitly
+    /// configured on this composition.
+    pub(crate) fn apply_defaults(&mut self, defaults: &Defaults) {
+        for (key, value) in defaults.env.iter() {
+            self.env
+                .entry(key.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+
+        if !self.log_options_configured {
+            if let Some(log_options) = &defaults.log_options {
+                self.log_options = log_options.clone();
+            }
+        }
+
+        if self.wait.is_none() && defaults.smart_wait_for {
+            self.wait = smart_defaults::wait_for(self.image.repository());
+        }
+        if self.wait.is_none() {
+            self.wait = defaults.wait_for.as_ref().map(|factory| factory());
+        }
+
+        if let Some(policy) = &defaults.pull_policy {
+            self.image.default_pull_policy(policy);
+        }
+
+        // Applied once the container is fully configured, e.g., its command.
+        if std::mem::take(&mut self.performance_mode) {
+            self.apply_performance_mode();
+        }
+    }
+
+    // Applies the speed knobs of the database image, if recognized.
+    fn apply_performance_mode(&mut self) {
+        let knobs = match performance::knobs(self.image.repository()) {
+            Some(knobs) => knobs,
+            None => {
+                event!(
+                    Level::DEBUG,
+                    "no performance mode for the image of container `{}`",
+                    self.handle()
+                );
+                return;
+            }
+        };
+
+        match performance::with_args(&knobs, &self.cmd) {
+            Some(cmd) => self.cmd = cmd,
+            None => event!(
+                Level::DEBUG,
+                "the command of container `{}` does not start `{}`, leaving it as is",
+                self.handle(),
+                knobs.server
+            ),
+        }
+
+        // A mounted data directory is kept, e.g., to seed the database.
+        let mounted = self
+            .named_volumes
+            .iter()
+            .map(|(_, path)| path)
+            .chain(self.bind_mounts.iter().map(|(_, path, _)| path))
+            .any(|path| path == knobs.data_dir);
+        if !mounted && !self.tmpfs.iter().any(|path| path == knobs.data_dir) {
+            self.tmpfs.push(knobs.data_dir.to_string());
+        }
+    }
+
+    /// Apply the overrides of the environment, which take precedence over the configuration of
+    /// this container and the defaults of the test.
+    ///
+    /// An explicitly configured [ExistingContainerPolicy] is kept, as the test may depend on it.
+    pub(crate) fn apply_overrides(&mut self, overrides: &Overrides) {
+        if let Some(policy) = &overrides.pull_policy {
+            self.image.override_pull_policy(policy);
+        }
+
+        // Containers whose logging is disabled are forwarded with the default log options.
+        if let Some(dir) = &overrides.log_dir {
+            let log_options = self.log_options.get_or_insert_with(LogOptions::default);
+            log_options.action = LogAction::ForwardToFile { path: dir.clone() };
+        }
+
+        if let Some(policy) = overrides.existing_containers {
+            if !self.existing_container_policy_configured {
+                self.existing_container_policy = policy;
+            }
+        }
+
+        if let Some(parent) = &overrides.cgroup_parent {
+            self.cgroup_parent = Some(parent.clone());
+        }
+    }
+
     /// Fetch the assigned [StaticManagementPolicy], if any.
     pub(crate) fn static_management_policy(&self) -> &Option<StaticManagementPolicy> {
         &self.management
     }
 
     /// Query whether this Composition should be handled through static container checks.
-    fn is_static(&self) -> bool {
+    pub(crate) fn is_static(&self) -> bool {
         self.management.is_some()
     }
 
@@ -533,19 +1447,173 @@ impl Composition {
     // We do this to ensure that we do not have overlapping container names
     // and make it clear which containers are run by DockerTest.
     pub(crate) fn configure_container_name(&mut self, namespace: &str, suffix: &str) {
-        let name = match &self.user_provided_container_name {
-            None => self.image.repository(),
-            Some(n) => n,
-        };
+        self.container_name = self.final_container_name(namespace, suffix);
+    }
 
-        if !self.is_static() {
+    /// The name of the container once configured with the given namespace and suffix.
+    ///
+    /// Static containers are named exactly as provided, as they are shared between tests.
+    pub(crate) fn final_container_name(&self, namespace: &str, suffix: &str) -> String {
+        if let Some(name) = &self.shared_container_name {
+            return name.clone();
+        }
+
+        match (&self.user_provided_container_name, self.is_static()) {
+            (Some(name), true) => name.clone(),
+            (None, true) => self.image.repository().to_string(),
             // The docker daemon does not like '/' or '\' in container names
-            let stripped_name = name.replace('/', "_");
+            (Some(name), false) => {
+                format!("{}-{}-{}", namespace, name.replace('/', "_"), suffix)
+            }
+            // The repository may contain the port of a registry, and is shortened to fit the
+            // length limit, as it is not chosen by the user.
+            (None, false) => {
+                let budget =
+                    MAX_CONTAINER_NAME_LENGTH.saturating_sub(namespace.len() + suffix.len() + 2);
+                format!(
+                    "{}-{}-{}",
+                    namespace,
+                    shorten_name(
+                        sanitize_name(&self.image.repository().replace('/', "_")),
+                        budget
+                    ),
+                    suffix
+                )
+            }
+        }
+    }
 
-            self.container_name = format!("{}-{}-{}", namespace, stripped_name, suffix);
-        } else {
-            self.container_name = name.to_string();
+    /// Make this container part of the shared environment it is labeled with, as the container
+    /// with the given name.
+    ///
+    /// The container is managed like a dynamic container, such that it is created if it does not
+    /// exist, and never removed.
+    pub(crate) fn share_environment(&mut self, name: String) {
+        self.labels
+            .insert(shared_environment::HANDLE_LABEL.to_string(), self.handle());
+        self.shared_container_name = Some(name);
+        self.management = Some(StaticManagementPolicy::Dynamic);
+    }
+
+    // Resolves the paths mounted as a tmpfs, including the one required by the wait condition.
+    pub(crate) fn resolve_tmpfs(&self) -> Vec<String> {
+        let mut tmpfs = self.tmpfs.clone();
+        if let Some(path) = self.wait.as_ref().and_then(|wait| wait.tmpfs()) {
+            if !tmpfs.contains(&path) {
+                tmpfs.push(path);
+            }
+        }
+        tmpfs
+    }
+
+    // Resolves the configuration the container is created with on the given network.
+    pub(crate) fn resolve(&self, network: Option<&str>) -> ResolvedConfig {
+        ResolvedConfig {
+            name: self.container_name.clone(),
+            handle: self.handle(),
+            image: self.image.reference(),
+            env: self.resolve_env(),
+            cmd: self.cmd.clone(),
+            binds: self
+                .bind_mounts
+                .iter()
+                .map(|(host, container, options)| bind_mount_spec(host, container, options))
+                .chain(self.final_named_volume_names.iter().cloned())
+                .chain(self.faked_time_bind())
+                .collect(),
+            tmpfs: self.resolve_tmpfs(),
+            port_bindings: self.port.clone(),
+            publish_all_ports: self.publish_all_ports,
+            privileged: self.privileged,
+            network: network.map(|n| n.to_string()),
+            aliases: self.network_aliases.clone().unwrap_or_default(),
+            labels: self.labels.clone(),
+        }
+    }
+
+    /// Describe everything the container is created with, except its name and network, such
+    /// that identical definitions are described identically by every test process.
+    ///
+    /// The labels managed by dockertest are omitted, as they identify the test, or are derived
+    /// from the definition. A raw config hook cannot be described, and is omitted. Secrets are
+    /// only described by a digest, such that their values never appear in the description.
+    pub(crate) fn definition(&self) -> String {
+        let config = self.resolve(None);
+        let env: BTreeMap<&String, &String> = config.env.iter().collect();
+        let labels: BTreeMap<&String, &String> = config
+            .labels
+            .iter()
+            .filter(|(key, _)| !key.starts_with("dockertest."))
+            .collect();
+        let secrets: BTreeMap<&String, &String> = self
+            .secrets
+            .iter()
+            .map(|(name, value)| (name, value.expose_secret()))
+            .collect();
+        format!(
+            "{}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{}|{}|{:?}|{:?}|{:?}|{}|{:?}|{:?}|{:?}|{:?}|{:?}|{}",
+            config.image,
+            self.image.configured_platform(),
+            env,
+            config.cmd,
+            config.binds,
+            config.tmpfs,
+            config.port_bindings,
+            config.publish_all_ports,
+            config.privileged,
+            labels,
+            self.named_pipes,
+            self.isolation,
+            self.userns_host,
+            self.cgroup_parent,
+            self.bind_mount_owner,
+            self.log_options,
+            self.network_aliases(),
+            self.faked_time,
+            stable_hash(&format!("{:?}", secrets))
+        )
+    }
+
+    // Resolves the environment of the container, passing through the matching variables of the
+    // test process.
+    fn resolve_env(&self) -> HashMap<String, String> {
+        self.resolve_env_from(std::env::vars_os())
+    }
+
+    // Resolves the environment of the container, passing through the matching variables of the
+    // given process environment.
+    pub(crate) fn resolve_env_from<I>(&self, process_env: I) -> HashMap<String, String>
+    where
+        I: IntoIterator<Item = (OsString, OsString)>,
+    {
+        let mut env: HashMap<String, String> = process_env
+            .into_iter()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+            .filter(|(name, _)| {
+                self.passthrough_env
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix.as_str()))
+            })
+            .collect();
+        // The placeholders have been substituted once the container is created.
+        env.extend(
+            self.env
+                .iter()
+                .map(|(name, value)| (name.clone(), template::unescape(value))),
+        );
+        if self.faked_time.is_some() {
+            env.extend(faketime::env());
         }
+        env
+    }
+
+    // The bind mount of libfaketime, if the clock of the container is faked. A missing library
+    // is reported by validation.
+    fn faked_time_bind(&self) -> Option<String> {
+        self.faked_time?;
+        faketime::library(std::env::var_os(faketime::LIBRARY_ENV))
+            .ok()
+            .map(|library| faketime::bind(&library))
     }
 
     /// TODO: Refactor what is returned when creating the static container.
@@ -555,7 +1623,10 @@ impl Composition {
         network: Option<&str>,
         network_settings: &Network,
     ) -> Result<CreatedContainer, DockerTestError> {
-        trace!("evaluating composition: {self:#?}");
+        trace!(
+            "evaluating composition: {}",
+            self.redactions().redact(&format!("{self:#?}"))
+        );
         if self.is_static() {
             STATIC_CONTAINERS
                 .create(self, client, network, network_settings)
@@ -577,18 +1648,68 @@ impl Composition {
     ) -> Result<PendingContainer, DockerTestError> {
         event!(Level::DEBUG, "creating container: {}", self.container_name);
 
-        let start_policy_clone = self.start_policy.clone();
-        let container_name_clone = self.container_name.clone();
-
         if !self.is_static() {
-            // Ensure we can remove the previous container instance, if it somehow still exists.
-            // Only bail on non-recoverable failure.
-            match remove_container_if_exists(client, &self.container_name).await {
-                Ok(_) => {}
-                Err(e) => match e {
-                    DockerTestError::Recoverable(_) => {}
-                    _ => return Err(e),
-                },
+            // Handle the previous container instance, if it somehow still exists.
+            if let Some((id, labels)) = existing_container(client, &self.container_name).await? {
+                let environment = shared_environment::ENVIRONMENT_LABEL;
+                match self.existing_container_policy {
+                    ExistingContainerPolicy::Remove => {
+                        remove_existing_container(client, &self.container_name).await?
+                    }
+                    ExistingContainerPolicy::Fail => {
+                        return Err(DockerTestError::Startup(format!(
+                            "container `{}` of `{}` already exists, remove it or configure \
+                             another `ExistingContainerPolicy`",
+                            self.container_name,
+                            self.handle()
+                        )))
+                    }
+                    // A container not created by dockertest is never replaced.
+                    ExistingContainerPolicy::Reuse if !labels.contains_key(environment) => {
+                        return Err(DockerTestError::Startup(format!(
+                            "container `{}` of `{}` already exists and was not created by \
+                             dockertest, remove it or configure another \
+                             `ExistingContainerPolicy`",
+                            self.container_name,
+                            self.handle()
+                        )))
+                    }
+                    // An existing container of another environment definition is outdated.
+                    ExistingContainerPolicy::Reuse
+                        if labels.get(environment) != self.labels.get(environment) =>
+                    {
+                        event!(
+                            Level::DEBUG,
+                            "replacing existing container of another environment: {}",
+                            self.container_name
+                        );
+                        remove_existing_container(client, &self.container_name).await?
+                    }
+                    ExistingContainerPolicy::Reuse => {
+                        event!(
+                            Level::DEBUG,
+                            "reusing existing container: {}",
+                            self.container_name
+                        );
+                        if let Some(n) = network {
+                            add_to_network(&id, n, client).await?;
+                        }
+                        let mut pending = self.into_pending(client, id);
+                        pending.reused = true;
+                        return Ok(pending);
+                    }
+                }
+            }
+
+            if let Some(size) = self.warm_pool {
+                let hash = warm_pool::definition_hash(&self);
+                let aliases = self.network_aliases.clone().unwrap_or_default();
+                let taken =
+                    warm_pool::take(client, &hash, &self.container_name, network, &aliases).await?;
+                warm_pool::refill(client.clone(), self.clone(), hash, size);
+                if let Some(id) = taken {
+                    return Ok(self.into_pending(client, id));
+                }
             }
         }
 
@@ -600,114 +1721,276 @@ impl Composition {
             return Err(DockerTestError::Processing("`Composition::create()` invoked without populating its image through `Image::pull()`".to_string()));
         }
 
-        // As we can't return temporary values owned by this closure
-        // we have to first convert our map into a vector of owned strings,
-        // then convert it to a vector of borrowed strings (&str).
-        // There is probably a better way to do this...
-        let envs: Vec<String> = self
-            .env
-            .iter()
-            .map(|(key, value)| format!("{}={}", key, value))
-            .collect();
-        let envs = envs.iter().map(|s| s.as_ref()).collect();
-        let cmds = self.cmd.iter().map(|s| s.as_ref()).collect();
-
-        let mut volumes: Vec<String> = Vec::new();
-        for v in self.bind_mounts.iter() {
+        let resolved = self.resolve(network);
+        for bind in resolved.binds.iter() {
             event!(
                 Level::DEBUG,
-                "creating host_mounted_volume: {} for container {}",
-                v.as_str(),
+                "creating volume: {} for container {}",
+                bind,
                 self.container_name
             );
-            volumes.push(v.to_string());
         }
 
-        for v in self.final_named_volume_names.iter() {
-            event!(
-                Level::DEBUG,
-                "creating named_volume: {} for container {}",
-                &v,
-                self.container_name
-            );
-            volumes.push(v.to_string());
-        }
+        let envs: Vec<String> = resolved
+            .env
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
 
         let mut port_map: HashMap<String, Option<Vec<PortBinding>>> = HashMap::new();
-        let mut exposed_ports: HashMap<&str, HashMap<(), ()>> = HashMap::new();
+        let mut exposed_ports: HashMap<String, HashMap<(), ()>> = HashMap::new();
 
-        for (exposed, host) in &self.port {
+        for (exposed, host) in &resolved.port_bindings {
             let dest_port: Vec<PortBinding> = vec![PortBinding {
                 host_ip: Some("127.0.0.1".to_string()),
                 host_port: Some(host.clone()),
             }];
             port_map.insert(exposed.to_string(), Some(dest_port));
-            exposed_ports.insert(exposed, HashMap::new());
+            exposed_ports.insert(exposed.clone(), HashMap::new());
         }
 
-        let network_aliases = self.network_aliases.as_ref();
-        let mut net_config = None;
-
         // Construct host config
-        let host_config = network.map(|n| HostConfig {
+        let mounts: Vec<Mount> = self
+            .named_pipes
+            .iter()
+            .map(|(host, container)| Mount {
+                typ: Some(MountTypeEnum::NPIPE),
+                source: Some(host.clone()),
+                target: Some(container.clone()),
+                ..Default::default()
+            })
+            .collect();
+        let mut host_config = resolved.network.as_ref().map(|n| HostConfig {
             network_mode: Some(n.to_string()),
-            binds: Some(volumes),
+            binds: Some(resolved.binds.clone()),
+            mounts: (!mounts.is_empty()).then_some(mounts),
+            tmpfs: (!resolved.tmpfs.is_empty()).then(|| {
+                resolved
+                    .tmpfs
+                    .iter()
+                    .map(|path| (path.clone(), String::new()))
+                    .collect()
+            }),
             port_bindings: Some(port_map),
-            publish_all_ports: Some(self.publish_all_ports),
-            privileged: Some(self.privileged),
+            publish_all_ports: Some(resolved.publish_all_ports),
+            privileged: Some(resolved.privileged),
+            isolation: self.isolation.map(HostConfigIsolationEnum::from),
+            userns_mode: self.userns_host.then(|| "host".to_string()),
+            cgroup_parent: self.cgroup_parent.clone(),
             ..Default::default()
         });
 
-        if let Some(n) = network {
-            net_config = network_aliases.map(|a| {
+        let mut net_config = None;
+        if let Some(n) = resolved.network.as_deref() {
+            if !resolved.aliases.is_empty() || self.ipv4_address.is_some() {
                 let mut endpoints = HashMap::new();
                 let settings = EndpointSettings {
-                    aliases: Some(a.to_vec()),
+                    aliases: (!resolved.aliases.is_empty()).then(|| resolved.aliases.clone()),
+                    ipam_config: self
+                        .ipv4_address
+                        .as_ref()
+                        .map(|address| EndpointIpamConfig {
+                            ipv4_address: Some(address.clone()),
+                            ..Default::default()
+                        }),
                     ..Default::default()
                 };
-                endpoints.insert(n, settings);
-                NetworkingConfig {
+                endpoints.insert(n.to_string(), settings);
+                net_config = Some(NetworkingConfig {
                     endpoints_config: endpoints,
-                }
-            });
+                });
+            }
         }
 
         // Construct options for create container
         let options = Some(CreateContainerOptions {
-            name: &self.container_name,
-            // Sets the platform of the server if its multi-platform capable, we might support user
-            // provided values here at a later time.
-            platform: None,
+            name: resolved.name.as_str(),
+            // Defaults to the platform of the server if its multi-platform capable.
+            platform: self.image.configured_platform(),
         });
 
-        let config = Config::<&str> {
-            image: Some(&image_id),
-            cmd: Some(cmds),
+        let mut config = Config::<String> {
+            image: Some(image_id),
+            cmd: Some(resolved.cmd.clone()),
             env: Some(envs),
             networking_config: net_config,
-            host_config,
+            host_config: None,
             exposed_ports: Some(exposed_ports),
+            labels: Some(resolved.labels.clone()),
             ..Default::default()
         };
+        if let Some(hook) = &self.raw_config_hook {
+            let mut host = host_config.take().unwrap_or_default();
+            hook.invoke(&mut config, &mut host);
+            if self.user_namespaced {
+                skip_user_namespaced_sysctls(&self.handle(), &mut host);
+            }
+            host_config = Some(host);
+        }
+        config.host_config = host_config;
+        trace!(
+            "creating container from options: {options:#?}, config: {}",
+            self.redactions().redact(&format!("{config:#?}"))
+        );
+
+        let mut attempts = 0;
+        let result = self
+            .retry_policy
+            .retry("create container", || {
+                attempts += 1;
+                let (options, config) = (options.clone(), config.clone());
+                // The permit is not held across the backoff between attempts.
+                async move {
+                    let _permit = throttle::daemon_operation().await;
+                    client.create_container(options, config).await
+                }
+            })
+            .await;
+        let (id, warnings) = match result {
+            Ok(info) => (info.id, info.warnings),
+            // A retried attempt conflicts with the container of a previous attempt whose response
+            // was lost, e.g., as the daemon failed after creating it. A shared container may
+            // instead have been created by another test binary in the meantime, which is used.
+            Err(e) if (attempts > 1 || self.shared_container_name.is_some()) && is_conflict(&e) => {
+                match existing_container(client, &self.container_name).await? {
+                    Some((id, _)) => {
+                        event!(
+                            Level::DEBUG,
+                            "container `{}` was created by a previous attempt or another test",
+                            self.container_name
+                        );
+                        (id, Vec::new())
+                    }
+                    None => return Err(daemon_error("failed to create container", e)),
+                }
+            }
+            Err(e) => {
+                return Err(
+                    match image::platform_mismatch(&self.image.describe(), &e.to_string()) {
+                        Some(mismatch) => mismatch,
+                        None => daemon_error("failed to create container", e),
+                    },
+                )
+            }
+        };
+        for warning in warnings.iter() {
+            event!(
+                Level::WARN,
+                "creating container `{}`: {}",
+                self.container_name,
+                warning
+            );
+        }
+
+        if !self.secrets.is_empty() {
+            if let Err(e) = secrets::upload(client, &id, &self.secrets).await {
+                if let Err(e) = remove_existing_container(client, &id).await {
+                    event!(
+                        Level::WARN,
+                        "failed to remove container `{}` without its secrets: {}",
+                        self.container_name,
+                        e
+                    );
+                }
+                return Err(e);
+            }
+        }
+        if let Some(offset) = self.faked_time {
+            if let Err(e) = faketime::write(client, &id, offset).await {
+                if let Err(e) = remove_existing_container(client, &id).await {
+                    event!(
+                        Level::WARN,
+                        "failed to remove container `{}` without its faked time: {}",
+                        self.container_name,
+                        e
+                    );
+                }
+                return Err(e);
+            }
+        }
 
-        trace!("creating container from options: {options:#?}, config: {config:#?}");
+        Ok(self.into_pending(client, id))
+    }
 
-        let container_info = client
-            .create_container(options, config)
-            .map_err(|e| DockerTestError::Daemon(format!("failed to create container: {}", e)))
-            .await?;
+    /// Create the container on the network of its warm pool, to be taken by a later test.
+    pub(crate) async fn create_pooled(mut self, client: &Docker) -> Result<(), DockerTestError> {
+        // Network-scoped aliases are only supported on user-defined networks.
+        self.network_aliases = None;
+        self.warm_pool = None;
+        Box::pin(self.create_inner(client, Some(warm_pool::POOL_NETWORK)))
+            .await
+            .map(|_| ())
+    }
 
+    // Converts into the PendingContainer of the created container with the given id.
+    pub(crate) fn into_pending(self, client: &Docker, id: String) -> PendingContainer {
         let static_management_policy = self.static_management_policy().clone();
-        Ok(PendingContainer::new(
-            &container_name_clone,
-            container_info.id,
-            self.handle(),
-            start_policy_clone,
-            self.wait,
+        let redactions = self.redactions();
+        let on_started = self.on_started.clone();
+        let handle = self.handle();
+        let wait = self.wait.unwrap_or_else(|| Box::new(NoWait {}));
+        let expect_exit = self.expect_exit || wait.expects_exit();
+        let mut pending = PendingContainer::new(
+            &self.container_name,
+            id,
+            handle,
+            self.start_policy.clone(),
+            wait,
             client.clone(),
             static_management_policy,
             self.log_options.clone(),
-        ))
+        );
+        pending.retry_policy = self.retry_policy;
+        pending.redactions = redactions;
+        pending.on_started = on_started;
+        pending.expect_exit = expect_exit;
+        pending
+    }
+
+    /// The values to redact from the logs and diagnostics of the container: its secrets and the
+    /// values of its sensitive environment variables.
+    pub(crate) fn redactions(&self) -> Redactions {
+        let sensitive = self
+            .resolve_env()
+            .into_iter()
+            .filter(|(name, _)| self.sensitive_env.contains(name))
+            .map(|(_, value)| Secret::new(value));
+        Redactions::new(
+            self.secrets
+                .iter()
+                .map(|(_, v)| v.clone())
+                .chain(sensitive)
+                .collect(),
+        )
+    }
+
+    /// The value of the environment variable to display in diagnostics, redacted if sensitive.
+    pub(crate) fn display_env<'a>(&self, name: &str, value: &'a str) -> &'a str {
+        if self.sensitive_env.contains(name) {
+            secrets::REDACTED
+        } else {
+            value
+        }
+    }
+
+    /// How to reset the state of the container before each test body, if at all.
+    pub(crate) fn reset(&self) -> Option<Box<dyn Resettable>> {
+        self.reset.clone()
+    }
+
+    /// The size of the warm pool the container is taken from, if pooled.
+    pub(crate) fn warm_pool(&self) -> Option<usize> {
+        self.warm_pool
+    }
+
+    /// Whether the raw configuration of the container is mutated by a hook.
+    pub(crate) fn has_raw_config_hook(&self) -> bool {
+        self.raw_config_hook.is_some()
+    }
+
+    /// The start policy of the container.
+    pub(crate) fn start_policy(&self) -> &StartPolicy {
+        &self.start_policy
     }
 
     // Returns the Image associated with this Composition.
@@ -715,6 +1998,11 @@ impl Composition {
         &self.image
     }
 
+    /// The container name explicitly provided through [Composition::with_container_name], if any.
+    pub(crate) fn user_provided_container_name(&self) -> Option<&str> {
+        self.user_provided_container_name.as_deref()
+    }
+
     /// Retrieve a copy of the applicable handle name for this composition.
     ///
     /// NOTE: this value will be outdated if [Composition::with_container_name] is invoked
@@ -727,15 +2015,212 @@ impl Composition {
     }
 }
 
-// Forcefully removes the given container if it exists.
-async fn remove_container_if_exists(client: &Docker, name: &str) -> Result<(), DockerTestError> {
-    client
+/// Shorten the name to `budget` characters by replacing its tail with a digest of the name, such
+/// that distinct names remain distinct. The name is expected to only consist of ascii characters.
+fn shorten_name(name: String, budget: usize) -> String {
+    if name.len() <= budget {
+        return name;
+    }
+
+    let digest = &stable_hash(&name)[..8];
+    let keep = budget.saturating_sub(digest.len() + 1);
+    if keep == 0 {
+        return digest.to_string();
+    }
+    format!("{}-{}", &name[..keep], digest)
+}
+
+/// Check that the name is accepted by the docker daemon as a container name, which must match
+/// `[a-zA-Z0-9][a-zA-Z0-9_.-]+`, and is resolvable on the network of the test.
+pub(crate) fn check_container_name(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    match chars.next() {
+        None => return Err("container name is empty".to_string()),
+        Some(c) if !c.is_ascii_alphanumeric() => {
+            return Err(format!(
+                "container name `{}` must start with a letter or digit, not `{}`",
+                name, c
+            ))
+        }
+        Some(_) => (),
+    }
+    if let Some(c) = chars.find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))) {
+        return Err(format!(
+            "container name `{}` contains invalid character `{}`, only letters, digits, `_`, \
+             `.` and `-` are allowed",
+            name, c
+        ));
+    }
+    if name.len() < 2 {
+        return Err(format!(
+            "container name `{}` must be at least two characters long",
+            name
+        ));
+    }
+    if name.len() > MAX_CONTAINER_NAME_LENGTH {
+        return Err(format!(
+            "container name `{}` is {} characters long, exceeding the limit of {}",
+            name,
+            name.len(),
+            MAX_CONTAINER_NAME_LENGTH
+        ));
+    }
+
+    Ok(())
+}
+
+/// Summarizes the composition on a single line.
+///
+/// Only the environment variable names are included, as their values may contain credentials.
+impl std::fmt::Display for Composition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut env: Vec<&str> = self.env.keys().map(|k| k.as_str()).collect();
+        env.sort_unstable();
+
+        write!(
+            f,
+            "`{}` from image `{}`, start policy: {:?}, env: [{}]",
+            self.handle(),
+            self.image,
+            self.start_policy,
+            env.join(", ")
+        )?;
+
+        if let Some(wait) = &self.wait {
+            write!(f, ", wait: {:?}", wait)?;
+        }
+
+        if let Some(management) = &self.management {
+            write!(f, ", management: {:?}", management)?;
+        }
+        if !self.port.is_empty() {
+            let ports: Vec<String> = self
+                .port
+                .iter()
+                .map(|(exposed, host)| format!("{} -> {}", exposed, host))
+                .collect();
+            write!(f, ", ports: [{}]", ports.join(", "))?;
+        }
+        if self.publish_all_ports {
+            write!(f, ", publish all ports")?;
+        }
+        if !self.named_volumes.is_empty() {
+            let volumes: Vec<String> = self
+                .named_volumes
+                .iter()
+                .map(|(name, path)| format!("{}:{}", name, path))
+                .collect();
+            write!(f, ", named volumes: [{}]", volumes.join(", "))?;
+        }
+        if !self.bind_mounts.is_empty() {
+            let mounts: Vec<String> = self
+                .bind_mounts
+                .iter()
+                .map(|(host, container, options)| bind_mount_spec(host, container, options))
+                .collect();
+            write!(f, ", bind mounts: [{}]", mounts.join(", "))?;
+        }
+        if !self.named_pipes.is_empty() {
+            let pipes: Vec<String> = self
+                .named_pipes
+                .iter()
+                .map(|(host, container)| format!("{} -> {}", host, container))
+                .collect();
+            write!(f, ", named pipes: [{}]", pipes.join(", "))?;
+        }
+        if let Some(isolation) = &self.isolation {
+            write!(f, ", isolation: {:?}", isolation)?;
+        }
+        if self.privileged {
+            write!(f, ", privileged")?;
+        }
+
+        Ok(())
+    }
+}
+
+// Formats the bind mount as understood by the docker daemon.
+fn bind_mount_spec(host_path: &str, path_in_container: &str, options: &BindMountOptions) -> String {
+    let mut flags: Vec<&str> = Vec::new();
+    if options.read_only {
+        flags.push("ro");
+    }
+    // Propagation and SELinux labels do not apply to Windows containers.
+    if !is_windows_path(path_in_container) {
+        if let Some(propagation) = options.propagation {
+            flags.push(propagation.as_str());
+        }
+        // Relabeling is needed due to permission issues under SELinux, see
+        // https://stackoverflow.com/questions/24288616/permission-denied-on-accessing-host-directory-in-docker
+        // for more details
+        match options.selinux_relabel {
+            Some(SelinuxRelabel::Shared) => flags.push("z"),
+            Some(SelinuxRelabel::Private) => flags.push("Z"),
+            None => (),
+        }
+    }
+
+    if flags.is_empty() {
+        format!("{}:{}", host_path, path_in_container)
+    } else {
+        format!("{}:{}:{}", host_path, path_in_container, flags.join(","))
+    }
+}
+
+/// Whether the path is an absolute Windows path, e.g., `C:\data`.
+pub(crate) fn is_windows_path(path: &str) -> bool {
+    let mut chars = path.chars();
+    matches!(
+        (chars.next(), chars.next(), chars.next()),
+        (Some(drive), Some(':'), Some('\\' | '/')) if drive.is_ascii_alphabetic()
+    )
+}
+
+/// Check that the path is an absolute path within a linux or Windows container.
+pub(crate) fn check_container_path(path: &str) -> Result<(), String> {
+    if path.starts_with('/') || is_windows_path(path) {
+        Ok(())
+    } else {
+        Err("the path must be absolute, e.g., `/data` or `C:\\data`".to_string())
+    }
+}
+
+/// Check that the path is that of a Windows named pipe.
+pub(crate) fn check_named_pipe(path: &str) -> Result<(), String> {
+    let prefix = r"\\.\pipe\";
+    if path.len() > prefix.len() && path.starts_with(prefix) {
+        Ok(())
+    } else {
+        Err(format!(
+            "the path must name a pipe, e.g., `{}docker_engine`",
+            prefix
+        ))
+    }
+}
+
+// Returns the id and labels of the given container if it exists.
+async fn existing_container(
+    client: &Docker,
+    name: &str,
+) -> Result<Option<(String, HashMap<String, String>)>, DockerTestError> {
+    match client
         .inspect_container(name, None::<InspectContainerOptions>)
-        .map_err(|e| DockerTestError::Recoverable(format!("container did not exist: {}", e)))
-        .await?;
+        .await
+    {
+        Ok(details) => {
+            let labels = details.config.and_then(|c| c.labels).unwrap_or_default();
+            Ok(details.id.map(|id| (id, labels)))
+        }
+        Err(e) if is_not_found(&e) => {
+            trace!("container `{}` did not exist: {}", name, e);
+            Ok(None)
+        }
+        Err(e) => Err(daemon_error("failed to inspect existing container", e)),
+    }
+}
 
-    // We were able to inspect it successfully, it exists.
-    // Therefore, we can simply force remove it.
+// Forcefully removes the given existing container.
+async fn remove_existing_container(client: &Docker, name: &str) -> Result<(), DockerTestError> {
     let options = Some(RemoveContainerOptions {
         force: true,
         ..Default::default()
@@ -745,3 +2230,23 @@ async fn remove_container_if_exists(client: &Docker, name: &str) -> Result<(), D
         .map_err(|e| DockerTestError::Daemon(format!("failed to remove existing container: {}", e)))
         .await
 }
+
+// Removes the sysctls configured through the raw config hook that cannot be set within a user
+// namespace, i.e., every sysctl outside of the network namespace.
+fn skip_user_namespaced_sysctls(handle: &str, host: &mut HostConfig) {
+    if let Some(sysctls) = host.sysctls.as_mut() {
+        sysctls.retain(|key, _| {
+            let supported = key.starts_with("net.");
+            if !supported {
+                event!(
+                    Level::WARN,
+                    "skipping sysctl `{}` of container `{}`, which cannot be set within the user \
+                     namespace of the docker daemon",
+                    key,
+                    handle
+                );
+            }
+            supported
+        });
+    }
+}
@@ -1,13 +1,23 @@
 //! Represents the multiple phases and variants a docker container exists in dockertest.
 
 mod cleanup;
+mod connection;
+mod expect_logs;
 mod pending;
 mod running;
+mod stats;
 
-pub(crate) use cleanup::CleanupContainer;
+pub(crate) use cleanup::{CleanupContainer, FollowedOutput};
+pub use connection::ConnectionKind;
+pub use expect_logs::LogExpectation;
+pub(crate) use pending::recent_logs;
 pub use pending::PendingContainer;
-pub(crate) use running::HostPortMappings;
-pub use running::RunningContainer;
+pub(crate) use running::{exec_output, ExecOutput, HostPortMappings};
+pub use running::{
+    ContainerPort, FsChange, FsChangeKind, HealthStatus, Process, Protocol, ResourceUpdate,
+    RunningContainer,
+};
+pub use stats::{StatsFormat, StatsRecorder, StatsSample};
 
 /// Represents an exisiting static external container.
 ///
@@ -18,6 +28,8 @@ pub struct StaticExternalContainer {
     pub id: String,
 }
 
+// NOTE: allowing this clippy warning, as containers are only created once per test
+#[allow(clippy::large_enum_variant)]
 pub enum CreatedContainer {
     StaticExternal(StaticExternalContainer),
     Pending(PendingContainer),
@@ -44,7 +56,7 @@ mod tests {
             &self,
             container: PendingContainer,
         ) -> Result<RunningContainer, DockerTestError> {
-            let mut invoked = self.invoked.write().expect("failed to take invoked lock");
+            let mut invoked = self.invoked.write().unwrap_or_else(|e| e.into_inner());
             *invoked = true;
             Ok(container.into())
         }
@@ -54,6 +66,8 @@ mod tests {
     // during the start method of Composition
     #[tokio::test]
     async fn test_wait_for_invoked_during_start() {
+        crate::selftest_images::ensure().await.unwrap();
+
         let wait_for = TestWaitFor {
             invoked: Arc::new(RwLock::new(false)),
         };
@@ -87,7 +101,7 @@ mod tests {
         let was_invoked = wrapped_wait_for
             .invoked
             .read()
-            .expect("failed to get read lock");
+            .unwrap_or_else(|e| e.into_inner());
 
         assert!(
             *was_invoked,
@@ -3,14 +3,160 @@
 use crate::{
     composition::{LogAction, LogOptions},
     container::{PendingContainer, RunningContainer},
+    secrets::Redactions,
     DockerTestError, LogSource,
 };
 
 use bollard::{container::LogOutput, Docker};
 use futures::StreamExt;
-use tracing::info;
+use lazy_static::lazy_static;
+use tracing::{event, info, Level};
 
+use std::collections::VecDeque;
 use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// How long teardown awaits the followers of containers that are not stopped, e.g., as they are
+/// left running, before the followers are aborted.
+const FOLLOW_GRACE: Duration = Duration::from_secs(2);
+
+/// The number of bytes of followed output buffered per container, beyond which the oldest
+/// output is discarded.
+const FOLLOW_BUFFER_LIMIT: usize = 1024 * 1024;
+
+lazy_static! {
+    /// Whether the test harness captures the output of each test, as it does unless instructed
+    /// otherwise through its arguments or `RUST_TEST_NOCAPTURE`.
+    static ref CAPTURED: bool = {
+        let flag = std::env::args().any(|arg| arg == "--nocapture" || arg == "--no-capture");
+        let env = matches!(std::env::var("RUST_TEST_NOCAPTURE"), Ok(value) if value != "0");
+        !flag && !env
+    };
+}
+
+/// The output stream of the test process container logs are forwarded to.
+#[derive(Clone, Copy, Debug)]
+enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    /// Print the message to the output of the test process.
+    ///
+    /// The test harness only captures output printed through the macros of the standard library,
+    /// which it shows once the test fails. These are therefore used while the output is
+    /// captured, escaping output that is not valid UTF-8. Otherwise, the message is written as
+    /// is to the handles of the process, such that a closed output fails rather than panics.
+    fn print(&self, message: &[u8]) -> Result<(), DockerTestError> {
+        if *CAPTURED {
+            let message = escape_utf8(message);
+            match self {
+                OutputStream::Stdout => print!("{}", message),
+                OutputStream::Stderr => eprint!("{}", message),
+            }
+            return Ok(());
+        }
+
+        let (result, name) = match self {
+            OutputStream::Stdout => (io::stdout().write_all(message), "stdout"),
+            OutputStream::Stderr => (io::stderr().write_all(message), "stderr"),
+        };
+        result.map_err(|error| DockerTestError::LogWriteError(format!("{}: {}", name, error)))
+    }
+}
+
+/// The message as text, escaping the bytes that are not valid UTF-8, e.g., of binary output.
+fn escape_utf8(mut message: &[u8]) -> String {
+    let mut escaped = String::with_capacity(message.len());
+    loop {
+        match std::str::from_utf8(message) {
+            Ok(valid) => {
+                escaped.push_str(valid);
+                return escaped;
+            }
+            Err(e) => {
+                let (valid, rest) = message.split_at(e.valid_up_to());
+                // The prefix was just validated.
+                escaped.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                let invalid = e.error_len().unwrap_or(rest.len());
+                for byte in &rest[..invalid] {
+                    escaped.push_str(&format!("\\x{:02x}", byte));
+                }
+                message = &rest[invalid..];
+            }
+        }
+    }
+}
+
+/// The output of a container followed in the background, see
+/// [LogPolicy::Follow](crate::LogPolicy::Follow).
+///
+/// The output captured for a test is bound to the thread executing it, which the background
+/// task does not execute on. The output is therefore buffered, and printed once the test is torn
+/// down, such that the output of parallel tests is not interleaved. Only the most recent
+/// [FOLLOW_BUFFER_LIMIT] bytes are buffered.
+#[derive(Clone, Default)]
+pub(crate) struct FollowedOutput {
+    output: Arc<Mutex<FollowedBuffer>>,
+    /// The task following the logs, awaited during teardown such that no output is lost.
+    task: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+/// A chunk of followed output, along with the stream it is forwarded to.
+type FollowedEntry = (OutputStream, Vec<u8>);
+
+/// The buffered followed output, bounded by [FOLLOW_BUFFER_LIMIT].
+#[derive(Default)]
+struct FollowedBuffer {
+    entries: VecDeque<FollowedEntry>,
+    /// The number of bytes buffered.
+    size: usize,
+    /// The number of bytes discarded to respect the limit since the last flush.
+    discarded: usize,
+}
+
+impl FollowedOutput {
+    fn push(&self, stream: OutputStream, message: Vec<u8>) {
+        let mut output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        output.size += message.len();
+        output.entries.push_back((stream, message));
+        while output.size > FOLLOW_BUFFER_LIMIT {
+            let discarded = match output.entries.pop_front() {
+                Some((_, message)) => message.len(),
+                None => break,
+            };
+            output.size -= discarded;
+            output.discarded += discarded;
+        }
+    }
+
+    /// Print the buffered output, clearing the buffer.
+    fn flush(&self) -> Result<(), DockerTestError> {
+        let output = std::mem::take(&mut *self.output.lock().unwrap_or_else(|e| e.into_inner()));
+        if output.discarded > 0 {
+            let notice = format!(
+                "[dockertest] discarded {} bytes of earlier followed output\n",
+                output.discarded
+            );
+            OutputStream::Stderr.print(notice.as_bytes())?;
+        }
+        for (stream, message) in output.entries {
+            stream.print(&message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for FollowedOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The output itself is deliberately omitted, as it may be arbitrarily long.
+        let output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        write!(f, "FollowedOutput({} entries)", output.entries.len())
+    }
+}
 
 /// A container representation of a pending or running container, that requires us to
 /// perform cleanup on it.
@@ -21,13 +167,21 @@ use std::io::{self, Write};
 pub(crate) struct CleanupContainer {
     pub(crate) id: String,
     is_static: bool,
+    /// Whether this is an existing container reused by the test, which outlives the test.
+    reused: bool,
     /// The generated docker name for this container.
     pub(crate) name: String,
+    /// The handle of this container within the test body.
+    pub(crate) handle: String,
     /// Client obtained from `PendingContainer` or `RunningContainer`, we need it because
     /// we want to call `client.logs` to get container logs.
     pub(crate) client: Docker,
     /// Container log options.
     pub(crate) log_options: Option<LogOptions>,
+    /// The secret values to redact from the forwarded logs.
+    redactions: Redactions,
+    /// The followed output awaiting to be forwarded to the test process.
+    followed: FollowedOutput,
 }
 
 impl CleanupContainer {
@@ -35,45 +189,48 @@ impl CleanupContainer {
         self.is_static
     }
 
-    /// Handle one log entry.
+    pub(crate) fn is_reused(&self) -> bool {
+        self.reused
+    }
+
+    /// Handle one log entry, redacting the values of secrets.
+    ///
+    /// Followed output forwarded to the test process is buffered instead, see [FollowedOutput].
     async fn handle_log_line(
         &self,
         action: &LogAction,
         output: LogOutput,
         file: &mut Option<tokio::fs::File>,
+        follow: bool,
     ) -> Result<(), DockerTestError> {
-        let write_to_stdout = |message| {
-            io::stdout()
-                .write(message)
-                .map_err(|error| DockerTestError::LogWriteError(format!("stdout: {}", error)))?;
-            Ok(())
-        };
-
-        let write_to_stderr = |message| {
-            io::stderr()
-                .write(message)
-                .map_err(|error| DockerTestError::LogWriteError(format!("stderr: {}", error)))?;
-            Ok(())
+        let forward = |stream: OutputStream, message: &[u8]| {
+            let message = self.redactions.redact_bytes(message);
+            if follow {
+                self.followed.push(stream, message);
+                Ok(())
+            } else {
+                stream.print(&message)
+            }
         };
 
         match action {
             // forward-only, print stdout/stderr output to current process stdout/stderr
             LogAction::Forward => match output {
-                LogOutput::StdOut { message } => write_to_stdout(&message[..]),
-                LogOutput::StdErr { message } => write_to_stderr(&message[..]),
+                LogOutput::StdOut { message } => forward(OutputStream::Stdout, &message[..]),
+                LogOutput::StdErr { message } => forward(OutputStream::Stderr, &message[..]),
                 LogOutput::StdIn { .. } | LogOutput::Console { .. } => Ok(()),
             },
             // forward everything to stderr
             LogAction::ForwardToStdErr => match output {
                 LogOutput::StdOut { message } | LogOutput::StdErr { message } => {
-                    write_to_stderr(&message[..])
+                    forward(OutputStream::Stderr, &message[..])
                 }
                 LogOutput::StdIn { .. } | LogOutput::Console { .. } => Ok(()),
             },
             // forward everything to stdout
             LogAction::ForwardToStdOut => match output {
                 LogOutput::StdOut { message } | LogOutput::StdErr { message } => {
-                    write_to_stdout(&message[..])
+                    forward(OutputStream::Stdout, &message[..])
                 }
                 LogOutput::StdIn { .. } | LogOutput::Console { .. } => Ok(()),
             },
@@ -83,15 +240,16 @@ impl CleanupContainer {
                     use tokio::io::AsyncWriteExt;
 
                     if let Some(ref mut file) = file {
-                        file.write(&message[..])
+                        file.write(&self.redactions.redact_bytes(&message[..]))
                             .await
+                            // Followed logs must reach the file before the process may be killed.
+                            .and(file.flush().await)
                             .map_err(|error| {
                                 DockerTestError::LogWriteError(format!(
                                     "unable to write to log file: {}",
                                     error
                                 ))
                             })
-                            .map(|_| ())
                     } else {
                         Err(DockerTestError::LogWriteError(
                             "log file should not be None".to_string(),
@@ -108,6 +266,71 @@ impl CleanupContainer {
         &self,
         action: &LogAction,
         source: &LogSource,
+    ) -> Result<(), DockerTestError> {
+        self.read_logs(action, source, false).await
+    }
+
+    /// Follow the container logs in the background until the container stops, see
+    /// [LogPolicy::Follow](crate::LogPolicy::Follow).
+    ///
+    /// Output forwarded to the test process is buffered until [CleanupContainer::flush_followed],
+    /// as the background task does not print to the output captured for the test.
+    pub(crate) fn follow_logs(self, options: LogOptions) {
+        let task = self.followed.task.clone();
+        let handle = tokio::spawn(async move {
+            if let Err(e) = self.read_logs(&options.action, &options.source, true).await {
+                event!(
+                    Level::WARN,
+                    "unable to follow logs of container `{}`: {}",
+                    self.name,
+                    e
+                );
+            }
+        });
+        *task.lock().unwrap_or_else(|e| e.into_inner()) = Some(handle);
+    }
+
+    /// Await the task following the logs, which completes once the container is stopped.
+    ///
+    /// If the containers are `stopped`, the task is aborted after [FOLLOW_GRACE] if the container
+    /// is still running, e.g., as it is left running by the prune strategy. Otherwise, the task
+    /// is aborted right away, keeping the output buffered so far.
+    pub(crate) async fn finish_following(&self, stopped: bool) {
+        let task = self
+            .followed
+            .task
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let mut task = match task {
+            Some(task) => task,
+            None => return,
+        };
+        let grace = if stopped {
+            FOLLOW_GRACE
+        } else {
+            Duration::from_secs(0)
+        };
+        if tokio::time::timeout(grace, &mut task).await.is_err() {
+            event!(
+                Level::DEBUG,
+                "aborting the log follower of running container `{}`",
+                self.name
+            );
+            task.abort();
+        }
+    }
+
+    /// Print the followed output buffered so far, from the task of the test.
+    pub(crate) fn flush_followed(&self) -> Result<(), DockerTestError> {
+        self.followed.flush()
+    }
+
+    async fn read_logs(
+        &self,
+        action: &LogAction,
+        source: &LogSource,
+        follow: bool,
     ) -> Result<(), DockerTestError> {
         use bollard::container::LogsOptions;
 
@@ -125,6 +348,7 @@ impl CleanupContainer {
         };
 
         let options = Some(LogsOptions::<String> {
+            follow,
             stdout: should_log_stdout,
             stderr: should_log_stderr,
             ..Default::default()
@@ -154,7 +378,10 @@ impl CleanupContainer {
 
         while let Some(data) = stream.next().await {
             match data {
-                Ok(line) => self.handle_log_line(action, line, &mut file).await?,
+                Ok(line) => {
+                    self.handle_log_line(action, line, &mut file, follow)
+                        .await?
+                }
                 Err(error) => {
                     return Err(DockerTestError::LogWriteError(format!(
                         "unable to read docker log: {}",
@@ -173,9 +400,13 @@ impl From<PendingContainer> for CleanupContainer {
         CleanupContainer {
             id: container.id,
             is_static: container.is_static,
+            reused: container.reused,
             client: container.client,
             log_options: container.log_options,
             name: container.name,
+            handle: container.handle,
+            redactions: container.redactions,
+            followed: container.followed,
         }
     }
 }
@@ -185,9 +416,13 @@ impl From<&PendingContainer> for CleanupContainer {
         CleanupContainer {
             id: container.id.clone(),
             is_static: container.is_static,
+            reused: container.reused,
             client: container.client.clone(),
             log_options: container.log_options.clone(),
             name: container.name.clone(),
+            handle: container.handle.clone(),
+            redactions: container.redactions.clone(),
+            followed: container.followed.clone(),
         }
     }
 }
@@ -197,9 +432,13 @@ impl From<RunningContainer> for CleanupContainer {
         CleanupContainer {
             id: container.id,
             is_static: container.is_static,
+            reused: container.reused,
             client: container.client,
             log_options: container.log_options,
             name: container.name,
+            handle: container.handle,
+            redactions: container.redactions,
+            followed: container.followed,
         }
     }
 }
@@ -209,9 +448,52 @@ impl From<&RunningContainer> for CleanupContainer {
         CleanupContainer {
             id: container.id.clone(),
             is_static: container.is_static,
+            reused: container.reused,
             client: container.client.clone(),
             log_options: container.log_options.clone(),
             name: container.name.clone(),
+            handle: container.handle.clone(),
+            redactions: container.redactions.clone(),
+            followed: container.followed.clone(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_utf8, FollowedOutput, OutputStream, FOLLOW_BUFFER_LIMIT};
+
+    // Tests that the followed output is buffered until flushed, and shared between clones
+    #[test]
+    fn test_followed_output_buffered_until_flushed() {
+        let output = FollowedOutput::default();
+        let follower = output.clone();
+        follower.push(OutputStream::Stdout, b"started\n".to_vec());
+        follower.push(OutputStream::Stderr, b"warning\n".to_vec());
+        assert_eq!(format!("{:?}", output), "FollowedOutput(2 entries)");
+
+        output.flush().unwrap();
+        assert_eq!(format!("{:?}", follower), "FollowedOutput(0 entries)");
+    }
+
+    // Tests that the oldest followed output is discarded beyond the limit
+    #[test]
+    fn test_followed_output_bounded() {
+        let output = FollowedOutput::default();
+        output.push(OutputStream::Stdout, vec![b'a'; FOLLOW_BUFFER_LIMIT]);
+        output.push(OutputStream::Stdout, b"latest\n".to_vec());
+
+        let buffer = output.output.lock().unwrap();
+        assert_eq!(buffer.entries.len(), 1);
+        assert_eq!(buffer.size, 7);
+        assert_eq!(buffer.discarded, FOLLOW_BUFFER_LIMIT);
+    }
+
+    // Tests that invalid UTF-8 is escaped rather than replaced, keeping valid characters
+    #[test]
+    fn test_escape_utf8() {
+        assert_eq!(escape_utf8("ready ✓\n".as_bytes()), "ready ✓\n");
+        assert_eq!(escape_utf8(b"\x00\xff\xfeok"), "\u{0}\\xff\\xfeok");
+        assert_eq!(escape_utf8(b"cut \xe2\x9c"), "cut \\xe2\\x9c");
+    }
+}
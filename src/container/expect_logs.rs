@@ -0,0 +1,221 @@
+//! Assertions over the captured log output of a container.
+
+use crate::DockerTestError;
+
+use bollard::{container::LogsOptions, Docker};
+use futures::StreamExt;
+
+/// The number of log lines surrounding the relevant line in a failure message.
+const CONTEXT_LINES: usize = 3;
+
+/// Assertions over the log output of a container captured at a point in time, see
+/// [RunningContainer::expect_logs].
+///
+/// Each assertion panics with an excerpt of the log output surrounding the relevant line, and
+/// returns the expectation to chain further assertions:
+///
+/// ```no_run
+/// # use dockertest::RunningContainer;
+/// # async fn example(app: &RunningContainer) {
+/// app.expect_logs()
+///     .await
+///     .contains("started")
+///     .then("listening")
+///     .not_contains("ERROR");
+/// # }
+/// ```
+///
+/// [RunningContainer::expect_logs]: crate::RunningContainer::expect_logs
+#[derive(Clone, Debug)]
+pub struct LogExpectation {
+    handle: String,
+    lines: Vec<String>,
+    /// The index of the line matched by the previous assertion.
+    position: Option<usize>,
+}
+
+impl LogExpectation {
+    pub(crate) fn new(handle: &str, logs: &str) -> LogExpectation {
+        LogExpectation {
+            handle: handle.to_string(),
+            lines: logs.lines().map(|l| l.to_string()).collect(),
+            position: None,
+        }
+    }
+
+    /// The captured log lines.
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Assert that a log line contains `pattern`.
+    ///
+    /// The first such line becomes the position subsequent [LogExpectation::then] assertions
+    /// continue from.
+    #[track_caller]
+    pub fn contains(mut self, pattern: &str) -> LogExpectation {
+        match self.find(0, pattern) {
+            Some(index) => {
+                self.position = Some(index);
+                self
+            }
+            None => panic!(
+                "expected a log line of container `{}` containing `{}`, found none:\n{}",
+                self.handle,
+                pattern,
+                self.excerpt(self.lines.len().saturating_sub(1))
+            ),
+        }
+    }
+
+    /// Assert that a log line following the line matched by the previous assertion contains
+    /// `pattern`. Without a previous assertion, this is equivalent to [LogExpectation::contains].
+    #[track_caller]
+    pub fn then(mut self, pattern: &str) -> LogExpectation {
+        let start = self.position.map(|p| p + 1).unwrap_or(0);
+        match self.find(start, pattern) {
+            Some(index) => {
+                self.position = Some(index);
+                self
+            }
+            None => {
+                let (after, excerpt) = match self.position {
+                    Some(p) => (format!(" after line {}", p + 1), self.excerpt(p)),
+                    None => (
+                        String::new(),
+                        self.excerpt(self.lines.len().saturating_sub(1)),
+                    ),
+                };
+                panic!(
+                    "expected a log line of container `{}` containing `{}`{}, found none:\n{}",
+                    self.handle, pattern, after, excerpt
+                )
+            }
+        }
+    }
+
+    /// Assert that no log line contains `pattern`.
+    #[track_caller]
+    pub fn not_contains(self, pattern: &str) -> LogExpectation {
+        if let Some(index) = self.find(0, pattern) {
+            panic!(
+                "expected no log line of container `{}` containing `{}`, found line {}:\n{}",
+                self.handle,
+                pattern,
+                index + 1,
+                self.excerpt(index)
+            );
+        }
+        self
+    }
+
+    fn find(&self, start: usize, pattern: &str) -> Option<usize> {
+        self.lines
+            .iter()
+            .enumerate()
+            .skip(start)
+            .find(|(_, line)| line.contains(pattern))
+            .map(|(index, _)| index)
+    }
+
+    /// The lines surrounding the line at `index`, numbered and with the line itself marked.
+    fn excerpt(&self, index: usize) -> String {
+        if self.lines.is_empty() {
+            return "  (no log output)".to_string();
+        }
+
+        let start = index.saturating_sub(CONTEXT_LINES);
+        let end = (index + CONTEXT_LINES + 1).min(self.lines.len());
+        let width = end.to_string().len();
+        let mut excerpt: Vec<String> = (start..end)
+            .map(|i| {
+                let marker = if i == index { '>' } else { ' ' };
+                format!(
+                    "{} {:>width$} | {}",
+                    marker,
+                    i + 1,
+                    self.lines[i],
+                    width = width
+                )
+            })
+            .collect();
+        if start > 0 {
+            excerpt.insert(0, format!("  ({} lines omitted)", start));
+        }
+        if end < self.lines.len() {
+            excerpt.push(format!("  ({} lines omitted)", self.lines.len() - end));
+        }
+        excerpt.join("\n")
+    }
+}
+
+/// Retrieve the complete log output of the container, both stdout and stderr.
+pub(crate) async fn captured_logs(client: &Docker, name: &str) -> Result<String, DockerTestError> {
+    let options = Some(LogsOptions::<String> {
+        stdout: true,
+        stderr: true,
+        ..Default::default()
+    });
+
+    let mut logs = String::new();
+    let mut stream = client.logs(name, options);
+    while let Some(data) = stream.next().await {
+        match data {
+            Ok(line) => logs.push_str(&line.to_string()),
+            Err(e) => {
+                return Err(DockerTestError::Daemon(format!(
+                    "failed to retrieve logs of container `{}`: {}",
+                    name, e
+                )))
+            }
+        }
+    }
+    Ok(logs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LogExpectation;
+
+    const LOGS: &str = "booting\nstarted\nWARN slow disk\nlistening on 8080\nready\n";
+
+    // Tests that chained assertions pass on log lines in the expected order
+    #[test]
+    fn test_log_expectation_in_order() {
+        let logs = LogExpectation::new("app", LOGS)
+            .contains("started")
+            .then("listening")
+            .then("ready")
+            .not_contains("ERROR");
+        assert_eq!(logs.lines().len(), 5);
+    }
+
+    // Tests that a line preceding the previous match fails the assertion
+    #[test]
+    #[should_panic(expected = "containing `booting` after line 4")]
+    fn test_log_expectation_out_of_order() {
+        LogExpectation::new("app", LOGS)
+            .contains("listening")
+            .then("booting");
+    }
+
+    // Tests that the failure message marks the offending line within its surrounding lines
+    #[test]
+    fn test_log_expectation_excerpt() {
+        let logs = LogExpectation::new("app", LOGS);
+        assert_eq!(
+            logs.excerpt(2),
+            "  1 | booting\n  2 | started\n> 3 | WARN slow disk\n  4 | listening on 8080\n  5 | ready"
+        );
+
+        let logs = LogExpectation::new("app", &"line\n".repeat(20));
+        assert_eq!(
+            logs.excerpt(10),
+            "  (7 lines omitted)\n   8 | line\n   9 | line\n  10 | line\n> 11 | line\n  12 | line\n  13 | line\n  14 | line\n  (6 lines omitted)"
+        );
+        assert_eq!(
+            LogExpectation::new("app", "").excerpt(0),
+            "  (no log output)"
+        );
+    }
+}
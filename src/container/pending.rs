@@ -1,14 +1,31 @@
 //! Represents a created container, in transit to become a RunningContainer.
 
 use crate::{
-    composition::{LogOptions, StaticManagementPolicy},
-    container::RunningContainer,
+    composition::{LogOptions, LogPolicy, OnStarted, StaticManagementPolicy},
+    container::{CleanupContainer, FollowedOutput, RunningContainer},
+    image::platform_mismatch,
+    retry::{daemon_error, RetryPolicy},
+    secrets::Redactions,
     static_container::STATIC_CONTAINERS,
+    throttle,
+    utils::Timestamp,
     waitfor::WaitFor,
     DockerTestError, StartPolicy,
 };
 
-use bollard::{container::StartContainerOptions, errors::Error, Docker};
+use bollard::{
+    container::{LogsOptions, StartContainerOptions},
+    errors::Error,
+    Docker,
+};
+use futures::StreamExt;
+use tracing::{event, span, Instrument, Level};
+
+/// The number of log lines included in the error of a container that failed to start.
+const STARTUP_ERROR_LOG_LINES: usize = 50;
+
+/// The maximum length of the logs included in the error of a container that failed to start.
+const STARTUP_ERROR_LOG_LENGTH: usize = 4096;
 
 /// Represent a docker container object in a pending phase between
 /// it being created on the daemon, but may not be running.
@@ -45,6 +62,31 @@ pub struct PendingContainer {
 
     /// Container log options, they are provided by `Composition`.
     pub(crate) log_options: Option<LogOptions>,
+
+    /// How to retry starting the container, provided by `Composition`.
+    pub(crate) retry_policy: RetryPolicy,
+
+    /// The secret values to redact from the logs of the container, provided by `Composition`.
+    pub(crate) redactions: Redactions,
+
+    /// The callback invoked once the container is started, provided by `Composition`.
+    pub(crate) on_started: Option<OnStarted>,
+
+    /// Whether the container is expected to exit during the test, provided by `Composition`.
+    pub(crate) expect_exit: bool,
+
+    /// Whether this is an existing container reused by the test, which outlives the test, see
+    /// [ExistingContainerPolicy::Reuse](crate::ExistingContainerPolicy::Reuse).
+    pub(crate) reused: bool,
+
+    /// The output followed in the background, forwarded once the test is torn down.
+    pub(crate) followed: FollowedOutput,
+
+    /// When the container was last started, if started before it is awaited, such that its
+    /// earlier logs are disregarded, see [DockerOperations::wait_for].
+    ///
+    /// [DockerOperations::wait_for]: crate::DockerOperations::wait_for
+    pub(crate) logs_since: Option<Timestamp>,
 }
 
 impl PendingContainer {
@@ -71,9 +113,31 @@ impl PendingContainer {
             is_static: static_management_policy.is_some(),
             static_management_policy,
             log_options,
+            retry_policy: RetryPolicy::default(),
+            redactions: Redactions::default(),
+            on_started: None,
+            expect_exit: false,
+            reused: false,
+            followed: FollowedOutput::default(),
+            logs_since: None,
         }
     }
 
+    /// A container of the given name and handle that is never started, for unit tests.
+    #[cfg(test)]
+    pub(crate) fn fixture(name: &str, handle: &str) -> PendingContainer {
+        PendingContainer::new(
+            name,
+            "id",
+            handle,
+            StartPolicy::Relaxed,
+            Box::new(crate::waitfor::NoWait {}),
+            crate::utils::connect_with_local_or_tls_defaults().unwrap(),
+            None,
+            None,
+        )
+    }
+
     /// Run the start command and initiate the WaitFor condition.
     /// Once the PendingContainer is successfully started and the WaitFor condition
     /// has been achived, the RunningContainer is returned.
@@ -87,44 +151,177 @@ impl PendingContainer {
 
     /// Internal start method should only be invoked from the static mod.
     pub(crate) async fn start_internal(mut self) -> Result<RunningContainer, DockerTestError> {
-        self.client
-            .start_container(&self.name, None::<StartContainerOptions<String>>)
+        let client = &self.client;
+        let name = &self.name;
+        self.retry_policy
+            .retry("start container", || async {
+                // The permit is not held across the backoff between attempts, nor while awaiting
+                // the wait condition, which may take arbitrarily long.
+                let _permit = throttle::daemon_operation().await;
+                client
+                    .start_container(name, None::<StartContainerOptions<String>>)
+                    .await
+            })
+            .instrument(span!(Level::INFO, "start", handle = %self.handle))
             .await
+            .or_else(|e| match e {
+                // A reused container may already be running.
+                Error::DockerResponseServerError {
+                    status_code: 304, ..
+                } => Ok(()),
+                e => Err(e),
+            })
             .map_err(|e| match e {
                 Error::DockerResponseServerError {
                     message,
-                    status_code,
+                    status_code: 404,
                 } => {
-                    if status_code == 404 {
-                        let json: Result<serde_json::Value, serde_json::error::Error> =
-                            serde_json::from_str(message.as_str());
-                        match json {
-                            Ok(json) => DockerTestError::Startup(format!(
-                                "failed to start container due to `{}`",
-                                json["message"].as_str().unwrap()
-                            )),
-                            Err(e) => DockerTestError::Daemon(format!(
-                                "daemon json response decode failure: {}",
-                                e
-                            )),
-                        }
-                    } else {
-                        DockerTestError::Daemon(format!("failed to start container: {}", message))
+                    let json: Result<serde_json::Value, serde_json::error::Error> =
+                        serde_json::from_str(message.as_str());
+                    match json {
+                        Ok(json) => DockerTestError::Startup(format!(
+                            "failed to start container due to `{}`",
+                            json["message"].as_str().unwrap()
+                        )),
+                        Err(e) => DockerTestError::Daemon(format!(
+                            "daemon json response decode failure: {}",
+                            e
+                        )),
+                    }
+                }
+                e => {
+                    let subject = format!("container `{}`", self.handle);
+                    match platform_mismatch(&subject, &e.to_string()) {
+                        Some(mismatch) => mismatch,
+                        None => daemon_error("failed to start container", e),
                     }
                 }
-                _ => DockerTestError::Daemon(format!("failed to start container: {}", e)),
             })?;
 
+        if let Some(options) = self
+            .log_options
+            .clone()
+            .filter(|options| matches!(options.policy, LogPolicy::Follow))
+        {
+            CleanupContainer::from(&self).follow_logs(options);
+        }
+
         let waitfor = self.wait.take().unwrap();
+        let client = self.client.clone();
+        let name = self.name.clone();
+        let redactions = self.redactions.clone();
+        let on_started = self.on_started.take();
+        let handle = self.handle.clone();
+
+        // Issue WaitFor operation, within a span such that the events of the implementation are
+        // attributed to this container.
+        let span = span!(Level::INFO, "wait", handle = %self.handle);
+        let running = match waitfor.wait_for_ready(self).instrument(span).await {
+            // Include the most recent logs, such that the failure can be diagnosed from the
+            // error alone.
+            Err(DockerTestError::Startup(msg)) => match recent_logs(&client, &name).await {
+                Some(logs) => Err(DockerTestError::Startup(format!(
+                    "{}\n--- last log lines of container `{}` ---\n{}",
+                    msg,
+                    name,
+                    redactions.redact(&logs)
+                ))),
+                None => Err(DockerTestError::Startup(msg)),
+            },
+            res => res,
+        }?;
 
-        // Issue WaitFor operation
-        let res = waitfor.wait_for_ready(self);
-        res.await
+        if let Some(on_started) = on_started {
+            let span = span!(Level::INFO, "on_started", handle = %handle);
+            on_started
+                .invoke(running.clone())
+                .instrument(span)
+                .await
+                .map_err(|e| {
+                    DockerTestError::Startup(format!(
+                        "the started callback of container `{}` failed: {}",
+                        handle, e
+                    ))
+                })?;
+        }
+
+        Ok(running)
     }
 }
 
+impl From<&RunningContainer> for PendingContainer {
+    /// The already started container, to await a [WaitFor] condition from within the test body.
+    fn from(container: &RunningContainer) -> PendingContainer {
+        PendingContainer {
+            client: container.client.clone(),
+            name: container.name.clone(),
+            id: container.id.clone(),
+            handle: container.handle.clone(),
+            start_policy: StartPolicy::Relaxed,
+            wait: None,
+            is_static: container.is_static,
+            static_management_policy: None,
+            log_options: container.log_options.clone(),
+            retry_policy: RetryPolicy::default(),
+            redactions: container.redactions.clone(),
+            on_started: None,
+            expect_exit: container.expect_exit,
+            reused: container.reused,
+            followed: container.followed.clone(),
+            logs_since: None,
+        }
+    }
+}
+
+/// Retrieve the most recent log lines of the container, if any.
+pub(crate) async fn recent_logs(client: &Docker, name: &str) -> Option<String> {
+    let options = Some(LogsOptions::<String> {
+        stdout: true,
+        stderr: true,
+        tail: STARTUP_ERROR_LOG_LINES.to_string(),
+        ..Default::default()
+    });
+
+    let mut logs = String::new();
+    let mut stream = client.logs(name, options);
+    while let Some(data) = stream.next().await {
+        match data {
+            Ok(line) => logs.push_str(&line.to_string()),
+            Err(e) => {
+                event!(
+                    Level::DEBUG,
+                    "failed to retrieve logs of container `{}`: {}",
+                    name,
+                    e
+                );
+                break;
+            }
+        }
+    }
+
+    if logs.trim().is_empty() {
+        None
+    } else {
+        Some(truncate_front(logs.trim_end(), STARTUP_ERROR_LOG_LENGTH))
+    }
+}
+
+/// Keep at most the last `max` bytes of `s`, respecting character boundaries.
+fn truncate_front(s: &str, max: usize) -> String {
+    if s.len() <= max {
+        return s.to_string();
+    }
+
+    let mut start = s.len() - max;
+    while !s.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("[truncated]...{}", &s[start..])
+}
+
 #[cfg(test)]
 mod tests {
+    use super::truncate_front;
     use crate::container::PendingContainer;
     use crate::utils::connect_with_local_or_tls_defaults;
     use crate::waitfor::NoWait;
@@ -159,4 +356,13 @@ mod tests {
             "wrong handle_key set in container creation"
         );
     }
+
+    // Tests that only the end of long logs is kept
+    #[test]
+    fn test_truncate_front() {
+        assert_eq!(truncate_front("short", 10), "short");
+        assert_eq!(truncate_front("0123456789", 4), "[truncated]...6789");
+        // Never split a multi-byte character
+        assert_eq!(truncate_front("aæb", 2), "[truncated]...b");
+    }
 }
@@ -0,0 +1,271 @@
+//! Periodic resource usage samples of a running container.
+
+use crate::DockerTestError;
+
+use bollard::container::{Stats, StatsOptions};
+use bollard::Docker;
+use futures::{Stream, StreamExt};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::{event, Level};
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// A sample of the resource usage of a container.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StatsSample {
+    /// The time elapsed since sampling started.
+    pub elapsed: Duration,
+    /// The CPU usage since the previous sample reported by the daemon, in percent of a single
+    /// CPU, such that a container fully utilizing two CPUs reports `200.0`.
+    pub cpu_percent: f64,
+    /// The memory usage in bytes.
+    pub memory_usage: u64,
+    /// The memory limit in bytes.
+    pub memory_limit: u64,
+    /// The total number of bytes received across all networks.
+    pub network_rx_bytes: u64,
+    /// The total number of bytes transmitted across all networks.
+    pub network_tx_bytes: u64,
+    /// The total number of bytes read from block devices.
+    pub block_read_bytes: u64,
+    /// The total number of bytes written to block devices.
+    pub block_write_bytes: u64,
+}
+
+/// The file format written by a [StatsRecorder].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsFormat {
+    /// Comma separated values, with a header row.
+    Csv,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+impl StatsSample {
+    /// The header row of the [StatsFormat::Csv] format.
+    pub const CSV_HEADER: &'static str = "elapsed_ms,cpu_percent,memory_usage,memory_limit,network_rx_bytes,network_tx_bytes,block_read_bytes,block_write_bytes";
+
+    /// Format this sample as a row of the [StatsFormat::Csv] format.
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{:.2},{},{},{},{},{},{}",
+            self.elapsed.as_millis(),
+            self.cpu_percent,
+            self.memory_usage,
+            self.memory_limit,
+            self.network_rx_bytes,
+            self.network_tx_bytes,
+            self.block_read_bytes,
+            self.block_write_bytes
+        )
+    }
+
+    /// Format this sample as a line of the [StatsFormat::JsonLines] format.
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "elapsed_ms": self.elapsed.as_millis() as u64,
+            "cpu_percent": self.cpu_percent,
+            "memory_usage": self.memory_usage,
+            "memory_limit": self.memory_limit,
+            "network_rx_bytes": self.network_rx_bytes,
+            "network_tx_bytes": self.network_tx_bytes,
+            "block_read_bytes": self.block_read_bytes,
+            "block_write_bytes": self.block_write_bytes,
+        })
+        .to_string()
+    }
+
+    fn from_stats(stats: &Stats, elapsed: Duration) -> StatsSample {
+        let cpu_delta = stats
+            .cpu_stats
+            .cpu_usage
+            .total_usage
+            .saturating_sub(stats.precpu_stats.cpu_usage.total_usage);
+        let system_delta = stats
+            .cpu_stats
+            .system_cpu_usage
+            .unwrap_or_default()
+            .saturating_sub(stats.precpu_stats.system_cpu_usage.unwrap_or_default());
+        let cpus = stats.cpu_stats.online_cpus.unwrap_or(1);
+        let cpu_percent = if system_delta > 0 {
+            cpu_delta as f64 / system_delta as f64 * cpus as f64 * 100.0
+        } else {
+            0.0
+        };
+
+        let (network_rx_bytes, network_tx_bytes) = stats
+            .networks
+            .as_ref()
+            .map(|networks| {
+                networks
+                    .values()
+                    .fold((0, 0), |(rx, tx), n| (rx + n.rx_bytes, tx + n.tx_bytes))
+            })
+            .unwrap_or_default();
+
+        let block_bytes = |op: &str| -> u64 {
+            stats
+                .blkio_stats
+                .io_service_bytes_recursive
+                .as_ref()
+                .map(|entries| {
+                    entries
+                        .iter()
+                        .filter(|e| e.op.eq_ignore_ascii_case(op))
+                        .map(|e| e.value)
+                        .sum()
+                })
+                .unwrap_or_default()
+        };
+
+        StatsSample {
+            elapsed,
+            cpu_percent,
+            memory_usage: stats.memory_stats.usage.unwrap_or_default(),
+            memory_limit: stats.memory_stats.limit.unwrap_or_default(),
+            network_rx_bytes,
+            network_tx_bytes,
+            block_read_bytes: block_bytes("read"),
+            block_write_bytes: block_bytes("write"),
+        }
+    }
+}
+
+/// Stream samples of the container, at most one per `interval`.
+pub(crate) fn stats_stream(
+    client: &Docker,
+    name: &str,
+    handle: &str,
+    interval: Duration,
+) -> impl Stream<Item = Result<StatsSample, DockerTestError>> {
+    let handle = handle.to_string();
+    let start = Instant::now();
+    let mut last: Option<Instant> = None;
+
+    let options = Some(StatsOptions {
+        stream: true,
+        one_shot: false,
+    });
+    client.stats(name, options).filter_map(move |stats| {
+        let sample = match stats {
+            Ok(stats) => {
+                let now = Instant::now();
+                match last {
+                    // The daemon reports roughly every second, we only keep one per interval.
+                    Some(l) if now.duration_since(l) < interval => None,
+                    _ => {
+                        last = Some(now);
+                        Some(Ok(StatsSample::from_stats(&stats, now - start)))
+                    }
+                }
+            }
+            Err(e) => Some(Err(DockerTestError::Daemon(format!(
+                "failed to retrieve stats of container `{}`: {}",
+                handle, e
+            )))),
+        };
+        futures::future::ready(sample)
+    })
+}
+
+/// Records samples of a container to a file in the background, until stopped.
+///
+/// Constructed through [RunningContainer::record_stats](crate::RunningContainer::record_stats).
+#[derive(Debug)]
+pub struct StatsRecorder {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<Result<usize, DockerTestError>>,
+}
+
+impl StatsRecorder {
+    pub(crate) fn start<S>(stream: S, path: PathBuf, format: StatsFormat) -> StatsRecorder
+    where
+        S: Stream<Item = Result<StatsSample, DockerTestError>> + Send + 'static,
+    {
+        let (stop, mut stopped) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            let write_error = |e: std::io::Error| {
+                DockerTestError::Processing(format!("failed to write stats to {:?}: {}", path, e))
+            };
+
+            let mut file = tokio::fs::File::create(&path).await.map_err(write_error)?;
+            if format == StatsFormat::Csv {
+                file.write_all(format!("{}\n", StatsSample::CSV_HEADER).as_bytes())
+                    .await
+                    .map_err(write_error)?;
+            }
+
+            let mut stream = Box::pin(stream);
+            let mut written = 0;
+            loop {
+                let sample = tokio::select! {
+                    _ = &mut stopped => break,
+                    sample = stream.next() => match sample {
+                        Some(sample) => sample?,
+                        // The container has stopped
+                        None => break,
+                    },
+                };
+
+                let line = match format {
+                    StatsFormat::Csv => sample.to_csv(),
+                    StatsFormat::JsonLines => sample.to_json(),
+                };
+                file.write_all(format!("{}\n", line).as_bytes())
+                    .await
+                    .map_err(write_error)?;
+                written += 1;
+            }
+
+            file.flush().await.map_err(write_error)?;
+            event!(
+                Level::DEBUG,
+                "recorded {} stats samples to {:?}",
+                written,
+                path
+            );
+            Ok(written)
+        });
+
+        StatsRecorder { stop, task }
+    }
+
+    /// Stop recording, returning the number of samples written.
+    pub async fn stop(self) -> Result<usize, DockerTestError> {
+        // The recording may already have completed, if the container stopped.
+        let _ = self.stop.send(());
+        self.task.await.map_err(|e| {
+            DockerTestError::Processing(format!("stats recorder did not complete: {}", e))
+        })?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatsSample;
+    use std::time::Duration;
+
+    // Tests that a sample is formatted consistently with the csv header
+    #[test]
+    fn test_sample_to_csv() {
+        let sample = StatsSample {
+            elapsed: Duration::from_millis(1500),
+            cpu_percent: 12.345,
+            memory_usage: 1024,
+            memory_limit: 2048,
+            network_rx_bytes: 1,
+            network_tx_bytes: 2,
+            block_read_bytes: 3,
+            block_write_bytes: 4,
+        };
+
+        assert_eq!(sample.to_csv(), "1500,12.35,1024,2048,1,2,3,4");
+        assert_eq!(
+            StatsSample::CSV_HEADER.split(',').count(),
+            sample.to_csv().split(',').count()
+        );
+    }
+}
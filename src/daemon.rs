@@ -0,0 +1,343 @@
+//! Capabilities of the docker daemon the test environment is executed against.
+
+use crate::DockerTestError;
+
+use bollard::{errors::Error, Docker};
+use tokio::time::{sleep, Instant};
+use tracing::{event, Level};
+
+use std::time::Duration;
+
+/// The oldest API version of the docker daemon that dockertest supports. Features requiring a
+/// newer API version are rejected individually.
+pub(crate) const MINIMUM_API_VERSION: (u32, u32) = (1, 25);
+
+/// How often to check whether a paused Docker Desktop engine has resumed.
+const ENGINE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Information about the docker daemon the test environment is executed against.
+///
+/// This is retrieved once when the environment is set up, and is available to the test body
+/// through [DockerOperations::daemon_info](crate::DockerOperations::daemon_info).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DaemonInfo {
+    version: String,
+    api_version: String,
+    min_api_version: String,
+    os: String,
+    arch: String,
+    kernel_version: String,
+    operating_system: String,
+    cgroup_version: Option<String>,
+    rootless: bool,
+    userns_remap: bool,
+}
+
+/// Downgrade the API version of the client to the one supported by the docker daemon, if older.
+pub(crate) async fn negotiate_api_version(client: Docker) -> Result<Docker, DockerTestError> {
+    client.negotiate_version().await.map_err(|e| {
+        DockerTestError::Daemon(format!(
+            "failed to negotiate the API version with the docker daemon: {}",
+            e
+        ))
+    })
+}
+
+impl DaemonInfo {
+    /// Query the version and system information of the docker daemon.
+    pub(crate) async fn query(client: &Docker) -> Result<DaemonInfo, DockerTestError> {
+        let (version, info) = futures::future::join(client.version(), client.info()).await;
+        let version = version.map_err(|e| {
+            DockerTestError::Daemon(format!("failed to query docker daemon version: {}", e))
+        })?;
+        let info = info.map_err(|e| {
+            DockerTestError::Daemon(format!("failed to query docker daemon info: {}", e))
+        })?;
+
+        let daemon = DaemonInfo {
+            version: version.version.unwrap_or_default(),
+            api_version: version.api_version.unwrap_or_default(),
+            min_api_version: version.min_api_version.unwrap_or_default(),
+            os: version.os.unwrap_or_default(),
+            arch: version.arch.unwrap_or_default(),
+            kernel_version: version.kernel_version.unwrap_or_default(),
+            operating_system: info.operating_system.unwrap_or_default(),
+            cgroup_version: info
+                .cgroup_version
+                .map(|v| v.to_string())
+                .filter(|v| !v.is_empty()),
+            rootless: has_security_option(&info.security_options, "rootless"),
+            userns_remap: has_security_option(&info.security_options, "userns"),
+        };
+        event!(Level::DEBUG, "docker daemon: {:?}", daemon);
+
+        Ok(daemon)
+    }
+
+    /// The version of the docker daemon, e.g., `24.0.7`.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The highest API version supported by the docker daemon, e.g., `1.43`.
+    pub fn api_version(&self) -> &str {
+        &self.api_version
+    }
+
+    /// The lowest API version supported by the docker daemon, e.g., `1.12`.
+    pub fn min_api_version(&self) -> &str {
+        &self.min_api_version
+    }
+
+    /// The operating system the docker daemon executes containers on, e.g., `linux`.
+    pub fn os(&self) -> &str {
+        &self.os
+    }
+
+    /// The architecture of the docker daemon host, e.g., `amd64`.
+    pub fn arch(&self) -> &str {
+        &self.arch
+    }
+
+    /// The kernel version of the docker daemon host.
+    pub fn kernel_version(&self) -> &str {
+        &self.kernel_version
+    }
+
+    /// The name of the operating system of the docker daemon host, e.g., `Docker Desktop`.
+    pub fn operating_system(&self) -> &str {
+        &self.operating_system
+    }
+
+    /// The cgroup version used by the docker daemon, if reported.
+    pub fn cgroup_version(&self) -> Option<&str> {
+        self.cgroup_version.as_deref()
+    }
+
+    /// Whether the docker daemon executes rootless, i.e., within a user namespace owned by an
+    /// unprivileged user of the host.
+    pub fn is_rootless(&self) -> bool {
+        self.rootless
+    }
+
+    /// Whether the docker daemon maps the users of containers to unprivileged users of the host,
+    /// as configured through `userns-remap`.
+    pub fn is_userns_remapped(&self) -> bool {
+        self.userns_remap
+    }
+
+    /// Whether the docker daemon supports at least the provided API version.
+    ///
+    /// Returns true if the API version of the daemon is unknown, such that the daemon itself
+    /// gets to reject the request.
+    pub fn supports_api_version(&self, major: u32, minor: u32) -> bool {
+        match parse_api_version(&self.api_version) {
+            Some(version) => version >= (major, minor),
+            None => true,
+        }
+    }
+
+    /// Reject the `feature` if the docker daemon does not support the provided API version.
+    pub(crate) fn require_api_version(
+        &self,
+        major: u32,
+        minor: u32,
+        feature: &str,
+    ) -> Result<(), DockerTestError> {
+        if self.supports_api_version(major, minor) {
+            Ok(())
+        } else {
+            Err(DockerTestError::Unsupported(format!(
+                "{} requires API >= {}.{}, but the docker daemon supports API {}",
+                feature, major, minor, self.api_version
+            )))
+        }
+    }
+
+    /// Reject the `feature` if the docker daemon does not execute linux containers.
+    pub(crate) fn require_linux(&self, feature: &str) -> Result<(), DockerTestError> {
+        if self.os.is_empty() || self.os == "linux" {
+            Ok(())
+        } else {
+            Err(DockerTestError::Unsupported(format!(
+                "{} requires a linux docker daemon, but the docker daemon runs on {}",
+                feature, self.os
+            )))
+        }
+    }
+
+    /// Reject the `feature` if the docker daemon does not execute Windows containers.
+    pub(crate) fn require_windows(&self, feature: &str) -> Result<(), DockerTestError> {
+        if self.os.is_empty() || self.os == "windows" {
+            Ok(())
+        } else {
+            Err(DockerTestError::Unsupported(format!(
+                "{} requires a Windows docker daemon, but the docker daemon runs on {}",
+                feature, self.os
+            )))
+        }
+    }
+}
+
+/// A Docker Desktop engine that cannot serve requests, as reported by the Docker Desktop proxy in
+/// front of the engine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DesktopCondition {
+    /// The engine has been paused through the Docker Desktop dashboard or menu.
+    Paused,
+    /// The engine has been stopped by Resource Saver, after a period of inactivity.
+    ResourceSaver,
+    /// The engine is still starting, e.g., when resuming from Resource Saver.
+    Starting,
+}
+
+impl std::fmt::Display for DesktopCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let condition = match self {
+            DesktopCondition::Paused => {
+                "Docker Desktop engine is paused, resume it through the Docker Desktop dashboard \
+                 or menu"
+            }
+            DesktopCondition::ResourceSaver => {
+                "Docker Desktop engine is stopped by Resource Saver, and did not resume in time"
+            }
+            DesktopCondition::Starting => "Docker Desktop engine is still starting",
+        };
+        write!(
+            f,
+            "{}, or wait for the engine through `DockerTest::with_engine_wait`",
+            condition
+        )
+    }
+}
+
+/// The Docker Desktop condition that caused the error, if any.
+pub(crate) fn desktop_condition(e: &Error) -> Option<DesktopCondition> {
+    let message = e.to_string().to_lowercase();
+    if message.contains("docker desktop is manually paused") || message.contains("engine is paused")
+    {
+        Some(DesktopCondition::Paused)
+    } else if message.contains("resource saver") {
+        Some(DesktopCondition::ResourceSaver)
+    } else if message.contains("docker desktop is starting")
+        || message.contains("engine is starting")
+    {
+        Some(DesktopCondition::Starting)
+    } else {
+        None
+    }
+}
+
+/// Ensure that a Docker Desktop engine is able to serve requests, waiting up to `wait` for a
+/// paused or stopped engine to resume, if provided.
+///
+/// Other failures to reach the daemon are left to be reported by the requests of the test.
+pub(crate) async fn await_engine(
+    client: &Docker,
+    wait: Option<Duration>,
+) -> Result<(), DockerTestError> {
+    let deadline = wait.map(|wait| Instant::now() + wait);
+    loop {
+        let condition = match client.ping().await {
+            Ok(_) => return Ok(()),
+            Err(e) => match desktop_condition(&e) {
+                Some(condition) => condition,
+                None => return Ok(()),
+            },
+        };
+
+        match deadline {
+            Some(deadline) if Instant::now() < deadline => {
+                event!(
+                    Level::INFO,
+                    "waiting for the Docker Desktop engine to resume: {:?}",
+                    condition
+                );
+                sleep(ENGINE_POLL_INTERVAL.min(deadline - Instant::now())).await;
+            }
+            _ => return Err(DockerTestError::Daemon(condition.to_string())),
+        }
+    }
+}
+
+/// Whether the security options reported by the docker daemon, e.g., `name=seccomp,profile=default`,
+/// contain the option `name`.
+fn has_security_option(options: &Option<Vec<String>>, name: &str) -> bool {
+    options.iter().flatten().any(|option| {
+        option
+            .split(',')
+            .any(|field| field.strip_prefix("name=") == Some(name))
+    })
+}
+
+fn parse_api_version(version: &str) -> Option<(u32, u32)> {
+    let (major, minor) = version.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{desktop_condition, has_security_option, DaemonInfo, DesktopCondition};
+    use crate::DockerTestError;
+
+    use bollard::errors::Error;
+
+    // Tests that API versions are compared numerically
+    #[test]
+    fn test_supports_api_version() {
+        let daemon = DaemonInfo {
+            api_version: "1.41".to_string(),
+            ..Default::default()
+        };
+
+        assert!(daemon.supports_api_version(1, 9));
+        assert!(daemon.supports_api_version(1, 41));
+        assert!(!daemon.supports_api_version(1, 43));
+        assert!(matches!(
+            daemon.require_api_version(1, 43, "feature"),
+            Err(DockerTestError::Unsupported(_))
+        ));
+    }
+
+    // Tests that an unknown API version is not rejected
+    #[test]
+    fn test_supports_unknown_api_version() {
+        let daemon = DaemonInfo::default();
+        assert!(daemon.supports_api_version(1, 43));
+    }
+
+    // Tests that rootless and remapping daemons are recognized through their security options
+    #[test]
+    fn test_has_security_option() {
+        let options = Some(vec![
+            "name=seccomp,profile=builtin".to_string(),
+            "name=rootless".to_string(),
+            "name=cgroupns".to_string(),
+        ]);
+        assert!(has_security_option(&options, "rootless"));
+        assert!(has_security_option(&options, "seccomp"));
+        assert!(!has_security_option(&options, "userns"));
+        assert!(!has_security_option(&None, "rootless"));
+    }
+
+    // Tests that the errors of a paused or stopped Docker Desktop engine are recognized
+    #[test]
+    fn test_desktop_condition() {
+        let paused = Error::DockerResponseServerError {
+            status_code: 500,
+            message: "Docker Desktop is manually paused. Unpause it through the Whale menu or \
+                      Dashboard."
+                .to_string(),
+        };
+        assert_eq!(desktop_condition(&paused), Some(DesktopCondition::Paused));
+        assert!(DesktopCondition::Paused
+            .to_string()
+            .starts_with("Docker Desktop engine is paused"));
+
+        let missing = Error::DockerResponseServerError {
+            status_code: 404,
+            message: "No such container: db".to_string(),
+        };
+        assert_eq!(desktop_condition(&missing), None);
+    }
+}
@@ -0,0 +1,257 @@
+//! Discovery of the dockertest environments running on the docker daemon, see [discover].
+//!
+//! Every container started by a test is labeled with the id of its environment and the
+//! namespace of its test, such that tooling can find the environments left behind by crashed or
+//! stuck test runs, e.g., to inspect or remove them. Static containers are shared by the
+//! environments of many tests, and are not labeled.
+
+use crate::composition::Composition;
+use crate::utils::connect_with_local_or_tls_defaults;
+use crate::DockerTestError;
+
+use bollard::{container::ListContainersOptions, models::ContainerSummary};
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The label holding the id of the environment a container belongs to.
+pub(crate) const ID_LABEL: &str = "dockertest.id";
+/// The label holding the namespace of the test a container belongs to.
+pub(crate) const NAMESPACE_LABEL: &str = "dockertest.namespace";
+
+/// The state of a container that is running, as reported by the docker daemon.
+const RUNNING: &str = "running";
+
+/// A dockertest environment running on the docker daemon, found through [discover].
+#[derive(Clone, Debug)]
+pub struct DiscoveredEnvironment {
+    id: String,
+    namespace: String,
+    created: SystemTime,
+    containers: Vec<DiscoveredContainer>,
+}
+
+impl DiscoveredEnvironment {
+    /// The id of the environment, unique to each test.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The namespace of the test, see [DockerTest::with_namespace].
+    ///
+    /// [DockerTest::with_namespace]: crate::DockerTest::with_namespace
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Every container of the environment, including those no longer running, ordered by name.
+    pub fn containers(&self) -> &[DiscoveredContainer] {
+        &self.containers
+    }
+
+    /// How long ago the first container of the environment was created.
+    pub fn age(&self) -> Duration {
+        self.created.elapsed().unwrap_or_default()
+    }
+}
+
+/// A container of a [DiscoveredEnvironment].
+#[derive(Clone, Debug)]
+pub struct DiscoveredContainer {
+    id: String,
+    name: String,
+    image: String,
+    state: String,
+}
+
+impl DiscoveredContainer {
+    /// The unique docker container identifier.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The name of the container.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The image the container was created from.
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+
+    /// The state of the container as reported by the docker daemon, e.g., `running` or `exited`.
+    pub fn state(&self) -> &str {
+        &self.state
+    }
+
+    /// Whether the container is running.
+    pub fn is_running(&self) -> bool {
+        self.state == RUNNING
+    }
+}
+
+/// List the dockertest environments with a running container on the docker daemon, oldest first.
+///
+/// This enables tooling, e.g., an `xtask`, to find the environments left behind by crashed or
+/// stuck test runs, and to inspect or remove their containers through the docker daemon:
+///
+/// ```no_run
+/// # async fn stuck() -> Result<(), dockertest::DockerTestError> {
+/// for environment in dockertest::discover().await? {
+///     println!(
+///         "{} ({}), running for {:?}",
+///         environment.id(),
+///         environment.namespace(),
+///         environment.age()
+///     );
+///     for container in environment.containers() {
+///         println!("  {} [{}]", container.name(), container.state());
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The docker daemon is connected to like [DockerTest] does by default.
+///
+/// [DockerTest]: crate::DockerTest
+pub async fn discover() -> Result<Vec<DiscoveredEnvironment>, DockerTestError> {
+    let client = connect_with_local_or_tls_defaults()?;
+    let options = ListContainersOptions {
+        all: true,
+        filters: HashMap::from([("label", vec![ID_LABEL])]),
+        ..Default::default()
+    };
+
+    let containers = client.list_containers(Some(options)).await.map_err(|e| {
+        DockerTestError::Daemon(format!(
+            "failed to list the containers of dockertest environments: {}",
+            e
+        ))
+    })?;
+
+    Ok(group(containers))
+}
+
+/// Label the containers of the environment with its `id` and the `namespace` of its test.
+pub(crate) fn label(id: &str, namespace: &str, compositions: &mut [Composition]) {
+    for c in compositions.iter_mut().filter(|c| !c.is_static()) {
+        c.labels.insert(ID_LABEL.to_string(), id.to_string());
+        c.labels
+            .insert(NAMESPACE_LABEL.to_string(), namespace.to_string());
+    }
+}
+
+/// Group the containers by environment, retaining the environments with a running container.
+fn group(containers: Vec<ContainerSummary>) -> Vec<DiscoveredEnvironment> {
+    let mut environments: BTreeMap<String, DiscoveredEnvironment> = BTreeMap::new();
+    for c in containers {
+        let mut labels = c.labels.unwrap_or_default();
+        let id = match labels.remove(ID_LABEL) {
+            Some(id) => id,
+            None => continue,
+        };
+        let created = UNIX_EPOCH + Duration::from_secs(c.created.unwrap_or_default().max(0) as u64);
+
+        let environment = environments
+            .entry(id.clone())
+            .or_insert_with(|| DiscoveredEnvironment {
+                id,
+                namespace: labels.remove(NAMESPACE_LABEL).unwrap_or_default(),
+                created,
+                containers: Vec::new(),
+            });
+        environment.created = environment.created.min(created);
+        environment.containers.push(DiscoveredContainer {
+            id: c.id.unwrap_or_default(),
+            name: c
+                .names
+                .and_then(|names| names.into_iter().next())
+                .map(|name| name.trim_start_matches('/').to_string())
+                .unwrap_or_default(),
+            image: c.image.unwrap_or_default(),
+            state: c.state.unwrap_or_default(),
+        });
+    }
+
+    let mut environments: Vec<DiscoveredEnvironment> = environments
+        .into_values()
+        .filter(|e| e.containers.iter().any(DiscoveredContainer::is_running))
+        .collect();
+    for environment in environments.iter_mut() {
+        environment.containers.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+    environments.sort_by_key(|e| e.created);
+    environments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{group, label, ID_LABEL, NAMESPACE_LABEL};
+    use crate::composition::Composition;
+
+    use bollard::models::ContainerSummary;
+
+    use std::collections::HashMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn container(name: &str, id: Option<&str>, state: &str, created: i64) -> ContainerSummary {
+        let mut labels = HashMap::from([(NAMESPACE_LABEL.to_string(), "ci".to_string())]);
+        if let Some(id) = id {
+            labels.insert(ID_LABEL.to_string(), id.to_string());
+        }
+        ContainerSummary {
+            id: Some(format!("{}-id", name)),
+            names: Some(vec![format!("/{}", name)]),
+            image: Some("postgres:16".to_string()),
+            state: Some(state.to_string()),
+            created: Some(created),
+            labels: Some(labels),
+            ..Default::default()
+        }
+    }
+
+    // Tests that only the containers not shared between environments are labeled
+    #[test]
+    fn test_label() {
+        let owned = Composition::with_repository("postgres");
+        let mut shared = Composition::with_repository("redis");
+        shared.share_environment("redis-shared".to_string());
+
+        let mut compositions = vec![owned, shared];
+        label("abc", "ci", &mut compositions);
+        assert_eq!(compositions[0].labels[ID_LABEL], "abc");
+        assert_eq!(compositions[0].labels[NAMESPACE_LABEL], "ci");
+        assert!(!compositions[1].labels.contains_key(ID_LABEL));
+    }
+
+    // Tests that containers are grouped by environment, retaining environments with a running
+    // container, oldest first
+    #[test]
+    fn test_group() {
+        let environments = group(vec![
+            container("ci-redis-b", Some("b"), "running", 200),
+            container("ci-postgres-a", Some("a"), "exited", 100),
+            container("ci-redis-a", Some("a"), "running", 150),
+            container("ci-postgres-c", Some("c"), "exited", 50),
+            container("unlabeled", None, "running", 10),
+        ]);
+
+        let ids: Vec<&str> = environments.iter().map(|e| e.id()).collect();
+        assert_eq!(ids, ["a", "b"]);
+        assert_eq!(environments[0].namespace(), "ci");
+        assert_eq!(
+            environments[0].created,
+            UNIX_EPOCH + Duration::from_secs(100)
+        );
+
+        let names: Vec<&str> = environments[0]
+            .containers()
+            .iter()
+            .map(|c| c.name())
+            .collect();
+        assert_eq!(names, ["ci-postgres-a", "ci-redis-a"]);
+        assert!(!environments[0].containers()[0].is_running());
+    }
+}
@@ -1,16 +1,41 @@
 //! Configure a DockerTest to run.
 
-use crate::composition::Composition;
-use crate::image::Source;
-use crate::runner::{DockerOperations, Runner};
+use crate::address_pool::AddressPool;
+use crate::composition::{
+    check_container_name, check_container_path, check_named_pipe, Composition,
+    ExistingContainerPolicy, LogOptions, ResolvedConfig, StartPolicy,
+};
+use crate::engine::bootstrap;
+use crate::environment_module::TestEnvironmentModule;
+use crate::faketime;
+use crate::image::{PullPolicy, Source};
+use crate::overrides::Overrides;
+use crate::retry::RetryPolicy;
+use crate::runner::{suffix_named_volumes, DockerOperations, Runner};
+use crate::secrets::{check_secret_name, is_hidden_by_mount};
+use crate::shared_environment;
 use crate::specification::ContainerSpecification;
+use crate::static_container::SCOPED_NETWORKS;
+use crate::teardown::{TeardownHook, TeardownReport};
+use crate::template::{self, Segment};
+use crate::utils::generate_random_string;
+use crate::waitfor::WaitFor;
 use crate::DockerTestError;
 
 use futures::future::Future;
-use tokio::runtime::Runtime;
+use tokio::runtime::{Handle, Runtime};
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, TcpListener, UdpSocket};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::{event, span, Instrument, Level};
 
 /// The main entry point to specify a test.
+///
+/// The [Display](std::fmt::Display) implementation provides a readable summary of the
+/// environment this test defines, with one line per container.
 pub struct DockerTest {
     /// All Compositions that have been added to this test run.
     /// They are stored in the order they where added by `add_composition`.
@@ -23,18 +48,185 @@ pub struct DockerTest {
     /// The default pull source to use for all images.
     /// Images with a specified source will override this default.
     pub(crate) default_source: Source,
-    /// Retrieved internally by an env variable the user has to set.
+    /// Detected or retrieved from an env variable when the [Runner](crate::Runner) is created.
     /// Will only be used in environments where dockertest itself is running inside a container.
     pub(crate) container_id: Option<String>,
     /// Network configuration, defaults to [Network::Singular] if not specified by
     /// user.
     pub(crate) network: Network,
+    /// Options applied to every container, unless configured on the container itself.
+    pub(crate) defaults: Defaults,
+    /// The runtime to execute the test within, instead of the ambient or a newly created one.
+    pub(crate) runtime: Option<Handle>,
+    /// The number of worker threads of the runtime created by [DockerTest::run].
+    pub(crate) worker_threads: Option<usize>,
+    /// Whether to use the docker client shared by all tests of the process.
+    pub(crate) shared_client: bool,
+    /// Whether the containers are shared with identical environments of other test binaries.
+    pub(crate) shared_environment: bool,
+    /// Whether to tear down the environment in the background, instead of awaiting it.
+    pub(crate) background_teardown: bool,
+    /// Whether the containers are stopped in reverse dependency order before removal.
+    pub(crate) ordered_teardown: bool,
+    /// The seconds to await the graceful stop of every container during teardown, if the
+    /// containers are stopped before their final state is captured.
+    pub(crate) graceful_stop: Option<i64>,
+    /// The subnets to fall back to when the address pools of the daemon are exhausted.
+    pub(crate) address_pool: Option<AddressPool>,
+    /// How to retry daemon operations failing with a transient error.
+    pub(crate) retry_policy: RetryPolicy,
+    /// How to reconnect to the docker daemon if the connection is lost during the test body.
+    pub(crate) reconnect_policy: RetryPolicy,
+    /// The named volumes declared for the test. If any are declared, every named volume of a
+    /// container must reference one of them.
+    pub(crate) declared_volumes: Vec<String>,
+    /// Whether validation checks that the mapped host ports are free on the host.
+    pub(crate) check_host_ports: bool,
+    /// The name of the test, included in all container names after the namespace.
+    pub(crate) test_name: Option<String>,
+    /// Generates the identifiers used to make resource names unique.
+    pub(crate) id_generator: IdGenerator,
+    /// Where to write the JSON environment report after startup, if anywhere.
+    pub(crate) report_path: Option<PathBuf>,
+    /// How long all containers may take to start, including their wait conditions.
+    pub(crate) startup_timeout: Option<Duration>,
+    /// The maximum number of relaxed containers started concurrently, if bounded.
+    pub(crate) start_concurrency: Option<usize>,
+    /// Whether to verify that every container is still alive right before the test body.
+    pub(crate) liveness_check: bool,
+    /// How long to wait for a paused or stopped Docker Desktop engine to resume, if at all.
+    pub(crate) engine_wait: Option<Duration>,
+    /// Whether to start a janitor removing the resources of the test process once it dies.
+    pub(crate) janitor: bool,
+    /// Whether to print a summary of every environment of the process once it exits.
+    pub(crate) suite_summary: bool,
+    /// Invoked with the final state of the containers once the environment is torn down.
+    pub(crate) teardown_hook: Option<TeardownHook>,
+    /// The names of the cross-process locks held while the environment exists.
+    pub(crate) exclusive_locks: Vec<String>,
+    /// The handles of the replicated containers, paired with their number of replicas.
+    pub(crate) replica_groups: Vec<(String, usize)>,
+    /// The handles of the containers provided the tls fixtures.
+    #[cfg(feature = "tls-fixtures")]
+    pub(crate) tls_fixtures: Vec<String>,
+}
+
+impl std::fmt::Debug for DockerTest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let compositions: Vec<CompositionDebug<'_>> =
+            self.compositions.iter().map(CompositionDebug).collect();
+        let mut debug = f.debug_struct("DockerTest");
+        debug
+            .field("compositions", &compositions)
+            .field("namespace", &self.namespace)
+            .field("default_source", &self.default_source)
+            .field("container_id", &self.container_id)
+            .field("network", &self.network)
+            .field("defaults", &self.defaults)
+            .field("runtime", &self.runtime)
+            .field("worker_threads", &self.worker_threads)
+            .field("shared_client", &self.shared_client)
+            .field("shared_environment", &self.shared_environment)
+            .field("background_teardown", &self.background_teardown)
+            .field("ordered_teardown", &self.ordered_teardown)
+            .field("graceful_stop", &self.graceful_stop)
+            .field("address_pool", &self.address_pool)
+            .field("retry_policy", &self.retry_policy)
+            .field("reconnect_policy", &self.reconnect_policy)
+            .field("declared_volumes", &self.declared_volumes)
+            .field("check_host_ports", &self.check_host_ports)
+            .field("test_name", &self.test_name)
+            .field("id_generator", &self.id_generator)
+            .field("report_path", &self.report_path)
+            .field("startup_timeout", &self.startup_timeout)
+            .field("start_concurrency", &self.start_concurrency)
+            .field("liveness_check", &self.liveness_check)
+            .field("engine_wait", &self.engine_wait)
+            .field("janitor", &self.janitor)
+            .field("suite_summary", &self.suite_summary)
+            .field("teardown_hook", &self.teardown_hook)
+            .field("exclusive_locks", &self.exclusive_locks)
+            .field("replica_groups", &self.replica_groups);
+        #[cfg(feature = "tls-fixtures")]
+        debug.field("tls_fixtures", &self.tls_fixtures);
+        debug.finish()
+    }
+}
+
+/// Prints a composition of a [DockerTest] without the values of its environment variables, which
+/// may hold credentials.
+struct CompositionDebug<'a>(&'a Composition);
+
+impl std::fmt::Debug for CompositionDebug<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Composition")
+            .field("handle", &self.0.handle())
+            .field("container_name", &self.0.container_name)
+            .field("image", self.0.image())
+            .field("env", &self.0.env.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Generates unique identifiers, such as the suffix of each container name.
+#[derive(Clone)]
+pub(crate) struct IdGenerator(Arc<dyn Fn() -> String + Send + Sync>);
+
+impl IdGenerator {
+    pub(crate) fn generate(&self) -> String {
+        (self.0)()
+    }
+}
+
+impl Default for IdGenerator {
+    fn default() -> Self {
+        IdGenerator(Arc::new(|| generate_random_string(20)))
+    }
+}
+
+impl std::fmt::Debug for IdGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("IdGenerator")
+    }
+}
+
+/// Produces a fresh [WaitFor] trait object for each container it is applied to.
+pub(crate) type WaitForFactory = Arc<dyn Fn() -> Box<dyn WaitFor> + Send + Sync>;
+
+/// The options configured on a [DockerTest] that apply to all of its containers.
+///
+/// Each option only applies to the containers that have not explicitly configured it.
+#[derive(Clone, Default)]
+pub(crate) struct Defaults {
+    /// Environment variables, the container's own environment variables take precedence.
+    pub(crate) env: HashMap<String, String>,
+    /// The log options, where `Some(None)` disables logging.
+    pub(crate) log_options: Option<Option<LogOptions>>,
+    /// Constructs the [WaitFor] for containers without one.
+    pub(crate) wait_for: Option<WaitForFactory>,
+    /// The [PullPolicy] of images without one.
+    pub(crate) pull_policy: Option<PullPolicy>,
+    /// Whether containers of recognized images without a [WaitFor] await their readiness.
+    pub(crate) smart_wait_for: bool,
+}
+
+impl std::fmt::Debug for Defaults {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Defaults")
+            .field("env", &self.env.keys().collect::<Vec<_>>())
+            .field("log_options", &self.log_options)
+            .field("wait_for", &self.wait_for.as_ref().map(|_| "<factory>"))
+            .field("pull_policy", &self.pull_policy)
+            .field("smart_wait_for", &self.smart_wait_for)
+            .finish()
+    }
 }
 
 /// Configure how the docker network should be applied to the containers within this test.
 ///
 /// The default value for a [DockerTest], if not provided, is [Network::Singular].
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Network {
     /// A single statically named network, with the namespace of the [DockerTest] as a prefix.
     ///
@@ -56,6 +248,13 @@ pub enum Network {
     ///
     /// The network will be deleted once the test body exits.
     Isolated,
+    /// A single network shared by every [DockerTest] of the process using the same namespace,
+    /// created by the first test and deleted once the last test using it exits.
+    ///
+    /// This avoids creating and deleting a network for each test, which is slow on some
+    /// daemons and may exhaust the address pools of the daemon, while not leaving the network
+    /// behind like [Network::Singular].
+    Shared,
 }
 
 impl DockerTest {
@@ -67,6 +266,33 @@ impl DockerTest {
             namespace: "dockertest-rs".to_string(),
             container_id: None,
             network: Network::Singular,
+            defaults: Defaults::default(),
+            runtime: None,
+            worker_threads: None,
+            shared_client: false,
+            shared_environment: false,
+            background_teardown: false,
+            ordered_teardown: false,
+            graceful_stop: None,
+            address_pool: None,
+            retry_policy: RetryPolicy::default(),
+            reconnect_policy: RetryPolicy::reconnect(),
+            declared_volumes: Vec::new(),
+            check_host_ports: false,
+            test_name: None,
+            id_generator: IdGenerator::default(),
+            report_path: None,
+            startup_timeout: None,
+            start_concurrency: None,
+            liveness_check: false,
+            engine_wait: None,
+            janitor: false,
+            suite_summary: false,
+            teardown_hook: None,
+            exclusive_locks: Vec::new(),
+            replica_groups: Vec::new(),
+            #[cfg(feature = "tls-fixtures")]
+            tls_fixtures: Vec::new(),
         }
     }
 
@@ -94,11 +320,442 @@ impl DockerTest {
         }
     }
 
+    /// Sets the name of the test, which is included in all container names.
+    ///
+    /// Container names will be on the form `{namespace}-{test_name}-{name}-{suffix}`, which
+    /// makes it easy to identify which containers belong to which test when tests are executed
+    /// in parallel. Unlike the namespace, the test name does not affect the name of the
+    /// [Network::Singular] network. Container names may not exceed 63 characters, the length of
+    /// a DNS label, which validation reports. The repository within the names of containers
+    /// without a handle is shortened to fit.
+    pub fn with_test_name<T: ToString>(self, test_name: T) -> Self {
+        Self {
+            test_name: Some(sanitize_name(&test_name.to_string())),
+            ..self
+        }
+    }
+
+    /// Sets the name of the test to the name of the current test, as named by the libtest harness.
+    ///
+    /// The test harness names the thread executing each test after the test path, from which the
+    /// final path segment is used. See [DockerTest::with_test_name].
+    /// Has no effect if the current thread is not named after a test, e.g., when the tests are
+    /// executed on the main thread with `--test-threads=1`.
+    pub fn with_test_name_from_thread(self) -> Self {
+        match std::thread::current().name() {
+            Some(name) if name != "main" => {
+                let test_name = name.rsplit("::").next().unwrap_or(name).to_string();
+                self.with_test_name(test_name)
+            }
+            _ => {
+                event!(
+                    Level::DEBUG,
+                    "current thread is not named after a test, not setting the test name"
+                );
+                self
+            }
+        }
+    }
+
+    /// Sets the generator of the identifiers used to make resource names unique, such as the
+    /// suffix of each container name and the name of [Network::Isolated] networks.
+    ///
+    /// The generator must produce identifiers that are unique across all concurrently
+    /// executing tests, and that are valid as part of a docker container name.
+    /// DockerTest defaults to a random string of 20 lowercase letters.
+    pub fn with_id_generator<F>(self, generator: F) -> Self
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        Self {
+            id_generator: IdGenerator(Arc::new(generator)),
+            ..self
+        }
+    }
+
+    /// The prefix of all container names, consisting of the namespace and the test name.
+    pub(crate) fn container_name_prefix(&self) -> String {
+        match &self.test_name {
+            Some(test_name) => format!("{}-{}", self.namespace, test_name),
+            None => self.namespace.clone(),
+        }
+    }
+
+    /// Write a JSON document describing every started container to `path`, once all containers
+    /// have started.
+    ///
+    /// The report is also available to the test body through
+    /// [DockerOperations::report](crate::DockerOperations::report). Failing to write the report
+    /// does not fail the test.
+    pub fn with_report_path<P: Into<PathBuf>>(self, path: P) -> Self {
+        Self {
+            report_path: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// Bound the time all containers may take to start, including their [WaitFor] conditions.
+    ///
+    /// Exceeding it fails the test with a [DockerTestError::Startup], and removes all containers.
+    /// Overridden by the `DOCKERTEST_STARTUP_TIMEOUT` environment variable, in seconds. By
+    /// default, the startup is only bounded by the wait conditions of the containers.
+    pub fn with_startup_timeout(self, timeout: Duration) -> Self {
+        Self {
+            startup_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Bound the number of containers with [StartPolicy::Relaxed] that are started concurrently,
+    /// including their [WaitFor] conditions. The bound also applies within each
+    /// [StartPolicy::Group].
+    ///
+    /// Starting many relaxed containers at once may overwhelm a small docker daemon. The
+    /// containers are still started concurrently with the strict containers, and in no particular
+    /// order. By default, all relaxed containers are started at once.
+    pub fn with_start_concurrency(self, concurrency: usize) -> Self {
+        Self {
+            start_concurrency: Some(concurrency),
+            ..self
+        }
+    }
+
+    /// Verify that every container is still alive right before the test body is entered, failing
+    /// the test with the recent logs of any container that died after becoming ready.
+    ///
+    /// A container that became ready early, e.g., with [StartPolicy::Relaxed], may crash while
+    /// the other containers are still starting. A container is considered dead if it is no longer
+    /// running, if it restarted, or if its health check reports it as unhealthy. Containers
+    /// expected to exit, e.g., when awaited through [ExitedWait](crate::waitfor::ExitedWait) or
+    /// marked through [TestBodySpecification::set_expect_exit](crate::TestBodySpecification::set_expect_exit),
+    /// are only considered dead if they exited unsuccessfully.
+    /// Disabled by default, as it inspects every container once more.
+    pub fn with_liveness_check(self) -> Self {
+        Self {
+            liveness_check: true,
+            ..self
+        }
+    }
+
+    /// Wait up to `timeout` for a Docker Desktop engine that is paused, or stopped by Resource
+    /// Saver, to resume before the environment is set up.
+    ///
+    /// By default, the test fails right away with an error describing the condition of the
+    /// engine.
+    pub fn with_engine_wait(self, timeout: Duration) -> Self {
+        Self {
+            engine_wait: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Start a janitor container that removes the containers and networks of the test process
+    /// once it dies without tearing them down, e.g., when killed or out of memory.
+    ///
+    /// The janitor is started once per test process, and requires access to the docker socket
+    /// of the docker daemon host. Dynamic containers, which are meant to outlive the test process,
+    /// are left untouched. When the test process executes within a container, the janitor is
+    /// started on the network of that container.
+    pub fn with_janitor(self) -> Self {
+        Self {
+            janitor: true,
+            ..self
+        }
+    }
+
+    /// Print a summary of every environment started by the test process to stderr once it
+    /// exits, or the suite finishes through [finish_suite](crate::finish_suite): the number of
+    /// containers, the total time spent pulling images and starting containers, the images used,
+    /// and the containers and volumes that could not be removed.
+    ///
+    /// Opting in from a single test suffices, as the summary covers all tests of the process.
+    /// The `DOCKERTEST_SUITE_SUMMARY` environment variable opts in every test instead.
+    pub fn with_suite_summary(self) -> Self {
+        Self {
+            suite_summary: true,
+            ..self
+        }
+    }
+
     /// Sets the network configuration
     pub fn with_network(self, network: Network) -> Self {
         Self { network, ..self }
     }
 
+    /// Sets the runtime to execute the test and spawn its tasks within.
+    ///
+    /// By default, [DockerTest::run] creates a new runtime for each test, and
+    /// [DockerTest::run_async] spawns onto the runtime it is invoked within.
+    /// Providing a handle is useful for custom harnesses that own their runtime, or to escape a
+    /// single-threaded runtime that cannot drive the test body concurrently with dockertest.
+    ///
+    /// NOTE: [DockerTest::run] blocks on the provided handle, which must not be invoked from
+    /// within an asynchronous context, and requires the runtime to be driven by other threads
+    /// if it is a current-thread runtime.
+    pub fn with_runtime_handle(self, runtime: Handle) -> Self {
+        Self {
+            runtime: Some(runtime),
+            ..self
+        }
+    }
+
+    /// Sets the number of worker threads of the runtime created by [DockerTest::run].
+    ///
+    /// This has no effect if a runtime is provided through [DockerTest::with_runtime_handle],
+    /// or when executed through [DockerTest::run_async].
+    pub fn with_worker_threads(self, worker_threads: usize) -> Self {
+        Self {
+            worker_threads: Some(worker_threads),
+            ..self
+        }
+    }
+
+    /// Use a docker client shared by all tests of this process that also opt in, instead of
+    /// establishing a new connection for this test.
+    ///
+    /// Sharing the client reuses its connection pool, which reduces the connection overhead
+    /// and daemon load when executing many tests, particularly with TLS.
+    /// Pooled connections are driven by the runtime that established them, such that all tests
+    /// sharing the client should execute within the same long-lived runtime, provided through
+    /// [DockerTest::with_runtime_handle].
+    pub fn with_shared_client(self) -> Self {
+        Self {
+            shared_client: true,
+            ..self
+        }
+    }
+
+    /// Share the containers of this test with tests of other test binaries that define an
+    /// identical environment, e.g., within the same `cargo test` run of a workspace.
+    ///
+    /// The definition of the environment is hashed, and each container is labeled with the hash
+    /// through the `dockertest.environment` label. If the containers of a matching environment
+    /// already exist, the test attaches to them instead of starting duplicates. Shared
+    /// containers are never removed, like containers of a [DynamicSpecification], and are
+    /// additionally labeled with `dockertest.handle`. Once no tests are executing, remove them
+    /// with `docker rm -f $(docker ps -aq --filter label=dockertest.handle)`.
+    ///
+    /// Static containers are left untouched, and named volumes are not supported, as they are
+    /// removed once the test terminates.
+    ///
+    /// [DynamicSpecification]: crate::DynamicSpecification
+    pub fn with_shared_environment(self) -> Self {
+        Self {
+            shared_environment: true,
+            ..self
+        }
+    }
+
+    /// Tear down the environment in the background once the test body completes, instead of
+    /// awaiting the removal of all containers before returning from the test.
+    ///
+    /// Container logs are still handled, and their final state captured in the [TeardownReport],
+    /// before returning. The removal executes on a runtime
+    /// shared by the process, such that a suite of many tests does not serialize on container
+    /// removal. The process awaits pending teardowns as it exits, see
+    /// [finish_suite](crate::finish_suite).
+    pub fn with_background_teardown(self) -> Self {
+        Self {
+            background_teardown: true,
+            ..self
+        }
+    }
+
+    /// Stop the containers in reverse dependency order during teardown, before any container is
+    /// removed, such that applications may flush their state to their databases before those are
+    /// stopped.
+    ///
+    /// A container depends on the containers started before it according to their
+    /// [StartPolicy], and on the containers whose name, network alias or host port is injected
+    /// into its environment, or referenced by the placeholders of its environment variable
+    /// values. The containers without dependencies between them are stopped concurrently. By
+    /// default, all containers are forcefully removed at once.
+    pub fn with_ordered_teardown(self) -> Self {
+        Self {
+            ordered_teardown: true,
+            ..self
+        }
+    }
+
+    /// Stop every container during teardown before its final state is captured in the
+    /// [TeardownReport], awaiting its graceful shutdown for up to `timeout` seconds before it is
+    /// killed.
+    ///
+    /// The report then captures how each container shut down, e.g., its exit code on `SIGTERM`.
+    /// By default, the state of the running containers is captured as is, and the containers
+    /// are forcefully removed. The timeout also applies to
+    /// [with_ordered_teardown](Self::with_ordered_teardown).
+    pub fn with_graceful_stop(self, timeout: u32) -> Self {
+        Self {
+            graceful_stop: Some(i64::from(timeout)),
+            ..self
+        }
+    }
+
+    /// Issue throwaway TLS material to the containers identified by `handles`, e.g., to test
+    /// mutual TLS between them.
+    ///
+    /// A certificate authority is generated at startup, issuing a server and a client certificate
+    /// to each container, which are written to `/run/secrets/tls-*.pem` within the container. The
+    /// material, along with a client certificate for the test body, is retrieved through
+    /// [DockerOperations::tls_fixtures](crate::DockerOperations::tls_fixtures). Requires the
+    /// `tls-fixtures` feature.
+    ///
+    /// The server certificates are valid for the ip of the container, which is pinned to an
+    /// address of the network of the test. The fixtures therefore require [Network::Isolated], and
+    /// cannot be issued to static, pooled, shared or reused containers, which outlive the test.
+    #[cfg(feature = "tls-fixtures")]
+    pub fn with_tls_fixtures<T: ToString>(mut self, handles: &[T]) -> Self {
+        self.tls_fixtures
+            .extend(handles.iter().map(|handle| handle.to_string()));
+        self
+    }
+
+    /// Invoke `hook` with the [TeardownReport] once the environment is torn down, e.g., to
+    /// assert that every container shut down cleanly.
+    ///
+    /// The report captures the final state of every container before it is removed, once it is
+    /// stopped with [with_graceful_stop](Self::with_graceful_stop). The hook is invoked after
+    /// the containers are pruned, or, with
+    /// [with_background_teardown](Self::with_background_teardown), once their removal is
+    /// scheduled. A panic within the hook fails the test. The report is also returned by
+    /// [DockerTest::run_with_report].
+    pub fn with_teardown_hook<F>(self, hook: F) -> Self
+    where
+        F: Fn(&TeardownReport) + Send + Sync + 'static,
+    {
+        Self {
+            teardown_hook: Some(TeardownHook::new(hook)),
+            ..self
+        }
+    }
+
+    /// Sets an address pool to allocate the subnets of created networks from, when the
+    /// address pools of the docker daemon are exhausted.
+    ///
+    /// `base` is an IPv4 range in CIDR notation, e.g., `10.210.0.0/16`, from which subnets with
+    /// a prefix length of `size` are allocated, e.g., `24`. Subnets already in use are skipped.
+    /// Without an address pool, network creation fails with an explanation of how to resolve
+    /// the exhaustion.
+    pub fn with_network_address_pool<T: ToString>(self, base: T, size: u8) -> Self {
+        Self {
+            address_pool: Some(AddressPool::new(base.to_string(), size)),
+            ..self
+        }
+    }
+
+    /// Sets how container creation, start, inspection and removal are retried when the docker
+    /// daemon fails with a transient error, such as an internal server error or a reset
+    /// connection.
+    ///
+    /// Defaults to [RetryPolicy::default]. Use [RetryPolicy::none] to fail on the first error.
+    pub fn with_retry_policy(self, policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy: policy,
+            ..self
+        }
+    }
+
+    /// Sets how to reconnect to the docker daemon if the connection to it is lost during the
+    /// test body, e.g., as the daemon restarts.
+    ///
+    /// Once reconnected, the test fails with [DockerTestError::EnvironmentLost] if any container
+    /// of the environment no longer exists, was restarted or is no longer running, before the
+    /// remaining containers are torn down.
+    /// If the daemon cannot be reached, the test fails without attempting the teardown.
+    /// Defaults to five attempts over about 15 seconds. Use [RetryPolicy::none] to not reconnect.
+    pub fn with_daemon_reconnect_policy(self, policy: RetryPolicy) -> Self {
+        Self {
+            reconnect_policy: policy,
+            ..self
+        }
+    }
+
+    /// Declares a named volume that containers may mount.
+    ///
+    /// Declaring volumes is optional. Once any volume is declared, validation rejects containers
+    /// referencing a named volume that is not declared, such that a misspelled volume name is
+    /// not silently created as a separate, empty volume.
+    pub fn with_named_volume<T: ToString>(mut self, name: T) -> Self {
+        self.declared_volumes.push(name.to_string());
+        self
+    }
+
+    /// Hold the cross-process lock `name` from before the environment is set up until it is torn
+    /// down, serializing the tests contending for a host-global resource, e.g., a fixed host
+    /// port, a GPU or an external license server.
+    ///
+    /// The lock is a file lock within `DOCKERTEST_LOCK_DIR`, defaulting to `dockertest-locks` in
+    /// the temporary directory, hence it serializes tests across test binaries. The time spent
+    /// waiting for the lock is reported separately from the startup of the environment, see
+    /// [DockerOperations::report](crate::DockerOperations::report).
+    pub fn with_exclusive_lock<T: ToString>(mut self, name: T) -> Self {
+        self.exclusive_locks.push(name.to_string());
+        self
+    }
+
+    /// Check that every host port mapped through `modify_port_map` is free on the host before
+    /// any container is created.
+    ///
+    /// Host ports mapped by containers living across tests are not checked, as they are in use
+    /// by the container itself. Conflicts between the containers of this test are always
+    /// rejected, regardless of this check.
+    pub fn with_host_port_check(self) -> Self {
+        Self {
+            check_host_ports: true,
+            ..self
+        }
+    }
+
+    /// Sets environment variables for all containers.
+    ///
+    /// Environment variables configured on a container specification take precedence over
+    /// the defaults provided here.
+    pub fn with_default_env(mut self, env: HashMap<String, String>) -> Self {
+        self.defaults.env = env;
+        self
+    }
+
+    /// Sets the log options for all containers that have not configured their own.
+    ///
+    /// Providing `None` disables logging for these containers.
+    pub fn with_default_log_options(mut self, log_options: Option<LogOptions>) -> Self {
+        self.defaults.log_options = Some(log_options);
+        self
+    }
+
+    /// Sets the [WaitFor] for all containers that have not configured their own.
+    ///
+    /// The factory is invoked once for each such container, such that no state is shared
+    /// between the [WaitFor] trait objects of multiple containers.
+    pub fn with_default_wait_for<F>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> Box<dyn WaitFor> + Send + Sync + 'static,
+    {
+        self.defaults.wait_for = Some(Arc::new(factory));
+        self
+    }
+
+    /// Await the readiness of the containers of recognized official images that have not
+    /// configured their own [WaitFor], instead of considering them ready once created.
+    ///
+    /// The servers of such images accept connections some time after their container is
+    /// started, racing the test body. The recognized images are `postgres`, `mysql`, `mariadb`
+    /// and `mongo`, which are probed from within the container, `rabbitmq`, whose port
+    /// connectivity is checked, and `redis`, whose readiness log line is awaited. The readiness
+    /// condition of a recognized image takes precedence over
+    /// [with_default_wait_for](Self::with_default_wait_for).
+    pub fn with_smart_defaults(mut self, enabled: bool) -> Self {
+        self.defaults.smart_wait_for = enabled;
+        self
+    }
+
+    /// Sets the [PullPolicy] for all images that have not configured their own.
+    pub fn with_default_pull_policy(mut self, policy: PullPolicy) -> Self {
+        self.defaults.pull_policy = Some(policy);
+        self
+    }
+
     /// Append a container specification as part of this specific test.
     ///
     /// The order of which container specifications are added to DockerTest is significant
@@ -117,7 +774,23 @@ impl DockerTest {
         specification: impl ContainerSpecification,
     ) -> &mut DockerTest {
         let composition = specification.into_composition();
-        self.compositions.push(composition);
+        if let Some(replicas) = composition.replicas() {
+            self.replica_groups.push((composition.handle(), replicas));
+        }
+        self.compositions.extend(composition.into_replicas());
+        self
+    }
+
+    /// Register the containers and configuration of a reusable [TestEnvironmentModule] as part
+    /// of this specific test.
+    ///
+    /// The containers registered by the module are started in the order they are registered,
+    /// relative to the containers provided before and after the module.
+    pub fn provide_module<M: TestEnvironmentModule + ?Sized>(
+        &mut self,
+        module: &M,
+    ) -> &mut DockerTest {
+        module.register(self);
         self
     }
 
@@ -126,66 +799,869 @@ impl DockerTest {
         &self.default_source
     }
 
-    /// Execute the test with the constructed environment in full operation.
+    /// Verify that the configured environment is consistent, without contacting the
+    /// docker daemon.
     ///
-    /// # Synchronous
-    /// This non-async version creates its own runtime to execute the test.
-    // NOTE(clippy): tracing generates cognitive complexity due to macro expansion.
-    #[allow(clippy::cognitive_complexity)]
-    pub fn run<T, Fut>(self, test: T)
-    where
-        T: FnOnce(DockerOperations) -> Fut,
-        Fut: Future<Output = ()> + Send + 'static,
-    {
-        let span = span!(Level::ERROR, "run");
-        let _guard = span.enter();
+    /// This is performed automatically before any container is created when the test is
+    /// executed, but may be invoked directly to detect configuration errors early.
+    /// All detected problems are reported together in a [DockerTestError::Validation].
+    pub fn validate(&self) -> Result<(), DockerTestError> {
+        let mut handles: HashMap<String, usize> = HashMap::new();
+        for c in self.compositions.iter() {
+            *handles.entry(c.handle()).or_insert(0) += 1;
+        }
 
-        // Allocate a new runtime for this test.
-        let rt = match Runtime::new() {
-            Ok(r) => r,
-            Err(e) => {
-                event!(Level::ERROR, "failed to allocate tokio runtime: {}", e);
-                panic!("{}", e);
-            }
-        };
+        let mut problems = Vec::new();
+        if self.worker_threads == Some(0) {
+            problems.push("the runtime requires at least one worker thread".to_string());
+        }
+        if self.start_concurrency == Some(0) {
+            problems.push("the start concurrency must allow at least one container".to_string());
+        }
+        if let Some(Err(e)) = self.address_pool.as_ref().map(|p| p.candidates()) {
+            problems.push(e);
+        }
+        self.validate_container_names(&mut problems);
+        self.validate_container_name_format(&mut problems);
+        self.validate_named_volumes(&mut problems);
+        self.validate_shared_environment(&mut problems);
+        self.validate_start_groups(&mut problems);
+        self.validate_warm_pools(&mut problems);
+        self.validate_raw_config_hooks(&mut problems);
+        self.validate_secrets(&mut problems);
+        self.validate_passthrough_env(&mut problems);
+        self.validate_mount_paths(&mut problems);
+        self.validate_faked_time(&mut problems);
+        self.validate_replicas(&mut problems);
+        #[cfg(feature = "tls-fixtures")]
+        self.validate_tls_fixtures(&handles, &mut problems);
+        self.validate_host_ports(&mut problems);
+        self.validate_inject_container_name(&handles, &mut problems);
+        self.validate_inject_network_alias(&handles, &mut problems);
+        self.validate_inject_host_port(&handles, &mut problems);
+        self.validate_env_templates(&handles, &mut problems);
 
-        let runner = rt.block_on(Runner::new(self));
-        process_run(rt.block_on(runner.run_impl(test).in_current_span()))
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(DockerTestError::Validation(problems.join(", ")))
+        }
     }
 
-    /// Async version of [DockerTest::run].
+    /// The hash of the definition of the environment, e.g., to key an external cache of the
+    /// environment.
     ///
-    /// # Asynchronous
-    /// This version allows the caller to provide the runtime to execute this test within.
-    /// This can be useful if the test executable is wrapped with a runtime macro, e.g.,
-    /// `#[tokio::test]`.
-    pub async fn run_async<T, Fut>(self, test: T)
-    where
-        T: FnOnce(DockerOperations) -> Fut,
-        Fut: Future<Output = ()> + Send + 'static,
-    {
-        let span = span!(Level::ERROR, "run");
-        let _guard = span.enter();
-
-        let runner = Runner::new(self).await;
-        process_run(runner.run_impl(test).in_current_span().await);
+    /// Identical definitions yield the same hash across test binaries, regardless of the order
+    /// the containers are provided in. The definition includes the network aliases and secrets of
+    /// every container, of which only a digest is hashed. Static containers are shared by the
+    /// tests of different environments, and are not part of the definition. Every other
+    /// container of the test is labeled `dockertest.environment` with the hash, which decides
+    /// whether an existing container is reused, see [ExistingContainerPolicy::Reuse], and which
+    /// containers are shared, see [DockerTest::with_shared_environment].
+    ///
+    /// [ExistingContainerPolicy::Reuse]: crate::ExistingContainerPolicy::Reuse
+    pub fn environment_hash(&self) -> String {
+        let mut compositions = self.compositions.clone();
+        self.prepare_compositions(&mut compositions, &Overrides::from_env());
+        shared_environment::environment_hash(&compositions)
     }
-}
 
-impl Default for DockerTest {
-    fn default() -> Self {
-        Self::new()
+    /// Apply the defaults, the environment overrides and the retry policy of the test to the
+    /// compositions, as they are when the containers are created.
+    pub(crate) fn prepare_compositions(
+        &self,
+        compositions: &mut [Composition],
+        overrides: &Overrides,
+    ) {
+        compositions.iter_mut().for_each(|c| {
+            c.apply_defaults(&self.defaults);
+            c.apply_overrides(overrides);
+            c.retry_policy = self.retry_policy.clone();
+        });
     }
-}
 
-fn process_run(result: Result<(), DockerTestError>) {
-    match result {
-        Ok(_) => event!(Level::DEBUG, "dockertest successfully executed"),
-        Err(e) => {
-            event!(
-                Level::ERROR,
-                "internal dockertest condition failure: {:?}",
-                e
+    /// Resolve the configuration each container of the test is created with, without contacting
+    /// the docker daemon.
+    ///
+    /// This performs the same resolution as the test setup: defaults are applied, and container
+    /// names, named volumes, injected environment variables and placeholders are resolved.
+    /// Externally managed containers are not created, and are therefore omitted.
+    ///
+    /// The unique suffixes of names differ from those of an actual run of the test. Host ports
+    /// assigned by the docker daemon, e.g., for [inject_host_port], are only known once the
+    /// referenced container has started, and are left as their `{host_port:NAME:PORT}`
+    /// placeholder. Resolving never binds or reserves host ports.
+    ///
+    /// [inject_host_port]: crate::TestBodySpecification::inject_host_port
+    pub fn resolve(&self) -> Result<Vec<ResolvedConfig>, DockerTestError> {
+        self.validate()?;
+
+        let id = self.id_generator.generate();
+        let mut compositions = self.compositions.clone();
+        compositions
+            .iter_mut()
+            .for_each(|c| c.apply_defaults(&self.defaults));
+        suffix_named_volumes(&mut compositions, &id);
+
+        let mut engine = bootstrap(compositions);
+        engine.resolve_final_container_name(&self.container_name_prefix(), &self.id_generator);
+        let mut engine = engine.fuel();
+        engine.resolve_inject_container_name_env()?;
+        engine.resolve_inject_network_alias_env()?;
+        engine.resolve_inject_host_port_env()?;
+        engine.resolve_env_templates()?;
+
+        let network = match &self.network {
+            Network::External(n) => Some(n.clone()),
+            Network::Isolated => Some(format!("dockertest-rs-{}", id)),
+            Network::Singular => Some(SCOPED_NETWORKS.name(&self.namespace)),
+            // Every acquisition of a shared network may create a differently named network.
+            Network::Shared => None,
+        };
+        Ok(engine.resolved_configs(network.as_deref()))
+    }
+
+    /// Named volumes are removed once the test terminates, while a shared environment is not.
+    fn validate_shared_environment(&self, problems: &mut Vec<String>) {
+        if !self.shared_environment {
+            return;
+        }
+
+        for c in self
+            .compositions
+            .iter()
+            .filter(|c| !c.is_static() && !c.named_volumes.is_empty())
+        {
+            problems.push(format!(
+                "container `{}` of a shared environment uses named volumes, which are not shared",
+                c.handle()
+            ));
+        }
+    }
+
+    /// A raw config hook is not part of the definition of a container, such that containers
+    /// created without it could be handed over by a warm pool, a shared environment or reuse.
+    fn validate_raw_config_hooks(&self, problems: &mut Vec<String>) {
+        for c in self.compositions.iter().filter(|c| c.has_raw_config_hook()) {
+            let mut uses = Vec::new();
+            if c.warm_pool().is_some() {
+                uses.push("a warm pool");
+            }
+            if self.shared_environment && !c.is_static() {
+                uses.push("a shared environment");
+            }
+            if c.existing_container_policy == ExistingContainerPolicy::Reuse {
+                uses.push("reuse of existing containers");
+            }
+            if !uses.is_empty() {
+                problems.push(format!(
+                    "container `{}` has a raw config hook, which cannot be combined with {}",
+                    c.handle(),
+                    uses.join(" or ")
+                ));
+            }
+        }
+    }
+
+    /// Every member of a start group must agree on the order of the group, as the group would
+    /// otherwise be split.
+    fn validate_start_groups(&self, problems: &mut Vec<String>) {
+        let mut orders: HashMap<&str, u32> = HashMap::new();
+        for c in self.compositions.iter() {
+            if let StartPolicy::Group(name, order) = c.start_policy() {
+                match orders.get(name.as_str()) {
+                    Some(first) if first != order => problems.push(format!(
+                        "container `{}` starts group `{}` with order {} instead of {}",
+                        c.handle(),
+                        name,
+                        order,
+                        first
+                    )),
+                    Some(_) => (),
+                    None => {
+                        orders.insert(name, *order);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Pooled containers are created ahead of time, and must therefore not depend on the test.
+    fn validate_warm_pools(&self, problems: &mut Vec<String>) {
+        for c in self.compositions.iter() {
+            let size = match c.warm_pool() {
+                Some(size) => size,
+                None => continue,
+            };
+            let templated = c.env.values().any(|value| {
+                template::parse(value)
+                    .map(|segments| {
+                        segments
+                            .iter()
+                            .any(|s| matches!(s, Segment::Placeholder(_)))
+                    })
+                    .unwrap_or(false)
+            });
+
+            if size == 0 {
+                problems.push(format!(
+                    "container `{}` has a warm pool without any containers",
+                    c.handle()
+                ));
+            }
+            if !c.named_volumes.is_empty()
+                || !c.inject_container_name_env.is_empty()
+                || !c.inject_host_port_env.is_empty()
+                || templated
+            {
+                problems.push(format!(
+                    "container `{}` of a warm pool uses named volumes, injections or env templates, \
+                     which depend on the test",
+                    c.handle()
+                ));
+            }
+            if !c.secrets.is_empty() {
+                problems.push(format!(
+                    "container `{}` of a warm pool has secrets, which are not part of the \
+                     definition of the pool",
+                    c.handle()
+                ));
+            }
+        }
+    }
+
+    /// Every secret is written to a file named after it, which must not be hidden by a mount.
+    fn validate_secrets(&self, problems: &mut Vec<String>) {
+        for c in self.compositions.iter() {
+            for (name, _) in c.secrets.iter() {
+                if let Err(e) = check_secret_name(name) {
+                    problems.push(format!(
+                        "container `{}` has an invalid secret `{}`: {}",
+                        c.handle(),
+                        name,
+                        e
+                    ));
+                }
+            }
+            if c.secrets.is_empty() {
+                continue;
+            }
+
+            let hiding = c
+                .named_volumes
+                .iter()
+                .map(|(_, path)| path)
+                .chain(c.bind_mounts.iter().map(|(_, path, _)| path))
+                .cloned()
+                .chain(c.resolve_tmpfs())
+                .find(|path| is_hidden_by_mount(path));
+            if let Some(path) = hiding {
+                problems.push(format!(
+                    "container `{}` mounts `{}`, which would hide its secrets",
+                    c.handle(),
+                    path
+                ));
+            }
+        }
+    }
+
+    /// An empty prefix would pass the entire environment of the test process through, replacing
+    /// variables such as `PATH` of the image.
+    fn validate_passthrough_env(&self, problems: &mut Vec<String>) {
+        for c in self.compositions.iter() {
+            if c.passthrough_env.iter().any(|prefix| prefix.is_empty()) {
+                problems.push(format!(
+                    "container `{}` passes through the environment with an empty prefix",
+                    c.handle()
+                ));
+            }
+        }
+    }
+
+    /// Mounts must target an absolute path within the container, and named pipes must name a
+    /// pipe, as the docker daemon would otherwise reject the container on creation.
+    fn validate_mount_paths(&self, problems: &mut Vec<String>) {
+        for c in self.compositions.iter() {
+            let paths = c
+                .named_volumes
+                .iter()
+                .map(|(_, path)| path)
+                .chain(c.bind_mounts.iter().map(|(_, path, _)| path))
+                .map(|path| (path, check_container_path(path)));
+            let pipes = c
+                .named_pipes
+                .iter()
+                .flat_map(|(host, container)| [host, container])
+                .map(|path| (path, check_named_pipe(path)));
+            for (path, result) in paths.chain(pipes) {
+                if let Err(e) = result {
+                    problems.push(format!(
+                        "container `{}` has an invalid mount path `{}`: {}",
+                        c.handle(),
+                        path,
+                        e
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Faking the clock of a container requires libfaketime on the host.
+    fn validate_faked_time(&self, problems: &mut Vec<String>) {
+        if let Some(c) = self.compositions.iter().find(|c| c.faked_time.is_some()) {
+            if let Err(e) = faketime::library(std::env::var_os(faketime::LIBRARY_ENV)) {
+                problems.push(format!(
+                    "container `{}` fakes its clock, but {}",
+                    c.handle(),
+                    e
+                ));
+            }
+        }
+    }
+
+    /// Every user provided container name must be unique, as the containers would otherwise
+    /// replace each other on creation.
+    fn validate_container_names(&self, problems: &mut Vec<String>) {
+        let mut names: Vec<(&str, Vec<&Composition>)> = Vec::new();
+        for c in self.compositions.iter() {
+            let name = match c.user_provided_container_name() {
+                Some(name) => name,
+                None => continue,
+            };
+            match names.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, users)) => users.push(c),
+                None => names.push((name, vec![c])),
+            }
+        }
+
+        for (name, users) in names.into_iter().filter(|(_, users)| users.len() > 1) {
+            let users: Vec<String> = users
+                .iter()
+                .map(|c| format!("`{}` from image `{}`", c.handle(), c.image()))
+                .collect();
+            problems.push(format!(
+                "container name `{}` is provided by multiple containers: {}",
+                name,
+                users.join(" and ")
+            ));
+        }
+    }
+
+    /// Every container name, including the namespace, test name and unique suffix, must be
+    /// accepted by the docker daemon.
+    fn validate_container_name_format(&self, problems: &mut Vec<String>) {
+        let prefix = self.container_name_prefix();
+        // A sample of the suffix, as its length is what may exceed the limit
+        let suffix = self.id_generator.generate();
+        for c in self.compositions.iter() {
+            if let Err(e) = check_container_name(&c.final_container_name(&prefix, &suffix)) {
+                problems.push(format!(
+                    "container `{}` has an invalid name: {}",
+                    c.handle(),
+                    e
+                ));
+            }
+        }
+    }
+
+    /// Every named volume must reference a declared volume, if any volumes are declared.
+    fn validate_named_volumes(&self, problems: &mut Vec<String>) {
+        if self.declared_volumes.is_empty() {
+            return;
+        }
+
+        for c in self.compositions.iter() {
+            for (volume, _) in c.named_volumes.iter() {
+                if !self.declared_volumes.contains(volume) {
+                    problems.push(format!(
+                        "container `{}` references undeclared named volume `{}`, declared volumes: `{}`",
+                        c.handle(),
+                        volume,
+                        self.declared_volumes.join("`, `")
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Every host port must be mapped by a single container per protocol, and be free on the
+    /// host if checked.
+    fn validate_host_ports(&self, problems: &mut Vec<String>) {
+        let mut mapped: Vec<(&str, &str, String, &str)> = Vec::new();
+        for c in self.compositions.iter() {
+            for (exported, host) in c.mapped_host_ports() {
+                let protocol = port_protocol(exported);
+                match mapped
+                    .iter()
+                    .find(|(h, p, _, _)| *h == host && *p == protocol)
+                {
+                    Some((_, _, other, other_exported)) => problems.push(format!(
+                        "host port `{}` is mapped by both container `{}` to port `{}` and container `{}` to port `{}`",
+                        host,
+                        other,
+                        other_exported,
+                        c.handle(),
+                        exported
+                    )),
+                    None => {
+                        if self.check_host_ports
+                            && !c.is_static()
+                            && !host_port_free(host, protocol)
+                        {
+                            problems.push(format!(
+                                "host port `{}` mapped by container `{}` is already in use on the host",
+                                host,
+                                c.handle()
+                            ));
+                        }
+                        mapped.push((host, protocol, c.handle(), exported));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replicated containers must have at least one replica, and be managed by the test.
+    fn validate_replicas(&self, problems: &mut Vec<String>) {
+        for (handle, replicas) in self.replica_groups.iter() {
+            if *replicas == 0 {
+                problems.push(format!("container `{}` has no replicas", handle));
+            }
+        }
+        for c in self.compositions.iter() {
+            if c.is_static() && c.replica_of.is_some() {
+                problems.push(format!(
+                    "container `{}` is a replica of a static container, which cannot be replicated",
+                    c.handle()
+                ));
+            }
+        }
+    }
+
+    /// The tls fixtures must be issued to single, existing handles, of containers created by the
+    /// test itself on its own network, as the material is issued anew for every test and the
+    /// server certificate is valid for the address pinned to the container.
+    #[cfg(feature = "tls-fixtures")]
+    fn validate_tls_fixtures(&self, handles: &HashMap<String, usize>, problems: &mut Vec<String>) {
+        if self.tls_fixtures.is_empty() {
+            return;
+        }
+
+        if !matches!(self.network, Network::Isolated) {
+            problems.push("tls fixtures require the `Network::Isolated` network".to_string());
+        }
+        for handle in self.tls_fixtures.iter() {
+            match handles.get(handle) {
+                None => problems.push(format!(
+                    "tls fixtures are issued to non-existent handle `{}`",
+                    handle
+                )),
+                Some(count) if *count > 1 => problems.push(format!(
+                    "tls fixtures are issued to duplicate handle `{}`",
+                    handle
+                )),
+                Some(_) => (),
+            }
+        }
+        for c in self
+            .compositions
+            .iter()
+            .filter(|c| self.tls_fixtures.contains(&c.handle()))
+        {
+            let mut uses = Vec::new();
+            if c.is_static() {
+                uses.push("a static container");
+            }
+            if c.warm_pool().is_some() {
+                uses.push("a warm pool");
+            }
+            if self.shared_environment && !c.is_static() {
+                uses.push("a shared environment");
+            }
+            if c.existing_container_policy == ExistingContainerPolicy::Reuse {
+                uses.push("reuse of existing containers");
+            }
+            if !uses.is_empty() {
+                problems.push(format!(
+                    "tls fixtures are issued to container `{}`, which cannot be combined with {}",
+                    c.handle(),
+                    uses.join(" or ")
+                ));
+            }
+        }
+    }
+
+    /// Every `inject_container_name` must reference a single, existing handle.
+    fn validate_inject_container_name(
+        &self,
+        handles: &HashMap<String, usize>,
+        problems: &mut Vec<String>,
+    ) {
+        for c in self.compositions.iter() {
+            for (handle, env) in c.inject_container_name_env.iter() {
+                match handles.get(handle) {
+                    None => problems.push(format!(
+                        "container `{}` injects the container name of non-existent handle `{}` into env `{}`",
+                        c.handle(),
+                        handle,
+                        env
+                    )),
+                    Some(count) if *count > 1 => problems.push(format!(
+                        "container `{}` injects the container name of duplicate handle `{}` into env `{}`",
+                        c.handle(),
+                        handle,
+                        env
+                    )),
+                    Some(_) => (),
+                }
+            }
+        }
+    }
+
+    /// Every `inject_network_alias` must reference a single, existing handle with a network alias.
+    fn validate_inject_network_alias(
+        &self,
+        handles: &HashMap<String, usize>,
+        problems: &mut Vec<String>,
+    ) {
+        for c in self.compositions.iter() {
+            for (handle, env) in c.inject_network_alias_env.iter() {
+                match handles.get(handle) {
+                    None => problems.push(format!(
+                        "container `{}` injects the network alias of non-existent handle `{}` into env `{}`",
+                        c.handle(),
+                        handle,
+                        env
+                    )),
+                    Some(count) if *count > 1 => problems.push(format!(
+                        "container `{}` injects the network alias of duplicate handle `{}` into env `{}`",
+                        c.handle(),
+                        handle,
+                        env
+                    )),
+                    Some(_) => {
+                        let aliased = self
+                            .compositions
+                            .iter()
+                            .any(|t| t.handle() == *handle && t.network_alias().is_some());
+                        if !aliased {
+                            problems.push(format!(
+                                "container `{}` injects the network alias of handle `{}` without a network alias into env `{}`",
+                                c.handle(),
+                                handle,
+                                env
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Every `inject_host_port` must reference a single, existing handle.
+    fn validate_inject_host_port(
+        &self,
+        handles: &HashMap<String, usize>,
+        problems: &mut Vec<String>,
+    ) {
+        for c in self.compositions.iter() {
+            for (handle, port, env) in c.inject_host_port_env.iter() {
+                match handles.get(handle) {
+                    None => problems.push(format!(
+                        "container `{}` injects host port `{}` of non-existent handle `{}` into env `{}`",
+                        c.handle(),
+                        port,
+                        handle,
+                        env
+                    )),
+                    Some(count) if *count > 1 => problems.push(format!(
+                        "container `{}` injects host port `{}` of duplicate handle `{}` into env `{}`",
+                        c.handle(),
+                        port,
+                        handle,
+                        env
+                    )),
+                    Some(_) => (),
+                }
+            }
+        }
+    }
+
+    /// Every placeholder within an environment variable value must be well-formed and
+    /// reference a single, existing handle.
+    fn validate_env_templates(&self, handles: &HashMap<String, usize>, problems: &mut Vec<String>) {
+        for c in self.compositions.iter() {
+            for (env, value) in c.env.iter() {
+                let segments = match template::parse(value) {
+                    Ok(segments) => segments,
+                    Err(e) => {
+                        problems.push(format!(
+                            "container `{}` has an invalid env `{}`: {}",
+                            c.handle(),
+                            env,
+                            c.redactions().redact(&e)
+                        ));
+                        continue;
+                    }
+                };
+
+                for segment in segments {
+                    let placeholder = match segment {
+                        Segment::Placeholder(p) => p,
+                        Segment::Literal(_) => continue,
+                    };
+                    match handles.get(placeholder.handle()) {
+                        None => problems.push(format!(
+                            "container `{}` references non-existent handle `{}` in env `{}`",
+                            c.handle(),
+                            placeholder.handle(),
+                            env
+                        )),
+                        Some(count) if *count > 1 => problems.push(format!(
+                            "container `{}` references duplicate handle `{}` in env `{}`",
+                            c.handle(),
+                            placeholder.handle(),
+                            env
+                        )),
+                        Some(_) => (),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Execute the test with the constructed environment in full operation.
+    ///
+    /// # Synchronous
+    /// This non-async version creates its own runtime to execute the test.
+    pub fn run<T, Fut>(self, test: T)
+    where
+        T: FnOnce(DockerOperations) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.run_with_report(test);
+    }
+
+    /// Execute the test like [DockerTest::run], returning the [TeardownReport] of the
+    /// environment, which captures the final state of every container once the test body
+    /// completes.
+    ///
+    /// # Synchronous
+    /// This non-async version creates its own runtime to execute the test.
+    // NOTE(clippy): tracing generates cognitive complexity due to macro expansion.
+    #[allow(clippy::cognitive_complexity)]
+    pub fn run_with_report<T, Fut>(self, test: T) -> TeardownReport
+    where
+        T: FnOnce(DockerOperations) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let span = span!(Level::ERROR, "run");
+        let _guard = span.enter();
+
+        // Reject an invalid environment before we allocate any resources.
+        if let Err(e) = self.validate() {
+            return process_run(Err(e));
+        }
+
+        // Use the provided runtime, or allocate a new one for this test.
+        let rt;
+        let handle = match self.runtime.clone() {
+            Some(handle) => handle,
+            None => {
+                rt = match self.build_runtime() {
+                    Ok(r) => r,
+                    Err(e) => {
+                        event!(Level::ERROR, "failed to allocate tokio runtime: {}", e);
+                        panic!("{}", e);
+                    }
+                };
+                rt.handle().clone()
+            }
+        };
+
+        let runner = handle.block_on(Runner::new(self));
+        process_run(handle.block_on(runner.run(test).in_current_span()))
+    }
+
+    /// Allocate the runtime used by [DockerTest::run] when none is provided.
+    fn build_runtime(&self) -> std::io::Result<Runtime> {
+        match self.worker_threads {
+            Some(worker_threads) => tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(worker_threads)
+                .enable_all()
+                .build(),
+            None => Runtime::new(),
+        }
+    }
+
+    /// Execute a synchronous test body with the constructed environment in full operation.
+    ///
+    /// # Synchronous
+    /// Like [DockerTest::run], this creates its own runtime to manage the environment. The test
+    /// body is a regular closure, executed on a thread where blocking is allowed, such that
+    /// synchronous tests can use dockertest without adopting an async runtime themselves.
+    pub fn run_blocking<T>(self, test: T)
+    where
+        T: FnOnce(DockerOperations) + Send + 'static,
+    {
+        self.run(|ops| async move {
+            if let Err(e) = tokio::task::spawn_blocking(move || test(ops)).await {
+                match e.try_into_panic() {
+                    // Propagate the original panic of the test body.
+                    Ok(panic) => std::panic::resume_unwind(panic),
+                    Err(e) => panic!("test body did not complete: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Execute a test body returning a [Result] with the constructed environment in full operation.
+    ///
+    /// # Synchronous
+    /// Like [DockerTest::run], this creates its own runtime to manage the environment. An error
+    /// returned by the test body fails the test like [DockerOperations::fail_with], such that
+    /// test bodies can use the `?` operator instead of panicking. The failure is reported along
+    /// with the state of the environment, and the environment is pruned as a failed test.
+    pub fn run_result<T, Fut, E>(self, test: T)
+    where
+        T: FnOnce(DockerOperations) -> Fut,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.run(|ops| {
+            let body = test(ops.clone());
+            async move {
+                if let Err(e) = body.await {
+                    ops.fail_with(e);
+                }
+            }
+        })
+    }
+
+    /// Async version of [DockerTest::run].
+    ///
+    /// # Asynchronous
+    /// This version allows the caller to provide the runtime to execute this test within.
+    /// This can be useful if the test executable is wrapped with a runtime macro, e.g.,
+    /// `#[tokio::test]`.
+    pub async fn run_async<T, Fut>(self, test: T)
+    where
+        T: FnOnce(DockerOperations) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.run_async_with_report(test).await;
+    }
+
+    /// Async version of [DockerTest::run_result].
+    ///
+    /// # Asynchronous
+    /// Like [DockerTest::run_async], this executes the test within the runtime of the caller.
+    /// An error returned by the test body fails the test like [DockerOperations::fail_with].
+    pub async fn run_async_result<T, Fut, E>(self, test: T)
+    where
+        T: FnOnce(DockerOperations) -> Fut,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.run_async(|ops| {
+            let body = test(ops.clone());
+            async move {
+                if let Err(e) = body.await {
+                    ops.fail_with(e);
+                }
+            }
+        })
+        .await;
+    }
+
+    /// Async version of [DockerTest::run_with_report].
+    ///
+    /// # Asynchronous
+    /// This version allows the caller to provide the runtime to execute this test within.
+    pub async fn run_async_with_report<T, Fut>(self, test: T) -> TeardownReport
+    where
+        T: FnOnce(DockerOperations) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let span = span!(Level::ERROR, "run");
+        let _guard = span.enter();
+
+        if let Err(e) = self.validate() {
+            return process_run(Err(e));
+        }
+
+        let runner = Runner::new(self).await;
+        process_run(runner.run(test).in_current_span().await)
+    }
+}
+
+impl Default for DockerTest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for DockerTest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "DockerTest `{}` (network: {:?}, default source: {:?}) with {} container(s)",
+            self.namespace,
+            self.network,
+            self.default_source,
+            self.compositions.len()
+        )?;
+        for c in self.compositions.iter() {
+            write!(f, "\n  - {}", c)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The protocol of an exported port on the form `{port}/{protocol}`, which defaults to tcp.
+fn port_protocol(exported: &str) -> &str {
+    match exported.split_once('/') {
+        Some((_, protocol)) => protocol,
+        None => "tcp",
+    }
+}
+
+/// Whether the host port may be bound for the protocol on localhost, where the docker daemon
+/// publishes it.
+fn host_port_free(port: &str, protocol: &str) -> bool {
+    match port.parse::<u16>() {
+        Ok(port) if protocol == "udp" => UdpSocket::bind((Ipv4Addr::LOCALHOST, port)).is_ok(),
+        Ok(port) => TcpListener::bind((Ipv4Addr::LOCALHOST, port)).is_ok(),
+        // The docker daemon reports malformed ports
+        Err(_) => true,
+    }
+}
+
+/// Replace all characters that are not allowed in a docker container name.
+pub(crate) fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+fn process_run(result: Result<TeardownReport, DockerTestError>) -> TeardownReport {
+    match result {
+        Ok(report) => {
+            event!(Level::DEBUG, "dockertest successfully executed");
+            report
+        }
+        Err(e) => {
+            event!(
+                Level::ERROR,
+                "internal dockertest condition failure: {:?}",
+                e
             );
             event!(Level::WARN, "dockertest failure");
             panic!("{}", e);
@@ -195,7 +1671,16 @@ fn process_run(result: Result<(), DockerTestError>) {
 
 #[cfg(test)]
 mod tests {
-    use crate::{DockerTest, Source};
+    use super::{host_port_free, port_protocol};
+    use crate::composition::check_container_name;
+    use crate::faketime;
+    use crate::{
+        BindMountOptions, ContainerSpecification, DockerTest, DockerTestError,
+        ExistingContainerPolicy, MountPropagation, SelinuxRelabel, Source, StartPolicy,
+        TestBodySpecification, TestEnvironmentModule,
+    };
+
+    use std::ffi::OsString;
 
     // The default DockerTest constructor produces a valid instance with the correct values set
     #[test]
@@ -239,4 +1724,977 @@ mod tests {
 
         assert!(equal, "default_source was not set correctly");
     }
+
+    // Injecting the container name of an unknown handle is rejected by validation
+    #[test]
+    fn test_validate_inject_container_name_unknown_handle() {
+        let mut test = DockerTest::new();
+        let mut hello = TestBodySpecification::with_repository("hello-world");
+        hello.inject_container_name("does_not_exist", "TARGET");
+        test.provide_container(hello);
+
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg)) if msg.contains("does_not_exist")),
+            "validation should reject unknown handle, got: {:?}",
+            result
+        );
+    }
+
+    // Injecting the container name of a duplicate handle is rejected by validation
+    #[test]
+    fn test_validate_inject_container_name_duplicate_handle() {
+        let mut test = DockerTest::new();
+        let mut hello = TestBodySpecification::with_repository("hello-world");
+        hello.inject_container_name("busybox", "TARGET");
+        test.provide_container(hello)
+            .provide_container(TestBodySpecification::with_repository("busybox"))
+            .provide_container(TestBodySpecification::with_repository("busybox"));
+
+        assert!(
+            test.validate().is_err(),
+            "validation should reject duplicate handle"
+        );
+    }
+
+    // Injecting the network alias of a container without aliases is rejected by validation
+    #[test]
+    fn test_validate_inject_network_alias_without_alias() {
+        let mut test = DockerTest::new();
+        let mut hello = TestBodySpecification::with_repository("hello-world");
+        hello.inject_network_alias("busybox", "TARGET");
+        test.provide_container(hello)
+            .provide_container(TestBodySpecification::with_repository("busybox"));
+
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg)) if msg.contains("without a network alias")),
+            "validation should reject the handle without alias, got: {:?}",
+            result
+        );
+    }
+
+    // Providing the same container name for multiple containers is rejected by validation
+    #[test]
+    fn test_validate_duplicate_container_name() {
+        let mut test = DockerTest::new();
+        test.provide_container(TestBodySpecification::with_repository("postgres").set_handle("db"))
+            .provide_container(TestBodySpecification::with_repository("mysql").set_handle("db"))
+            .provide_container(TestBodySpecification::with_repository("hello-world"))
+            .provide_container(TestBodySpecification::with_repository("hello-world"));
+
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg))
+                if msg.contains("postgres:latest") && msg.contains("mysql:latest")
+                    && !msg.contains("hello-world")),
+            "validation should reject duplicate container name, got: {:?}",
+            result
+        );
+    }
+
+    // Container names that the docker daemon rejects are reported with the offending character
+    #[test]
+    fn test_validate_container_name_format() {
+        let mut test = DockerTest::new();
+        test.provide_container(TestBodySpecification::with_repository("hello-world"))
+            .provide_container(TestBodySpecification::with_repository("localhost:5000/app"));
+        assert_eq!(test.validate(), Ok(()));
+
+        test.provide_container(
+            TestBodySpecification::with_repository("hello-world").set_handle("my app"),
+        );
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg)) if msg.contains("` `")),
+            "validation should reject the space, got: {:?}",
+            result
+        );
+
+        let mut test = DockerTest::new().with_namespace("n".repeat(60));
+        test.provide_container(TestBodySpecification::with_repository("hello-world"));
+        assert!(
+            test.validate().is_err(),
+            "validation should reject a name exceeding the length limit"
+        );
+    }
+
+    // The repository within generated container names is shortened to the length of a DNS label
+    #[test]
+    fn test_validate_long_repository_name() {
+        let mut test = DockerTest::new();
+        test.provide_container(TestBodySpecification::with_repository(
+            "registry.example.com:5000/dockertest-rs/hello-privileged",
+        ))
+        .provide_container(TestBodySpecification::with_repository(
+            "registry.example.com:5000/dockertest-rs/hello-unprivileged",
+        ));
+        assert_eq!(test.validate(), Ok(()));
+
+        let names: Vec<String> = test
+            .compositions
+            .iter()
+            .map(|c| c.final_container_name(&test.container_name_prefix(), &"s".repeat(20)))
+            .collect();
+        assert!(names.iter().all(|name| name.len() <= 63), "{:?}", names);
+        assert_ne!(names[0], names[1]);
+    }
+
+    // A raw config hook is rejected for pooled, shared and reused containers
+    #[test]
+    fn test_validate_raw_config_hook() {
+        let hooked =
+            || TestBodySpecification::with_repository("hello-world").set_raw_config_hook(|_, _| {});
+
+        let mut test = DockerTest::new();
+        test.provide_container(hooked());
+        assert_eq!(test.validate(), Ok(()));
+
+        let mut test = DockerTest::new();
+        test.provide_container(hooked().set_warm_pool(2));
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg)) if msg.contains("a warm pool")),
+            "validation should reject a hooked warm pool, got: {:?}",
+            result
+        );
+
+        let mut test = DockerTest::new().with_shared_environment();
+        test.provide_container(
+            hooked().set_existing_container_policy(ExistingContainerPolicy::Reuse),
+        );
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg))
+                if msg.contains("a shared environment or reuse of existing containers")),
+            "validation should reject a hooked shared and reused container, got: {:?}",
+            result
+        );
+    }
+
+    // Tls fixtures are rejected outside of an isolated network and for static, pooled, shared and
+    // reused containers
+    #[cfg(feature = "tls-fixtures")]
+    #[test]
+    fn test_validate_tls_fixtures() {
+        use crate::{DynamicSpecification, Network};
+
+        let mut test = DockerTest::new()
+            .with_network(Network::Isolated)
+            .with_tls_fixtures(&["hello-world"]);
+        test.provide_container(TestBodySpecification::with_repository("hello-world"));
+        assert_eq!(test.validate(), Ok(()));
+
+        let mut test = DockerTest::new().with_tls_fixtures(&["hello-world"]);
+        test.provide_container(TestBodySpecification::with_repository("hello-world"));
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg)) if msg.contains("Network::Isolated")),
+            "validation should reject the singular network, got: {:?}",
+            result
+        );
+
+        let mut test = DockerTest::new()
+            .with_network(Network::Isolated)
+            .with_tls_fixtures(&["tls"]);
+        test.provide_container(DynamicSpecification::with_repository("hello-world", "tls"));
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg)) if msg.contains("a static container")),
+            "validation should reject a static container, got: {:?}",
+            result
+        );
+
+        let mut test = DockerTest::new()
+            .with_network(Network::Isolated)
+            .with_tls_fixtures(&["hello-world"]);
+        test.provide_container(
+            TestBodySpecification::with_repository("hello-world")
+                .set_warm_pool(2)
+                .set_existing_container_policy(ExistingContainerPolicy::Reuse),
+        );
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg))
+                if msg.contains("a warm pool or reuse of existing containers")),
+            "validation should reject a pooled and reused container, got: {:?}",
+            result
+        );
+    }
+
+    // Container names are accepted up to the length of a DNS label
+    #[test]
+    fn test_check_container_name_length() {
+        assert_eq!(check_container_name(&"a".repeat(63)), Ok(()));
+        let result = check_container_name(&"a".repeat(64));
+        assert!(
+            matches!(result, Err(ref msg) if msg.contains("64 characters long")),
+            "a name exceeding a DNS label should be rejected, got: {:?}",
+            result
+        );
+    }
+
+    // Referencing an undeclared named volume is rejected by validation, once volumes are declared
+    #[test]
+    fn test_validate_undeclared_named_volume() {
+        let provide = |test: &mut DockerTest| {
+            let mut writer = TestBodySpecification::with_repository("writer");
+            writer.modify_named_volume("data", "/data");
+            let mut reader = TestBodySpecification::with_repository("reader");
+            reader.modify_named_volume("dat", "/data");
+            test.provide_container(writer).provide_container(reader);
+        };
+
+        let mut test = DockerTest::new();
+        provide(&mut test);
+        assert_eq!(test.validate(), Ok(()));
+
+        let mut test = DockerTest::new().with_named_volume("data");
+        provide(&mut test);
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg))
+                if msg.contains("`dat`") && msg.contains("`data`") && !msg.contains("`writer`")),
+            "validation should reject the undeclared volume, got: {:?}",
+            result
+        );
+    }
+
+    // Mapping the same host port for multiple containers is rejected by validation
+    #[test]
+    fn test_validate_host_port_conflict() {
+        let mut postgres = TestBodySpecification::with_repository("postgres");
+        postgres.modify_port_map(5432, 15432);
+        let mut replica = TestBodySpecification::with_repository("replica");
+        replica
+            .modify_port_map(5432, 15433)
+            .modify_port_map(8080, 15432);
+        let mut other = TestBodySpecification::with_repository("other");
+        other.modify_port_map(80, 0).modify_port_map(81, 0);
+
+        let mut test = DockerTest::new();
+        test.provide_container(postgres)
+            .provide_container(replica)
+            .provide_container(other);
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg))
+                if msg.contains("`postgres`") && msg.contains("`replica`")
+                    && !msg.contains("`other`")),
+            "validation should reject the conflicting host port, got: {:?}",
+            result
+        );
+    }
+
+    // Mapping a host port in use on the host is rejected by validation, if checked
+    #[test]
+    fn test_validate_host_port_in_use() {
+        let listener = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let provide = |test: &mut DockerTest| {
+            let mut postgres = TestBodySpecification::with_repository("postgres");
+            postgres.modify_port_map(5432, port as u32);
+            test.provide_container(postgres);
+        };
+
+        let mut test = DockerTest::new();
+        provide(&mut test);
+        assert_eq!(test.validate(), Ok(()));
+
+        let mut test = DockerTest::new().with_host_port_check();
+        provide(&mut test);
+        assert!(
+            test.validate().is_err(),
+            "validation should reject the host port in use"
+        );
+    }
+
+    // A host port is only in use for the protocol it is bound with
+    #[test]
+    fn test_host_port_free_protocol() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port().to_string();
+
+        assert_eq!(port_protocol("53/udp"), "udp");
+        assert_eq!(port_protocol("53"), "tcp");
+        assert!(!host_port_free(&port, port_protocol("8080/tcp")));
+        assert!(host_port_free(&port, port_protocol("8080/udp")));
+    }
+
+    // Injecting the host port of an unknown handle is rejected by validation
+    #[test]
+    fn test_validate_inject_host_port_unknown_handle() {
+        let mut test = DockerTest::new();
+        let mut hello = TestBodySpecification::with_repository("hello-world");
+        hello.inject_host_port("does_not_exist", 5432, "DB_PORT");
+        test.provide_container(hello);
+
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg)) if msg.contains("does_not_exist")),
+            "validation should reject unknown handle, got: {:?}",
+            result
+        );
+    }
+
+    // Tests that the test name is sanitized and included in the container name prefix
+    #[test]
+    fn test_with_test_name() {
+        let test = DockerTest::new()
+            .with_namespace("myproject")
+            .with_test_name("api::my test");
+        assert_eq!(test.container_name_prefix(), "myproject-api--my-test");
+    }
+
+    // Tests that the test name is derived from the thread named by the test harness
+    #[test]
+    fn test_with_test_name_from_thread() {
+        let test = std::thread::Builder::new()
+            .name("api::integration::test_database".to_string())
+            .spawn(|| DockerTest::new().with_test_name_from_thread())
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(test.container_name_prefix(), "dockertest-rs-test_database");
+    }
+
+    // A runtime without worker threads is rejected by validation
+    #[test]
+    fn test_validate_zero_worker_threads() {
+        let test = DockerTest::new().with_worker_threads(0);
+        assert!(
+            test.validate().is_err(),
+            "validation should reject zero worker threads"
+        );
+    }
+
+    // A start concurrency without any permits is rejected by validation
+    #[test]
+    fn test_validate_zero_start_concurrency() {
+        let test = DockerTest::new().with_start_concurrency(0);
+        assert!(
+            test.validate().is_err(),
+            "validation should reject a start concurrency of zero"
+        );
+    }
+
+    // Members of a start group with differing orders are rejected by validation
+    #[test]
+    fn test_validate_start_group_order() {
+        let mut test = DockerTest::new();
+        test.provide_container(
+            TestBodySpecification::with_repository("postgres")
+                .set_start_policy(StartPolicy::Group("infrastructure".to_string(), 0)),
+        );
+        test.provide_container(
+            TestBodySpecification::with_repository("redis")
+                .set_start_policy(StartPolicy::Group("infrastructure".to_string(), 0)),
+        );
+        assert!(test.validate().is_ok());
+
+        test.provide_container(
+            TestBodySpecification::with_repository("nginx")
+                .set_start_policy(StartPolicy::Group("infrastructure".to_string(), 1)),
+        );
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg)) if msg.contains("`nginx`")),
+            "validation should reject the differing order, got: {:?}",
+            result
+        );
+    }
+
+    // Pooled containers depending on the test are rejected by validation
+    #[test]
+    fn test_validate_warm_pool() {
+        let mut test = DockerTest::new();
+        test.provide_container(TestBodySpecification::with_repository("postgres").set_warm_pool(2));
+        assert!(test.validate().is_ok());
+
+        let mut app = TestBodySpecification::with_repository("app").set_warm_pool(2);
+        app.inject_container_name("postgres", "DB_HOST");
+        test.provide_container(app);
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg)) if msg.contains("`app`")),
+            "validation should reject the injection, got: {:?}",
+            result
+        );
+    }
+
+    // A secret must be named by a file name
+    #[test]
+    fn test_validate_secret_name() {
+        let mut test = DockerTest::new();
+        let mut postgres = TestBodySpecification::with_repository("postgres");
+        postgres.modify_secret("db_password", "hunter2");
+        test.provide_container(postgres);
+        assert!(test.validate().is_ok());
+
+        let mut app = TestBodySpecification::with_repository("app");
+        app.modify_secret("../db_password", "hunter2");
+        test.provide_container(app);
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg)) if msg.contains("`../db_password`") && !msg.contains("hunter2")),
+            "validation should reject the secret name, got: {:?}",
+            result
+        );
+    }
+
+    // A mount hiding the secrets is rejected
+    #[test]
+    fn test_validate_secrets_hidden_by_mount() {
+        let mut test = DockerTest::new();
+        let mut postgres = TestBodySpecification::with_repository("postgres");
+        postgres
+            .modify_secret("db_password", "hunter2")
+            .modify_named_volume("secrets", "/run/secrets");
+        test.provide_container(postgres);
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg)) if msg.contains("`/run/secrets`")),
+            "validation should reject the mount, got: {:?}",
+            result
+        );
+    }
+
+    // Mounts must target absolute linux or Windows paths, and named pipes must name a pipe
+    #[test]
+    fn test_validate_mount_paths() {
+        let mut test = DockerTest::new();
+        let mut windows = TestBodySpecification::with_repository("windows");
+        windows
+            .modify_bind_mount("C:\\host", "C:\\data")
+            .modify_named_pipe("\\\\.\\pipe\\docker_engine", "\\\\.\\pipe\\docker_engine");
+        test.provide_container(windows);
+        assert!(test.validate().is_ok());
+
+        let mut relative = TestBodySpecification::with_repository("relative");
+        relative.modify_bind_mount("/host", "data");
+        test.provide_container(relative);
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg)) if msg.contains("`data`")),
+            "validation should reject the relative path, got: {:?}",
+            result
+        );
+
+        let mut test = DockerTest::new();
+        let mut pipe = TestBodySpecification::with_repository("pipe");
+        pipe.modify_named_pipe("docker_engine", "\\\\.\\pipe\\docker_engine");
+        test.provide_container(pipe);
+        assert!(
+            test.validate().is_err(),
+            "validation should reject the named pipe"
+        );
+    }
+
+    // Tests that bind mounts into Windows containers are not relabeled
+    #[test]
+    fn test_resolve_windows_bind_mount() {
+        let mut test = DockerTest::new();
+        let mut windows = TestBodySpecification::with_repository("windows");
+        windows
+            .modify_bind_mount("C:\\host", "C:\\data")
+            .modify_bind_mount("/host", "/data");
+        test.provide_container(windows);
+
+        let resolved = test.resolve().unwrap();
+        assert_eq!(
+            resolved[0].binds(),
+            &["C:\\host:C:\\data".to_string(), "/host:/data:Z".to_string()]
+        );
+    }
+
+    // Tests that the options of bind mounts are passed to the docker daemon
+    #[test]
+    fn test_resolve_bind_mount_options() {
+        let mut test = DockerTest::new();
+        let mut fedora = TestBodySpecification::with_repository("fedora");
+        fedora
+            .modify_bind_mount_with(
+                "/host",
+                "/data",
+                BindMountOptions::new()
+                    .with_read_only(true)
+                    .with_propagation(MountPropagation::RShared)
+                    .with_selinux_relabel(Some(SelinuxRelabel::Shared)),
+            )
+            .modify_bind_mount_with(
+                "/usr",
+                "/host-usr",
+                BindMountOptions::new().with_selinux_relabel(None),
+            )
+            .modify_bind_mount_with(
+                "C:\\host",
+                "C:\\data",
+                BindMountOptions::new()
+                    .with_read_only(true)
+                    .with_propagation(MountPropagation::Slave),
+            );
+        test.provide_container(fedora);
+
+        let resolved = test.resolve().unwrap();
+        assert_eq!(
+            resolved[0].binds(),
+            &[
+                "/host:/data:ro,rshared,z".to_string(),
+                "/usr:/host-usr".to_string(),
+                "C:\\host:C:\\data:ro".to_string()
+            ]
+        );
+    }
+
+    // A malformed network address pool is rejected by validation
+    #[test]
+    fn test_validate_network_address_pool() {
+        let test = DockerTest::new().with_network_address_pool("10.210.0.0", 24);
+        assert!(
+            test.validate().is_err(),
+            "validation should reject an address pool without prefix"
+        );
+
+        let test = DockerTest::new().with_network_address_pool("10.210.0.0/16", 24);
+        assert!(test.validate().is_ok());
+    }
+
+    // A consistent environment passes validation
+    #[test]
+    fn test_validate_inject_container_name_valid() {
+        let mut test = DockerTest::new();
+        let mut hello = TestBodySpecification::with_repository("hello-world");
+        hello.inject_container_name("busybox", "TARGET");
+        test.provide_container(hello)
+            .provide_container(TestBodySpecification::with_repository("busybox"));
+
+        assert_eq!(test.validate(), Ok(()));
+    }
+
+    // The containers of a module are registered in order, between the containers provided
+    // before and after it
+    #[test]
+    fn test_provide_module() {
+        struct Stack;
+
+        impl TestEnvironmentModule for Stack {
+            fn register(&self, test: &mut DockerTest) {
+                let mut grafana = TestBodySpecification::with_repository("grafana");
+                grafana.inject_container_name("prometheus", "PROMETHEUS_HOST");
+                test.provide_container(TestBodySpecification::with_repository("prometheus"))
+                    .provide_container(grafana);
+            }
+        }
+
+        let mut test = DockerTest::new();
+        test.provide_container(TestBodySpecification::with_repository("app"))
+            .provide_module(&Stack)
+            .provide_module(&|test: &mut DockerTest| {
+                test.provide_container(TestBodySpecification::with_repository("collector"));
+            });
+
+        let handles: Vec<String> = test.compositions.iter().map(|c| c.handle()).collect();
+        assert_eq!(handles, ["app", "prometheus", "grafana", "collector"]);
+        assert_eq!(test.validate(), Ok(()));
+    }
+
+    // The Display implementation lists every container, without any environment values
+    #[test]
+    fn test_display_summarizes_environment() {
+        let mut test = DockerTest::new();
+        let mut hello = TestBodySpecification::with_repository("hello-world");
+        hello.modify_env("PASSWORD", "hunter2");
+        test.provide_container(hello)
+            .provide_container(TestBodySpecification::with_repository("busybox"));
+
+        let summary = test.to_string();
+        assert!(summary.contains("`hello-world` from image `hello-world:latest`"));
+        assert!(summary.contains("`busybox` from image `busybox:latest`"));
+        assert!(summary.contains("PASSWORD"));
+        assert!(
+            !summary.contains("hunter2"),
+            "environment values must not be displayed"
+        );
+    }
+
+    // A placeholder referencing an unknown handle is rejected by validation
+    #[test]
+    fn test_validate_env_template_unknown_handle() {
+        let mut test = DockerTest::new();
+        let mut hello = TestBodySpecification::with_repository("hello-world");
+        hello.modify_env("DB_URL", "postgres://{handle:db}:5432/app");
+        test.provide_container(hello);
+
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg)) if msg.contains("`db`")),
+            "validation should reject unknown placeholder handle, got: {:?}",
+            result
+        );
+    }
+
+    // Tests that an escaped placeholder is passed to the container as a literal
+    #[test]
+    fn test_resolve_escaped_env_template() {
+        let mut test = DockerTest::new();
+        let mut hello = TestBodySpecification::with_repository("hello-world");
+        hello.modify_env("TEMPLATE", "{{handle:db}/{{host_port:db:5432}");
+        test.provide_container(hello);
+
+        let resolved = test.resolve().unwrap();
+        assert_eq!(
+            resolved[0].env()["TEMPLATE"],
+            "{handle:db}/{host_port:db:5432}"
+        );
+    }
+
+    // Tests that the values of sensitive environment variables are redacted from errors
+    #[test]
+    fn test_validate_env_template_redacts_sensitive_env() {
+        let mut test = DockerTest::new();
+        let mut hello = TestBodySpecification::with_repository("hello-world");
+        hello
+            .modify_env("DB_PASSWORD", "hunter2{handle:db")
+            .modify_env("DB_USER", "app")
+            .mark_env_sensitive(&["DB_PASSWORD"]);
+        test.provide_container(hello);
+
+        let redactions = test.compositions[0].redactions();
+        assert_eq!(
+            redactions.redact("user app, password hunter2{handle:db"),
+            "user app, password [REDACTED]"
+        );
+
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg))
+                if msg.contains("DB_PASSWORD") && !msg.contains("hunter2")),
+            "validation should redact the sensitive value, got: {:?}",
+            result
+        );
+    }
+
+    // Tests that the debug output of a test only lists the keys of the environment variables
+    #[test]
+    fn test_debug_omits_env_values() {
+        let mut test = DockerTest::new();
+        let mut hello = TestBodySpecification::with_repository("hello-world");
+        hello.modify_env("DB_PASSWORD", "hunter2");
+        test.provide_container(hello);
+
+        let debug = format!("{:?}", test);
+        assert!(debug.contains("DB_PASSWORD"), "{}", debug);
+        assert!(!debug.contains("hunter2"), "{}", debug);
+    }
+
+    // Tests that default environment variables do not override those of the container
+    #[test]
+    fn test_default_env_does_not_override_container_env() {
+        let mut env = std::collections::HashMap::new();
+        env.insert("A".to_string(), "default".to_string());
+        env.insert("B".to_string(), "default".to_string());
+        let mut test = DockerTest::new().with_default_env(env);
+
+        let mut hello = TestBodySpecification::with_repository("hello-world");
+        hello.modify_env("A", "container");
+        test.provide_container(hello);
+
+        let mut composition = test.compositions.remove(0);
+        composition.apply_defaults(&test.defaults);
+        assert_eq!(composition.env["A"], "container");
+        assert_eq!(composition.env["B"], "default");
+    }
+
+    // Tests that matching variables of the test process are passed through, without replacing
+    // configured variables
+    #[test]
+    fn test_passthrough_env() {
+        let mut hello = TestBodySpecification::with_repository("hello-world");
+        hello
+            .append_passthrough_env("DOCKERTEST_PASSTHROUGH_TEST_")
+            .modify_env("DOCKERTEST_PASSTHROUGH_TEST_B", "container");
+        let process_env = [
+            ("DOCKERTEST_PASSTHROUGH_TEST_A", "process"),
+            ("DOCKERTEST_PASSTHROUGH_TEST_B", "process"),
+            ("PATH", "/usr/bin"),
+        ]
+        .map(|(name, value)| (OsString::from(name), OsString::from(value)));
+
+        let env = hello.into_composition().resolve_env_from(process_env);
+        assert_eq!(env["DOCKERTEST_PASSTHROUGH_TEST_A"], "process");
+        assert_eq!(env["DOCKERTEST_PASSTHROUGH_TEST_B"], "container");
+        assert!(!env.contains_key("PATH"));
+
+        let mut test = DockerTest::new();
+        let mut hello = TestBodySpecification::with_repository("hello-world");
+        hello.append_passthrough_env("");
+        test.provide_container(hello);
+        assert!(
+            test.validate().is_err(),
+            "validation should reject an empty prefix"
+        );
+    }
+
+    // Tests that the environment hash depends on the definition of the containers only
+    #[test]
+    fn test_environment_hash() {
+        let provide = |test: &mut DockerTest, password: &str| {
+            let mut postgres = TestBodySpecification::with_repository("postgres");
+            postgres
+                .modify_env("POSTGRES_PASSWORD", password)
+                .modify_named_volume("data", "/var/lib/postgresql/data");
+            test.provide_container(postgres);
+        };
+
+        let mut test = DockerTest::new();
+        provide(&mut test, "secret");
+        let hash = test.environment_hash();
+        assert_eq!(hash, test.environment_hash());
+
+        let mut other = DockerTest::new().with_namespace("other");
+        provide(&mut other, "secret");
+        assert_eq!(hash, other.environment_hash());
+
+        let mut changed = DockerTest::new();
+        provide(&mut changed, "other");
+        assert_ne!(hash, changed.environment_hash());
+    }
+
+    // Tests that the performance mode applies the knobs of recognized database images only
+    #[test]
+    fn test_ephemeral_performance_mode() {
+        let mut test = DockerTest::new();
+        let postgres =
+            TestBodySpecification::with_repository("postgres").set_ephemeral_performance_mode();
+        let mut mysql = TestBodySpecification::with_repository("mysql")
+            .set_ephemeral_performance_mode()
+            .replace_cmd(vec!["mysqld".to_string()]);
+        mysql.modify_named_volume("seed", "/var/lib/mysql");
+        let redis =
+            TestBodySpecification::with_repository("redis").set_ephemeral_performance_mode();
+        test.provide_container(postgres)
+            .provide_container(mysql)
+            .provide_container(redis);
+
+        let resolved = test.resolve().unwrap();
+        assert!(resolved[0].cmd().contains(&"fsync=off".to_string()));
+        assert_eq!(
+            resolved[0].tmpfs(),
+            &["/var/lib/postgresql/data".to_string()]
+        );
+        assert_eq!(resolved[1].cmd()[0], "mysqld");
+        assert!(resolved[1]
+            .cmd()
+            .contains(&"--skip-innodb-doublewrite".to_string()));
+        assert!(resolved[1].tmpfs().is_empty());
+        assert!(resolved[2].cmd().is_empty());
+        assert!(resolved[2].tmpfs().is_empty());
+    }
+
+    // Tests that libfaketime is mounted and preloaded into containers with a faked clock, as
+    // located on the host running the tests
+    #[test]
+    fn test_faked_time() {
+        let mut test = DockerTest::new();
+        test.provide_container(
+            TestBodySpecification::with_repository("postgres").set_faked_time(-3600),
+        )
+        .provide_container(TestBodySpecification::with_repository("redis"));
+
+        let library = match faketime::library(std::env::var_os(faketime::LIBRARY_ENV)) {
+            Ok(library) => library,
+            Err(_) => {
+                let err = test.resolve().unwrap_err().to_string();
+                assert!(
+                    err.contains("container `postgres` fakes its clock"),
+                    "{}",
+                    err
+                );
+                return;
+            }
+        };
+
+        let resolved = test.resolve().unwrap();
+        assert_eq!(
+            resolved[0].env()["FAKETIME_TIMESTAMP_FILE"],
+            "/usr/lib/dockertest/faketime"
+        );
+        assert_eq!(resolved[0].env()["FAKETIME_NO_CACHE"], "1");
+        assert!(resolved[0].env().contains_key("LD_PRELOAD"));
+        assert_eq!(
+            resolved[0].binds(),
+            &[format!(
+                "{}:/usr/lib/dockertest/libfaketime.so.1:ro",
+                library.display()
+            )]
+        );
+        assert!(!resolved[1].env().contains_key("LD_PRELOAD"));
+        assert!(resolved[1].binds().is_empty());
+    }
+
+    // The resolved configuration includes injected environment variables and suffixed names
+    #[test]
+    fn test_resolve_without_daemon() {
+        let mut postgres = TestBodySpecification::with_repository("postgres");
+        postgres
+            .modify_port_map(5432, 15432)
+            .modify_named_volume("data", "/var/lib/postgresql/data");
+        let mut app = TestBodySpecification::with_repository("app");
+        app.inject_container_name("postgres", "DB_HOST")
+            .modify_env("DB_PORT", "{host_port:postgres:5432}")
+            .inject_host_port("postgres", 8080, "ADMIN_PORT");
+
+        let mut test = DockerTest::new()
+            .with_namespace("ns")
+            .with_id_generator(|| "id".to_string());
+        test.provide_container(postgres).provide_container(app);
+
+        let resolved = test.resolve().unwrap();
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(resolved[0].name(), "ns-postgres-id");
+        assert_eq!(resolved[0].image(), "postgres:latest");
+        assert_eq!(resolved[0].binds(), ["data-id:/var/lib/postgresql/data"]);
+        assert_eq!(
+            resolved[0].port_bindings(),
+            [
+                ("5432/tcp".to_string(), "15432".to_string()),
+                ("8080/tcp".to_string(), "0".to_string())
+            ]
+        );
+        assert_eq!(resolved[1].env()["DB_HOST"], "ns-postgres-id");
+        assert_eq!(resolved[1].env()["DB_PORT"], "15432");
+        assert_eq!(resolved[1].env()["ADMIN_PORT"], "{host_port:postgres:8080}");
+        assert_eq!(resolved[1].network(), Some("ns-dockertest"));
+    }
+
+    // Resolving leaves unmapped injected host ports to the docker daemon, instead of binding them
+    #[test]
+    fn test_resolve_does_not_bind_host_ports() {
+        let postgres = TestBodySpecification::with_repository("postgres");
+        let mut app = TestBodySpecification::with_repository("app");
+        app.inject_host_port("postgres", 5432, "DB_PORT");
+
+        let mut test = DockerTest::new();
+        test.provide_container(postgres).provide_container(app);
+
+        for _ in 0..2 {
+            let resolved = test.resolve().unwrap();
+            assert_eq!(
+                resolved[0].port_bindings(),
+                [("5432/tcp".to_string(), "0".to_string())]
+            );
+            assert_eq!(resolved[1].env()["DB_PORT"], "{host_port:postgres:5432}");
+        }
+    }
+
+    // Tests that a published port is bound to a host port assigned by the docker daemon, which
+    // an explicit mapping replaces
+    #[test]
+    fn test_resolve_published_port() {
+        let mut postgres = TestBodySpecification::with_repository("postgres");
+        postgres.publish_port(5432).publish_port(8080);
+        postgres.modify_port_map(8080, 18080);
+        let mut app = TestBodySpecification::with_repository("app");
+        app.inject_host_port("postgres", 5432, "DB_PORT")
+            .inject_host_port("postgres", 8080, "ADMIN_PORT");
+
+        let mut test = DockerTest::new();
+        test.provide_container(postgres).provide_container(app);
+
+        let resolved = test.resolve().unwrap();
+        assert!(resolved[0]
+            .port_bindings()
+            .contains(&("5432/tcp".to_string(), "0".to_string())));
+        assert_eq!(resolved[1].env()["DB_PORT"], "{host_port:postgres:5432}");
+        assert_eq!(resolved[1].env()["ADMIN_PORT"], "18080");
+    }
+
+    // The first network alias of the referenced container is injected into the environment
+    #[test]
+    fn test_resolve_inject_network_alias() {
+        let mut postgres = TestBodySpecification::with_repository("postgres");
+        postgres
+            .append_network_alias("db".to_string())
+            .append_network_alias("database".to_string());
+        let mut app = TestBodySpecification::with_repository("app");
+        app.inject_network_alias("postgres", "DB_HOST");
+
+        let mut test = DockerTest::new();
+        test.provide_container(postgres).provide_container(app);
+
+        let resolved = test.resolve().unwrap();
+        assert_eq!(resolved[1].env()["DB_HOST"], "db");
+    }
+
+    // Replicas are suffixed by their index and substitute it within their environment
+    #[test]
+    fn test_resolve_replicas() {
+        let mut kafka = TestBodySpecification::with_repository("kafka").set_replicas(2);
+        kafka.modify_env("KAFKA_BROKER_ID", "{replica}");
+
+        let mut test = DockerTest::new()
+            .with_namespace("ns")
+            .with_id_generator(|| "id".to_string());
+        test.provide_container(kafka);
+
+        let resolved = test.resolve().unwrap();
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(resolved[0].name(), "ns-kafka-0-id");
+        assert_eq!(resolved[1].name(), "ns-kafka-1-id");
+        assert_eq!(resolved[0].env()["KAFKA_BROKER_ID"], "0");
+        assert_eq!(resolved[1].env()["KAFKA_BROKER_ID"], "1");
+    }
+
+    // The directory of the readiness marker is mounted as a tmpfs
+    #[test]
+    fn test_resolve_marker_file_tmpfs() {
+        let app = TestBodySpecification::with_repository("app").set_wait_for(Box::new(
+            crate::waitfor::MarkerFileWait {
+                path: crate::waitfor::MARKER_FILE.to_string(),
+                check_interval: 1,
+                max_checks: 1,
+            },
+        ));
+
+        let mut test = DockerTest::new();
+        test.provide_container(app);
+
+        let resolved = test.resolve().unwrap();
+        assert_eq!(resolved[0].tmpfs(), ["/run/dockertest"]);
+    }
+
+    // Containers without any replicas are rejected by validation
+    #[test]
+    fn test_validate_zero_replicas() {
+        let kafka = TestBodySpecification::with_repository("kafka").set_replicas(0);
+
+        let mut test = DockerTest::new();
+        test.provide_container(kafka);
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg)) if msg.contains("no replicas")),
+            "validation should reject zero replicas, got: {:?}",
+            result
+        );
+    }
+
+    // Named volumes are rejected by validation within a shared environment
+    #[test]
+    fn test_validate_shared_environment_named_volume() {
+        let mut postgres = TestBodySpecification::with_repository("postgres");
+        postgres.modify_named_volume("data", "/var/lib/postgresql/data");
+
+        let mut test = DockerTest::new();
+        test.provide_container(postgres);
+        assert!(test.validate().is_ok());
+
+        let test = test.with_shared_environment();
+        let result = test.validate();
+        assert!(
+            matches!(result, Err(DockerTestError::Validation(ref msg)) if msg.contains("`postgres`")),
+            "validation should reject the named volume, got: {:?}",
+            result
+        );
+    }
 }
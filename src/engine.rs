@@ -1,12 +1,14 @@
 //! The meaty internals of executing a single test.
 
-use crate::composition::{Composition, LogPolicy};
+use crate::composition::{Composition, LogPolicy, ResolvedConfig, StaticManagementPolicy};
 use crate::container::{
-    CleanupContainer, CreatedContainer, HostPortMappings, PendingContainer, RunningContainer,
-    StaticExternalContainer,
+    CleanupContainer, CreatedContainer, PendingContainer, RunningContainer, StaticExternalContainer,
 };
-use crate::static_container::STATIC_CONTAINERS;
-use crate::utils::generate_random_string;
+use crate::dockertest::IdGenerator;
+use crate::retry::{daemon_error, is_not_found, RetryPolicy};
+use crate::static_container::{disconnect_container, STATIC_CONTAINERS};
+use crate::teardown::{self, ContainerExit, TeardownReport};
+use crate::template::{self, Placeholder};
 use crate::{DockerTestError, Network, Source, StartPolicy};
 
 use bollard::{
@@ -14,11 +16,18 @@ use bollard::{
     Docker,
 };
 use futures::future::join_all;
+use futures::stream::{self, StreamExt};
+use tokio::runtime::Handle;
+use tokio::sync::Semaphore;
 use tokio::task::JoinHandle;
-use tracing::{event, Level};
+use tracing::{event, span, Instrument, Level};
 
-use std::collections::{hash_map::Entry, HashMap, HashSet};
-use std::convert::TryFrom;
+use std::collections::{hash_map::Entry, BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The number of containers inspected concurrently once started.
+const INSPECT_CONCURRENCY: usize = 8;
 
 /// The initial phase.
 pub struct Bootstrapping {
@@ -31,6 +40,12 @@ pub struct Fueling {
 /// The creating phase.
 pub struct Igniting {
     kept: Vec<Transitional>,
+    /// The client and network the deferred containers are created with.
+    client: Docker,
+    network: String,
+    /// The tasks starting the relaxed containers, tracked until they complete such that they can
+    /// be stopped if the startup is abandoned.
+    starting: Vec<JoinHandle<Result<RunningContainer, DockerTestError>>>,
 }
 /// The in-flight phase.
 #[derive(Clone)]
@@ -51,7 +66,8 @@ enum Transitional {
     Running(RunningContainer),
     CreationFailure(DockerTestError),
     StaticExternal(StaticExternalContainer),
-    Sentinel,
+    /// Not yet created, as it awaits the host ports assigned to other containers as they start.
+    Deferred(Box<Composition>),
 }
 
 /// The purpose of the Keeper is to hold the reference to each Container throughout the test,
@@ -66,6 +82,8 @@ struct Keeper {
     lookup_collisions: HashSet<String>,
     /// This map stores the mapping between a handle and its index into `kept`.
     lookup_handlers: HashMap<String, usize>,
+    /// The stage each container is stopped in by an ordered teardown, keyed by handle.
+    stop_stages: HashMap<String, usize>,
 }
 
 // NOTE: Clone is only derived for Engine<Orbiting>, to delegate ownership into DockerOperations.
@@ -77,6 +95,22 @@ pub(crate) struct Engine<P> {
     phase: P,
 }
 
+/// Whether the environment of the composition references a host port assigned by the daemon,
+/// which is only resolved once the referenced container has started.
+fn awaits_host_ports(c: &Composition) -> bool {
+    c.env.values().any(|value| {
+        template::parse(value)
+            .unwrap_or_default()
+            .iter()
+            .any(|segment| {
+                matches!(
+                    segment,
+                    template::Segment::Placeholder(Placeholder::HostPort { .. })
+                )
+            })
+    })
+}
+
 /// Create a new [Engine] in [Bootstrapping] phase.
 pub(crate) fn bootstrap(compositions: Vec<Composition>) -> Engine<Bootstrapping> {
     let mut handlers: HashMap<String, usize> = HashMap::new();
@@ -97,6 +131,7 @@ pub(crate) fn bootstrap(compositions: Vec<Composition>) -> Engine<Bootstrapping>
     let keeper = Keeper {
         lookup_collisions: collisions,
         lookup_handlers: handlers,
+        stop_stages: teardown::stop_stages(&compositions),
     };
 
     Engine {
@@ -107,9 +142,9 @@ pub(crate) fn bootstrap(compositions: Vec<Composition>) -> Engine<Bootstrapping>
 
 impl Engine<Bootstrapping> {
     /// Perform the magic transformation info the final container name.
-    pub fn resolve_final_container_name(&mut self, namespace: &str) {
+    pub fn resolve_final_container_name(&mut self, namespace: &str, id_generator: &IdGenerator) {
         for c in self.phase.kept.iter_mut() {
-            let suffix = generate_random_string(20);
+            let suffix = id_generator.generate();
             c.configure_container_name(namespace, &suffix);
         }
     }
@@ -157,7 +192,58 @@ impl Engine<Fueling> {
             for (handle, name, env) in composition_transforms[index].iter() {
                 // Inject the container name into env
                 if let Some(old) = c.env.insert(env.to_string(), name.to_string()) {
-                    event!(Level::WARN, "overwriting previously configured environment variable `{} = {}` with injected container name for handle `{}`", env, old, handle);
+                    event!(Level::WARN, "overwriting previously configured environment variable `{} = {}` with injected container name for handle `{}`", env, c.display_env(env, &old), handle);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Issue the tls fixtures to the containers identified by `handles`, pinning each of them to
+    /// an address of the ipv4 `subnet` of the network.
+    #[cfg(feature = "tls-fixtures")]
+    pub fn provision_tls_fixtures(
+        &mut self,
+        handles: &[String],
+        subnet: &str,
+    ) -> Result<crate::tls_fixtures::TlsFixtures, DockerTestError> {
+        crate::tls_fixtures::pin_addresses(&mut self.phase.kept, handles, subnet)?;
+        crate::tls_fixtures::provision(&mut self.phase.kept, handles)
+    }
+
+    /// Inject the network aliases requested through `inject_network_alias` into the environment.
+    pub fn resolve_inject_network_alias_env(&mut self) -> Result<(), DockerTestError> {
+        let mut composition_transforms: Vec<Vec<(String, String, String)>> = Vec::new();
+
+        for c in self.phase.kept.iter() {
+            let mut transformed = Vec::new();
+            for (handle, env) in c.inject_network_alias_env.iter() {
+                let index = self.resolve_handle_index(c, handle, "inject_network_alias_env")?;
+                let alias = match self.phase.kept[index].network_alias() {
+                    Some(alias) => alias.to_string(),
+                    None => {
+                        return Err(DockerTestError::Startup(format!(
+                            "composition `{}` attempted to inject_network_alias_env on handle `{}` without a network alias",
+                            c.handle(),
+                            handle
+                        )))
+                    }
+                };
+                transformed.push((handle.clone(), alias, env.clone()));
+            }
+            composition_transforms.push(transformed);
+        }
+
+        for (index, c) in self.phase.kept.iter_mut().enumerate() {
+            for (handle, alias, env) in composition_transforms[index].iter() {
+                if c.env.insert(env.to_string(), alias.to_string()).is_some() {
+                    event!(
+                        Level::WARN,
+                        "overwriting previously configured environment variable `{}` with injected network alias for handle `{}`",
+                        env,
+                        handle
+                    );
                 }
             }
         }
@@ -165,26 +251,285 @@ impl Engine<Fueling> {
         Ok(())
     }
 
+    /// Inject the host ports requested through `inject_host_port` into the environment.
+    ///
+    /// Any exported port referenced by `inject_host_port` or a `{host_port:NAME:PORT}`
+    /// placeholder that is not explicitly mapped to a host port is published on a host port
+    /// assigned by the docker daemon once the container starts. The referencing containers are
+    /// injected a placeholder, which is resolved once the referenced containers have started.
+    pub fn resolve_inject_host_port_env(&mut self) -> Result<(), DockerTestError> {
+        // Gather every referenced (index, port) pair before we mutate the compositions.
+        let mut referenced: HashSet<(usize, u32)> = HashSet::new();
+        let mut composition_transforms: Vec<Vec<(String, usize, u32, String)>> = Vec::new();
+
+        for c in self.phase.kept.iter() {
+            let mut transformed = Vec::new();
+            for (handle, port, env) in c.inject_host_port_env.iter() {
+                let index = self.resolve_handle_index(c, handle, "inject_host_port_env")?;
+                referenced.insert((index, *port));
+                transformed.push((handle.clone(), index, *port, env.clone()));
+            }
+
+            // Templated host ports are substituted by `resolve_env_templates`, and only
+            // require the port to be published. Invalid templates are reported there.
+            for value in c.env.values() {
+                for segment in template::parse(value).unwrap_or_default() {
+                    if let template::Segment::Placeholder(Placeholder::HostPort { handle, port }) =
+                        segment
+                    {
+                        if let Some(index) = self.keeper.lookup_handlers.get(&handle) {
+                            referenced.insert((*index, port));
+                        }
+                    }
+                }
+            }
+
+            composition_transforms.push(transformed);
+        }
+
+        for (index, port) in referenced {
+            let target = &mut self.phase.kept[index];
+            if target.mapped_host_port(port).is_none() {
+                event!(
+                    Level::DEBUG,
+                    "publishing port `{}` of handle `{}` on a host port assigned by the daemon",
+                    port,
+                    target.handle()
+                );
+                target.publish_port(port);
+            }
+        }
+
+        let mut resolved: Vec<Vec<(String, String, String)>> = Vec::new();
+        for transforms in composition_transforms {
+            resolved.push(
+                transforms
+                    .into_iter()
+                    .map(|(handle, index, port, env)| {
+                        let host = match self.phase.kept[index].mapped_host_port(port) {
+                            Some(host) => host.to_string(),
+                            None => Placeholder::HostPort {
+                                handle: handle.clone(),
+                                port,
+                            }
+                            .to_string(),
+                        };
+                        (handle, host, env)
+                    })
+                    .collect(),
+            );
+        }
+
+        for (c, transforms) in self.phase.kept.iter_mut().zip(resolved) {
+            for (handle, host, env) in transforms {
+                if let Some(old) = c.env.insert(env.clone(), host) {
+                    event!(Level::WARN, "overwriting previously configured environment variable `{} = {}` with injected host port for handle `{}`", env, c.display_env(&env, &old), handle);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resolve the index of a handle referenced by the composition, guarding against
+    /// duplicate and non-existent handles.
+    fn resolve_handle_index(
+        &self,
+        c: &Composition,
+        handle: &str,
+        operation: &str,
+    ) -> Result<usize, DockerTestError> {
+        if self.keeper.lookup_collisions.contains(handle) {
+            return Err(DockerTestError::Startup(format!(
+                "composition `{}` attempted to {} on duplicate handle `{}`",
+                c.handle(),
+                operation,
+                handle
+            )));
+        }
+
+        self.keeper
+            .lookup_handlers
+            .get(handle)
+            .copied()
+            .ok_or_else(|| {
+                DockerTestError::Startup(format!(
+                    "composition `{}` attempted to {} on non-existent handle `{}`",
+                    c.handle(),
+                    operation,
+                    handle
+                ))
+            })
+    }
+
+    /// Substitute the placeholders within the environment variable values of all compositions.
+    ///
+    /// This must be performed after the final container names have been resolved.
+    pub fn resolve_env_templates(&mut self) -> Result<(), DockerTestError> {
+        // Due to ownership issues, we must resolve all values before we can apply them.
+        let mut composition_transforms: Vec<Vec<(String, String)>> = Vec::new();
+
+        for c in self.phase.kept.iter() {
+            let mut transformed = Vec::new();
+            for (env, value) in c.env.iter() {
+                let segments = template::parse(value).map_err(|e| {
+                    DockerTestError::Startup(format!(
+                        "composition `{}` has an invalid env `{}`: {}",
+                        c.handle(),
+                        env,
+                        c.redactions().redact(&e)
+                    ))
+                })?;
+
+                // Nothing to substitute
+                if !segments
+                    .iter()
+                    .any(|s| matches!(s, template::Segment::Placeholder(_)))
+                {
+                    continue;
+                }
+
+                // Host ports assigned by the daemon are resolved once the container has started.
+                let rendered = template::render(&segments, |p| match p {
+                    Placeholder::HostPort { handle, port }
+                        if self.assigned_host_port(handle, *port) =>
+                    {
+                        Ok(p.to_string())
+                    }
+                    _ => self.resolve_placeholder(p),
+                })
+                .map_err(|e| {
+                    DockerTestError::Startup(format!(
+                        "composition `{}` failed to resolve env `{}`: {}",
+                        c.handle(),
+                        env,
+                        e
+                    ))
+                })?;
+                transformed.push((env.clone(), rendered));
+            }
+
+            composition_transforms.push(transformed);
+        }
+
+        for (c, transforms) in self.phase.kept.iter_mut().zip(composition_transforms) {
+            c.env.extend(transforms);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the exported `port` of the container with the unique `handle` is published on a
+    /// host port assigned by the daemon, only known once the container has started.
+    fn assigned_host_port(&self, handle: &str, port: u32) -> bool {
+        !self.keeper.lookup_collisions.contains(handle)
+            && matches!(
+                self.keeper.lookup_handlers.get(handle),
+                Some(i) if self.phase.kept[*i].mapped_host_port(port).is_none()
+            )
+    }
+
+    fn resolve_placeholder(&self, placeholder: &Placeholder) -> Result<String, String> {
+        let handle = placeholder.handle();
+        if self.keeper.lookup_collisions.contains(handle) {
+            return Err(format!(
+                "placeholder references duplicate handle `{}`",
+                handle
+            ));
+        }
+
+        let composition = match self.keeper.lookup_handlers.get(handle) {
+            Some(i) => &self.phase.kept[*i],
+            None => {
+                return Err(format!(
+                    "placeholder references non-existent handle `{}`",
+                    handle
+                ))
+            }
+        };
+
+        match placeholder {
+            Placeholder::ContainerName { .. } => Ok(composition.container_name.clone()),
+            Placeholder::HostPort { port, .. } => composition
+                .mapped_host_port(*port)
+                .map(|host| host.to_string())
+                .ok_or_else(|| {
+                    format!(
+                        "placeholder references port `{}` of handle `{}`, which is not mapped to a host port",
+                        port, handle
+                    )
+                }),
+        }
+    }
+
+    /// The configuration of every container the test creates, on the given network.
+    pub fn resolved_configs(&self, network: Option<&str>) -> Vec<ResolvedConfig> {
+        self.phase
+            .kept
+            .iter()
+            .filter(|c| c.static_management_policy() != &Some(StaticManagementPolicy::External))
+            .map(|c| c.resolve(network))
+            .collect()
+    }
+
     /// Pull the `Image` of all `Composition`s.
     ///
     /// This will ensure that all docker images is present on the local daemon
     /// and we are able to issue a create container operation.
+    ///
+    /// Every image that neither exists locally nor can be pulled given its source and pull policy
+    /// is reported by a single error, listing the command pulling each missing image. Other
+    /// failures surface once the container is created.
     pub async fn pull_images(
         &self,
         client: &Docker,
         default: &Source,
     ) -> Result<(), DockerTestError> {
-        let mut future_vec = Vec::new();
-
-        // QUESTION: Can we not iter().map() this?
-        for composition in self.phase.kept.iter() {
-            let fut = composition.image().pull(client, default);
+        // Static external containers are managed by the user, who provides their image.
+        let compositions: Vec<&Composition> = self
+            .phase
+            .kept
+            .iter()
+            .filter(|c| c.static_management_policy() != &Some(StaticManagementPolicy::External))
+            .collect();
+        let results = join_all(compositions.iter().map(|c| {
+            c.image()
+                .pull(client, default)
+                .instrument(span!(Level::INFO, "pull", handle = %c.handle()))
+        }))
+        .await;
 
-            future_vec.push(fut);
+        let mut missing: Vec<String> = Vec::new();
+        let mut listed: HashSet<String> = HashSet::new();
+        for (composition, result) in compositions.iter().zip(results) {
+            match result {
+                Ok(()) => (),
+                Err(DockerTestError::Pull { error, .. }) => {
+                    // Containers may share an image, which is only listed once.
+                    let command = composition.image().pull_command();
+                    if listed.insert(command.clone()) {
+                        missing.push(format!(
+                            "  {}  # container `{}`: {}",
+                            command,
+                            composition.handle(),
+                            error
+                        ));
+                    }
+                }
+                Err(e) => event!(
+                    Level::DEBUG,
+                    "failed to pull the image of container `{}`: {}",
+                    composition.handle(),
+                    e
+                ),
+            }
         }
 
-        join_all(future_vec).await;
-        Ok(())
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(DockerTestError::MissingImages(missing.join("\n")))
+        }
     }
 
     /// On error, the engine contains at least one container that failed to ignite.
@@ -199,22 +544,38 @@ impl Engine<Fueling> {
         // NOTE: The insertion order is preserved.
         // To achieve this, we need to keep all inserted compositions when they also represent
         // a static external container.
-        let created: Vec<Result<CreatedContainer, DockerTestError>> = join_all(
-            self.phase
-                .kept
-                .into_iter()
-                .map(|c| c.create(client, Some(network), network_settings)),
-        )
-        .await;
+        let created: Vec<Result<Transitional, DockerTestError>> =
+            join_all(self.phase.kept.into_iter().map(|c| async move {
+                if !awaits_host_ports(&c) {
+                    let span = span!(Level::INFO, "create", handle = %c.handle());
+                    return match c
+                        .create(client, Some(network), network_settings)
+                        .instrument(span)
+                        .await?
+                    {
+                        CreatedContainer::StaticExternal(e) => Ok(Transitional::StaticExternal(e)),
+                        CreatedContainer::Pending(p) => Ok(Transitional::Pending(p)),
+                    };
+                }
+
+                // Static containers are shared, and must not depend on the containers of a test.
+                if c.is_static() {
+                    Err(DockerTestError::Startup(format!(
+                        "static container `{}` references a host port assigned by the daemon, \
+                         map the referenced port to a host port instead",
+                        c.handle()
+                    )))
+                } else {
+                    Ok(Transitional::Deferred(Box::new(c)))
+                }
+            }))
+            .await;
 
         let mut startup_failure = false;
         let kept = created
             .into_iter()
             .map(|c| match c {
-                Ok(c) => match c {
-                    CreatedContainer::StaticExternal(e) => Transitional::StaticExternal(e),
-                    CreatedContainer::Pending(p) => Transitional::Pending(p),
-                },
+                Ok(c) => c,
                 Err(e) => {
                     startup_failure = true;
                     Transitional::CreationFailure(e)
@@ -224,7 +585,12 @@ impl Engine<Fueling> {
 
         let engine = Engine::<Igniting> {
             keeper: self.keeper,
-            phase: Igniting { kept },
+            phase: Igniting {
+                kept,
+                client: client.clone(),
+                network: network.to_string(),
+                starting: Vec::new(),
+            },
         };
         if startup_failure {
             Err(engine)
@@ -237,11 +603,30 @@ impl Engine<Fueling> {
 impl Engine<Igniting> {
     /// Move the engine forward into [Orbiting] phase.
     ///
-    /// This will start and execute the relevant waitfor directives for each container.
+    /// This will start and execute the relevant waitfor directives for each container, failing
+    /// if they do not complete within the `timeout`, if any. At most `concurrency` relaxed
+    /// containers are started at once, if bounded.
     pub async fn orbiting(
         mut self,
+        runtime: &Handle,
+        timeout: Option<Duration>,
+        concurrency: Option<usize>,
     ) -> Result<Engine<Orbiting>, (Engine<Igniting>, DockerTestError)> {
-        let result = self.start_containers().await;
+        let result = match timeout {
+            // The pending containers are cloned when started, such that the engine still knows
+            // every container to remove if the startup is abandoned.
+            Some(timeout) => {
+                tokio::time::timeout(timeout, self.start_containers(runtime, concurrency))
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(DockerTestError::Startup(format!(
+                            "containers did not start within {:?}",
+                            timeout
+                        )))
+                    })
+            }
+            None => self.start_containers(runtime, concurrency).await,
+        };
 
         match result {
             Ok(_) => Ok(Engine::<Orbiting> {
@@ -250,12 +635,35 @@ impl Engine<Igniting> {
                     kept: self.phase.kept,
                 },
             }),
-            Err(e) => Err((self, e)),
+            Err(e) => {
+                self.stop_starting_containers().await;
+                Err((self, e))
+            }
+        }
+    }
+
+    /// Stop the tasks still starting relaxed containers once the startup is abandoned, e.g., as a
+    /// strict container failed to start, or the startup timed out.
+    ///
+    /// Every task is awaited, such that none of them starts its container after the containers
+    /// have been torn down. The containers they started remain pending in the engine, and are
+    /// therefore removed by the teardown.
+    async fn stop_starting_containers(&mut self) {
+        for task in self.phase.starting.drain(..) {
+            // Tasks whose result was already gathered must not be awaited again.
+            if !task.is_finished() {
+                task.abort();
+                let _ = task.await;
+            }
         }
     }
 
     // TODO: Refactor to return Vec<DockerTestError> on Err
-    async fn start_containers(&mut self) -> Result<(), DockerTestError> {
+    async fn start_containers(
+        &mut self,
+        runtime: &Handle,
+        concurrency: Option<usize>,
+    ) -> Result<(), DockerTestError> {
         // We clone out all our pending containers.
         // This will simplify alot of the gathering logic. We may be able to avoid this
         // clone in the future if we commit to changing the [WaitFor] signature.
@@ -267,58 +675,242 @@ impl Engine<Igniting> {
             _ => None,
         });
 
-        let (relaxed, strict): (Vec<_>, Vec<_>) = pending
-            .into_iter()
-            .partition(|c| c.start_policy == StartPolicy::Relaxed);
+        let mut relaxed = Vec::new();
+        let mut strict = Vec::new();
+        let mut groups: BTreeMap<(u32, String), Vec<PendingContainer>> = BTreeMap::new();
+        for c in pending {
+            match &c.start_policy {
+                StartPolicy::Relaxed => relaxed.push(c),
+                StartPolicy::Strict => strict.push(c),
+                StartPolicy::Group(name, order) => {
+                    groups.entry((*order, name.clone())).or_default().push(c)
+                }
+            }
+        }
 
         // Asynchronously start all relaxed containers.
-        let starting_relaxed = Self::start_relaxed_containers(relaxed);
-        let strict_success = Self::start_strict_containers(strict).await?;
-        let relaxed_success = Self::wait_for_relaxed_containers(starting_relaxed).await?;
+        self.phase.starting = Self::start_relaxed_containers(relaxed, runtime, concurrency);
+        let relaxed_count = self.phase.starting.len();
+        let mut strict_success = Self::start_strict_containers(strict).await?;
+
+        // Each group starts concurrently once every container of the previous group is ready.
+        for ((order, name), group) in groups {
+            event!(Level::TRACE, "starting group `{}` ({})", name, order);
+            let starting = Self::start_relaxed_containers(group, runtime, concurrency);
+            self.phase.starting.extend(starting);
+            let started =
+                Self::wait_for_relaxed_containers(&mut self.phase.starting[relaxed_count..])
+                    .await?;
+            self.phase.starting.truncate(relaxed_count);
+            strict_success.extend(started);
+        }
 
-        let mut containers = Vec::new();
-        containers.extend(strict_success.into_iter());
-        containers.extend(relaxed_success.into_iter());
-        containers.extend(STATIC_CONTAINERS.external_containers().await.into_iter());
+        let relaxed_success = Self::wait_for_relaxed_containers(&mut self.phase.starting).await?;
+        self.phase.starting.clear();
 
         // An important consideration herein is to maintain the same insertion order
         // of the original vector, when updating our Transitional::* variants.
         // This is due to the [Keeper] holding the handle -> indices lookup table,
         // which we must use to resolve the correct [RunningContainer]
-        for started in containers.into_iter() {
-            // Locate the entry into kept of the started container
-            let position = match self.phase.kept.iter().position(|x| match x {
-                Transitional::Pending(p) => p.id == started.id,
-                Transitional::StaticExternal(e) => e.handle == started.handle,
-                _ => false,
-            }) {
-                Some(e) => e,
-                None => continue,
-            };
+        let mut pending_positions = HashMap::new();
+        let mut external_positions = HashMap::new();
+        for (position, transitional) in self.phase.kept.iter().enumerate() {
+            match transitional {
+                Transitional::Pending(p) => {
+                    pending_positions.entry(p.id.clone()).or_insert(position);
+                }
+                Transitional::StaticExternal(e) => {
+                    external_positions
+                        .entry(e.handle.clone())
+                        .or_insert(position);
+                }
+                _ => (),
+            }
+        }
+        let mut locate = |started: &RunningContainer| {
+            pending_positions
+                .remove(&started.id)
+                .or_else(|| external_positions.remove(&started.handle))
+        };
 
-            // Create the [RunningContainer] variant out of the pending
-            let current = std::mem::replace(&mut self.phase.kept[position], Transitional::Sentinel);
-            let running = match current {
-                Transitional::Pending(_) | Transitional::StaticExternal(_) => {
-                    Transitional::Running(started)
+        let mut located = Vec::new();
+        for started in strict_success.into_iter().chain(relaxed_success) {
+            match locate(&started) {
+                Some(position) => located.push((position, started)),
+                None => {
+                    return Err(DockerTestError::Startup(format!(
+                        "started container `{}` with id `{}` is not part of the environment",
+                        started.handle, started.id
+                    )))
                 }
-                _ => continue,
-            };
+            }
+        }
+        // External containers are shared by all tests, and only those of this test are kept
+        for started in STATIC_CONTAINERS.external_containers().await {
+            if let Some(position) = locate(&started) {
+                located.push((position, started));
+            }
+        }
 
-            self.phase.kept[position] = running;
+        for (position, started) in located {
+            self.phase.kept[position] = Transitional::Running(started);
         }
 
-        Ok(())
+        self.start_deferred_containers(runtime, concurrency).await
+    }
+
+    /// Create and start the deferred containers once the containers whose host ports they
+    /// reference have started, regardless of their start policy.
+    async fn start_deferred_containers(
+        &mut self,
+        runtime: &Handle,
+        concurrency: Option<usize>,
+    ) -> Result<(), DockerTestError> {
+        loop {
+            let mut ready = Vec::new();
+            let mut waiting = Vec::new();
+            for (position, transitional) in self.phase.kept.iter().enumerate() {
+                if let Transitional::Deferred(c) = transitional {
+                    if self.referenced_host_ports(c).is_some() {
+                        ready.push(position);
+                    } else {
+                        waiting.push(c.handle());
+                    }
+                }
+            }
+
+            if ready.is_empty() && waiting.is_empty() {
+                return Ok(());
+            } else if ready.is_empty() {
+                return Err(DockerTestError::Startup(format!(
+                    "containers `{}` reference the host ports of each other",
+                    waiting.join("`, `")
+                )));
+            }
+
+            let mut created = Vec::new();
+            for position in ready {
+                let c = match self.create_deferred_container(position).await {
+                    Ok(c) => c,
+                    Err(e) => {
+                        self.phase.kept[position] = Transitional::CreationFailure(e.clone());
+                        return Err(e);
+                    }
+                };
+                self.phase.kept[position] = Transitional::Pending(c.clone());
+                created.push(c);
+            }
+
+            self.phase.starting = Self::start_relaxed_containers(created, runtime, concurrency);
+            let started = Self::wait_for_relaxed_containers(&mut self.phase.starting).await?;
+            self.phase.starting.clear();
+
+            for started in started {
+                let position = self
+                    .phase
+                    .kept
+                    .iter()
+                    .position(|t| matches!(t, Transitional::Pending(p) if p.id == started.id));
+                if let Some(position) = position {
+                    self.phase.kept[position] = Transitional::Running(started);
+                }
+            }
+        }
+    }
+
+    /// Resolve the host ports assigned to the containers referenced by the deferred container
+    /// at `position`, and create it.
+    async fn create_deferred_container(
+        &self,
+        position: usize,
+    ) -> Result<PendingContainer, DockerTestError> {
+        let mut c = match &self.phase.kept[position] {
+            Transitional::Deferred(c) => (**c).clone(),
+            _ => unreachable!("dockertest bug: container is not deferred"),
+        };
+
+        let mut assigned: HashMap<(String, u32), String> = HashMap::new();
+        for (target, port) in self.referenced_host_ports(&c).unwrap_or_default() {
+            // The published ports of the started containers are only inspected once all
+            // containers have started.
+            let mut target = target.clone();
+            target.refresh().await?;
+            let host = target.host_port(port).map(|(_, host)| host.to_string());
+            let host = host.ok_or_else(|| {
+                DockerTestError::Startup(format!(
+                    "container `{}` did not publish port `{}` referenced by `{}`",
+                    target.handle,
+                    port,
+                    c.handle()
+                ))
+            })?;
+            assigned.insert((target.handle.clone(), port), host);
+        }
+
+        for value in c.env.values_mut() {
+            let segments = template::parse(value).unwrap_or_default();
+            *value = template::render::<DockerTestError, _>(&segments, |p| match p {
+                Placeholder::HostPort { handle, port } => assigned
+                    .get(&(handle.clone(), *port))
+                    .cloned()
+                    .ok_or_else(|| {
+                        DockerTestError::Startup(format!(
+                            "dockertest bug: host port `{}` of handle `{}` is not resolved",
+                            port, handle
+                        ))
+                    }),
+                _ => Ok(p.to_string()),
+            })?;
+        }
+
+        let span = span!(Level::INFO, "create", handle = %c.handle());
+        c.create_inner(&self.phase.client, Some(&self.phase.network))
+            .instrument(span)
+            .await
+    }
+
+    /// The started containers and ports referenced by the host port placeholders of the
+    /// composition, or `None` if any of them has not started.
+    fn referenced_host_ports(&self, c: &Composition) -> Option<Vec<(&RunningContainer, u32)>> {
+        let mut referenced = Vec::new();
+        for value in c.env.values() {
+            for segment in template::parse(value).unwrap_or_default() {
+                if let template::Segment::Placeholder(Placeholder::HostPort { handle, port }) =
+                    segment
+                {
+                    let index = self.keeper.lookup_handlers.get(&handle)?;
+                    match &self.phase.kept[*index] {
+                        Transitional::Running(r) => referenced.push((r, port)),
+                        _ => return None,
+                    }
+                }
+            }
+        }
+        Some(referenced)
     }
 
     // Implementation detail
     fn start_relaxed_containers(
         containers: Vec<PendingContainer>,
+        runtime: &Handle,
+        concurrency: Option<usize>,
     ) -> Vec<JoinHandle<Result<RunningContainer, DockerTestError>>> {
         event!(Level::TRACE, "starting relaxed containers");
+        let permits = concurrency.map(|c| Arc::new(Semaphore::new(c)));
         containers
             .into_iter()
-            .map(|c| tokio::spawn(c.start()))
+            .map(|c| {
+                let permits = permits.clone();
+                let start = async move {
+                    // The semaphore is never closed
+                    let _permit = match &permits {
+                        Some(permits) => permits.acquire().await.ok(),
+                        None => None,
+                    };
+                    c.start().await
+                };
+                runtime.spawn(start.in_current_span())
+            })
             .collect()
     }
 
@@ -356,12 +948,12 @@ impl Engine<Igniting> {
 
     // Implementation detail
     async fn wait_for_relaxed_containers(
-        starting_relaxed: Vec<JoinHandle<Result<RunningContainer, DockerTestError>>>,
+        starting_relaxed: &mut [JoinHandle<Result<RunningContainer, DockerTestError>>],
     ) -> Result<Vec<RunningContainer>, DockerTestError> {
         let mut running_relaxed: Vec<RunningContainer> = Vec::new();
         let mut first_error = None;
 
-        for join_handle in join_all(starting_relaxed).await {
+        for join_handle in join_all(starting_relaxed.iter_mut()).await {
             match join_handle {
                 Ok(start_result) => match start_result {
                     Ok(c) => running_relaxed.push(c),
@@ -428,7 +1020,7 @@ impl Engine<Igniting> {
                     external.push(s);
                     None
                 }
-                Transitional::Sentinel | Transitional::CreationFailure(_) => None,
+                Transitional::CreationFailure(_) | Transitional::Deferred(_) => None,
             })
             .collect();
 
@@ -462,11 +1054,30 @@ impl Engine<Orbiting> {
         }
     }
 
+    /// All running containers, in the order they were provided.
+    pub fn running_containers(&self) -> impl Iterator<Item = &RunningContainer> {
+        self.phase.kept.iter().filter_map(|t| match t {
+            Transitional::Running(r) => Some(r),
+            _ => None,
+        })
+    }
+
     /// Query whether or not the provided handle resolve to conflicting containers.
     pub fn handle_collision(&self, handle: &str) -> bool {
         self.keeper.lookup_collisions.contains(handle)
     }
 
+    /// The running container at the given index of [Engine::running_containers].
+    pub fn resolve_index(&self, index: usize) -> Result<&RunningContainer, DockerTestError> {
+        self.running_containers().nth(index).ok_or_else(|| {
+            DockerTestError::TestBody(format!(
+                "container at index {} not found, {} containers are running",
+                index,
+                self.running_containers().count()
+            ))
+        })
+    }
+
     pub fn resolve_handle(&self, handle: &str) -> Option<&RunningContainer> {
         let index = match self.keeper.lookup_handlers.get(handle) {
             None => return None,
@@ -480,16 +1091,20 @@ impl Engine<Orbiting> {
         }
     }
 
+    /// Inspect every running container to retrieve its ip address, published ports and details.
+    ///
+    /// Containers are inspected concurrently, reusing the inspection performed by the WaitFor
+    /// condition of the container where available. `on_network` indicates whether the test
+    /// process runs inside a container attached to the network.
     pub async fn inspect(
         &mut self,
         client: &Docker,
         network_name: &str,
+        on_network: bool,
+        retry_policy: &RetryPolicy,
     ) -> Result<(), Vec<DockerTestError>> {
-        // TODO: Run the inspect operation in paralell with futures, and join_all
-        // Need to figure out how to best update their state in their future.
-
-        let mut errors = Vec::new();
-        for transitional in self.phase.kept.iter_mut() {
+        let mut to_inspect = Vec::new();
+        for (index, transitional) in self.phase.kept.iter_mut().enumerate() {
             // Ensure that we have a RunningContainer
             let container = match transitional {
                 Transitional::Running(r) => r,
@@ -497,75 +1112,53 @@ impl Engine<Orbiting> {
                 _ => continue,
             };
 
-            // On Windows container IPs cannot be resolved from outside a container.
-            // So container IPs in the test body are useless and the only way to contact a
-            // container is through a port map and localhost.
-            // To avoid have users to have cfg!(windows) in their test bodies, we simply set all
-            // container ips to localhost
-            //
-            // TODO: Find another strategy to contact containers from the test body on Windows.
-            if cfg!(windows) {
-                container.ip = std::net::Ipv4Addr::new(127, 0, 0, 1);
-                continue;
-            }
-            let details = match client
-                .inspect_container(&container.id, None::<InspectContainerOptions>)
-                .await
-            {
-                Ok(details) => details,
-                Err(e) => {
-                    let err =
-                        DockerTestError::Daemon(format!("failed to inspect container: {}", e));
-                    errors.push(err);
-                    continue;
-                }
-            };
+            // The ip is set to localhost on Windows, and for Windows containers, when inspected,
+            // see `RunningContainer::update_from_inspect`.
+            container.network = network_name.to_string();
+            container.on_network = on_network;
 
-            // Get the ip address from the network
-            container.ip = if let Some(inspected_network) = details
-                .network_settings
-                .as_ref()
-                .unwrap()
-                .networks
-                .as_ref()
-                .unwrap()
-                .get(network_name)
-            {
-                event!(
-                    Level::DEBUG,
-                    "container ip from inspect: {}",
-                    inspected_network.ip_address.as_ref().unwrap()
-                );
-                inspected_network
-                    .ip_address
-                    .as_ref()
-                    .unwrap()
-                    .parse::<std::net::Ipv4Addr>()
-                    // Exited containers will not have an IP address
-                    .unwrap_or_else(|e| {
-                        event!(Level::TRACE, "container ip address failed to parse: {}", e);
-                        std::net::Ipv4Addr::UNSPECIFIED
+            // Static containers are shared between tests, and may have been inspected by the
+            // WaitFor condition of another test, on another network.
+            if container.is_static {
+                container.inspected = None;
+            }
+            if container.inspected.is_none() {
+                to_inspect.push((index, container.id.clone()));
+            }
+        }
+
+        let mut inspected: HashMap<usize, _> = stream::iter(to_inspect)
+            .map(|(index, id)| async move {
+                let details = retry_policy
+                    .retry("inspect container", || {
+                        client.inspect_container(&id, None::<InspectContainerOptions>)
                     })
-            } else {
-                std::net::Ipv4Addr::UNSPECIFIED
+                    .await;
+                (index, details)
+            })
+            .buffer_unordered(INSPECT_CONCURRENCY)
+            .collect()
+            .await;
+
+        let mut errors = Vec::new();
+        for (index, transitional) in self.phase.kept.iter_mut().enumerate() {
+            let container = match transitional {
+                Transitional::Running(r) => r,
+                _ => continue,
             };
 
-            container.ports = if let Some(ports) = details.network_settings.unwrap().ports {
-                event!(
-                    Level::DEBUG,
-                    "container ports from inspect: {:?}",
-                    ports.clone()
-                );
-                match HostPortMappings::try_from(ports) {
-                    Ok(h) => h,
-                    Err(e) => {
-                        let err = DockerTestError::HostPort(e.to_string());
-                        errors.push(err);
-                        continue;
-                    }
+            let details = match (container.inspected.take(), inspected.remove(&index)) {
+                (Some(details), _) => *details,
+                (None, Some(Ok(details))) => details,
+                (None, Some(Err(e))) => {
+                    errors.push(daemon_error("failed to inspect container", e));
+                    continue;
                 }
-            } else {
-                HostPortMappings::default()
+                (None, None) => continue,
+            };
+
+            if let Err(e) = container.update_from_inspect(details) {
+                errors.push(e);
             }
         }
 
@@ -578,12 +1171,58 @@ impl Engine<Orbiting> {
 }
 
 impl Engine<Debris> {
+    /// Capture the final state of every container, once the containers are stopped, and before
+    /// they are removed.
+    ///
+    /// Warns about every container killed by the kernel as it ran out of memory, as the test
+    /// failures this causes, e.g., refused connections, do not point to the cause.
+    pub async fn exit_report(&self, client: &Docker) -> TeardownReport {
+        let inspections = self.phase.kept.iter().map(|c| async move {
+            let details = client
+                .inspect_container(&c.id, None::<InspectContainerOptions>)
+                .await;
+            match details {
+                Ok(details) => ContainerExit::from_state(&c.handle, &c.name, details.state),
+                Err(e) => ContainerExit::from_error(&c.handle, &c.name, e.to_string()),
+            }
+        });
+
+        let containers = join_all(inspections).await;
+        for container in containers.iter() {
+            if container.oom_killed {
+                event!(
+                    Level::WARN,
+                    "container `{}` was killed as it ran out of memory",
+                    container.name
+                );
+            }
+            if let Some(error) = &container.error {
+                event!(
+                    Level::DEBUG,
+                    "container `{}` did not shut down cleanly: {}",
+                    container.name,
+                    error
+                );
+            }
+        }
+
+        TeardownReport { containers }
+    }
+
     /// Handle container logs during test execution.
     ///
-    /// This function handles logs on per-container bases.
-    pub async fn handle_logs(&self, test_failed: bool) -> Result<(), Vec<DockerTestError>> {
+    /// This function handles logs on per-container bases. Log followers are awaited if the
+    /// containers are `stopped`, and are otherwise aborted with the output buffered so far.
+    pub async fn handle_logs(
+        &self,
+        test_failed: bool,
+        stopped: bool,
+    ) -> Result<(), Vec<DockerTestError>> {
         let mut errors = vec![];
 
+        // Followers complete once their containers are stopped, with the full output buffered.
+        join_all(self.phase.kept.iter().map(|c| c.finish_following(stopped))).await;
+
         for container in self.phase.kept.iter() {
             if let Some(log_options) = &container.log_options {
                 let result = match log_options.policy {
@@ -601,6 +1240,8 @@ impl Engine<Debris> {
                             .await
                     }
                     LogPolicy::OnStartupError => continue,
+                    // Followed since the container started, forwarding the buffered output.
+                    LogPolicy::Follow => container.flush_followed(),
                 };
 
                 let result = result.map_err(|error| {
@@ -631,15 +1272,20 @@ impl Engine<Debris> {
 
         for container in self.phase.kept.iter() {
             if let Some(log_options) = &container.log_options {
-                let result = container
-                    .handle_log(&log_options.action, &log_options.source)
-                    .await
-                    .map_err(|error| {
-                        DockerTestError::LogWriteError(format!(
-                            "unable to handle logs for: {}: {}",
-                            container.name, error
-                        ))
-                    });
+                // Followed since the container started, forwarding the buffered output.
+                let result = if matches!(log_options.policy, LogPolicy::Follow) {
+                    container.flush_followed()
+                } else {
+                    container
+                        .handle_log(&log_options.action, &log_options.source)
+                        .await
+                };
+                let result = result.map_err(|error| {
+                    DockerTestError::LogWriteError(format!(
+                        "unable to handle logs for: {}: {}",
+                        container.name, error
+                    ))
+                });
 
                 if let Err(err) = result {
                     errors.push(err);
@@ -654,7 +1300,7 @@ impl Engine<Debris> {
         }
     }
 
-    /// Ensure that our static containers are cleaned up individually.
+    /// Ensure that our static and reused containers are cleaned up individually.
     pub async fn disconnect_static_containers(
         &self,
         client: &Docker,
@@ -682,50 +1328,121 @@ impl Engine<Debris> {
         STATIC_CONTAINERS
             .cleanup(client, network, network_mode, static_cleanup)
             .await;
-    }
 
-    pub async fn stop_containers(self, client: &Docker) {
-        let cleanup: Vec<CleanupContainer> = self
-            .phase
-            .kept
-            .into_iter()
-            .filter(|c| !c.is_static())
-            .collect();
+        // Reused containers outlive the test, and must not keep its isolated network in use.
+        if let Network::Isolated = network_mode {
+            for c in self.phase.kept.iter().filter(|c| c.is_reused()) {
+                disconnect_container(client, &c.id, network).await;
+            }
+        }
+    }
 
+    /// Stop the containers concurrently, awaiting their graceful shutdown for `timeout` seconds,
+    /// or the default of the daemon.
+    pub async fn stop_containers(&self, client: &Docker, timeout: Option<i64>) {
         join_all(
-            cleanup
+            self.phase
+                .kept
                 .iter()
-                .map(|c| client.stop_container(&c.id, None::<StopContainerOptions>))
+                .filter(|c| !c.is_static() && !c.is_reused())
+                .map(|c| {
+                    client
+                        .stop_container(&c.id, timeout.map(|t| StopContainerOptions { t }))
+                        .instrument(span!(Level::INFO, "teardown", handle = %c.handle))
+                })
                 .collect::<Vec<_>>(),
         )
         .await;
     }
 
+    /// Stop the containers in reverse dependency order, see [teardown::stop_stages], such that
+    /// each container may flush its state to its dependencies before they are stopped. The
+    /// containers of a stage are stopped concurrently.
+    pub async fn stop_containers_in_order(&self, client: &Docker, timeout: Option<i64>) {
+        let mut stages: BTreeMap<usize, Vec<&CleanupContainer>> = BTreeMap::new();
+        for c in self
+            .phase
+            .kept
+            .iter()
+            .filter(|c| !c.is_static() && !c.is_reused())
+        {
+            let stage = self
+                .keeper
+                .stop_stages
+                .get(&c.handle)
+                .copied()
+                .unwrap_or_default();
+            stages.entry(stage).or_default().push(c);
+        }
+
+        for (stage, containers) in stages.into_iter().rev() {
+            event!(Level::TRACE, "stopping teardown stage {}", stage);
+            join_all(
+                containers
+                    .into_iter()
+                    .map(|c| {
+                        client
+                            .stop_container(&c.id, timeout.map(|t| StopContainerOptions { t }))
+                            .instrument(span!(Level::INFO, "teardown", handle = %c.handle))
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .await;
+        }
+    }
+
     /// The container must be removed prior to removing volumes.
-    pub async fn remove_containers(self, client: &Docker) {
+    ///
+    /// Removal is retried while the daemon reports a conflict, e.g., as the removal of the
+    /// container is already in progress, until the container no longer exists. Returns the number
+    /// of containers that could not be removed.
+    pub async fn remove_containers(self, client: &Docker, retry_policy: &RetryPolicy) -> usize {
         let cleanup: Vec<CleanupContainer> = self
             .phase
             .kept
             .into_iter()
-            .filter(|c| !c.is_static())
+            .filter(|c| !c.is_static() && !c.is_reused())
             .collect();
 
         let futures = cleanup
             .iter()
             .map(|c| {
-                // It's unlikely that anonymous volumes will be used by several containers.
-                // In this case there will be remove errors that it's possible just to ignore
-                // See:
-                // https://github.com/moby/moby/blob/7b9275c0da707b030e62c96b679a976f31f929d3/daemon/mounts.go#L34).
-                let options = Some(RemoveContainerOptions {
-                    force: true,
-                    v: true,
-                    ..Default::default()
-                });
+                let span = span!(Level::INFO, "teardown", handle = %c.handle);
+                async move {
+                    // It's unlikely that anonymous volumes will be used by several containers.
+                    // In this case there will be remove errors that it's possible just to ignore
+                    // See:
+                    // https://github.com/moby/moby/blob/7b9275c0da707b030e62c96b679a976f31f929d3/daemon/mounts.go#L34).
+                    let options = Some(RemoveContainerOptions {
+                        force: true,
+                        v: true,
+                        ..Default::default()
+                    });
 
-                client.remove_container(&c.id, options)
+                    let result = retry_policy
+                        .retry_removal("remove container", || {
+                            client.remove_container(&c.id, options)
+                        })
+                        .await;
+                    match result {
+                        Ok(_) => true,
+                        // The container is already removed
+                        Err(e) if is_not_found(&e) => true,
+                        Err(e) => {
+                            event!(
+                                Level::WARN,
+                                "failed to remove container `{}`: {}",
+                                c.name,
+                                e
+                            );
+                            false
+                        }
+                    }
+                }
+                .instrument(span)
             })
             .collect::<Vec<_>>();
-        join_all(futures).await;
+        let removed = join_all(futures).await;
+        removed.into_iter().filter(|removed| !removed).count()
     }
 }
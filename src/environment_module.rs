@@ -0,0 +1,48 @@
+//! Reusable bundles of containers, composed into a test through
+//! [DockerTest::provide_module](crate::DockerTest::provide_module).
+
+use crate::DockerTest;
+
+/// A reusable fragment of a test environment, e.g., an observability stack of prometheus, grafana
+/// and an OpenTelemetry collector, packaged once and composed into any test with a single call to
+/// [DockerTest::provide_module].
+///
+/// A module registers its containers, and any other configuration it requires, on the test it is
+/// provided to. Handles must be unique within a test, hence a module intended to be composed with
+/// other modules should use handles that are unlikely to collide, or allow its user to configure
+/// them.
+///
+/// ```rust
+/// use dockertest::{DockerTest, TestBodySpecification, TestEnvironmentModule};
+///
+/// struct Observability;
+///
+/// impl TestEnvironmentModule for Observability {
+///     fn register(&self, test: &mut DockerTest) {
+///         let mut grafana = TestBodySpecification::with_repository("grafana/grafana");
+///         grafana.inject_container_name("prometheus", "PROMETHEUS_HOST");
+///         let prometheus =
+///             TestBodySpecification::with_repository("prom/prometheus").set_handle("prometheus");
+///         test.provide_container(prometheus).provide_container(grafana);
+///     }
+/// }
+///
+/// let mut test = DockerTest::new();
+/// test.provide_module(&Observability);
+/// assert!(test.validate().is_ok());
+/// ```
+///
+/// [DockerTest::provide_module]: crate::DockerTest::provide_module
+pub trait TestEnvironmentModule {
+    /// Register the containers and configuration of this module on the test.
+    fn register(&self, test: &mut DockerTest);
+}
+
+impl<F> TestEnvironmentModule for F
+where
+    F: Fn(&mut DockerTest),
+{
+    fn register(&self, test: &mut DockerTest) {
+        self(test)
+    }
+}
@@ -5,9 +5,12 @@ use thiserror::Error;
 /// Public library error conditions.
 #[derive(Error, Debug, PartialEq, Clone, Eq)]
 #[allow(missing_docs)]
+#[non_exhaustive]
 pub enum DockerTestError {
     #[error("docker daemon interaction error `{0}`")]
     Daemon(String),
+    #[error("transient docker daemon error `{0}`")]
+    Transient(String),
     #[error("recoverable error condition")]
     Recoverable(String),
     #[error("container teardown error")]
@@ -18,6 +21,8 @@ pub enum DockerTestError {
         tag: String,
         error: String,
     },
+    #[error("missing images, pull them through:\n{0}")]
+    MissingImages(String),
     #[error("startup condition not fulfilled `{0}`")]
     Startup(String),
     #[error("processing error condition `{0}`")]
@@ -28,4 +33,12 @@ pub enum DockerTestError {
     LogWriteError(String),
     #[error("host port error `{0}`")]
     HostPort(String),
+    #[error("invalid environment configuration `{0}`")]
+    Validation(String),
+    #[error("unsupported by the docker daemon `{0}`")]
+    Unsupported(String),
+    #[error("image platform mismatch `{0}`")]
+    PlatformMismatch(String),
+    #[error("docker daemon connection lost, environment lost `{0}`")]
+    EnvironmentLost(String),
 }
@@ -0,0 +1,136 @@
+//! Cross-process locks serializing tests that contend for a host-global resource, see
+//! [DockerTest::with_exclusive_lock].
+//!
+//! Each lock is an advisory file lock on `{lock directory}/{name}.lock`, held from before the
+//! environment is set up until it is torn down. The lock is released by the operating system once
+//! the lock file is closed, also if the test process is killed. The lock directory is
+//! `DOCKERTEST_LOCK_DIR`, defaulting to `dockertest-locks` in the temporary directory, and must be
+//! shared by all contending processes.
+//!
+//! [DockerTest::with_exclusive_lock]: crate::DockerTest::with_exclusive_lock
+
+use crate::DockerTestError;
+
+use tracing::{event, Level};
+
+use std::fs::{File, OpenOptions};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// The environment variable holding the directory of the lock files.
+const LOCK_DIR_ENV: &str = "DOCKERTEST_LOCK_DIR";
+
+/// A held exclusive lock, released once dropped.
+#[derive(Debug)]
+pub(crate) struct ExclusiveLock {
+    name: String,
+    // Closing the file releases the lock.
+    _file: File,
+}
+
+impl Drop for ExclusiveLock {
+    fn drop(&mut self) {
+        event!(Level::DEBUG, "released exclusive lock `{}`", self.name);
+    }
+}
+
+/// Acquire every lock, waiting as long as necessary, returning the held locks and the time spent
+/// waiting for them.
+///
+/// The locks are acquired in a global order, such that tests acquiring overlapping sets of locks
+/// cannot deadlock.
+pub(crate) async fn acquire_all(
+    names: &[String],
+) -> Result<(Vec<ExclusiveLock>, Duration), DockerTestError> {
+    let mut names = names.to_vec();
+    names.sort();
+    names.dedup();
+
+    let started = Instant::now();
+    let mut locks = Vec::new();
+    for name in names {
+        locks.push(acquire(name).await?);
+    }
+    Ok((locks, started.elapsed()))
+}
+
+async fn acquire(name: String) -> Result<ExclusiveLock, DockerTestError> {
+    let path = lock_path(&name);
+    let failed = |e: std::io::Error| {
+        DockerTestError::Startup(format!(
+            "failed to acquire exclusive lock `{}` through `{}`: {}",
+            name,
+            path.display(),
+            e
+        ))
+    };
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(failed)?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .map_err(failed)?;
+
+    event!(Level::DEBUG, "acquiring exclusive lock `{}`", name);
+    let file = tokio::task::spawn_blocking(move || file.lock().map(|_| file))
+        .await
+        .map_err(|e| failed(std::io::Error::other(e)))?
+        .map_err(failed)?;
+    event!(Level::DEBUG, "acquired exclusive lock `{}`", name);
+
+    Ok(ExclusiveLock { name, _file: file })
+}
+
+/// The lock file of the lock `name`, with every character but alphanumerics, `-` and `_`
+/// replaced, such that any name maps to a file within the lock directory.
+fn lock_path(name: &str) -> PathBuf {
+    let dir = std::env::var_os(LOCK_DIR_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join("dockertest-locks"));
+    let file: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    dir.join(format!("{}.lock", file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{acquire_all, lock_path};
+
+    use std::time::Duration;
+
+    // Tests that lock names map to files within the lock directory
+    #[test]
+    fn test_lock_path() {
+        let path = lock_path("../host port 8080");
+        assert_eq!(path.file_name().unwrap(), "___host_port_8080.lock");
+    }
+
+    // Tests that a held lock serializes another acquisition of the same lock
+    #[tokio::test]
+    async fn test_acquire_all() {
+        let name = format!("test-{}", crate::utils::generate_random_string(10));
+        let (held, _) = acquire_all(&[name.clone(), name.clone()]).await.unwrap();
+        assert_eq!(held.len(), 1);
+
+        let contender = tokio::spawn(async move { acquire_all(&[name]).await.unwrap() });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!contender.is_finished());
+
+        drop(held);
+        let (_, waited) = contender.await.unwrap();
+        assert!(waited >= Duration::from_millis(200));
+    }
+}
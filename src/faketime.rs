@@ -0,0 +1,192 @@
+//! Faking the clock of a container through [libfaketime], see
+//! [TestBodySpecification::set_faked_time].
+//!
+//! The libfaketime library of the host is mounted read-only into the container, and preloaded
+//! into every process of the container through `LD_PRELOAD`. The library must therefore be built
+//! for the C library of the image, e.g., glibc for debian based images. Its location is read
+//! from `DOCKERTEST_FAKETIME_LIBRARY`, defaulting to the locations of the distribution packages.
+//!
+//! The offset is written to the timestamp file [TIMESTAMP_FILE] within the created container,
+//! which libfaketime reads on every clock access as caching is disabled. Rewriting the file
+//! through [DockerOperations::set_faked_time] therefore shifts the clock of the running
+//! container, without restarting it.
+//!
+//! [libfaketime]: https://github.com/wolfcw/libfaketime
+//! [TestBodySpecification::set_faked_time]: crate::TestBodySpecification::set_faked_time
+//! [DockerOperations::set_faked_time]: crate::DockerOperations::set_faked_time
+
+use crate::DockerTestError;
+
+use bollard::{container::UploadToContainerOptions, Docker};
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// The environment variable holding the location of libfaketime on the host.
+pub(crate) const LIBRARY_ENV: &str = "DOCKERTEST_FAKETIME_LIBRARY";
+
+/// The locations of libfaketime installed by the distribution packages.
+const LIBRARY_LOCATIONS: &[&str] = &[
+    "/usr/lib/x86_64-linux-gnu/faketime/libfaketime.so.1",
+    "/usr/lib/aarch64-linux-gnu/faketime/libfaketime.so.1",
+    "/usr/lib64/faketime/libfaketime.so.1",
+    "/usr/lib/faketime/libfaketime.so.1",
+    "/usr/local/lib/faketime/libfaketime.so.1",
+];
+
+/// The location libfaketime is mounted at within the container.
+const CONTAINER_LIBRARY: &str = "/usr/lib/dockertest/libfaketime.so.1";
+
+/// The timestamp file within the container holding the offset of the clock.
+pub(crate) const TIMESTAMP_FILE: &str = "/usr/lib/dockertest/faketime";
+
+/// Locate libfaketime on the host, given the value of [LIBRARY_ENV] in the environment of the
+/// test process.
+pub(crate) fn library(configured: Option<OsString>) -> Result<PathBuf, String> {
+    if let Some(path) = configured.filter(|p| !p.is_empty()) {
+        let path = PathBuf::from(path);
+        return if path.is_file() {
+            Ok(path)
+        } else {
+            Err(format!(
+                "libfaketime does not exist at `{}`, as set through `{}`",
+                path.display(),
+                LIBRARY_ENV
+            ))
+        };
+    }
+
+    LIBRARY_LOCATIONS
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            format!(
+                "libfaketime was not found, install it or set `{}` to its location",
+                LIBRARY_ENV
+            )
+        })
+}
+
+/// The bind mount of the library, read-only as it is a file of the host system.
+pub(crate) fn bind(library: &Path) -> String {
+    format!("{}:{}:ro", library.display(), CONTAINER_LIBRARY)
+}
+
+/// The environment variables preloading libfaketime, reading the offset of the clock from the
+/// timestamp file on every clock access.
+pub(crate) fn env() -> [(String, String); 4] {
+    [
+        ("LD_PRELOAD".to_string(), CONTAINER_LIBRARY.to_string()),
+        (
+            "FAKETIME_TIMESTAMP_FILE".to_string(),
+            TIMESTAMP_FILE.to_string(),
+        ),
+        ("FAKETIME_NO_CACHE".to_string(), "1".to_string()),
+        // Faking monotonic clocks hangs some runtimes, e.g., the JVM.
+        ("DONT_FAKE_MONOTONIC".to_string(), "1".to_string()),
+    ]
+}
+
+/// Whether the environment of a container, as reported by the daemon, preloads libfaketime.
+pub(crate) fn is_faked(env: &[String]) -> bool {
+    let variable = format!("FAKETIME_TIMESTAMP_FILE={}", TIMESTAMP_FILE);
+    env.contains(&variable)
+}
+
+/// Write the offset of the clock in seconds to the timestamp file within the container, whether
+/// it is started or not.
+pub(crate) async fn write(client: &Docker, id: &str, offset: i64) -> Result<(), DockerTestError> {
+    let options = UploadToContainerOptions {
+        path: "/",
+        ..Default::default()
+    };
+    client
+        .upload_to_container(id, Some(options), archive(offset)?.into())
+        .await
+        .map_err(|e| DockerTestError::Daemon(format!("failed to write the faked time: {}", e)))
+}
+
+/// Build the tar archive of the timestamp file, relative to the root of the container.
+fn archive(offset: i64) -> Result<Vec<u8>, DockerTestError> {
+    let content = timestamp(offset);
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    // Readable by the user the container process runs as, which need not be root.
+    header.set_mode(0o444);
+    header.set_cksum();
+    builder
+        .append_data(
+            &mut header,
+            TIMESTAMP_FILE.trim_start_matches('/'),
+            content.as_bytes(),
+        )
+        .map_err(|e| DockerTestError::Processing(format!("failed to archive faked time: {}", e)))?;
+
+    builder
+        .into_inner()
+        .map_err(|e| DockerTestError::Processing(format!("failed to archive faked time: {}", e)))
+}
+
+/// The content of the timestamp file, offsetting the clock by `offset` seconds.
+fn timestamp(offset: i64) -> String {
+    format!("{:+}s", offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{archive, is_faked, library, timestamp, LIBRARY_ENV};
+
+    use std::io::Read;
+
+    // Tests that the offset is passed to libfaketime with its sign
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(3600), "+3600s");
+        assert_eq!(timestamp(-60), "-60s");
+    }
+
+    // Tests that the timestamp file is archived at its location within the container
+    #[test]
+    fn test_archive() {
+        let archive = archive(-3600).unwrap();
+
+        let mut archive = tar::Archive::new(archive.as_slice());
+        let mut entry = archive.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(
+            entry.path().unwrap().to_str(),
+            Some("usr/lib/dockertest/faketime")
+        );
+        let mut content = String::new();
+        entry.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "-3600s");
+    }
+
+    // Tests that the configured library location takes precedence, and must exist
+    #[test]
+    fn test_library() {
+        let path = std::env::temp_dir().join("dockertest-test-library-libfaketime.so.1");
+        std::fs::write(&path, b"").unwrap();
+        assert_eq!(
+            library(Some(path.clone().into_os_string())),
+            Ok(path.clone())
+        );
+
+        let err = library(Some(path.with_extension("missing").into_os_string())).unwrap_err();
+        assert!(err.contains("libfaketime does not exist at"), "{}", err);
+        assert!(err.contains(LIBRARY_ENV), "{}", err);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    // Tests that only containers preloading libfaketime are recognized as faking their clock
+    #[test]
+    fn test_is_faked() {
+        let env = vec![
+            "PATH=/usr/bin".to_string(),
+            "FAKETIME_TIMESTAMP_FILE=/usr/lib/dockertest/faketime".to_string(),
+        ];
+        assert!(is_faked(&env));
+        assert!(!is_faked(&env[..1]));
+    }
+}
@@ -1,5 +1,6 @@
 //! An Image persisted in Docker.
 
+use crate::throttle;
 use crate::DockerTestError;
 
 use bollard::{
@@ -23,7 +24,11 @@ pub struct Image {
     repository: String,
     tag: String,
     source: Option<Source>,
-    pull_policy: PullPolicy,
+    /// The explicitly configured [PullPolicy], if any.
+    /// Falls back to the default policy of [crate::DockerTest], then [PullPolicy::IfNotPresent].
+    pull_policy: Option<PullPolicy>,
+    /// The platform to pull and run the image for, e.g., `linux/amd64`.
+    platform: Option<String>,
     id: Arc<RwLock<String>>,
 }
 
@@ -80,7 +85,8 @@ impl Image {
             repository: repository.to_string(),
             tag: "latest".to_string(),
             source: None,
-            pull_policy: PullPolicy::IfNotPresent,
+            pull_policy: None,
+            platform: None,
             id: Arc::new(RwLock::new("".to_string())),
         }
     }
@@ -95,6 +101,15 @@ impl Image {
         }
     }
 
+    /// Set the tag for this `Image` from the environment variable `name`, falling back to
+    /// `default` if the variable is unset or empty.
+    ///
+    /// This allows CI to run the test suite against a freshly built image, e.g., tagged by the
+    /// commit, while local runs use `default`, e.g., `latest`.
+    pub fn with_tag_from_env<T: ToString>(self, name: &str, default: T) -> Image {
+        self.tag(tag_or_default(std::env::var(name).ok(), default))
+    }
+
     /// Set the [Source] for this `Image`.
     ///
     /// If left unconfigured, it will default to [Source::Local].
@@ -110,11 +125,47 @@ impl Image {
     /// If left unconfigured, it will default to [PullPolicy::IfNotPresent].
     pub fn pull_policy(self, policy: PullPolicy) -> Image {
         Image {
-            pull_policy: policy,
+            pull_policy: Some(policy),
+            ..self
+        }
+    }
+
+    /// Set the platform to pull and run this `Image` for, e.g., `linux/amd64`.
+    ///
+    /// This is required to run images that are not published for the platform of the docker
+    /// daemon, e.g., `amd64` only images on Apple Silicon, which the daemon runs under emulation.
+    /// Requires docker API 1.41. If left unconfigured, the platform of the docker daemon is used.
+    pub fn platform<T: ToString>(self, platform: T) -> Image {
+        Image {
+            platform: Some(platform.to_string()),
             ..self
         }
     }
 
+    /// The explicitly configured platform of this `Image`, if any.
+    pub(crate) fn configured_platform(&self) -> Option<&str> {
+        self.platform.as_deref()
+    }
+
+    /// Apply the provided [PullPolicy] unless one has been explicitly configured.
+    pub(crate) fn default_pull_policy(&mut self, policy: &PullPolicy) {
+        if self.pull_policy.is_none() {
+            self.pull_policy = Some(policy.clone());
+        }
+    }
+
+    /// Apply the provided [PullPolicy], regardless of the configured one.
+    pub(crate) fn override_pull_policy(&mut self, policy: &PullPolicy) {
+        self.pull_policy = Some(policy.clone());
+    }
+
+    /// The [PullPolicy] in effect for this `Image`.
+    fn effective_pull_policy(&self) -> &PullPolicy {
+        self.pull_policy
+            .as_ref()
+            .unwrap_or(&PullPolicy::IfNotPresent)
+    }
+
     /// Returns the repository of this `Image`.
     ///
     /// This property is often generalized as the variable `name`.
@@ -122,9 +173,27 @@ impl Image {
         &self.repository
     }
 
+    /// Returns the reference of this `Image`, e.g., `postgres:latest`.
+    pub(crate) fn reference(&self) -> String {
+        format!("{}:{}", self.repository, self.tag)
+    }
+
+    /// Describe the image by its reference, for error messages.
+    pub(crate) fn describe(&self) -> String {
+        format!("image `{}`", self.reference())
+    }
+
+    /// The docker CLI command pulling the image, for error messages.
+    pub(crate) fn pull_command(&self) -> String {
+        match &self.platform {
+            Some(platform) => format!("docker pull --platform {} {}", platform, self.reference()),
+            None => format!("docker pull {}", self.reference()),
+        }
+    }
+
     /// Returns the id of the image
     pub(crate) fn retrieved_id(&self) -> String {
-        let id = self.id.read().expect("failed to get id lock");
+        let id = self.id.read().unwrap_or_else(|e| e.into_inner());
         id.clone()
     }
 
@@ -136,10 +205,12 @@ impl Image {
         client: &Docker,
         auth: Option<DockerCredentials>,
     ) -> Result<(), DockerTestError> {
+        let _permit = throttle::daemon_operation().await;
         debug!("pulling image: {}:{}", self.repository, self.tag);
         let options = Some(CreateImageOptions::<&str> {
             from_image: &self.repository,
             tag: &self.tag,
+            platform: self.platform.as_deref().unwrap_or_default(),
             ..Default::default()
         });
 
@@ -156,7 +227,12 @@ impl Image {
                         progress,
                         progress_detail,
                     } => {
-                        if error.is_some() {
+                        if let Some(mismatch) = error
+                            .as_deref()
+                            .and_then(|error| platform_mismatch(&self.describe(), error))
+                        {
+                            return Err(mismatch);
+                        } else if error.is_some() {
                             event!(
                                 Level::ERROR,
                                 "pull error {} {:?}",
@@ -176,6 +252,9 @@ impl Image {
                     }
                 },
                 Err(e) => {
+                    if let Some(mismatch) = platform_mismatch(&self.describe(), &e.to_string()) {
+                        return Err(mismatch);
+                    }
                     let msg = match e {
                         Error::DockerResponseServerError {
                             message: _,
@@ -218,7 +297,7 @@ impl Image {
             .await
         {
             Ok(details) => {
-                let mut id = self.id.write().expect("failed to get id lock");
+                let mut id = self.id.write().unwrap_or_else(|e| e.into_inner());
                 *id = details.id.expect("image did not have an id");
                 Ok(())
             }
@@ -281,7 +360,9 @@ impl Image {
 
         if self.should_pull(exists, pull_source)? {
             let auth = self.resolve_auth(pull_source)?;
-            self.do_pull(client, auth).await?;
+            if let Err(e) = self.do_pull(client, auth).await {
+                self.fall_back_to_local(exists, e)?;
+            }
         }
 
         // FIXME: If we encounter a scenario where the image should not be pulled, we need to err
@@ -290,6 +371,27 @@ impl Image {
         self.retrieve_and_set_id(client).await
     }
 
+    /// Use the local image if the pull failed, e.g., as the registry is unavailable or rate
+    /// limits the pull, warning about the failure. Fails if the image does not exist locally.
+    fn fall_back_to_local(
+        &self,
+        exists: bool,
+        error: DockerTestError,
+    ) -> Result<(), DockerTestError> {
+        match error {
+            DockerTestError::Pull { error, .. } if exists => {
+                event!(
+                    Level::WARN,
+                    "failed to pull {}, using the local image: {}",
+                    self.describe(),
+                    error
+                );
+                Ok(())
+            }
+            e => Err(e),
+        }
+    }
+
     /// Determine whether or not the `Image` should be pulled from `Source`.
     ///
     /// This function will consult the `Source`, `PullPolicy` and whether it already
@@ -297,33 +399,36 @@ impl Image {
     fn should_pull(&self, exists: bool, source: &Source) -> Result<bool, DockerTestError> {
         match source {
             Source::RegistryWithCredentials(_) => {
-                let valid = is_valid_pull_policy(exists, &self.pull_policy).map_err(|e| {
-                    DockerTestError::Pull {
-                        repository: self.repository.to_string(),
-                        tag: self.tag.to_string(),
-                        error: e,
-                    }
-                })?;
+                let valid =
+                    is_valid_pull_policy(exists, self.effective_pull_policy()).map_err(|e| {
+                        DockerTestError::Pull {
+                            repository: self.repository.to_string(),
+                            tag: self.tag.to_string(),
+                            error: e,
+                        }
+                    })?;
                 Ok(valid)
             }
             Source::RegistryWithDockerLogin(_) => {
-                let valid = is_valid_pull_policy(exists, &self.pull_policy).map_err(|e| {
-                    DockerTestError::Pull {
-                        repository: self.repository.to_string(),
-                        tag: self.tag.to_string(),
-                        error: e,
-                    }
-                })?;
+                let valid =
+                    is_valid_pull_policy(exists, self.effective_pull_policy()).map_err(|e| {
+                        DockerTestError::Pull {
+                            repository: self.repository.to_string(),
+                            tag: self.tag.to_string(),
+                            error: e,
+                        }
+                    })?;
                 Ok(valid)
             }
             Source::DockerHub => {
-                let valid = is_valid_pull_policy(exists, &self.pull_policy).map_err(|e| {
-                    DockerTestError::Pull {
-                        repository: self.repository.to_string(),
-                        tag: self.tag.to_string(),
-                        error: e,
-                    }
-                })?;
+                let valid =
+                    is_valid_pull_policy(exists, self.effective_pull_policy()).map_err(|e| {
+                        DockerTestError::Pull {
+                            repository: self.repository.to_string(),
+                            tag: self.tag.to_string(),
+                            error: e,
+                        }
+                    })?;
                 Ok(valid)
             }
             Source::Local => {
@@ -473,6 +578,37 @@ fn resolve_docker_login_auth(address: &str) -> Result<DockerCredentials, String>
     Ok(credentials)
 }
 
+/// The messages of the docker daemon when an image is not available for, or does not execute on,
+/// its platform.
+const PLATFORM_MISMATCH_MESSAGES: [&str; 3] = [
+    "does not match the specified platform",
+    "no matching manifest for",
+    "exec format error",
+];
+
+/// Describe the failure as a [DockerTestError::PlatformMismatch] if the docker daemon failed
+/// the operation on `subject` as the image does not support the platform of the daemon.
+pub(crate) fn platform_mismatch(subject: &str, message: &str) -> Option<DockerTestError> {
+    if PLATFORM_MISMATCH_MESSAGES
+        .iter()
+        .any(|m| message.contains(m))
+    {
+        Some(DockerTestError::PlatformMismatch(format!(
+            "{} does not support the platform of the docker daemon, configure the platform to \
+             run the image under emulation through `Image::platform`, e.g., `linux/amd64`: {}",
+            subject, message
+        )))
+    } else {
+        None
+    }
+}
+
+impl std::fmt::Display for Image {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.repository, self.tag)
+    }
+}
+
 impl RegistryCredentials {
     /// Creates a new [RegistryCredentials]
     pub fn new(address: String, username: String, password: Secret<String>) -> RegistryCredentials {
@@ -483,3 +619,83 @@ impl RegistryCredentials {
         }
     }
 }
+
+/// The tag set through an environment variable holding `value`, `default` if unset or empty.
+fn tag_or_default<T: ToString>(value: Option<String>, default: T) -> String {
+    value
+        .filter(|tag| !tag.is_empty())
+        .unwrap_or_else(|| default.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{platform_mismatch, tag_or_default, Image};
+    use crate::DockerTestError;
+
+    // Tests that platform mismatches are told apart from other daemon errors
+    #[test]
+    fn test_platform_mismatch() {
+        let mismatch = platform_mismatch(
+            "image `postgres:latest`",
+            "no matching manifest for linux/arm64/v8 in the manifest list entries",
+        );
+        assert!(matches!(
+            mismatch,
+            Some(DockerTestError::PlatformMismatch(ref msg)) if msg.contains("postgres:latest")
+        ));
+        assert!(platform_mismatch(
+            "container `db`",
+            "exec /docker-entrypoint.sh: exec format error"
+        )
+        .is_some());
+        assert!(platform_mismatch("image `postgres:latest`", "manifest unknown").is_none());
+    }
+
+    // Tests that the tag is read from the environment, falling back to the default
+    #[test]
+    fn test_with_tag_from_env() {
+        assert_eq!(tag_or_default(None, "latest"), "latest");
+        assert_eq!(tag_or_default(Some(String::new()), "latest"), "latest");
+        assert_eq!(
+            tag_or_default(Some("3f2c1ab".to_string()), "latest"),
+            "3f2c1ab"
+        );
+
+        let name = "DOCKERTEST_IMAGE_TAG_FROM_ENV_UNSET";
+        let image = Image::with_repository("postgres").with_tag_from_env(name, "latest");
+        assert_eq!(image.reference(), "postgres:latest");
+    }
+
+    // Tests that the pull command of an image includes its tag and platform
+    #[test]
+    fn test_pull_command() {
+        let image = Image::with_repository("postgres").tag("14");
+        assert_eq!(image.pull_command(), "docker pull postgres:14");
+        let image = image.platform("linux/amd64");
+        assert_eq!(
+            image.pull_command(),
+            "docker pull --platform linux/amd64 postgres:14"
+        );
+    }
+
+    // Tests that a failed pull falls back to the image if it exists locally
+    #[test]
+    fn test_fall_back_to_local() {
+        let image = Image::with_repository("postgres").tag("14");
+        let failed_pull = || DockerTestError::Pull {
+            repository: "postgres".to_string(),
+            tag: "14".to_string(),
+            error: "toomanyrequests: rate limit exceeded".to_string(),
+        };
+
+        assert_eq!(image.fall_back_to_local(true, failed_pull()), Ok(()));
+        assert!(matches!(
+            image.fall_back_to_local(false, failed_pull()),
+            Err(DockerTestError::Pull { .. })
+        ));
+        assert!(matches!(
+            image.fall_back_to_local(true, DockerTestError::Daemon("gone".to_string())),
+            Err(DockerTestError::Daemon(_))
+        ));
+    }
+}
@@ -0,0 +1,322 @@
+//! A janitor container removing the resources of the test process once it dies, see
+//! [DockerTest::with_janitor].
+//!
+//! Every container and network created by the test process is labeled with the session of the
+//! process, except for dynamic and pooled containers, which are meant to outlive it. The janitor
+//! is the [ryuk] container of testcontainers, started once per process. The process holds a
+//! connection to the janitor, telling it the label of its session. Once the connection is closed,
+//! e.g., as the test process is killed, the janitor removes every resource labeled with the
+//! session, and exits.
+//!
+//! The janitor requires access to the docker socket at `/var/run/docker.sock` of the docker
+//! daemon host, and is contacted through its published port on localhost. When the test process
+//! executes within a container, the janitor is started on the network that container was started
+//! on instead, and contacted through its ip address on that network.
+//!
+//! [DockerTest::with_janitor]: crate::DockerTest::with_janitor
+//! [ryuk]: https://github.com/testcontainers/moby-ryuk
+
+use crate::composition::{Composition, StaticManagementPolicy};
+use crate::image::{Image, Source};
+use crate::utils::generate_random_string;
+use crate::DockerTestError;
+
+use bollard::{
+    container::{Config, CreateContainerOptions, InspectContainerOptions},
+    models::{ContainerInspectResponse, HostConfig},
+    Docker,
+};
+use lazy_static::lazy_static;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tracing::{event, Level};
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+/// The label holding the session of the test process that created a resource.
+pub(crate) const SESSION_LABEL: &str = "dockertest.session";
+
+/// The image of the janitor.
+const JANITOR_IMAGE: &str = "testcontainers/ryuk";
+const JANITOR_TAG: &str = "0.5.1";
+
+/// The port the janitor accepts connections on.
+const JANITOR_PORT: &str = "8080/tcp";
+const JANITOR_PORT_NUMBER: u16 = 8080;
+
+/// The docker socket of the docker daemon host.
+const DOCKER_SOCKET: &str = "/var/run/docker.sock";
+
+/// How long to attempt to connect to the started janitor.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    /// The session of the test process.
+    static ref SESSION: String = generate_random_string(20);
+    /// The connection to the janitor of the process, once started. Kept open until the process
+    /// exits, as the janitor removes the resources of the session once it is closed.
+    static ref CONNECTION: tokio::sync::Mutex<Option<std::net::TcpStream>> =
+        tokio::sync::Mutex::new(None);
+}
+
+/// The labels of a resource owned by the test process.
+pub(crate) fn session_labels() -> HashMap<String, String> {
+    HashMap::from([(SESSION_LABEL.to_string(), SESSION.clone())])
+}
+
+/// Label the containers owned by the test process with its session.
+///
+/// Dynamic containers outlive the test process, and are left untouched.
+pub(crate) fn label(compositions: &mut [Composition]) {
+    for c in compositions
+        .iter_mut()
+        .filter(|c| c.static_management_policy() != &Some(StaticManagementPolicy::Dynamic))
+    {
+        c.labels.extend(session_labels());
+    }
+}
+
+/// Start the janitor of the process, unless it has already been started.
+///
+/// The janitor is reached from the container the test process executes within, if any.
+pub(crate) async fn ensure(
+    client: &Docker,
+    own_container: Option<&str>,
+) -> Result<(), DockerTestError> {
+    // The connection is shared by the runtimes of all tests, hence an asynchronous lock.
+    let mut connection = CONNECTION.lock().await;
+    if connection.is_some() {
+        return Ok(());
+    }
+
+    let address = start(client, own_container)
+        .await
+        .map_err(|e| DockerTestError::Startup(format!("failed to start the janitor: {}", e)))?;
+    let stream = register(address).await.map_err(|e| {
+        DockerTestError::Startup(format!("failed to register with the janitor: {}", e))
+    })?;
+    event!(
+        Level::DEBUG,
+        "registered session `{}` with the janitor",
+        *SESSION
+    );
+    *connection = Some(stream);
+
+    Ok(())
+}
+
+/// Start the janitor container, returning the address it accepts connections on.
+async fn start(client: &Docker, own_container: Option<&str>) -> Result<SocketAddr, String> {
+    let network = match own_container {
+        Some(id) => client
+            .inspect_container(id, None::<InspectContainerOptions>)
+            .await
+            .map(|details| own_network(&details))
+            .map_err(|e| e.to_string())?,
+        None => None,
+    };
+
+    let image = Image::with_repository(JANITOR_IMAGE).tag(JANITOR_TAG);
+    image
+        .pull(client, &Source::DockerHub)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let reference = image.reference();
+    let name = format!("dockertest-janitor-{}", *SESSION);
+    let options = CreateContainerOptions {
+        name: name.as_str(),
+        platform: None,
+    };
+    let config = Config::<&str> {
+        image: Some(&reference),
+        exposed_ports: Some(HashMap::from([(JANITOR_PORT, HashMap::new())])),
+        host_config: Some(HostConfig {
+            binds: Some(vec![format!("{}:{}", DOCKER_SOCKET, DOCKER_SOCKET)]),
+            publish_all_ports: Some(network.is_none()),
+            network_mode: network.clone(),
+            auto_remove: Some(true),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    client
+        .create_container(Some(options), config)
+        .await
+        .map_err(|e| e.to_string())?;
+    client
+        .start_container::<&str>(&name, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let details = client
+        .inspect_container(&name, None::<InspectContainerOptions>)
+        .await
+        .map_err(|e| e.to_string())?;
+    address(details, network.as_deref()).ok_or_else(|| match network {
+        Some(network) => format!("container `{}` has no address on `{}`", name, network),
+        None => format!("container `{}` did not publish port {}", name, JANITOR_PORT),
+    })
+}
+
+/// The network the container the test process executes within was started on, which the
+/// janitor is started on to be reachable. `None` if the container shares the network of the
+/// host, such that the published port of the janitor is reachable through localhost.
+fn own_network(details: &ContainerInspectResponse) -> Option<String> {
+    let mode = details
+        .host_config
+        .as_ref()
+        .and_then(|config| config.network_mode.as_deref())
+        .unwrap_or_default();
+    match mode {
+        "host" => None,
+        "" | "default" => Some("bridge".to_string()),
+        mode => Some(mode.to_string()),
+    }
+}
+
+/// The address of the started janitor, either its ip address on the network it was started on,
+/// or its published port on localhost.
+fn address(details: ContainerInspectResponse, network: Option<&str>) -> Option<SocketAddr> {
+    let settings = details.network_settings?;
+    match network {
+        Some(network) => {
+            let ip: Ipv4Addr = settings
+                .networks?
+                .remove(network)?
+                .ip_address
+                .filter(|ip| !ip.is_empty())?
+                .parse()
+                .ok()?;
+            Some(SocketAddr::from((ip, JANITOR_PORT_NUMBER)))
+        }
+        None => {
+            let port: u16 = settings
+                .ports?
+                .remove(JANITOR_PORT)
+                .flatten()?
+                .into_iter()
+                .find_map(|b| b.host_port)?
+                .parse()
+                .ok()?;
+            Some(SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+        }
+    }
+}
+
+/// Tell the janitor to remove the resources of the session once the returned connection closes.
+async fn register(address: SocketAddr) -> Result<std::net::TcpStream, String> {
+    let mut stream = connect(address).await?;
+    let filter = format!("label={}={}\n", SESSION_LABEL, *SESSION);
+    stream
+        .write_all(filter.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut reply = String::new();
+    BufReader::new(&mut stream)
+        .read_line(&mut reply)
+        .await
+        .map_err(|e| e.to_string())?;
+    if reply.trim() != "ACK" {
+        return Err(format!("unexpected reply `{}`", reply.trim()));
+    }
+
+    // The connection must outlive the runtime of the test, which deregisters tokio streams.
+    stream.into_std().map_err(|e| e.to_string())
+}
+
+/// Connect to the janitor, which may not yet accept connections once started.
+async fn connect(address: SocketAddr) -> Result<TcpStream, String> {
+    let deadline = tokio::time::Instant::now() + CONNECT_TIMEOUT;
+    loop {
+        match TcpStream::connect(address).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) if tokio::time::Instant::now() >= deadline => return Err(e.to_string()),
+            Err(_) => tokio::time::sleep(Duration::from_millis(200)).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{address, label, own_network, JANITOR_PORT, SESSION_LABEL};
+    use crate::composition::Composition;
+
+    use bollard::models::{
+        ContainerInspectResponse, EndpointSettings, HostConfig, NetworkSettings, PortBinding,
+    };
+
+    use std::collections::HashMap;
+
+    // Tests that only the containers owned by the test process are labeled with its session
+    #[test]
+    fn test_label() {
+        let owned = Composition::with_repository("postgres");
+        let mut shared = Composition::with_repository("redis");
+        shared.share_environment("redis-shared".to_string());
+
+        let mut compositions = vec![owned, shared];
+        label(&mut compositions);
+        assert!(compositions[0].labels.contains_key(SESSION_LABEL));
+        assert!(!compositions[1].labels.contains_key(SESSION_LABEL));
+    }
+
+    // Tests that the janitor joins the network of the container the tests execute within
+    #[test]
+    fn test_own_network() {
+        let details = |mode: Option<&str>| ContainerInspectResponse {
+            host_config: Some(HostConfig {
+                network_mode: mode.map(|m| m.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(own_network(&details(Some("host"))), None);
+        assert_eq!(
+            own_network(&details(Some("default"))),
+            Some("bridge".to_string())
+        );
+        assert_eq!(own_network(&details(None)), Some("bridge".to_string()));
+        assert_eq!(
+            own_network(&details(Some("ci_default"))),
+            Some("ci_default".to_string())
+        );
+    }
+
+    // Tests that the janitor is reached on its network, or through its published port
+    #[test]
+    fn test_address() {
+        let details = ContainerInspectResponse {
+            network_settings: Some(NetworkSettings {
+                networks: Some(HashMap::from([(
+                    "ci_default".to_string(),
+                    EndpointSettings {
+                        ip_address: Some("172.18.0.3".to_string()),
+                        ..Default::default()
+                    },
+                )])),
+                ports: Some(HashMap::from([(
+                    JANITOR_PORT.to_string(),
+                    Some(vec![PortBinding {
+                        host_ip: Some("0.0.0.0".to_string()),
+                        host_port: Some("32768".to_string()),
+                    }]),
+                )])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            address(details.clone(), Some("ci_default")),
+            Some("172.18.0.3:8080".parse().unwrap())
+        );
+        assert_eq!(
+            address(details.clone(), None),
+            Some("127.0.0.1:32768".parse().unwrap())
+        );
+        assert_eq!(address(details, Some("bridge")), None);
+    }
+}
@@ -53,6 +53,26 @@
 //! that can utilize the same underlying container without causing cross-test contamination.
 //! This will lead to significantly faster test execution time.
 //!
+//! State written to such containers by one test is visible to the next. A [Resettable] configured
+//! through `set_reset` resets the state of the container before each test body, e.g., through
+//! the [PostgresReset], [RedisReset] or [VolumeReset] implementations.
+//!
+//! ### Secrets
+//!
+//! Secrets provided through `modify_secret` are written to `/run/secrets/{name}` within the
+//! container before it is started, instead of through its environment, such that they are not
+//! visible to `docker inspect`. Their values are redacted from the container logs forwarded by
+//! dockertest.
+//!
+//! ### Isolation of failing tests
+//!
+//! Multiple tests may execute in parallel within the same test binary, sharing the static
+//! containers of the `TestSuiteSpecification` and `DynamicSpecification`. A panicking test never
+//! affects the other tests: its environment is torn down before the panic is propagated, and the
+//! shared state of dockertest is never poisoned by the panic. If the startup of a static container
+//! panics, e.g., within its [WaitFor], the container is recorded as failed, and the other tests
+//! using it fail with an error instead of waiting on it.
+//!
 //! # `WaitFor` - determining when a container is ready
 //!
 //! Each container that dockertest creates and starts must also have a policy to detect
@@ -65,6 +85,15 @@
 //! * [ExitedWait] - wait for the container to report _exited_ status.
 //! * [NoWait] - don't wait for anything
 //! * [MessageWait] - wait for the following message to appear in the log stream.
+//! * [MessageSequenceWait] - wait for the following messages to appear in the log stream, in
+//!   order and a number of times each.
+//! * [HostPortWait] - wait for a connection to be accepted on the published host port.
+//! * [ExecWait] - wait for a command executed within the container to succeed.
+//! * [HealthyWait] - wait for the healthcheck of the container to report it as healthy.
+//! * [MarkerFileWait] - wait for the container to write a readiness marker file.
+//!
+//! Custom implementations may build on [poll_until], polling a predicate until the container is
+//! ready.
 //!
 //! # Environment variables
 //!
@@ -80,14 +109,46 @@
 //! * `stop_on_failure`: stop containers on execution failure
 //! * `running_on_failure`: leave containers running on execution failure
 //!
+//! ## Overrides
+//!
+//! The following environment variables override the configuration of every test, taking
+//! precedence over the configuration in code. Unrecognized values are ignored with a warning.
+//! * `DOCKERTEST_PULL_POLICY`: `always`, `never` or `if_not_present`, the pull policy of every
+//!   image.
+//! * `DOCKERTEST_NAMESPACE`: the namespace of all container names.
+//! * `DOCKERTEST_LOG_DIR`: forward the logs of every container to files in this directory, including
+//!   the containers whose logging is disabled.
+//! * `DOCKERTEST_NETWORK_SUBNET`: the address pool to allocate network subnets from, e.g.,
+//!   `10.210.0.0/16`, optionally followed by the prefix length of each subnet, e.g., `:26`.
+//! * `DOCKERTEST_STARTUP_TIMEOUT`: the number of seconds all containers must start within.
+//! * `DOCKERTEST_EXISTING_CONTAINERS`: `fail`, `remove` or `reuse`, how to handle an existing
+//!   container with the same name as a container of the test, unless its policy is explicitly
+//!   set.
+//! * `DOCKERTEST_CGROUP_PARENT`: the parent cgroup of every container, e.g., the cgroup of the CI
+//!   job.
+//! * `DOCKERTEST_SUITE_SUMMARY`: `true` or `false`, whether to print a summary of every
+//!   environment of the process once it exits, see [DockerTest::with_suite_summary].
+//!
+//! The effective overrides are logged at the `DEBUG` level when the environment is set up.
+//!
+//! ## Daemon load
+//!
+//! Tests executing in parallel pull, create and start their containers concurrently. To avoid
+//! overloading the docker daemon, the number of such operations in flight across all tests of the
+//! process can be bounded by setting the environment variable `DOCKERTEST_MAX_DAEMON_OPERATIONS`
+//! to a positive number. By default, it is unbounded.
+//!
 //! ## Dockertest in Docker
 //!
 //! If the execution environment of running dockertest is itself a docker-in-docker container, one
 //! may have connectivity issues between the test body code, and the container dependencies.
-//! To ensure connectivity, dockertest must be instructed about the name/identifier of the
-//! execution container to include it into the docker network of the test containers.
-//! This is usually because the docker-in-docker docker daemon connection is routed to
-//! the underlying host itself.
+//! To ensure connectivity, dockertest includes the execution container into the docker network
+//! of the test containers. This is usually because the docker-in-docker docker daemon connection
+//! is routed to the underlying host itself.
+//!
+//! The execution container is detected automatically through the cgroup membership, mount table
+//! and hostname of the test process, provided it is known to the docker daemon. Detection can be
+//! overridden with the name/identifier of the execution container, or disabled with an empty value:
 //!
 //! `DOCKERTEST_CONTAINER_ID_INJECT_TO_NETWORK=your_container_id/name`
 //!
@@ -137,29 +198,81 @@
 //! [ExitedWait]: crate::waitfor::ExitedWait
 //! [NoWait]: crate::waitfor::NoWait
 //! [MessageWait]: crate::waitfor::MessageWait
+//! [MessageSequenceWait]: crate::waitfor::MessageSequenceWait
+//! [HostPortWait]: crate::waitfor::HostPortWait
+//! [ExecWait]: crate::waitfor::ExecWait
+//! [HealthyWait]: crate::waitfor::HealthyWait
+//! [MarkerFileWait]: crate::waitfor::MarkerFileWait
+//! [poll_until]: crate::waitfor::poll_until
+//! [Resettable]: crate::reset::Resettable
+//! [PostgresReset]: crate::reset::PostgresReset
+//! [RedisReset]: crate::reset::RedisReset
+//! [VolumeReset]: crate::reset::VolumeReset
 
+mod address_pool;
+mod background;
 mod composition;
 mod container;
+mod daemon;
+mod discovery;
 mod dockertest;
 mod engine;
+mod environment_module;
 mod error;
+mod exclusive_lock;
+mod faketime;
 mod image;
+mod janitor;
+mod overrides;
+mod performance;
+pub mod reset;
+mod retry;
 mod runner;
+mod secrets;
+mod self_container;
+pub mod selftest_images;
+mod shared_environment;
+mod smart_defaults;
 mod specification;
 mod static_container;
+mod suite_summary;
+mod teardown;
+mod template;
+mod throttle;
+#[cfg(feature = "tls-fixtures")]
+mod tls_fixtures;
+mod traffic;
+mod userns;
+mod warm_pool;
 // We only make this public because a function is used in our integration test
 #[doc(hidden)]
 pub mod utils;
 pub mod waitfor;
 
-pub use crate::composition::{LogAction, LogOptions, LogPolicy, LogSource, StartPolicy};
-pub use crate::container::{PendingContainer, RunningContainer};
+pub use crate::background::finish_suite;
+pub use crate::composition::{
+    BindMountOptions, ExistingContainerPolicy, Isolation, LogAction, LogOptions, LogPolicy,
+    LogSource, MountPropagation, ResolvedConfig, SelinuxRelabel, StartPolicy,
+};
+pub use crate::container::{
+    ConnectionKind, ContainerPort, FsChange, FsChangeKind, HealthStatus, LogExpectation,
+    PendingContainer, Process, Protocol, ResourceUpdate, RunningContainer, StatsFormat,
+    StatsRecorder, StatsSample,
+};
+pub use crate::daemon::DaemonInfo;
+pub use crate::discovery::{discover, DiscoveredContainer, DiscoveredEnvironment};
 pub use crate::dockertest::DockerTest;
 pub use crate::dockertest::Network;
+pub use crate::environment_module::TestEnvironmentModule;
 pub use crate::error::DockerTestError;
 pub use crate::image::{Image, PullPolicy, RegistryCredentials, Source};
-pub use crate::runner::DockerOperations;
+pub use crate::retry::RetryPolicy;
+pub use crate::runner::{DockerOperations, Runner};
 pub use crate::specification::{
     ContainerSpecification, DynamicSpecification, ExternalSpecification, TestBodySpecification,
     TestSuiteSpecification,
 };
+pub use crate::teardown::{ContainerExit, TeardownReport};
+#[cfg(feature = "tls-fixtures")]
+pub use crate::tls_fixtures::{TlsFixtures, TlsIdentity};
+pub use crate::traffic::TrafficShaping;
@@ -0,0 +1,288 @@
+//! Configuration overrides provided through `DOCKERTEST_*` environment variables.
+//!
+//! Overrides allow CI pipelines to tweak the behavior of tests without changing their code, and
+//! therefore take precedence over the configuration of the [DockerTest] and of each container.
+//! Unset variables leave the configuration untouched, and unrecognized values are ignored with a
+//! warning. The overrides in effect are logged when the environment is set up.
+//!
+//! [DockerTest]: crate::DockerTest
+
+use crate::address_pool::AddressPool;
+use crate::composition::{ExistingContainerPolicy, StaticManagementPolicy};
+use crate::image::PullPolicy;
+use crate::DockerTest;
+
+use tracing::{event, Level};
+
+use std::time::Duration;
+
+const PRUNE_ENV: &str = "DOCKERTEST_PRUNE";
+const DYNAMIC_ENV: &str = "DOCKERTEST_DYNAMIC";
+const PULL_POLICY_ENV: &str = "DOCKERTEST_PULL_POLICY";
+const NAMESPACE_ENV: &str = "DOCKERTEST_NAMESPACE";
+const LOG_DIR_ENV: &str = "DOCKERTEST_LOG_DIR";
+const NETWORK_SUBNET_ENV: &str = "DOCKERTEST_NETWORK_SUBNET";
+const STARTUP_TIMEOUT_ENV: &str = "DOCKERTEST_STARTUP_TIMEOUT";
+const EXISTING_CONTAINERS_ENV: &str = "DOCKERTEST_EXISTING_CONTAINERS";
+const CGROUP_PARENT_ENV: &str = "DOCKERTEST_CGROUP_PARENT";
+const SUITE_SUMMARY_ENV: &str = "DOCKERTEST_SUITE_SUMMARY";
+
+/// The prefix length of the subnets allocated from an overridden network subnet.
+const DEFAULT_SUBNET_SIZE: u8 = 24;
+
+/// The prune strategy for teardown of containers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum PruneStrategy {
+    /// Always leave the container running
+    RunningRegardless,
+    /// Do not perform any action if the test failed.
+    RunningOnFailure,
+    /// With a stop-only strategy, docker volumes will NOT be pruned.
+    StopOnFailure,
+    /// Prune everything, including named and anonymous volumes.
+    RemoveRegardless,
+}
+
+/// The overrides provided by the environment.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Overrides {
+    /// `DOCKERTEST_PRUNE`: the prune strategy of the teardown.
+    pub(crate) prune: Option<PruneStrategy>,
+    /// `DOCKERTEST_PULL_POLICY`: the pull policy of every image.
+    pub(crate) pull_policy: Option<PullPolicy>,
+    /// `DOCKERTEST_NAMESPACE`: the namespace of all container names.
+    pub(crate) namespace: Option<String>,
+    /// `DOCKERTEST_LOG_DIR`: the directory container logs are forwarded to.
+    pub(crate) log_dir: Option<String>,
+    /// `DOCKERTEST_NETWORK_SUBNET`: the address pool to allocate network subnets from, in CIDR
+    /// notation, optionally followed by the prefix length of each subnet, e.g., `10.210.0.0/16:24`.
+    pub(crate) network_subnet: Option<(String, u8)>,
+    /// `DOCKERTEST_STARTUP_TIMEOUT`: the number of seconds all containers must start within.
+    pub(crate) startup_timeout: Option<Duration>,
+    /// `DOCKERTEST_EXISTING_CONTAINERS`: how to handle existing containers with the same name,
+    /// one of `fail`, `remove` or `reuse`.
+    pub(crate) existing_containers: Option<ExistingContainerPolicy>,
+    /// `DOCKERTEST_CGROUP_PARENT`: the parent cgroup of every container.
+    pub(crate) cgroup_parent: Option<String>,
+    /// `DOCKERTEST_SUITE_SUMMARY`: whether to print a summary of every environment of the
+    /// process once it exits, `true` or `false`.
+    pub(crate) suite_summary: Option<bool>,
+}
+
+impl Overrides {
+    /// Read the overrides from the environment of the process.
+    pub(crate) fn from_env() -> Overrides {
+        Self::parse(|name| std::env::var(name).ok())
+    }
+
+    fn parse<F>(lookup: F) -> Overrides
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let read = |name: &str| lookup(name).filter(|value| !value.trim().is_empty());
+        Overrides {
+            prune: read(PRUNE_ENV).and_then(|v| recognized(PRUNE_ENV, &v, parse_prune(&v))),
+            pull_policy: read(PULL_POLICY_ENV)
+                .and_then(|v| recognized(PULL_POLICY_ENV, &v, parse_pull_policy(&v))),
+            namespace: read(NAMESPACE_ENV),
+            log_dir: read(LOG_DIR_ENV),
+            network_subnet: read(NETWORK_SUBNET_ENV)
+                .and_then(|v| recognized(NETWORK_SUBNET_ENV, &v, parse_network_subnet(&v))),
+            startup_timeout: read(STARTUP_TIMEOUT_ENV).and_then(|v| {
+                let timeout = v.trim().parse::<u64>().ok().filter(|secs| *secs > 0);
+                recognized(STARTUP_TIMEOUT_ENV, &v, timeout.map(Duration::from_secs))
+            }),
+            existing_containers: read(EXISTING_CONTAINERS_ENV).and_then(|v| {
+                recognized(EXISTING_CONTAINERS_ENV, &v, parse_existing_containers(&v))
+            }),
+            cgroup_parent: read(CGROUP_PARENT_ENV),
+            suite_summary: read(SUITE_SUMMARY_ENV)
+                .and_then(|v| recognized(SUITE_SUMMARY_ENV, &v, parse_bool(&v))),
+        }
+    }
+
+    /// Apply the overrides to the configuration of the test.
+    pub(crate) fn apply(&self, config: &mut DockerTest) {
+        event!(Level::DEBUG, "effective environment overrides: {:?}", self);
+
+        if let Some(namespace) = &self.namespace {
+            config.namespace = namespace.clone();
+        }
+        if let Some((base, size)) = &self.network_subnet {
+            config.address_pool = Some(AddressPool::new(base.clone(), *size));
+        }
+        if let Some(timeout) = self.startup_timeout {
+            config.startup_timeout = Some(timeout);
+        }
+        if let Some(enabled) = self.suite_summary {
+            config.suite_summary = enabled;
+        }
+    }
+}
+
+/// The management policy that replaces [StaticManagementPolicy::Dynamic], if overridden through
+/// `DOCKERTEST_DYNAMIC`.
+pub(crate) fn dynamic_management_policy() -> Option<StaticManagementPolicy> {
+    let value = std::env::var(DYNAMIC_ENV).ok()?;
+    let policy = match value.as_str() {
+        "EXTERNAL" => Some(StaticManagementPolicy::External),
+        "INTERNAL" => Some(StaticManagementPolicy::Internal),
+        "DYNAMIC" => Some(StaticManagementPolicy::Dynamic),
+        _ => None,
+    };
+    recognized(DYNAMIC_ENV, &value, policy)
+}
+
+fn recognized<T>(name: &str, value: &str, parsed: Option<T>) -> Option<T> {
+    if parsed.is_none() {
+        event!(
+            Level::WARN,
+            "ignoring unrecognized `{} = {:?}`",
+            name,
+            value
+        );
+    }
+    parsed
+}
+
+fn parse_prune(value: &str) -> Option<PruneStrategy> {
+    match value.to_lowercase().as_str() {
+        "stop_on_failure" => Some(PruneStrategy::StopOnFailure),
+        "never" => Some(PruneStrategy::RunningRegardless),
+        "running_on_failure" => Some(PruneStrategy::RunningOnFailure),
+        "always" => Some(PruneStrategy::RemoveRegardless),
+        _ => None,
+    }
+}
+
+fn parse_pull_policy(value: &str) -> Option<PullPolicy> {
+    match value.to_lowercase().replace(['-', '_'], "").as_str() {
+        "always" => Some(PullPolicy::Always),
+        "never" => Some(PullPolicy::Never),
+        "ifnotpresent" => Some(PullPolicy::IfNotPresent),
+        _ => None,
+    }
+}
+
+fn parse_network_subnet(value: &str) -> Option<(String, u8)> {
+    let (base, size) = match value.trim().split_once(':') {
+        Some((base, size)) => (base, size.parse().ok()?),
+        None => {
+            let prefix: u8 = value.trim().split_once('/')?.1.parse().ok()?;
+            (value.trim(), prefix.max(DEFAULT_SUBNET_SIZE))
+        }
+    };
+    Some((base.to_string(), size))
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_existing_containers(value: &str) -> Option<ExistingContainerPolicy> {
+    match value.to_lowercase().as_str() {
+        "fail" => Some(ExistingContainerPolicy::Fail),
+        "remove" => Some(ExistingContainerPolicy::Remove),
+        "reuse" => Some(ExistingContainerPolicy::Reuse),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Overrides, PruneStrategy};
+    use crate::composition::{Composition, ExistingContainerPolicy};
+    use crate::{LogAction, LogOptions, PullPolicy};
+
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    fn parse(vars: &[(&str, &str)]) -> Overrides {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Overrides::parse(|name| vars.get(name).cloned())
+    }
+
+    // Tests that recognized values are parsed, and others ignored
+    #[test]
+    fn test_parse_overrides() {
+        let overrides = parse(&[
+            ("DOCKERTEST_PRUNE", "never"),
+            ("DOCKERTEST_PULL_POLICY", "if-not-present"),
+            ("DOCKERTEST_NAMESPACE", "ci"),
+            ("DOCKERTEST_LOG_DIR", ""),
+            ("DOCKERTEST_NETWORK_SUBNET", "10.210.0.0/16"),
+            ("DOCKERTEST_STARTUP_TIMEOUT", "soon"),
+            ("DOCKERTEST_EXISTING_CONTAINERS", "reuse"),
+            ("DOCKERTEST_CGROUP_PARENT", "/ci/job-42"),
+            ("DOCKERTEST_SUITE_SUMMARY", "yes"),
+        ]);
+
+        assert_eq!(overrides.prune, Some(PruneStrategy::RunningRegardless));
+        assert!(matches!(
+            overrides.pull_policy,
+            Some(PullPolicy::IfNotPresent)
+        ));
+        assert_eq!(overrides.namespace.as_deref(), Some("ci"));
+        assert_eq!(overrides.log_dir, None);
+        assert_eq!(
+            overrides.network_subnet,
+            Some(("10.210.0.0/16".to_string(), 24))
+        );
+        assert_eq!(overrides.startup_timeout, None);
+        assert_eq!(
+            overrides.existing_containers,
+            Some(ExistingContainerPolicy::Reuse)
+        );
+        assert_eq!(overrides.cgroup_parent.as_deref(), Some("/ci/job-42"));
+        assert_eq!(overrides.suite_summary, None);
+
+        let overrides = parse(&[
+            ("DOCKERTEST_NETWORK_SUBNET", "10.210.0.0/16:26"),
+            ("DOCKERTEST_STARTUP_TIMEOUT", "90"),
+            ("DOCKERTEST_SUITE_SUMMARY", "TRUE"),
+        ]);
+        assert_eq!(
+            overrides.network_subnet,
+            Some(("10.210.0.0/16".to_string(), 26))
+        );
+        assert_eq!(overrides.startup_timeout, Some(Duration::from_secs(90)));
+        assert_eq!(overrides.suite_summary, Some(true));
+    }
+
+    // Tests that overrides apply to every container, except for explicitly configured policies
+    #[test]
+    fn test_apply_overrides() {
+        let overrides = parse(&[
+            ("DOCKERTEST_LOG_DIR", "/tmp/logs"),
+            ("DOCKERTEST_EXISTING_CONTAINERS", "reuse"),
+        ]);
+        let mut default = Composition::with_repository("postgres").with_log_options(None);
+        let mut explicit = Composition::with_repository("redis")
+            .with_existing_container_policy(ExistingContainerPolicy::Fail);
+        default.apply_overrides(&overrides);
+        explicit.apply_overrides(&overrides);
+
+        assert_eq!(
+            default.existing_container_policy,
+            ExistingContainerPolicy::Reuse
+        );
+        assert_eq!(
+            explicit.existing_container_policy,
+            ExistingContainerPolicy::Fail
+        );
+        assert!(matches!(
+            default.log_options,
+            Some(LogOptions {
+                action: LogAction::ForwardToFile { ref path },
+                ..
+            }) if path == "/tmp/logs"
+        ));
+    }
+}
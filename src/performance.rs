@@ -0,0 +1,95 @@
+//! Well-known speed knobs of database images, trading durability for speed, see
+//! [TestBodySpecification::set_ephemeral_performance_mode].
+//!
+//! The data of a test database is discarded along with its container, hence it need not survive
+//! a crash. The knobs disable flushing the data to disk, and keep the data directory in memory
+//! through a tmpfs mount.
+//!
+//! [TestBodySpecification::set_ephemeral_performance_mode]: crate::TestBodySpecification::set_ephemeral_performance_mode
+
+/// The speed knobs of a recognized database image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Knobs {
+    /// The server executable, which the entrypoint of the image prepends to arguments starting
+    /// with `-`.
+    pub(crate) server: &'static str,
+    /// The arguments passed to the server.
+    pub(crate) args: &'static [&'static str],
+    /// The data directory of the server, mounted as a tmpfs.
+    pub(crate) data_dir: &'static str,
+}
+
+const POSTGRES: Knobs = Knobs {
+    server: "postgres",
+    args: &[
+        "-c",
+        "fsync=off",
+        "-c",
+        "synchronous_commit=off",
+        "-c",
+        "full_page_writes=off",
+    ],
+    data_dir: "/var/lib/postgresql/data",
+};
+
+const MYSQL: Knobs = Knobs {
+    server: "mysqld",
+    args: &[
+        "--skip-innodb-doublewrite",
+        "--innodb-flush-log-at-trx-commit=0",
+        "--sync-binlog=0",
+    ],
+    data_dir: "/var/lib/mysql",
+};
+
+/// The knobs of the official image of the repository, if recognized.
+pub(crate) fn knobs(repository: &str) -> Option<Knobs> {
+    let name = repository.rsplit('/').next().unwrap_or(repository);
+    match name {
+        "postgres" => Some(POSTGRES),
+        "mysql" | "mariadb" => Some(MYSQL),
+        _ => None,
+    }
+}
+
+/// Pass the arguments of the knobs to the server started by the command, if any.
+///
+/// An empty command, or one of only arguments, is passed to the server by the entrypoint of the
+/// image. Returns `None` for commands that do not start the server.
+pub(crate) fn with_args(knobs: &Knobs, cmd: &[String]) -> Option<Vec<String>> {
+    match cmd.first() {
+        Some(first) if first != knobs.server && !first.starts_with('-') => None,
+        _ => Some(
+            cmd.iter()
+                .cloned()
+                .chain(knobs.args.iter().map(|a| a.to_string()))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{knobs, with_args, MYSQL, POSTGRES};
+
+    // Tests that the official database images are recognized
+    #[test]
+    fn test_knobs() {
+        assert_eq!(knobs("postgres"), Some(POSTGRES));
+        assert_eq!(knobs("library/postgres"), Some(POSTGRES));
+        assert_eq!(knobs("mariadb"), Some(MYSQL));
+        assert_eq!(knobs("redis"), None);
+    }
+
+    // Tests that the arguments are only passed to commands starting the server
+    #[test]
+    fn test_with_args() {
+        let cmd = with_args(&POSTGRES, &[]).unwrap();
+        assert_eq!(cmd[..2], ["-c", "fsync=off"]);
+
+        let cmd = with_args(&POSTGRES, &["postgres".to_string(), "-N".to_string()]).unwrap();
+        assert_eq!(cmd[..4], ["postgres", "-N", "-c", "fsync=off"]);
+
+        assert!(with_args(&POSTGRES, &["psql".to_string()]).is_none());
+    }
+}
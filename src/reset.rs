@@ -0,0 +1,181 @@
+//! Contains the `Resettable` trait used to reset the state of a container between test bodies,
+//! and the default implementations of it.
+
+use crate::container::RunningContainer;
+use crate::DockerTestError;
+
+pub use async_trait::async_trait;
+use dyn_clone::DynClone;
+
+/// Trait to reset the state of a container before each test body using it.
+///
+/// Containers of a [TestSuiteSpecification] or [DynamicSpecification] outlive the individual
+/// test, such that state written by one test is visible to the next. A container configured
+/// with a [Resettable] is reset once it is ready, before the test body is invoked, such that
+/// every test starts from the same state. A failed reset fails the test.
+///
+/// NOTE: A reset removes the state of all tests using the container, hence tests sharing a
+/// resettable container must not execute concurrently.
+///
+/// [TestSuiteSpecification]: crate::TestSuiteSpecification
+/// [DynamicSpecification]: crate::DynamicSpecification
+#[async_trait]
+pub trait Resettable: Send + Sync + DynClone + std::fmt::Debug {
+    /// Reset the state of the running container.
+    async fn reset(&self, container: &RunningContainer) -> Result<(), DockerTestError>;
+}
+
+dyn_clone::clone_trait_object!(Resettable);
+
+/// The `PostgresReset` resets a postgres database to a snapshot kept as a template database.
+///
+/// The first reset takes the snapshot of the database as it is, e.g., once seeded by the
+/// entrypoint of the image. Later resets recreate the database from the snapshot. Requires `psql`
+/// within the container.
+#[derive(Clone, Debug)]
+pub struct PostgresReset {
+    /// The database to reset.
+    pub database: String,
+    /// The user to connect as, which must be allowed to create databases.
+    pub user: String,
+    /// The template database holding the snapshot.
+    pub template: String,
+}
+
+impl PostgresReset {
+    /// Reset the `database` as the `postgres` user, keeping the snapshot in the
+    /// `{database}_template` database.
+    pub fn new<T: ToString>(database: T) -> PostgresReset {
+        let database = database.to_string();
+        PostgresReset {
+            template: format!("{}_template", database),
+            user: "postgres".to_string(),
+            database,
+        }
+    }
+
+    async fn psql(
+        &self,
+        container: &RunningContainer,
+        sql: &str,
+    ) -> Result<String, DockerTestError> {
+        container
+            .exec(&["psql", "-U", &self.user, "-d", "postgres", "-tAc", sql])
+            .await
+    }
+}
+
+#[async_trait]
+impl Resettable for PostgresReset {
+    async fn reset(&self, container: &RunningContainer) -> Result<(), DockerTestError> {
+        let exists = self
+            .psql(
+                container,
+                &format!(
+                    "SELECT 1 FROM pg_database WHERE datname = {}",
+                    sql_literal(&self.template)
+                ),
+            )
+            .await?;
+
+        // A database with open connections can neither be copied nor dropped.
+        self.psql(
+            container,
+            &format!(
+                "SELECT pg_terminate_backend(pid) FROM pg_stat_activity \
+                 WHERE datname = {} AND pid <> pg_backend_pid()",
+                sql_literal(&self.database)
+            ),
+        )
+        .await?;
+
+        let database = sql_identifier(&self.database);
+        let template = sql_identifier(&self.template);
+        if exists.trim().is_empty() {
+            self.psql(
+                container,
+                &format!("CREATE DATABASE {} TEMPLATE {}", template, database),
+            )
+            .await?;
+        } else {
+            self.psql(container, &format!("DROP DATABASE IF EXISTS {}", database))
+                .await?;
+            self.psql(
+                container,
+                &format!("CREATE DATABASE {} TEMPLATE {}", database, template),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The `RedisReset` removes all keys of all databases of a redis server through `FLUSHALL`.
+///
+/// Requires `redis-cli` within the container.
+#[derive(Clone, Debug)]
+pub struct RedisReset {}
+
+#[async_trait]
+impl Resettable for RedisReset {
+    async fn reset(&self, container: &RunningContainer) -> Result<(), DockerTestError> {
+        container.exec(&["redis-cli", "FLUSHALL"]).await.map(|_| ())
+    }
+}
+
+/// The `VolumeReset` restores the contents of a directory, e.g., a volume, from a seed directory
+/// within the container.
+///
+/// The first reset takes the seed as a copy of the directory, if the seed does not exist.
+/// Requires `sh`, `cp` and `find` within the container.
+#[derive(Clone, Debug)]
+pub struct VolumeReset {
+    /// The directory to restore.
+    pub path: String,
+    /// The directory holding the seed to restore from.
+    pub seed: String,
+}
+
+#[async_trait]
+impl Resettable for VolumeReset {
+    async fn reset(&self, container: &RunningContainer) -> Result<(), DockerTestError> {
+        let path = shell_quote(&self.path);
+        let seed = shell_quote(&self.seed);
+        let script = format!(
+            "if [ -d {seed} ]; then find {path} -mindepth 1 -delete && cp -a {seed}/. {path}/; \
+             else mkdir -p {seed} && cp -a {path}/. {seed}/; fi",
+            path = path,
+            seed = seed
+        );
+        container.exec(&["sh", "-c", &script]).await.map(|_| ())
+    }
+}
+
+/// Quote the value as a SQL string literal.
+fn sql_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Quote the value as a SQL identifier.
+fn sql_identifier(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+/// Quote the value as a single shell word.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{shell_quote, sql_identifier, sql_literal};
+
+    // Tests that quoted values cannot escape their quotes
+    #[test]
+    fn test_quoting() {
+        assert_eq!(sql_literal("it's"), "'it''s'");
+        assert_eq!(sql_identifier("my \"db\""), "\"my \"\"db\"\"\"");
+        assert_eq!(shell_quote("/data/it's"), r"'/data/it'\''s'");
+    }
+}
@@ -0,0 +1,357 @@
+//! Retrying docker daemon operations that fail with transient errors.
+
+use crate::daemon::desktop_condition;
+use crate::DockerTestError;
+
+use bollard::errors::Error;
+use futures::Future;
+use tracing::{event, Level};
+
+use std::time::Duration;
+
+/// How to retry docker daemon operations that fail with a transient error, such as an internal
+/// server error of an overloaded daemon, or a reset connection.
+///
+/// Applies to creating, starting, inspecting and removing containers. Permanent failures, such as
+/// a missing image, are never retried. Configured through [DockerTest::with_retry_policy].
+///
+/// [DockerTest::with_retry_policy]: crate::DockerTest::with_retry_policy
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts of an operation, including the first attempt.
+    pub max_attempts: u32,
+    /// The delay before the first retry, which doubles for every subsequent retry.
+    pub initial_backoff: Duration,
+    /// The upper bound of the delay between retries.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, with a backoff starting at 250 milliseconds.
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Reconnect to a docker daemon that is restarting, for up to about 15 seconds.
+    pub(crate) fn reconnect() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(8),
+        }
+    }
+
+    /// Wait for a conflicting removal or use of an object to complete, for up to about 5 seconds.
+    fn conflict() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+
+    /// Never retry operations.
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Perform the `operation` until it succeeds, fails permanently, or runs out of attempts.
+    pub(crate) async fn retry<T, F, Fut>(&self, description: &str, operation: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        self.retry_if(description, is_transient, operation).await
+    }
+
+    /// Perform the removal `operation`, retrying transient errors as configured by this policy.
+    ///
+    /// Conflicts, e.g., with a removal that is already in progress or a volume that is still in
+    /// use by a container being removed, are retried regardless of this policy, as they resolve
+    /// once the daemon completes the conflicting operation.
+    pub(crate) async fn retry_removal<T, F, Fut>(
+        &self,
+        description: &str,
+        mut operation: F,
+    ) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let conflicts = RetryPolicy::conflict();
+        let mut backoff = conflicts.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            match self.retry(description, &mut operation).await {
+                Err(e) if attempt < conflicts.max_attempts && is_conflict(&e) => {
+                    event!(
+                        Level::DEBUG,
+                        "failed to {} due to a conflict, retrying in {:?}: {}",
+                        description,
+                        backoff,
+                        e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(conflicts.max_backoff);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Perform the `operation` until it succeeds, fails with an error that is not `retryable`,
+    /// or runs out of attempts.
+    pub(crate) async fn retry_if<T, F, Fut, P>(
+        &self,
+        description: &str,
+        retryable: P,
+        mut operation: F,
+    ) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+        P: Fn(&Error) -> bool,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            match operation().await {
+                Err(e) if attempt < self.max_attempts && retryable(&e) => {
+                    event!(
+                        Level::WARN,
+                        "failed to {} (attempt {} of {}), retrying in {:?}: {}",
+                        description,
+                        attempt,
+                        self.max_attempts,
+                        backoff,
+                        e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.max_backoff);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// The messages of internal server errors of the daemon that may not occur if the operation is
+/// performed again, as opposed to, e.g., a container whose entrypoint does not exist.
+const TRANSIENT_MESSAGES: &[&str] = &[
+    "context deadline exceeded",
+    "connection reset by peer",
+    "i/o timeout",
+    "timed out",
+    "transport is closing",
+    "database is locked",
+    "resource temporarily unavailable",
+    "try again",
+];
+
+/// Whether the error may not occur if the operation is performed again.
+pub(crate) fn is_transient(e: &Error) -> bool {
+    match e {
+        Error::DockerResponseServerError {
+            status_code,
+            message,
+        } => {
+            let message = message.to_lowercase();
+            matches!(status_code, 502..=504)
+                || (*status_code == 500
+                    && TRANSIENT_MESSAGES.iter().any(|m| message.contains(m)))
+                // The daemon may still be releasing the mounts of a container on removal
+                || message.contains("device or resource busy")
+        }
+        Error::IOError { err } => matches!(
+            err.kind(),
+            std::io::ErrorKind::ConnectionReset
+                | std::io::ErrorKind::ConnectionAborted
+                | std::io::ErrorKind::BrokenPipe
+                | std::io::ErrorKind::UnexpectedEof
+                | std::io::ErrorKind::TimedOut
+        ),
+        Error::HyperResponseError { .. } | Error::RequestTimeoutError => true,
+        _ => false,
+    }
+}
+
+/// Whether the daemon rejected the operation as it conflicts with the current state of the
+/// object, e.g., removing a container whose removal is already in progress, or a volume that is
+/// still in use.
+pub(crate) fn is_conflict(e: &Error) -> bool {
+    matches!(
+        e,
+        Error::DockerResponseServerError {
+            status_code: 409,
+            ..
+        }
+    )
+}
+
+/// Whether the object of the operation does not exist.
+pub(crate) fn is_not_found(e: &Error) -> bool {
+    matches!(
+        e,
+        Error::DockerResponseServerError {
+            status_code: 404,
+            ..
+        }
+    )
+}
+
+/// Describe a failed daemon operation, as a [DockerTestError::Transient] error if it failed with
+/// a transient error on its last attempt.
+pub(crate) fn daemon_error(context: &str, e: Error) -> DockerTestError {
+    if let Some(condition) = desktop_condition(&e) {
+        DockerTestError::Daemon(format!("{}: {}", context, condition))
+    } else if is_transient(&e) {
+        DockerTestError::Transient(format!("{}: {}", context, e))
+    } else {
+        DockerTestError::Daemon(format!("{}: {}", context, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_conflict, is_transient, RetryPolicy};
+    use bollard::errors::Error;
+
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    fn server_error(status_code: u16, message: &str) -> Error {
+        Error::DockerResponseServerError {
+            status_code,
+            message: message.to_string(),
+        }
+    }
+
+    // Tests that unavailable daemons, known transient server errors and busy devices are
+    // transient, while other server errors and client errors are not
+    #[test]
+    fn test_is_transient() {
+        assert!(is_transient(&server_error(503, "service unavailable")));
+        assert!(is_transient(&server_error(
+            500,
+            "failed to create task: context deadline exceeded"
+        )));
+        assert!(is_transient(&server_error(
+            500,
+            "read unix @->/run/containerd/containerd.sock: read: connection reset by peer"
+        )));
+        assert!(is_transient(&server_error(
+            409,
+            "unlinkat /var/lib/docker/overlay2: device or resource busy"
+        )));
+
+        assert!(!is_transient(&server_error(500, "internal error")));
+        assert!(!is_transient(&server_error(
+            500,
+            "failed to create task for container: exec: \"migrate\": executable file not found in $PATH"
+        )));
+        assert!(!is_transient(&server_error(
+            500,
+            "driver failed programming external connectivity: port is already allocated"
+        )));
+        assert!(!is_transient(&server_error(404, "No such container: db")));
+        assert!(!is_transient(&server_error(409, "name is already in use")));
+    }
+
+    // Tests that transient failures are retried until the attempts are exhausted
+    #[tokio::test]
+    async fn test_retry_transient_failures() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+        };
+
+        let attempts = AtomicU32::new(0);
+        let result: Result<(), Error> = policy
+            .retry("test", || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(server_error(500, "context deadline exceeded"))
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        let attempts = AtomicU32::new(0);
+        let result: Result<(), Error> = policy
+            .retry("test", || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(server_error(404, "No such container: db"))
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    // Tests that operations are retried while the predicate deems the error retryable
+    #[tokio::test]
+    async fn test_retry_if_conflict() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+        };
+
+        let attempts = AtomicU32::new(0);
+        let result: Result<(), Error> = policy
+            .retry_if("test", is_conflict, || async {
+                match attempts.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => Err(server_error(
+                        409,
+                        "removal of container db is already in progress",
+                    )),
+                    _ => Err(server_error(404, "No such container: db")),
+                }
+            })
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::DockerResponseServerError {
+                status_code: 404,
+                ..
+            })
+        ));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    // Tests that removal retries conflicts even when the policy disables retries
+    #[tokio::test]
+    async fn test_retry_removal_conflict_without_retries() {
+        let attempts = AtomicU32::new(0);
+        let result: Result<(), Error> = RetryPolicy::none()
+            .retry_removal("test", || async {
+                match attempts.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(server_error(
+                        409,
+                        "removal of container db is already in progress",
+                    )),
+                    1 => Err(server_error(500, "internal error")),
+                    _ => Ok(()),
+                }
+            })
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::DockerResponseServerError {
+                status_code: 500,
+                ..
+            })
+        ));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,24 +1,49 @@
 //! The main library structures.
 
-use crate::container::RunningContainer;
+use crate::address_pool::{self, AddressPool};
+use crate::background::spawn_teardown;
+use crate::composition::{Composition, Isolation, StaticManagementPolicy};
+use crate::container::{recent_logs, PendingContainer, RunningContainer};
+use crate::daemon::{self, DaemonInfo};
+use crate::discovery;
 use crate::dockertest::Network;
 use crate::engine::{bootstrap, Debris, Engine, Orbiting};
+use crate::exclusive_lock::{self, ExclusiveLock};
+use crate::faketime;
+use crate::janitor;
+use crate::overrides::{Overrides, PruneStrategy};
+use crate::reset::Resettable;
+use crate::retry::is_not_found;
+use crate::self_container::resolve_own_container_id;
+use crate::shared_environment;
 use crate::static_container::SCOPED_NETWORKS;
-use crate::utils::{connect_with_local_or_tls_defaults, generate_random_string};
+use crate::suite_summary;
+use crate::teardown::TeardownReport;
+#[cfg(feature = "tls-fixtures")]
+use crate::tls_fixtures::TlsFixtures;
+use crate::traffic::{self, TrafficShaping};
+use crate::userns;
+use crate::utils::{connect_with_local_or_tls_defaults, shared_client, Timestamp};
+use crate::waitfor::WaitFor;
 use crate::{DockerTest, DockerTestError};
 
 use bollard::{
-    network::{CreateNetworkOptions, DisconnectNetworkOptions},
+    container::InspectContainerOptions,
+    models::{ContainerStateStatusEnum, HealthStatusEnum, Ipam, IpamConfig, NetworkCreateResponse},
+    network::{CreateNetworkOptions, DisconnectNetworkOptions, InspectNetworkOptions},
     volume::RemoveVolumeOptions,
     Docker,
 };
 use futures::future::{join_all, Future};
+use tokio::runtime::Handle;
 use tracing::{error, event, trace, Level};
 
 use std::any::Any;
 use std::clone::Clone;
 use std::collections::HashMap;
 use std::panic;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 /// Represents a single docker test body execution environment.
 ///
@@ -32,14 +57,23 @@ use std::panic;
 ///
 /// Otherwise local connection is used - via unix socket or named pipe (on Windows).
 ///
-/// Before running the test body through [run](DockerTest::run), one should configure
-/// the docker container dependencies through adding [Composition] with the configured
-/// environment the running container should end up representing.
+/// Most tests should use [DockerTest::run] or [DockerTest::run_async], which drive the full
+/// lifecycle of the environment around a single test body. The [Runner] exposes the individual
+/// lifecycle steps for custom harnesses that need to own the lifetime of the environment:
+/// [setup](Runner::setup) starts all containers, and [teardown](Runner::teardown) removes them
+/// in accordance with the prune strategy.
 ///
-/// By default, all images will have the local [Source], meaning that unless otherwise specified
-/// in the composition stage, we expect the [Image](crate::image::Image) referenced must be
-/// available on the local docker daemon.
-pub(crate) struct Runner {
+/// ```no_run
+/// # use dockertest::{DockerTest, Runner};
+/// # async fn harness(test: DockerTest) -> Result<(), dockertest::DockerTestError> {
+/// let mut runner = Runner::try_new(test).await?;
+/// let ops = runner.setup().await?;
+/// // Drive the environment through `ops`.
+/// runner.teardown(false).await;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Runner {
     /// The docker client to interact with the docker daemon with.
     client: Docker,
     /// The config to run this test with.
@@ -59,6 +93,18 @@ pub(crate) struct Runner {
     /// suffixed with this ID.
     /// This applies to resouces such as docker network names and named volumes.
     pub(crate) id: String,
+    /// The environment established by [Runner::setup], until it is torn down.
+    engine: Option<Engine<Orbiting>>,
+    /// The runtime to spawn the test body and container startup tasks onto.
+    runtime: Handle,
+    /// The capabilities of the docker daemon.
+    daemon_info: DaemonInfo,
+    /// The configuration overrides provided by the environment.
+    overrides: Overrides,
+    /// The failures recorded by the test body through [DockerOperations].
+    failures: Arc<Mutex<Vec<String>>>,
+    /// The exclusive locks held from setup until the environment is torn down.
+    exclusive_locks: Vec<ExclusiveLock>,
 }
 
 /// The test body parameter provided in the [DockerTest::run] argument closure.
@@ -70,18 +116,17 @@ pub struct DockerOperations {
     /// We _really_ wish to use a reference somehow here, but cannot easily do so due to
     /// lifetime conflicts. We may want to revisit this architecture decision in the future.
     engine: Engine<Orbiting>,
-}
-
-/// The prune strategy for teardown of containers.
-enum PruneStrategy {
-    /// Always leave the container running
-    RunningRegardless,
-    /// Do not perform any action if the test failed.
-    RunningOnFailure,
-    /// With a stop-only strategy, docker volumes will NOT be pruned.
-    StopOnFailure,
-    /// Prune everything, including named and anonymous volumes.
-    RemoveRegardless,
+    /// The capabilities of the docker daemon.
+    daemon_info: DaemonInfo,
+    /// The JSON report describing the environment after startup.
+    report: String,
+    /// The failures recorded by the test body, shared with the [Runner].
+    failures: Arc<Mutex<Vec<String>>>,
+    /// The handles of the replicated containers, paired with their number of replicas.
+    replica_groups: Vec<(String, usize)>,
+    /// The tls material issued to the containers, if requested.
+    #[cfg(feature = "tls-fixtures")]
+    tls_fixtures: Option<Arc<TlsFixtures>>,
 }
 
 impl DockerOperations {
@@ -120,11 +165,219 @@ impl DockerOperations {
         }
     }
 
-    /// Indicate that this test failed with the accompanied message.
+    /// Iterate over every `RunningContainer`, in the order the containers were provided to the
+    /// [DockerTest].
+    ///
+    /// The order is deterministic regardless of the order the containers are started in, e.g.,
+    /// to connect to the first three of several containers of the same image:
+    ///
+    /// ```rust,no_run
+    /// # use dockertest::DockerOperations;
+    /// # fn brokers(ops: &DockerOperations) {
+    /// let brokers: Vec<String> = ops
+    ///     .containers()
+    ///     .filter(|c| c.handle().starts_with("kafka"))
+    ///     .take(3)
+    ///     .map(|c| c.ip().to_string())
+    ///     .collect();
+    /// # }
+    /// ```
+    pub fn containers(&self) -> impl Iterator<Item = &RunningContainer> {
+        self.engine.running_containers()
+    }
+
+    /// Retrieve every replica of the container replicated through `set_replicas` with the
+    /// handle `handle`, ordered by the index of the replica.
+    ///
+    /// # Panics
+    /// This function panics if no container with the handle is replicated.
+    pub fn replicas(&self, handle: &str) -> Vec<&RunningContainer> {
+        let replicas = match self.replica_groups.iter().find(|(h, _)| h == handle) {
+            Some((_, replicas)) => *replicas,
+            None => {
+                let msg = format!("replicated container with handle '{}' not found", handle);
+                event!(Level::ERROR, "{}", msg);
+                panic!("{}", msg);
+            }
+        };
+        (0..replicas)
+            .map(|index| {
+                let replica = format!("{}-{}", handle, index);
+                match self.engine.resolve_handle(&replica) {
+                    Some(c) => c,
+                    None => {
+                        let msg = format!("replica with handle '{}' not found", replica);
+                        event!(Level::ERROR, "{}", msg);
+                        panic!("{}", msg);
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Retrieve the `RunningContainer` at `index` of [DockerOperations::containers], counting
+    /// from zero in the order the containers were provided to the [DockerTest].
+    ///
+    /// # Panics
+    /// This function panics if the index is out of range.
+    pub fn container(&self, index: usize) -> &RunningContainer {
+        match self.engine.resolve_index(index) {
+            Ok(c) => c,
+            Err(e) => {
+                event!(Level::ERROR, "{}", e.to_string());
+                panic!("{}", e);
+            }
+        }
+    }
+
+    /// A JSON document describing every started container of the environment.
+    ///
+    /// Each container is described by its handle, name, id, image, image id, ip, port mappings,
+    /// the names of its environment variables, and its mounts. The report reflects the state of
+    /// the environment when the test body was entered. The time spent waiting for the exclusive
+    /// locks of [DockerTest::with_exclusive_lock] and the time spent starting the environment
+    /// are reported separately, as `lock_wait_ms` and `startup_ms`.
+    pub fn report(&self) -> &str {
+        &self.report
+    }
+
+    /// Information about the docker daemon the test environment is executed against.
+    pub fn daemon_info(&self) -> &DaemonInfo {
+        &self.daemon_info
+    }
+
+    /// The tls material issued to the containers through [DockerTest::with_tls_fixtures], if any.
+    #[cfg(feature = "tls-fixtures")]
+    pub fn tls_fixtures(&self) -> Option<&TlsFixtures> {
+        self.tls_fixtures.as_deref()
+    }
+
+    /// Shape the network traffic sent by the container identified by `handle`, e.g., adding
+    /// latency and packet loss to test timeouts and retries under realistic WAN conditions.
+    ///
+    /// The traffic is shaped through `tc` by a short-lived helper container with the
+    /// `NET_ADMIN` capability, which joins the network namespace of the container. The shaping
+    /// lasts until it is replaced by another invocation, e.g., with the default
+    /// [TrafficShaping] to remove it, or until the container is restarted.
+    pub async fn shape_traffic(
+        &self,
+        handle: &str,
+        shaping: TrafficShaping,
+    ) -> Result<(), DockerTestError> {
+        let container = self.try_handle(handle)?;
+        traffic::shape(container, &shaping).await
+    }
+
+    /// Offset the clock of the container identified by `handle` by `offset` seconds from now
+    /// on, e.g., to let a certificate expire while the test runs, without restarting the
+    /// container.
+    ///
+    /// The offset replaces the one the container was started with, and must have been faked
+    /// through [TestBodySpecification::set_faked_time]. The clock of every process within the
+    /// container is shifted on its next clock access.
+    ///
+    /// [TestBodySpecification::set_faked_time]: crate::TestBodySpecification::set_faked_time
+    pub async fn set_faked_time(&self, handle: &str, offset: i64) -> Result<(), DockerTestError> {
+        let container = self.try_handle(handle)?;
+        let details = container
+            .client
+            .inspect_container(container.name(), None::<InspectContainerOptions>)
+            .await
+            .map_err(|e| {
+                DockerTestError::Daemon(format!("failed to inspect container `{}`: {}", handle, e))
+            })?;
+        let env = details.config.and_then(|c| c.env).unwrap_or_default();
+        if !faketime::is_faked(&env) {
+            return Err(DockerTestError::TestBody(format!(
+                "container `{}` does not fake its clock, see `set_faked_time`",
+                handle
+            )));
+        }
+        faketime::write(&container.client, container.id(), offset).await
+    }
+
+    /// Await the container identified by `handle` to fulfill the `wait` condition, e.g., to await
+    /// its recovery after restarting it or healing a network partition, through the same
+    /// [WaitFor] implementations used at startup:
+    ///
+    /// ```rust,no_run
+    /// # use dockertest::{DockerOperations, DockerTestError};
+    /// # use dockertest::waitfor::HealthyWait;
+    /// # async fn recover(ops: &DockerOperations) -> Result<(), DockerTestError> {
+    /// let wait = HealthyWait {
+    ///     check_interval: 1,
+    ///     max_checks: 30,
+    /// };
+    /// let db = ops.wait_for("db", wait).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Message based conditions only consider the logs written since the container was last
+    /// started, disregarding those of a run preceding a restart.
+    ///
+    /// The returned container reflects the current ip address and published ports, which may
+    /// have changed since they were cached before entering the test body, see
+    /// [RunningContainer::refresh].
+    pub async fn wait_for<W: WaitFor>(
+        &self,
+        handle: &str,
+        wait: W,
+    ) -> Result<RunningContainer, DockerTestError> {
+        let container = self.try_handle(handle)?;
+        let details = container
+            .client
+            .inspect_container(container.name(), None::<InspectContainerOptions>)
+            .await
+            .map_err(|e| {
+                DockerTestError::Daemon(format!("failed to inspect container `{}`: {}", handle, e))
+            })?;
+        let mut pending = PendingContainer::from(container);
+        pending.logs_since = details
+            .state
+            .and_then(|state| state.started_at)
+            .and_then(|started_at| Timestamp::parse(&started_at));
+        wait.wait_for_ready(pending).await?;
+
+        let mut container = container.clone();
+        container.refresh().await?;
+        Ok(container)
+    }
+
+    /// Indicate that this test failed with the accompanied message, and abort the test body.
+    ///
+    /// The failure is recorded like [fail_with](DockerOperations::fail_with) before panicking.
     pub fn failure(&self, msg: &str) {
-        event!(Level::ERROR, "test failure: {}", msg);
+        self.record_failure(msg.to_string());
         panic!("test failure: {}", msg);
     }
+
+    /// Indicate that this test failed with the accompanied error, without aborting the test body.
+    ///
+    /// The error, including its chain of sources, is reported along with the state of the
+    /// environment. The test fails once the test body completes, with all recorded failures
+    /// returned as a [DockerTestError::TestBody] error.
+    pub fn fail_with<E>(&self, error: E)
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let error = error.into();
+        let mut reason = error.to_string();
+        let mut source = error.source();
+        while let Some(cause) = source {
+            reason.push_str(&format!(": {}", cause));
+            source = cause.source();
+        }
+        self.record_failure(reason);
+    }
+
+    fn record_failure(&self, reason: String) {
+        event!(Level::ERROR, "test failure: {}", reason);
+        self.failures
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(reason);
+    }
 }
 
 impl Runner {
@@ -137,10 +390,34 @@ impl Runner {
         Self::try_new(config).await.unwrap()
     }
 
-    /// Creates a new DockerTest [Runner]. Returns error on Docker daemon connection failure.
-    pub async fn try_new(config: DockerTest) -> Result<Runner, DockerTestError> {
-        let client = connect_with_local_or_tls_defaults()?;
-        let id = generate_random_string(20);
+    /// Creates a new DockerTest [Runner]. Returns error on an invalid environment
+    /// configuration, or on Docker daemon connection failure.
+    ///
+    /// Tasks are spawned onto the runtime configured through [DockerTest::with_runtime_handle],
+    /// falling back to the runtime this is invoked within.
+    pub async fn try_new(mut config: DockerTest) -> Result<Runner, DockerTestError> {
+        let overrides = Overrides::from_env();
+        overrides.apply(&mut config);
+        config.validate()?;
+        if config.suite_summary {
+            suite_summary::enable();
+        }
+        let runtime = config.runtime.clone().unwrap_or_else(Handle::current);
+
+        let client = if config.shared_client {
+            shared_client()?
+        } else {
+            connect_with_local_or_tls_defaults()?
+        };
+        daemon::await_engine(&client, config.engine_wait).await?;
+        let client = daemon::negotiate_api_version(client).await?;
+        let daemon_info = DaemonInfo::query(&client).await?;
+        let (major, minor) = daemon::MINIMUM_API_VERSION;
+        daemon_info.require_api_version(major, minor, "dockertest")?;
+        let id = config.id_generator.generate();
+
+        // If we are inside a container, we need to retrieve our container ID.
+        config.container_id = resolve_own_container_id(&client).await;
 
         let network = match &config.network {
             Network::External(n) => n.clone(),
@@ -152,8 +429,19 @@ impl Runner {
                 SCOPED_NETWORKS
                     .create_singular_network(
                         &client,
-                        own_container_id().as_deref(),
+                        config.container_id.as_deref(),
+                        &config.namespace,
+                        config.address_pool.as_ref(),
+                    )
+                    .await?
+            }
+            Network::Shared => {
+                SCOPED_NETWORKS
+                    .acquire_shared_network(
+                        &client,
+                        config.container_id.as_deref(),
                         &config.namespace,
+                        config.address_pool.as_ref(),
                     )
                     .await?
             }
@@ -165,33 +453,165 @@ impl Runner {
             network,
             id,
             config,
+            engine: None,
+            runtime,
+            daemon_info,
+            overrides,
+            failures: Arc::new(Mutex::new(Vec::new())),
+            exclusive_locks: Vec::new(),
         })
     }
 
-    /// Internal impl of the public `run` method, to catch internal panics
-    pub async fn run_impl<T, Fut>(mut self, test: T) -> Result<(), DockerTestError>
+    /// Execute the test body within the environment, tearing it down afterwards.
+    ///
+    /// This performs [setup](Runner::setup), invokes the test body, and performs
+    /// [teardown](Runner::teardown), returning its [TeardownReport]. A panic within the test
+    /// body is resumed after teardown.
+    pub async fn run<T, Fut>(mut self, test: T) -> Result<TeardownReport, DockerTestError>
     where
         T: FnOnce(DockerOperations) -> Fut,
         Fut: Future<Output = ()> + Send + 'static,
     {
-        // If we are inside a container, we need to retrieve our container ID.
-        self.check_if_inside_container();
+        let ops = self.setup().await?;
+
+        // Run test body
+        let result: Result<(), Option<Box<dyn Any + Send + 'static>>> =
+            match self.runtime.spawn(test(ops)).await {
+                Ok(_) => {
+                    event!(Level::DEBUG, "test body success");
+                    Ok(())
+                }
+                Err(e) => {
+                    // Test failed
+                    event!(
+                        Level::DEBUG,
+                        "test body failed (cancelled: {}, panicked: {})",
+                        e.is_cancelled(),
+                        e.is_panic()
+                    );
+                    Err(e.try_into_panic().ok())
+                }
+            };
+
+        // Failures recorded by the test body fail the test, even if the test body completed.
+        let failures = self.recorded_failures();
+        let failed = result.is_err() || !failures.is_empty();
+
+        // A lost connection to the daemon is the root cause of any failure of the test body.
+        let connection = self.verify_daemon_connection().await;
+        if failed && connection.is_ok() {
+            // Provide context for the failure, while the containers are still present.
+            let summary = self.failure_summary().await;
+            error!("{}", summary);
+        }
+
+        let report = self.teardown(failed || connection.is_err()).await;
+
+        if let Err(e) = connection {
+            if failed {
+                error!("test body failed while the connection to the docker daemon was lost");
+            }
+            return Err(e);
+        }
+        if !failures.is_empty() {
+            return Err(DockerTestError::TestBody(failures.join(", ")));
+        }
+        if let Err(option) = result {
+            match option {
+                Some(panic) => panic::resume_unwind(panic),
+                None => panic!("test future cancelled"),
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Create and start all containers of the environment, returning the [DockerOperations]
+    /// to interact with them.
+    ///
+    /// If any container fails to start, everything created thus far is torn down before the
+    /// error is returned. On success, the caller is responsible for invoking
+    /// [teardown](Runner::teardown) once the environment is no longer needed.
+    ///
+    /// An environment can only be set up once per [Runner].
+    pub async fn setup(&mut self) -> Result<DockerOperations, DockerTestError> {
+        if self.engine.is_some() {
+            return Err(DockerTestError::Startup(
+                "the environment of this runner has already been set up".to_string(),
+            ));
+        }
+
+        // Serialize with tests contending for the same host resources before allocating any,
+        // such that the time spent waiting is not attributed to the startup.
+        let (locks, lock_wait) = exclusive_lock::acquire_all(&self.config.exclusive_locks).await?;
+        if !locks.is_empty() {
+            event!(
+                Level::INFO,
+                "waited {:?} for exclusive locks {:?}",
+                lock_wait,
+                self.config.exclusive_locks
+            );
+        }
+        self.exclusive_locks = locks;
+        let started = Instant::now();
 
         // Before constructing the compositions, we ensure that all configured
         // docker volumes have been created.
         self.resolve_named_volumes().await?;
 
-        let compositions = std::mem::take(&mut self.config.compositions);
+        let mut compositions = std::mem::take(&mut self.config.compositions);
+        self.config
+            .prepare_compositions(&mut compositions, &self.overrides);
+        if self.config.janitor {
+            janitor::ensure(&self.client, self.config.container_id.as_deref()).await?;
+        }
+        let hash = shared_environment::environment_hash(&compositions);
+        shared_environment::label(&hash, &mut compositions);
+        if self.config.shared_environment {
+            shared_environment::share(
+                &self.client,
+                &self.config.namespace,
+                &hash,
+                &mut compositions,
+            )
+            .await;
+        }
+        janitor::label(&mut compositions);
+        discovery::label(&self.id, &self.config.namespace, &mut compositions);
+        self.check_daemon_capabilities(&compositions)?;
+        self.check_injected_network_aliases(&compositions);
+        self.adapt_to_user_namespace(&mut compositions).await?;
+        let resets: Vec<(String, Box<dyn Resettable>)> = compositions
+            .iter()
+            .filter_map(|c| Some((c.handle(), c.reset()?)))
+            .collect();
+        let images: Vec<String> = compositions.iter().map(|c| c.image().reference()).collect();
         let mut engine = bootstrap(compositions);
-        engine.resolve_final_container_name(&self.config.namespace);
+        engine.resolve_final_container_name(
+            &self.config.container_name_prefix(),
+            &self.config.id_generator,
+        );
 
         let mut engine = engine.fuel();
         engine.resolve_inject_container_name_env()?;
+        engine.resolve_inject_network_alias_env()?;
+        engine.resolve_inject_host_port_env()?;
+        engine.resolve_env_templates()?;
+        let pulling = Instant::now();
         engine
             .pull_images(&self.client, &self.config.default_source)
             .await?;
+        let pull = pulling.elapsed();
 
         self.resolve_network().await?;
+        #[cfg(feature = "tls-fixtures")]
+        let tls_fixtures = match self.provision_tls_fixtures(&mut engine).await {
+            Ok(fixtures) => fixtures,
+            Err(e) => {
+                self.teardown_network().await;
+                return Err(e);
+            }
+        };
 
         // Create PendingContainers from the Compositions
         let engine = match engine
@@ -212,7 +632,7 @@ impl Runner {
                         error!("{err}");
                     }
                 }
-                self.teardown(engine, false).await;
+                self.teardown_engine(engine, false).await;
 
                 // QUESTION: What is the best option for us to propagate multiple errors?
                 return Err(creation_failures
@@ -222,7 +642,14 @@ impl Runner {
         };
 
         // Ensure we drive all the waitfor conditions to completion when we start the containers
-        let mut engine = match engine.orbiting().await {
+        let mut engine = match engine
+            .orbiting(
+                &self.runtime,
+                self.config.startup_timeout,
+                self.config.start_concurrency,
+            )
+            .await
+        {
             Ok(e) => e,
             Err((engine, e)) => {
                 // Teardown everything on error
@@ -232,7 +659,7 @@ impl Runner {
                         error!("{err}");
                     }
                 }
-                self.teardown(engine, false).await;
+                self.teardown_engine(engine, false).await;
 
                 return Err(e);
             }
@@ -247,11 +674,23 @@ impl Runner {
         // containers are connected to.
         let network_name = match self.config.network {
             Network::Singular => SCOPED_NETWORKS.name(&self.config.namespace),
-            Network::External(_) | Network::Isolated => self.network.clone(),
+            Network::External(_) | Network::Isolated | Network::Shared => self.network.clone(),
         };
 
+        // The container of dockertest itself is attached to every network but external ones.
+        let on_network = self.config.container_id.is_some()
+            && !matches!(self.config.network, Network::External(_));
+
         // Run container inspection to get up-to-date runtime information
-        if let Err(mut errors) = engine.inspect(&self.client, &network_name).await {
+        if let Err(mut errors) = engine
+            .inspect(
+                &self.client,
+                &network_name,
+                on_network,
+                &self.config.retry_policy,
+            )
+            .await
+        {
             let total = errors.len();
             errors.iter().enumerate().for_each(|(i, e)| {
                 trace!("container {} of {} inspect failures: {}", i + 1, total, e);
@@ -259,7 +698,7 @@ impl Runner {
 
             // Teardown everything on error
             let engine = engine.decommission();
-            self.teardown(engine, false).await;
+            self.teardown_engine(engine, false).await;
 
             // QUESTION: What is the best option for us to propagate multiple errors?
             return Err(errors
@@ -267,90 +706,560 @@ impl Runner {
                 .expect("dockertest bug: cleanup path expected container inspect error"));
         };
 
+        // Reset the state left behind by earlier tests in containers outliving them
+        for (handle, reset) in resets.iter() {
+            let result = match engine.resolve_handle(handle) {
+                Some(container) => reset.reset(container).await,
+                None => continue,
+            };
+            if let Err(e) = result {
+                let engine = engine.decommission();
+                self.teardown_engine(engine, false).await;
+                return Err(DockerTestError::Startup(format!(
+                    "failed to reset container `{}`: {}",
+                    handle, e
+                )));
+            }
+        }
+
+        // Containers may have died since they became ready
+        if self.config.liveness_check {
+            if let Err(e) = self.verify_liveness(&engine).await {
+                let engine = engine.decommission();
+                self.teardown_engine(engine, false).await;
+                return Err(e);
+            }
+        }
+
+        let startup = started.elapsed();
+        event!(Level::INFO, "started the environment in {:?}", startup);
+        suite_summary::record_startup(&images, pull, startup);
+
+        let report = self.environment_report(&engine, &network_name, lock_wait, startup);
+        if let Some(path) = &self.config.report_path {
+            match std::fs::write(path, &report) {
+                Ok(_) => event!(Level::DEBUG, "wrote environment report to {:?}", path),
+                Err(e) => event!(
+                    Level::WARN,
+                    "failed to write environment report to {:?}: {}",
+                    path,
+                    e
+                ),
+            }
+        }
+
         // We are ready to invoke the test body now
         let ops = DockerOperations {
             engine: engine.clone(),
+            daemon_info: self.daemon_info.clone(),
+            report,
+            failures: self.failures.clone(),
+            replica_groups: self.config.replica_groups.clone(),
+            #[cfg(feature = "tls-fixtures")]
+            tls_fixtures,
         };
+        self.engine = Some(engine);
 
-        // Run test body
-        let result: Result<(), Option<Box<dyn Any + Send + 'static>>> =
-            match tokio::spawn(test(ops)).await {
-                Ok(_) => {
-                    event!(Level::DEBUG, "test body success");
-                    Ok(())
-                }
-                Err(e) => {
-                    // Test failed
-                    event!(
-                        Level::DEBUG,
-                        "test body failed (cancelled: {}, panicked: {})",
-                        e.is_cancelled(),
-                        e.is_panic()
-                    );
-                    Err(e.try_into_panic().ok())
-                }
-            };
+        Ok(ops)
+    }
+
+    /// Teardown the environment established by [setup](Runner::setup), in accordance with the
+    /// prune strategy.
+    ///
+    /// `test_failed` indicates whether the environment should be treated as the environment of
+    /// a failed test, which determines how logs are handled and containers are pruned.
+    /// This is a no-op if the environment was never set up.
+    ///
+    /// With [DockerTest::with_background_teardown], this returns once logs are handled, while
+    /// the containers are pruned in the background.
+    ///
+    /// Returns the final state of every container, captured before the containers are removed,
+    /// which is empty if the environment was never set up. The containers are only stopped
+    /// beforehand with [DockerTest::with_graceful_stop], and are otherwise captured as running.
+    pub async fn teardown(mut self, test_failed: bool) -> TeardownReport {
+        let engine = match self.engine.take() {
+            Some(engine) => engine,
+            None => return TeardownReport::default(),
+        };
 
         let engine = engine.decommission();
-        if let Err(errors) = engine.handle_logs(result.is_err()).await {
+
+        // The containers are only stopped before the report is captured and the logs are
+        // handled when asked to, as a graceful stop awaits the shutdown of every container.
+        let graceful = self.config.graceful_stop.is_some();
+        if graceful {
+            self.stop_engine(&engine, test_failed).await;
+        }
+        let report = engine.exit_report(&self.client).await;
+        if let Err(errors) = engine.handle_logs(test_failed, graceful).await {
             for err in errors {
                 error!("{err}");
             }
         }
-        self.teardown(engine, result.is_err()).await;
 
-        if let Err(option) = result {
-            match option {
-                Some(panic) => panic::resume_unwind(panic),
-                None => panic!("test future cancelled"),
+        // The runner is moved into the background teardown.
+        let hook = self.config.teardown_hook.clone();
+        if self.config.background_teardown {
+            spawn_teardown(async move {
+                // The connections of the client are driven by the runtime of the test,
+                // which may be dropped before the teardown completes.
+                match connect_with_local_or_tls_defaults() {
+                    Ok(client) => self.client = client,
+                    Err(e) => event!(Level::WARN, "background teardown reuses client: {}", e),
+                }
+                if graceful {
+                    self.prune_engine(engine, test_failed).await;
+                } else {
+                    self.teardown_engine(engine, test_failed).await;
+                }
+            });
+        } else if graceful {
+            self.prune_engine(engine, test_failed).await;
+        } else {
+            self.teardown_engine(engine, test_failed).await;
+        }
+
+        if let Some(hook) = &hook {
+            hook.invoke(&report);
+        }
+        report
+    }
+
+    /// Verify that every container is still alive, see [DockerTest::with_liveness_check].
+    async fn verify_liveness(&self, engine: &Engine<Orbiting>) -> Result<(), DockerTestError> {
+        let checks = engine.running_containers().map(|container| async move {
+            let alive = is_alive(&self.client, container).await;
+            (container, alive)
+        });
+
+        let mut failures = Vec::new();
+        for (container, alive) in join_all(checks).await {
+            if alive {
+                continue;
+            }
+            let mut failure = format!(
+                "container `{}` died before the test body: {}",
+                container.handle(),
+                container_state(&self.client, container).await
+            );
+            if let Some(logs) = recent_logs(&self.client, container.name()).await {
+                failure.push_str(&format!(
+                    "\n--- last log lines of container `{}` ---\n{}",
+                    container.name(),
+                    container.redactions.redact(&logs)
+                ));
+            }
+            failures.push(failure);
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(DockerTestError::Startup(failures.join("\n")))
+        }
+    }
+
+    /// Verify that the docker daemon remained reachable throughout the test body.
+    ///
+    /// If the connection was lost, e.g., as the daemon restarted, reconnect and verify that every
+    /// container of the environment still exists. The environment is abandoned if the daemon
+    /// cannot be reached, as nothing can be torn down without it.
+    async fn verify_daemon_connection(&mut self) -> Result<(), DockerTestError> {
+        match self.client.ping().await {
+            Ok(_) => return Ok(()),
+            Err(e) => event!(
+                Level::WARN,
+                "lost the connection to the docker daemon: {}",
+                e
+            ),
+        }
+
+        let client = connect_with_local_or_tls_defaults()?;
+        let reconnected = self
+            .config
+            .reconnect_policy
+            .retry_if("reconnect to the docker daemon", |_| true, || client.ping())
+            .await;
+        if let Err(e) = reconnected {
+            self.engine = None;
+            return Err(DockerTestError::EnvironmentLost(format!(
+                "unable to reconnect to the docker daemon: {}",
+                e
+            )));
+        }
+        self.client = client;
+
+        let engine = match &self.engine {
+            Some(engine) => engine,
+            None => return Ok(()),
+        };
+        let client = &self.client;
+        let inspections = engine.running_containers().map(|c| async move {
+            let details = client
+                .inspect_container(&c.id, None::<InspectContainerOptions>)
+                .await;
+            (c, details)
+        });
+        // The state of the containers is compared to the state recorded before the test body.
+        let lost: Vec<String> = join_all(inspections)
+            .await
+            .into_iter()
+            .filter_map(|(c, details)| {
+                let lost = match details {
+                    Ok(details) => c.lost_state(&details)?,
+                    Err(e) if is_not_found(&e) => "no longer exists",
+                    Err(_) => return None,
+                };
+                Some(format!("`{}` {}", c.handle, lost))
+            })
+            .collect();
+
+        if lost.is_empty() {
+            event!(Level::INFO, "reconnected to the docker daemon");
+            Ok(())
+        } else {
+            Err(DockerTestError::EnvironmentLost(format!(
+                "the docker daemon restarted during the test body, and container {}",
+                lost.join(", ")
+            )))
+        }
+    }
+
+    /// The failures recorded by the test body through [DockerOperations].
+    fn recorded_failures(&self) -> Vec<String> {
+        self.failures
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Summarize the state of every container of the environment, for a failed test body.
+    async fn failure_summary(&self) -> String {
+        let engine = match &self.engine {
+            Some(engine) => engine,
+            None => return "dockertest environment was not set up".to_string(),
+        };
+
+        let states = join_all(
+            engine
+                .running_containers()
+                .map(|c| container_state(&self.client, c)),
+        )
+        .await;
+
+        let mut summary = format!(
+            "dockertest environment `{}` at test body failure:",
+            self.config.namespace
+        );
+        for (container, state) in engine.running_containers().zip(states) {
+            summary.push_str(&format!("\n  - {}: {}", container, state));
+        }
+        for failure in self.recorded_failures() {
+            summary.push_str(&format!("\n  failure: {}", failure));
+        }
+
+        summary
+    }
+
+    /// Describe the started environment as a JSON document.
+    fn environment_report(
+        &self,
+        engine: &Engine<Orbiting>,
+        network_name: &str,
+        lock_wait: Duration,
+        startup: Duration,
+    ) -> String {
+        let containers: Vec<serde_json::Value> =
+            engine.running_containers().map(|c| c.report()).collect();
+        let report = serde_json::json!({
+            "namespace": self.config.namespace,
+            "network": network_name,
+            "lock_wait_ms": lock_wait.as_millis() as u64,
+            "startup_ms": startup.as_millis() as u64,
+            "containers": containers,
+        });
+
+        serde_json::to_string_pretty(&report)
+            .expect("dockertest bug: environment report is always valid json")
+    }
+
+    /// Reject compositions that require capabilities the docker daemon lacks, before any
+    /// container is created.
+    fn check_daemon_capabilities(
+        &self,
+        compositions: &[Composition],
+    ) -> Result<(), DockerTestError> {
+        for c in compositions.iter() {
+            if c.privileged {
+                self.daemon_info
+                    .require_linux(&format!("privileged container `{}`", c.handle()))?;
+            }
+            if c.cgroup_parent.is_some() {
+                self.daemon_info
+                    .require_linux(&format!("the cgroup parent of container `{}`", c.handle()))?;
+            }
+            if matches!(c.isolation, Some(Isolation::Process | Isolation::HyperV)) {
+                self.daemon_info
+                    .require_windows(&format!("the isolation of container `{}`", c.handle()))?;
+            }
+            if !c.named_pipes.is_empty() {
+                self.daemon_info
+                    .require_windows(&format!("the named pipes of container `{}`", c.handle()))?;
+            }
+            if c.image().configured_platform().is_some() {
+                self.daemon_info.require_api_version(
+                    1,
+                    41,
+                    &format!("the image platform of container `{}`", c.handle()),
+                )?;
             }
         }
 
         Ok(())
     }
 
-    /// Checks if we are inside a container, and if so sets our container ID.
-    /// The user of dockertest is responsible for setting these env variables.
-    fn check_if_inside_container(&mut self) {
-        if let Some(id) = own_container_id() {
-            event!(
-                Level::TRACE,
-                "dockertest container id env is set, we are running inside a container, id: {}",
-                id
-            );
-            self.config.container_id = Some(id);
+    /// Warn about network aliases injected on a network shared with other tests, on which the
+    /// alias resolves to the containers of every test using it, see
+    /// [Composition::inject_network_alias].
+    fn check_injected_network_aliases(&self, compositions: &[Composition]) {
+        if matches!(self.config.network, Network::Isolated) {
+            return;
+        }
+        for c in compositions {
+            for (handle, _) in c.inject_network_alias_env.iter() {
+                event!(
+                    Level::WARN,
+                    "container `{}` is injected the network alias of `{}` on a network shared \
+                     with other tests, on which the alias may resolve to their containers, use \
+                     `Network::Isolated` for tests executing in parallel",
+                    c.handle(),
+                    handle
+                );
+            }
+        }
+    }
+
+    /// Adapt the compositions to a docker daemon executing containers within a user namespace,
+    /// i.e., a rootless daemon or a daemon remapping the users of containers.
+    ///
+    /// Privileged containers of a remapping daemon are rejected, unless they opt into joining the
+    /// user namespace of the host, as the daemon rejects them otherwise. Options unsupported
+    /// within the user namespace, i.e., the cgroup parent of a rootless daemon without cgroup v2
+    /// and sysctls outside of the network namespace, are skipped. The owner of writable bind
+    /// mounted host paths is changed as configured through [Composition::bind_mount_owner].
+    /// Options that are likely to fail within the user namespace are reported as warnings.
+    async fn adapt_to_user_namespace(
+        &self,
+        compositions: &mut [Composition],
+    ) -> Result<(), DockerTestError> {
+        let daemon = &self.daemon_info;
+        let mode = if daemon.is_rootless() {
+            "a rootless docker daemon"
+        } else if daemon.is_userns_remapped() {
+            "a docker daemon remapping the users of containers"
         } else {
-            event!(
-                Level::TRACE,
-                "dockertest container id env is not set, running native on host"
-            );
+            return Ok(());
+        };
+        event!(Level::DEBUG, "executing containers through {}", mode);
+
+        for c in compositions.iter_mut() {
+            if c.static_management_policy() == &Some(StaticManagementPolicy::External) {
+                continue;
+            }
+            c.user_namespaced = !c.userns_host;
+
+            if c.privileged && daemon.is_userns_remapped() && !c.userns_host {
+                return Err(DockerTestError::Unsupported(format!(
+                    "privileged container `{}` requires joining the user namespace of the host \
+                     with {}, opt in through `host_user_namespace`",
+                    c.handle(),
+                    mode
+                )));
+            } else if c.privileged && daemon.is_rootless() {
+                event!(
+                    Level::WARN,
+                    "privileged container `{}` is only granted the privileges of the user \
+                     executing {}",
+                    c.handle(),
+                    mode
+                );
+            }
+
+            if daemon.is_rootless() && daemon.cgroup_version() != Some("2") {
+                if let Some(parent) = c.cgroup_parent.take() {
+                    event!(
+                        Level::WARN,
+                        "skipping the cgroup parent `{}` of container `{}`, as {} only manages \
+                         cgroups with cgroup v2",
+                        parent,
+                        c.handle(),
+                        mode
+                    );
+                }
+            }
+
+            if daemon.is_rootless() {
+                for (exposed, host) in c.mapped_host_ports() {
+                    if matches!(host.parse::<u16>(), Ok(port) if port != 0 && port < 1024) {
+                        event!(
+                            Level::WARN,
+                            "container `{}` publishes port {} on the privileged host port {}, \
+                             which {} is unable to bind unless permitted by \
+                             `net.ipv4.ip_unprivileged_port_start`",
+                            c.handle(),
+                            exposed,
+                            host,
+                            mode
+                        );
+                    }
+                }
+            }
+
+            // Read-only bind mounts are never written to by the container.
+            let paths: Vec<String> = c
+                .bind_mounts
+                .iter()
+                .filter(|(_, _, options)| !options.read_only)
+                .map(|(host, _, _)| host.clone())
+                .collect();
+            if paths.is_empty() {
+                continue;
+            }
+            match c.bind_mount_owner {
+                Some(owner) => {
+                    userns::chown(&self.client, daemon, &c.handle(), &paths, owner).await?;
+                }
+                None => event!(
+                    Level::WARN,
+                    "the bind mounts of container `{}` may not be writable by its users with {}, \
+                     configure their owner through `modify_bind_mount_owner`",
+                    c.handle(),
+                    mode
+                ),
+            }
         }
+
+        Ok(())
     }
 
     async fn resolve_network(&self) -> Result<(), DockerTestError> {
+        // The tls fixtures pin the containers to addresses of the network.
+        #[cfg(feature = "tls-fixtures")]
+        let fixed_subnet = !self.config.tls_fixtures.is_empty();
+        #[cfg(not(feature = "tls-fixtures"))]
+        let fixed_subnet = false;
+
         match &self.config.network {
-            // Singular network is created during runner creation.
+            // Singular and shared networks are created during runner creation.
             // External network is created externally.
-            Network::Singular | Network::External(_) => Ok(()),
+            Network::Singular | Network::Shared | Network::External(_) => Ok(()),
             Network::Isolated => {
                 create_network(
                     &self.client,
                     &self.network,
                     self.config.container_id.as_deref(),
+                    self.config.address_pool.as_ref(),
+                    fixed_subnet,
                 )
                 .await
             }
         }
     }
 
+    /// Issue the tls fixtures requested through [DockerTest::with_tls_fixtures], if any, pinning
+    /// the containers to addresses of the subnet of the network.
+    #[cfg(feature = "tls-fixtures")]
+    async fn provision_tls_fixtures(
+        &self,
+        engine: &mut Engine<crate::engine::Fueling>,
+    ) -> Result<Option<Arc<TlsFixtures>>, DockerTestError> {
+        if self.config.tls_fixtures.is_empty() {
+            return Ok(None);
+        }
+
+        let network = self
+            .client
+            .inspect_network(&self.network, None::<InspectNetworkOptions<String>>)
+            .await
+            .map_err(|e| {
+                DockerTestError::Startup(format!(
+                    "failed to inspect network `{}`: {}",
+                    self.network, e
+                ))
+            })?;
+        let subnet = network
+            .ipam
+            .and_then(|ipam| ipam.config)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|config| config.subnet)
+            .find(|subnet| subnet.contains('.'))
+            .ok_or_else(|| {
+                DockerTestError::Startup(format!(
+                    "network `{}` has no ipv4 subnet to pin the tls fixtures to",
+                    self.network
+                ))
+            })?;
+
+        let fixtures = engine.provision_tls_fixtures(&self.config.tls_fixtures, &subnet)?;
+        Ok(Some(Arc::new(fixtures)))
+    }
+
     /// Teardown everything this test created, in accordance with the prune strategy.
-    async fn teardown(&self, engine: Engine<Debris>, test_failed: bool) {
+    async fn teardown_engine(&self, engine: Engine<Debris>, test_failed: bool) {
+        self.stop_engine(&engine, test_failed).await;
+        self.prune_engine(engine, test_failed).await;
+    }
+
+    /// Stop the containers not left running by the prune strategy.
+    ///
+    /// Containers to be removed are only stopped beforehand if the stop is graceful, or the
+    /// teardown is ordered, as they are otherwise killed by their forced removal.
+    async fn stop_engine(&self, engine: &Engine<Debris>, test_failed: bool) {
+        let stop = match self.prune_strategy() {
+            PruneStrategy::RunningRegardless => false,
+            PruneStrategy::RunningOnFailure => !test_failed,
+            PruneStrategy::StopOnFailure if test_failed => true,
+            PruneStrategy::StopOnFailure | PruneStrategy::RemoveRegardless => {
+                self.config.graceful_stop.is_some() || self.config.ordered_teardown
+            }
+        };
+        if !stop {
+            return;
+        }
+
+        if self.config.ordered_teardown {
+            event!(
+                Level::DEBUG,
+                "stopping all containers in reverse dependency order"
+            );
+            engine
+                .stop_containers_in_order(&self.client, self.config.graceful_stop)
+                .await;
+        } else {
+            engine
+                .stop_containers(&self.client, self.config.graceful_stop)
+                .await;
+        }
+    }
+
+    /// The prune strategy of the test, as overridden by the environment.
+    fn prune_strategy(&self) -> PruneStrategy {
+        self.overrides
+            .prune
+            .clone()
+            .unwrap_or(PruneStrategy::RemoveRegardless)
+    }
+
+    /// Prune the containers, networks and volumes this test created, in accordance with the
+    /// prune strategy, once the containers are stopped by [stop_engine](Runner::stop_engine).
+    async fn prune_engine(&self, engine: Engine<Debris>, test_failed: bool) {
         // Ensure we cleanup static container regardless of prune strategy
         engine
             .disconnect_static_containers(&self.client, &self.network, &self.config.network)
             .await;
 
-        match env_prune_strategy() {
+        match self.prune_strategy() {
             PruneStrategy::RunningRegardless => {
                 event!(
                     Level::DEBUG,
@@ -368,7 +1277,6 @@ impl Runner {
             // We only stop, and do not remove, if test failed and our strategy
             // tells us to do so.
             PruneStrategy::StopOnFailure if test_failed => {
-                engine.stop_containers(&self.client).await;
                 self.teardown_network().await;
             }
 
@@ -383,60 +1291,53 @@ impl Runner {
                 // We therefore run the container remove futures to completion before trying to remove
                 // volumes. We will not be able to remove volumes if the associated container was not
                 // removed successfully.
-                engine.remove_containers(&self.client).await;
+                let leaked_containers = engine
+                    .remove_containers(&self.client, &self.config.retry_policy)
+                    .await;
                 self.teardown_network().await;
 
-                self.remove_volumes().await;
+                let leaked_volumes = self.remove_volumes().await;
+                suite_summary::record_leaked(leaked_containers, leaked_volumes);
             }
         }
     }
 
-    async fn remove_volumes(&self) {
-        join_all(
+    /// Remove the named volumes, retrying while they are still in use by containers whose
+    /// removal has not yet completed. Returns the number of volumes that could not be removed.
+    async fn remove_volumes(&self) -> usize {
+        let removed = join_all(
             self.named_volumes
                 .iter()
-                .map(|v| {
+                .map(|v| async move {
                     event!(Level::INFO, "removing named volume: {:?}", &v);
-                    let options = Some(RemoveVolumeOptions { force: true });
-                    self.client.remove_volume(v, options)
+                    let result = self
+                        .config
+                        .retry_policy
+                        .retry_removal("remove volume", || {
+                            let options = Some(RemoveVolumeOptions { force: true });
+                            self.client.remove_volume(v, options)
+                        })
+                        .await;
+                    match result {
+                        Ok(_) => true,
+                        Err(e) if is_not_found(&e) => true,
+                        Err(e) => {
+                            event!(Level::WARN, "failed to remove volume `{}`: {}", v, e);
+                            false
+                        }
+                    }
                 })
                 .collect::<Vec<_>>(),
         )
         .await;
+        removed.into_iter().filter(|removed| !removed).count()
     }
 
     // Determines the final name for all named volumes, and modifies the Compositions accordingly.
     // Named volumes will have the following form: "USER_PROVIDED_VOLUME_NAME-DOCKERTEST_ID:PATH_IN_CONTAINER".
     async fn resolve_named_volumes(&mut self) -> Result<(), DockerTestError> {
-        // Maps the original volume name to the suffixed ones
-        // Key: "USER_PROVIDED_VOLUME_NAME"
-        // Value: "USER_PROVIDED_VOLUME_NAME-DOCKERTEST_ID"
-        let mut volume_name_map: HashMap<String, String> = HashMap::new();
-
-        let suffix = self.id.clone();
-
-        // Add the dockertest ID as a suffix to all named volume names.
-        self.config.compositions.iter_mut().for_each(|c| {
-            // Includes path aswell: "USER_PROVIDED_VOLUME_NAME-DOCKERTEST_ID:PATH_IN_CONTAINER"
-            let mut volume_names_with_path: Vec<String> = Vec::new();
-
-            c.named_volumes.iter().for_each(|(id, path)| {
-                if let Some(suffixed_name) = volume_name_map.get(id) {
-                    volume_names_with_path.push(format!("{}:{}", &suffixed_name, &path));
-                } else {
-                    let volume_name_with_path = format!("{}-{}:{}", id, &suffix, path);
-                    volume_names_with_path.push(volume_name_with_path);
-
-                    let suffixed_volume_name = format!("{}-{}", id, &suffix);
-                    volume_name_map.insert(id.to_string(), suffixed_volume_name);
-                }
-            });
-
-            c.final_named_volume_names = volume_names_with_path;
-        });
-
         // Add all the suffixed volumes names to dockertest such that we can clean them up later.
-        self.named_volumes = volume_name_map.drain().map(|(_k, v)| v).collect();
+        self.named_volumes = suffix_named_volumes(&mut self.config.compositions, &self.id);
 
         event!(
             Level::DEBUG,
@@ -452,6 +1353,11 @@ impl Runner {
             // The singular network should never be deleted
             Network::Singular => (),
             Network::External(_) => (),
+            Network::Shared => {
+                SCOPED_NETWORKS
+                    .release_shared_network(&self.client, &self.config.namespace)
+                    .await
+            }
             Network::Isolated => {
                 delete_network(
                     &self.client,
@@ -464,27 +1370,110 @@ impl Runner {
     }
 }
 
-fn own_container_id() -> Option<String> {
-    std::env::var("DOCKERTEST_CONTAINER_ID_INJECT_TO_NETWORK").ok()
+/// Whether the container is running, not unhealthy and never restarted, or, if expected to exit,
+/// exited successfully.
+///
+/// A container that cannot be inspected is considered alive, leaving the failure to the test body.
+async fn is_alive(client: &Docker, container: &RunningContainer) -> bool {
+    let details = match client
+        .inspect_container(container.name(), None::<InspectContainerOptions>)
+        .await
+    {
+        Ok(details) => details,
+        Err(e) => {
+            event!(
+                Level::WARN,
+                "unable to inspect container `{}` for liveness: {}",
+                container.handle(),
+                e
+            );
+            return true;
+        }
+    };
+
+    let state = match details.state {
+        Some(state) => state,
+        None => return true,
+    };
+    // Long-running dependencies restarted by their restart policy may lose state.
+    let restarted = details.restart_count.unwrap_or(0) > 0;
+    match state.status {
+        Some(ContainerStateStatusEnum::RUNNING) => {
+            (container.expect_exit || !restarted)
+                && state.health.and_then(|h| h.status) != Some(HealthStatusEnum::UNHEALTHY)
+        }
+        Some(ContainerStateStatusEnum::EXITED) => {
+            container.expect_exit && state.exit_code == Some(0)
+        }
+        Some(ContainerStateStatusEnum::DEAD) | Some(ContainerStateStatusEnum::RESTARTING) => false,
+        _ => true,
+    }
 }
 
-/// Resolve the current prune strategy, provided by the environment.
-fn env_prune_strategy() -> PruneStrategy {
-    match std::env::var_os("DOCKERTEST_PRUNE") {
-        Some(val) => match val.to_string_lossy().to_lowercase().as_str() {
-            "stop_on_failure" => PruneStrategy::StopOnFailure,
-            "never" => PruneStrategy::RunningRegardless,
-            "running_on_failure" => PruneStrategy::RunningOnFailure,
-            "always" => PruneStrategy::RemoveRegardless,
-            _ => {
-                event!(Level::WARN, "unrecognized `DOCKERTEST_PRUNE = {:?}`", val);
-                event!(Level::DEBUG, "defaulting to prune stategy RemoveRegardless");
-                PruneStrategy::RemoveRegardless
-            }
-        },
-        // Default strategy
-        None => PruneStrategy::RemoveRegardless,
+/// Describe the current status, exit code and health of the container, whether it was
+/// killed as it ran out of memory, and how often it restarted.
+async fn container_state(client: &Docker, container: &RunningContainer) -> String {
+    let details = match client
+        .inspect_container(container.name(), None::<InspectContainerOptions>)
+        .await
+    {
+        Ok(details) => details,
+        Err(e) => return format!("unable to inspect container: {}", e),
+    };
+
+    let state = match details.state {
+        Some(state) => state,
+        None => return "unknown state".to_string(),
+    };
+
+    let mut description = match state.status {
+        Some(status) => status.to_string(),
+        None => "unknown status".to_string(),
+    };
+    if let Some(exit_code) = state.exit_code.filter(|_| !state.running.unwrap_or(false)) {
+        description.push_str(&format!(" (exit code {})", exit_code));
     }
+    if let Some(health) = state.health.and_then(|h| h.status) {
+        description.push_str(&format!(", health: {}", health));
+    }
+    if state.oom_killed == Some(true) {
+        description.push_str(", killed as it ran out of memory");
+    }
+    if let Some(restarts) = details.restart_count.filter(|count| *count > 0) {
+        description.push_str(&format!(", restarted {} times", restarts));
+    }
+
+    description
+}
+
+// Adds the dockertest ID as a suffix to all named volume names of the Compositions, and returns
+// the suffixed volume names.
+pub(crate) fn suffix_named_volumes(compositions: &mut [Composition], suffix: &str) -> Vec<String> {
+    // Maps the original volume name to the suffixed ones
+    // Key: "USER_PROVIDED_VOLUME_NAME"
+    // Value: "USER_PROVIDED_VOLUME_NAME-DOCKERTEST_ID"
+    let mut volume_name_map: HashMap<String, String> = HashMap::new();
+
+    compositions.iter_mut().for_each(|c| {
+        // Includes path aswell: "USER_PROVIDED_VOLUME_NAME-DOCKERTEST_ID:PATH_IN_CONTAINER"
+        let mut volume_names_with_path: Vec<String> = Vec::new();
+
+        c.named_volumes.iter().for_each(|(id, path)| {
+            if let Some(suffixed_name) = volume_name_map.get(id) {
+                volume_names_with_path.push(format!("{}:{}", &suffixed_name, &path));
+            } else {
+                let volume_name_with_path = format!("{}-{}:{}", id, suffix, path);
+                volume_names_with_path.push(volume_name_with_path);
+
+                let suffixed_volume_name = format!("{}-{}", id, suffix);
+                volume_name_map.insert(id.to_string(), suffixed_volume_name);
+            }
+        });
+
+        c.final_named_volume_names = volume_names_with_path;
+    });
+
+    volume_name_map.into_values().collect()
 }
 
 /// Make sure we remove the network we have previously created.
@@ -517,28 +1506,31 @@ pub(crate) async fn delete_network(
     }
 }
 
+/// Create the network, with a fixed subnet if addresses of the network are pinned to containers.
 pub(crate) async fn create_network(
     client: &Docker,
     network_name: &str,
     self_container: Option<&str>,
+    address_pool: Option<&AddressPool>,
+    fixed_subnet: bool,
 ) -> Result<(), DockerTestError> {
-    let config = CreateNetworkOptions {
-        name: network_name,
-        ..Default::default()
-    };
-
     event!(Level::TRACE, "creating network {}", network_name);
-    let res = client
-        .create_network(config)
+    let mut res = create_network_with_fallback(client, network_name, address_pool, true)
         .await
         .map(|_| ())
-        .map_err(|e| DockerTestError::Startup(format!("creating docker network failed: {}", e)));
+        .map_err(|e| network_creation_error("creating docker network failed", e, address_pool));
+    if res.is_ok() && fixed_subnet {
+        res = fix_subnet(client, network_name)
+            .await
+            .map_err(|e| DockerTestError::Startup(format!("fixing the subnet failed: {}", e)));
+    }
 
     event!(
         Level::TRACE,
         "finished created network with result: {}",
         res.is_ok()
     );
+    res?;
 
     if let Some(id) = self_container {
         if let Err(e) = add_self_to_network(client, id, network_name).await {
@@ -554,7 +1546,99 @@ pub(crate) async fn create_network(
         }
     }
 
-    res
+    Ok(())
+}
+
+/// Create the network, retrying with subnets of the address pool if the docker daemon has
+/// exhausted its own address pools.
+///
+/// Networks `owned` by the test process are labeled with its session, see [janitor].
+pub(crate) async fn create_network_with_fallback(
+    client: &Docker,
+    network_name: &str,
+    address_pool: Option<&AddressPool>,
+    owned: bool,
+) -> Result<NetworkCreateResponse, bollard::errors::Error> {
+    let session = janitor::session_labels();
+    let labels: HashMap<&str, &str> = session
+        .iter()
+        .filter(|_| owned)
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    let config = CreateNetworkOptions {
+        name: network_name,
+        labels: labels.clone(),
+        ..Default::default()
+    };
+    let exhausted = match client.create_network(config).await {
+        Err(e) if address_pool.is_some() && address_pool::is_exhausted(&e) => e,
+        res => return res,
+    };
+
+    // The address pool has been validated along with the rest of the configuration.
+    let candidates = address_pool
+        .and_then(|pool| pool.candidates().ok())
+        .unwrap_or_default();
+    for subnet in candidates {
+        event!(
+            Level::DEBUG,
+            "address pools exhausted, creating network {} with subnet {}",
+            network_name,
+            subnet
+        );
+        let config = CreateNetworkOptions {
+            name: network_name,
+            labels: labels.clone(),
+            ipam: Ipam {
+                config: Some(vec![IpamConfig {
+                    subnet: Some(subnet),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        match client.create_network(config).await {
+            Err(e) if address_pool::is_overlapping(&e) => continue,
+            res => return res,
+        }
+    }
+
+    Err(exhausted)
+}
+
+/// Recreate the network owned by the test process with the subnet allocated to it by the docker
+/// daemon, as the daemon only pins addresses to containers on networks with a configured subnet.
+async fn fix_subnet(client: &Docker, network_name: &str) -> Result<(), bollard::errors::Error> {
+    let network = client
+        .inspect_network(network_name, None::<InspectNetworkOptions<String>>)
+        .await?;
+    client.remove_network(network_name).await?;
+
+    let config = CreateNetworkOptions {
+        name: network_name.to_string(),
+        labels: network.labels.unwrap_or_default(),
+        ipam: network.ipam.unwrap_or_default(),
+        ..Default::default()
+    };
+    client.create_network(config).await.map(|_| ())
+}
+
+/// Describe the failure to create a network, explaining how to resolve exhausted address pools.
+pub(crate) fn network_creation_error(
+    context: &str,
+    e: bollard::errors::Error,
+    address_pool: Option<&AddressPool>,
+) -> DockerTestError {
+    if address_pool::is_exhausted(&e) {
+        DockerTestError::Startup(format!(
+            "{}: {}",
+            context,
+            address_pool::exhausted_explanation(address_pool.is_some())
+        ))
+    } else {
+        DockerTestError::Startup(format!("{}: {}", context, e))
+    }
 }
 
 pub(crate) async fn add_self_to_network(
@@ -0,0 +1,201 @@
+//! Secrets provided to containers as files instead of environment variables, see
+//! [TestBodySpecification::modify_secret].
+//!
+//! A secret is written to `/run/secrets/{name}` within the container before it is started, and
+//! is therefore never part of the environment, configuration or labels reported by
+//! `docker inspect`. The values of the secrets are redacted from the container logs forwarded by
+//! dockertest, and from the logs included in startup errors, like the values of the environment
+//! variables marked as sensitive, see [TestBodySpecification::mark_env_sensitive].
+//!
+//! NOTE: The secrets are not written to a tmpfs mounted at [SECRETS_PATH], as the docker daemon
+//! writes archives beneath tmpfs mounts, whether the container is started or not. Written before
+//! the container is started, the secrets would be hidden by the tmpfs once mounted, and written
+//! afterwards, neither would they be visible within the container, nor could the entrypoint rely
+//! on them. The secrets are therefore written to the filesystem of the container, which is removed
+//! along with the container. A mount at [SECRETS_PATH], or at `/run`, which would hide the secrets
+//! or receive them instead, is rejected.
+//!
+//! [TestBodySpecification::modify_secret]: crate::TestBodySpecification::modify_secret
+//! [TestBodySpecification::mark_env_sensitive]: crate::TestBodySpecification::mark_env_sensitive
+
+use crate::DockerTestError;
+
+use bollard::{container::UploadToContainerOptions, Docker};
+use secrecy::{ExposeSecret, Secret};
+
+/// The directory within the container holding the secrets.
+pub(crate) const SECRETS_PATH: &str = "/run/secrets";
+
+/// Replaces the value of a secret in logs.
+pub(crate) const REDACTED: &str = "[REDACTED]";
+
+/// The values of the secrets to redact from the logs of a container.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Redactions {
+    values: Vec<Secret<String>>,
+}
+
+impl Redactions {
+    pub(crate) fn new(values: Vec<Secret<String>>) -> Redactions {
+        Redactions {
+            values: values
+                .into_iter()
+                .filter(|v| !v.expose_secret().is_empty())
+                .collect(),
+        }
+    }
+
+    /// Replace every secret value within the message, leaving the remaining bytes as they are,
+    /// whether they are valid UTF-8 or not.
+    pub(crate) fn redact_bytes(&self, message: &[u8]) -> Vec<u8> {
+        self.values.iter().fold(message.to_vec(), |message, v| {
+            replace_bytes(&message, v.expose_secret().as_bytes(), REDACTED.as_bytes())
+        })
+    }
+
+    /// Replace every secret value within the message.
+    pub(crate) fn redact(&self, message: &str) -> String {
+        self.values.iter().fold(message.to_string(), |message, v| {
+            message.replace(v.expose_secret().as_str(), REDACTED)
+        })
+    }
+}
+
+/// Replace every non-overlapping occurrence of `from` within `haystack` by `to`.
+fn replace_bytes(haystack: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut replaced = Vec::with_capacity(haystack.len());
+    let mut rest = haystack;
+    while let Some(at) = rest.windows(from.len()).position(|w| w == from) {
+        replaced.extend_from_slice(&rest[..at]);
+        replaced.extend_from_slice(to);
+        rest = &rest[at + from.len()..];
+    }
+    replaced.extend_from_slice(rest);
+    replaced
+}
+
+/// Check that the name of a secret is a valid file name.
+pub(crate) fn check_secret_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name == "." || name == ".." {
+        Err("the name must be a file name".to_string())
+    } else if name.contains(['/', '\\', '\0']) {
+        Err("the name must not contain path separators".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+/// Whether a mount at the path within the container would hide the secrets, or receive them
+/// instead of the filesystem of the container.
+pub(crate) fn is_hidden_by_mount(path: &str) -> bool {
+    let path = path.trim_end_matches('/');
+    path == SECRETS_PATH || path == "/run"
+}
+
+/// Write the secrets to [SECRETS_PATH] within the created container.
+pub(crate) async fn upload(
+    client: &Docker,
+    id: &str,
+    secrets: &[(String, Secret<String>)],
+) -> Result<(), DockerTestError> {
+    let options = UploadToContainerOptions {
+        path: "/",
+        ..Default::default()
+    };
+    client
+        .upload_to_container(id, Some(options), archive(secrets)?.into())
+        .await
+        .map_err(|e| DockerTestError::Startup(format!("failed to write secrets: {}", e)))
+}
+
+/// Build the tar archive of the secrets, relative to the root of the container.
+fn archive(secrets: &[(String, Secret<String>)]) -> Result<Vec<u8>, DockerTestError> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, value) in secrets {
+        let value = value.expose_secret().as_bytes();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(value.len() as u64);
+        // Readable by the user the container process runs as, which need not be root.
+        header.set_mode(0o444);
+        header.set_cksum();
+        let path = format!("{}/{}", SECRETS_PATH.trim_start_matches('/'), name);
+        builder.append_data(&mut header, path, value).map_err(|e| {
+            DockerTestError::Processing(format!("failed to archive secret `{}`: {}", name, e))
+        })?;
+    }
+
+    builder
+        .into_inner()
+        .map_err(|e| DockerTestError::Processing(format!("failed to archive secrets: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{archive, check_secret_name, is_hidden_by_mount, Redactions};
+
+    use secrecy::Secret;
+
+    // Tests that every secret value is redacted
+    #[test]
+    fn test_redact() {
+        let redactions = Redactions::new(vec![
+            Secret::new("hunter2".to_string()),
+            Secret::new(String::new()),
+        ]);
+        assert_eq!(
+            redactions.redact("password: hunter2, again: hunter2"),
+            "password: [REDACTED], again: [REDACTED]"
+        );
+        assert_eq!(redactions.redact_bytes(b"no secret"), b"no secret");
+    }
+
+    // Tests that secrets are redacted from binary output, which is otherwise left untouched
+    #[test]
+    fn test_redact_bytes() {
+        let redactions = Redactions::new(vec![Secret::new("hunter2".to_string())]);
+        assert_eq!(
+            redactions.redact_bytes(b"\xff\xfehunter2\x00hunter2hunter\xc3"),
+            b"\xff\xfe[REDACTED]\x00[REDACTED]hunter\xc3"
+        );
+        assert_eq!(redactions.redact_bytes(b"\xffhunter"), b"\xffhunter");
+    }
+
+    // Tests that secrets are archived below the secrets directory
+    #[test]
+    fn test_archive() {
+        let secrets = vec![(
+            "db_password".to_string(),
+            Secret::new("hunter2".to_string()),
+        )];
+        let archive = archive(&secrets).unwrap();
+
+        let mut archive = tar::Archive::new(archive.as_slice());
+        let entry = archive.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(
+            entry.path().unwrap().to_str(),
+            Some("run/secrets/db_password")
+        );
+        assert_eq!(entry.header().mode().unwrap(), 0o444);
+        assert_eq!(entry.header().size().unwrap(), 7);
+    }
+
+    // Tests that only mounts at the secrets directory or its parent hide the secrets
+    #[test]
+    fn test_is_hidden_by_mount() {
+        assert!(is_hidden_by_mount("/run/secrets"));
+        assert!(is_hidden_by_mount("/run/secrets/"));
+        assert!(is_hidden_by_mount("/run"));
+        assert!(!is_hidden_by_mount("/run/secrets/db_password"));
+        assert!(!is_hidden_by_mount("/var/run"));
+        assert!(!is_hidden_by_mount("/tmp"));
+    }
+
+    // Tests that the name of a secret must be a file name
+    #[test]
+    fn test_check_secret_name() {
+        assert!(check_secret_name("db_password").is_ok());
+        assert!(check_secret_name("").is_err());
+        assert!(check_secret_name("..").is_err());
+        assert!(check_secret_name("../etc/passwd").is_err());
+    }
+}
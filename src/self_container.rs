@@ -0,0 +1,175 @@
+//! Detection of the container the test process itself executes within.
+//!
+//! When dockertest runs inside a container, that container must be connected to the docker
+//! network of the test for the test body to reach the containers it starts.
+//! The `DOCKERTEST_CONTAINER_ID_INJECT_TO_NETWORK` environment variable takes precedence,
+//! and setting it to an empty value disables detection altogether. Otherwise, candidate
+//! identifiers are collected from the cgroup membership, the mount table, and the hostname of
+//! the process, and the first candidate known to the docker daemon is used.
+
+use bollard::{container::InspectContainerOptions, Docker};
+use tracing::{event, Level};
+
+const INJECT_ENV: &str = "DOCKERTEST_CONTAINER_ID_INJECT_TO_NETWORK";
+
+/// The length of a full docker container identifier.
+const ID_LEN: usize = 64;
+/// The length of the abbreviated container identifier docker uses as the default hostname.
+const SHORT_ID_LEN: usize = 12;
+
+/// Resolve the identifier of the container this process executes within, if any.
+pub(crate) async fn resolve_own_container_id(client: &Docker) -> Option<String> {
+    if let Some(id) = std::env::var_os(INJECT_ENV) {
+        let id = id.to_string_lossy().to_string();
+        if id.is_empty() {
+            event!(Level::TRACE, "inside container detection disabled by env");
+            return None;
+        }
+        event!(
+            Level::TRACE,
+            "dockertest container id env is set, id: {}",
+            id
+        );
+        return Some(id);
+    }
+
+    for candidate in candidate_ids() {
+        // The daemon may not be the one executing this container, e.g., with a remote daemon.
+        match client
+            .inspect_container(&candidate, None::<InspectContainerOptions>)
+            .await
+        {
+            Ok(details) => {
+                let id = details.id.unwrap_or(candidate);
+                event!(
+                    Level::DEBUG,
+                    "detected that dockertest runs inside container: {}",
+                    id
+                );
+                return Some(id);
+            }
+            Err(e) => event!(
+                Level::TRACE,
+                "container id candidate `{}` is unknown to the docker daemon: {}",
+                candidate,
+                e
+            ),
+        }
+    }
+
+    event!(Level::TRACE, "running native on host");
+    None
+}
+
+/// Collect the candidate container identifiers of this process, in order of confidence.
+fn candidate_ids() -> Vec<String> {
+    if !cfg!(target_os = "linux") {
+        return Vec::new();
+    }
+
+    let read = |path: &str| std::fs::read_to_string(path).unwrap_or_default();
+    let (cgroup, mountinfo, hostname) = (
+        read("/proc/self/cgroup"),
+        read("/proc/self/mountinfo"),
+        read("/etc/hostname"),
+    );
+    let candidates = [
+        id_from_cgroup(&cgroup),
+        id_from_mountinfo(&mountinfo),
+        id_from_hostname(&hostname),
+    ];
+
+    let mut ids: Vec<String> = Vec::new();
+    for id in candidates.iter().flatten() {
+        if !ids.iter().any(|i| i == *id) {
+            ids.push(id.to_string());
+        }
+    }
+    ids
+}
+
+/// Find a full container identifier within the cgroup paths of the process.
+///
+/// Covers cgroup v1 paths, e.g., `/docker/<id>`, as well as systemd managed paths,
+/// e.g., `/system.slice/docker-<id>.scope`. Under cgroup v2 with a private cgroup namespace
+/// the path is only `/`, for which [id_from_mountinfo] is the fallback.
+fn id_from_cgroup(cgroup: &str) -> Option<&str> {
+    cgroup
+        .lines()
+        .filter_map(|line| line.splitn(3, ':').nth(2))
+        .find_map(find_id)
+}
+
+/// Find a full container identifier within the mount table of the process.
+///
+/// The container runtime bind mounts files such as `/etc/hostname` from the directory of the
+/// container, e.g., `/var/lib/docker/containers/<id>/hostname`.
+fn id_from_mountinfo(mountinfo: &str) -> Option<&str> {
+    mountinfo
+        .lines()
+        .filter_map(|line| line.split(' ').nth(3))
+        .filter(|root| root.contains("/containers/"))
+        .find_map(find_id)
+}
+
+/// Docker assigns the abbreviated container identifier as hostname, unless overridden.
+fn id_from_hostname(hostname: &str) -> Option<&str> {
+    let hostname = hostname.trim();
+    if hostname.len() == SHORT_ID_LEN && is_hex(hostname) {
+        Some(hostname)
+    } else {
+        None
+    }
+}
+
+fn find_id(path: &str) -> Option<&str> {
+    path.split(|c: char| !c.is_ascii_hexdigit())
+        .find(|token| token.len() == ID_LEN)
+}
+
+fn is_hex(s: &str) -> bool {
+    s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{id_from_cgroup, id_from_hostname, id_from_mountinfo};
+
+    const ID: &str = "3f4c8d0a6e0b1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d";
+
+    // Tests that the container id is found in cgroup v1 and systemd cgroup paths
+    #[test]
+    fn test_id_from_cgroup() {
+        let v1 = format!("12:memory:/docker/{}\n1:name=systemd:/docker/{}\n", ID, ID);
+        assert_eq!(id_from_cgroup(&v1), Some(ID));
+
+        let systemd = format!("0::/system.slice/docker-{}.scope\n", ID);
+        assert_eq!(id_from_cgroup(&systemd), Some(ID));
+
+        assert_eq!(id_from_cgroup("0::/\n"), None);
+        assert_eq!(id_from_cgroup("0::/user.slice/user-1000.slice\n"), None);
+    }
+
+    // Tests that the container id is found in the bind mounts of the container directory
+    #[test]
+    fn test_id_from_mountinfo() {
+        let mountinfo = format!(
+            "1 0 0:1 / / rw - overlay overlay rw\n\
+             2 1 8:1 /var/lib/docker/containers/{}/hostname /etc/hostname rw - ext4 /dev/sda1 rw\n",
+            ID
+        );
+        assert_eq!(id_from_mountinfo(&mountinfo), Some(ID));
+        assert_eq!(
+            id_from_mountinfo("1 0 0:1 / / rw - overlay overlay rw\n"),
+            None
+        );
+    }
+
+    // Tests that only abbreviated container ids are accepted as hostname
+    #[test]
+    fn test_id_from_hostname() {
+        assert_eq!(id_from_hostname("3f4c8d0a6e0b\n"), Some("3f4c8d0a6e0b"));
+        assert_eq!(id_from_hostname("my-laptop\n"), None);
+        assert_eq!(id_from_hostname("deadbeef\n"), None);
+    }
+}
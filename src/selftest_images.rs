@@ -0,0 +1,310 @@
+//! Builds the images used by the test suite of dockertest through the docker API.
+//!
+//! Every dockerfile of the `dockerfiles/` directory is built as the image
+//! `dockertest-rs/{file stem}`, e.g., `dockerfiles/hello.dockerfile` as `dockertest-rs/hello`.
+//! The images are built by the docker daemon itself, such that neither the docker CLI nor an
+//! emulated platform is required, pulling their base images as needed.
+//!
+//! The tests relying on the images invoke [ensure], which builds the images once per test
+//! process, and only those whose dockerfile changed since they were last built. Running the test
+//! suite therefore only requires `cargo test`. The images may also be built upfront through the
+//! `build_test_images` example:
+//!
+//! ```text
+//! cargo run --example build_test_images
+//! ```
+//!
+//! The images are built for the platform of the docker daemon, e.g., `linux/arm64` on ARM
+//! machines, unless another platform is selected through `DOCKERTEST_TEST_IMAGES_PLATFORM`.
+//!
+//! The module is public for the integration tests and examples of dockertest, which are compiled
+//! as separate crates. It only builds the images of the `dockerfiles/` directory of dockertest
+//! itself, and is not meant to build the images of its users.
+
+use crate::utils::{connect_with_local_or_tls_defaults, stable_hash};
+use crate::DockerTestError;
+
+use bollard::{image::BuildImageOptions, Docker};
+use futures::StreamExt;
+use lazy_static::lazy_static;
+use tokio::sync::OnceCell;
+use tracing::{event, Level};
+
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The repository prefix of the test images.
+pub const REPOSITORY_PREFIX: &str = "dockertest-rs";
+
+/// The environment variable selecting the platform to build the test images for, e.g.,
+/// `linux/amd64`.
+pub const PLATFORM_ENV: &str = "DOCKERTEST_TEST_IMAGES_PLATFORM";
+
+/// The label holding the digest of the dockerfile and platform a test image was built from.
+const DIGEST_LABEL: &str = "dockertest.selftest.digest";
+
+lazy_static! {
+    /// The outcome of ensuring the test images, shared by every test of the process.
+    static ref ENSURED: OnceCell<Result<(), DockerTestError>> = OnceCell::new();
+}
+
+/// Ensure that every test image is built and up to date, for the platform selected through
+/// [PLATFORM_ENV], if any.
+///
+/// The images are only built by the first invocation within the test process, which every
+/// concurrent invocation awaits. Images built from the current dockerfile for the same platform
+/// are reused.
+pub async fn ensure() -> Result<(), DockerTestError> {
+    ENSURED
+        .get_or_init(|| async {
+            let dockerfiles = concat!(env!("CARGO_MANIFEST_DIR"), "/dockerfiles");
+            let platform = platform_from_env();
+            match ensure_in(dockerfiles, platform.as_deref()).await {
+                Ok(built) => {
+                    event!(Level::INFO, "ensured test images, built {:?}", built);
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            }
+        })
+        .await
+        .clone()
+}
+
+/// Build every dockerfile of the `dockerfiles` directory for the `platform`, or the platform of
+/// the docker daemon if `None`, returning the names of the built images.
+pub async fn build<P: AsRef<Path>>(
+    dockerfiles: P,
+    platform: Option<&str>,
+) -> Result<Vec<String>, DockerTestError> {
+    let dockerfiles = dockerfiles.as_ref();
+    let client = connect_with_local_or_tls_defaults()?;
+    let context = archive(dockerfiles)?;
+
+    let mut built = Vec::new();
+    for dockerfile in list_dockerfiles(dockerfiles)? {
+        let repository = image_name(&dockerfile);
+        let digest = digest(&read_dockerfile(dockerfiles, &dockerfile)?, platform);
+        build_image(
+            &client,
+            &context,
+            &dockerfile,
+            &repository,
+            platform,
+            &digest,
+        )
+        .await?;
+        built.push(repository);
+    }
+
+    Ok(built)
+}
+
+/// Build the test images for the platform selected through [PLATFORM_ENV], if any.
+pub async fn build_from_env<P: AsRef<Path>>(
+    dockerfiles: P,
+) -> Result<Vec<String>, DockerTestError> {
+    build(dockerfiles, platform_from_env().as_deref()).await
+}
+
+/// Build the dockerfiles of the `dockerfiles` directory whose image is missing or was built from
+/// another dockerfile or platform, returning the names of the built images.
+async fn ensure_in(
+    dockerfiles: &str,
+    platform: Option<&str>,
+) -> Result<Vec<String>, DockerTestError> {
+    let dockerfiles = Path::new(dockerfiles);
+    let client = connect_with_local_or_tls_defaults()?;
+
+    let mut context = None;
+    let mut built = Vec::new();
+    for dockerfile in list_dockerfiles(dockerfiles)? {
+        let repository = image_name(&dockerfile);
+        let digest = digest(&read_dockerfile(dockerfiles, &dockerfile)?, platform);
+        if built_digest(&client, &repository).await.as_deref() == Some(digest.as_str()) {
+            event!(Level::DEBUG, "test image `{}` is up to date", repository);
+            continue;
+        }
+
+        // The build context is only archived once an image must be built.
+        if context.is_none() {
+            context = Some(archive(dockerfiles)?);
+        }
+        let context = context.as_deref().unwrap_or_default();
+        build_image(
+            &client,
+            context,
+            &dockerfile,
+            &repository,
+            platform,
+            &digest,
+        )
+        .await?;
+        built.push(repository);
+    }
+
+    Ok(built)
+}
+
+fn platform_from_env() -> Option<String> {
+    std::env::var(PLATFORM_ENV)
+        .ok()
+        .filter(|platform| !platform.is_empty())
+}
+
+/// The digest of the dockerfile the image was built from, if the image exists.
+async fn built_digest(client: &Docker, repository: &str) -> Option<String> {
+    let image = client.inspect_image(repository).await.ok()?;
+    image.config?.labels?.remove(DIGEST_LABEL)
+}
+
+/// The digest identifying the image built from the `dockerfile` for the `platform`, stable
+/// across test processes and toolchains.
+fn digest(dockerfile: &[u8], platform: Option<&str>) -> String {
+    stable_hash(&format!(
+        "{:?}",
+        (String::from_utf8_lossy(dockerfile), platform)
+    ))
+}
+
+async fn build_image(
+    client: &Docker,
+    context: &[u8],
+    dockerfile: &str,
+    repository: &str,
+    platform: Option<&str>,
+    digest: &str,
+) -> Result<(), DockerTestError> {
+    event!(
+        Level::INFO,
+        "building test image `{}` from `{}` for platform `{}`",
+        repository,
+        dockerfile,
+        platform.unwrap_or("default")
+    );
+    let options = BuildImageOptions {
+        dockerfile,
+        t: repository,
+        platform: platform.unwrap_or_default(),
+        rm: true,
+        labels: HashMap::from([(DIGEST_LABEL, digest)]),
+        ..Default::default()
+    };
+
+    let mut stream = client.build_image(options, None, Some(context.to_vec().into()));
+    while let Some(result) = stream.next().await {
+        let info = result.map_err(|e| {
+            DockerTestError::Startup(format!("failed to build image `{}`: {}", repository, e))
+        })?;
+        if let Some(error) = info.error {
+            return Err(DockerTestError::Startup(format!(
+                "failed to build image `{}`: {}",
+                repository, error
+            )));
+        }
+        if let Some(output) = info.stream {
+            event!(Level::TRACE, "build output {}", output.trim_end());
+        }
+    }
+
+    Ok(())
+}
+
+/// The contents of the dockerfile within the directory.
+fn read_dockerfile(dockerfiles: &Path, dockerfile: &str) -> Result<Vec<u8>, DockerTestError> {
+    let path = dockerfiles.join(dockerfile);
+    std::fs::read(&path).map_err(|e| {
+        DockerTestError::Processing(format!("failed to read `{}`: {}", path.display(), e))
+    })
+}
+
+/// The file names of the dockerfiles within the directory, in alphabetical order.
+fn list_dockerfiles(dockerfiles: &Path) -> Result<Vec<String>, DockerTestError> {
+    let entries = std::fs::read_dir(dockerfiles).map_err(|e| {
+        DockerTestError::Processing(format!("failed to read `{}`: {}", dockerfiles.display(), e))
+    })?;
+
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// The name of the image built from the dockerfile, i.e., `dockertest-rs/{file stem}`.
+fn image_name(dockerfile: &str) -> String {
+    let stem = Path::new(dockerfile)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(dockerfile);
+    format!("{}/{}", REPOSITORY_PREFIX, stem)
+}
+
+/// Build the tar archive of the build context, i.e., the directory holding the dockerfiles.
+fn archive(dockerfiles: &Path) -> Result<Vec<u8>, DockerTestError> {
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.append_dir_all(".", dockerfiles).map_err(|e| {
+        DockerTestError::Processing(format!(
+            "failed to archive `{}`: {}",
+            dockerfiles.display(),
+            e
+        ))
+    })?;
+
+    builder.into_inner().map_err(|e| {
+        DockerTestError::Processing(format!("failed to archive the build context: {}", e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{archive, digest, image_name, list_dockerfiles};
+
+    use std::path::Path;
+
+    // Tests that every dockerfile is built as an image of the test repository
+    #[test]
+    fn test_dockerfiles() {
+        let dockerfiles = Path::new(env!("CARGO_MANIFEST_DIR")).join("dockerfiles");
+        let names = list_dockerfiles(&dockerfiles).unwrap();
+        assert!(names.contains(&"hello.dockerfile".to_string()));
+        assert_eq!(image_name("hello.dockerfile"), "dockertest-rs/hello");
+        assert_eq!(
+            image_name("hello-privileged.dockerfile"),
+            "dockertest-rs/hello-privileged"
+        );
+
+        let context = archive(&dockerfiles).unwrap();
+        let mut context = tar::Archive::new(context.as_slice());
+        let archived: Vec<String> = context
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect();
+        assert!(
+            archived
+                .iter()
+                .any(|path| path.ends_with("hello.dockerfile")),
+            "{:?}",
+            archived
+        );
+    }
+
+    // Tests that the digest is stable, and changes with the dockerfile and the platform it is
+    // built for
+    #[test]
+    fn test_digest() {
+        let dockerfile = b"FROM alpine:3\n";
+        assert_eq!(digest(dockerfile, None), "f799e523c70d5bf7");
+        assert_ne!(
+            digest(dockerfile, None),
+            digest(b"FROM alpine:3.19\n", None)
+        );
+        assert_ne!(
+            digest(dockerfile, None),
+            digest(dockerfile, Some("linux/amd64"))
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Sharing the containers of identical environments between test binaries, see
+//! [DockerTest::with_shared_environment].
+//!
+//! The definition of the environment is hashed, see [DockerTest::environment_hash], and every
+//! container is labeled with the hash. Shared containers are additionally labeled with their
+//! handle. A test binary discovers the shared containers started by another test binary through
+//! the labels, and attaches to them instead of starting duplicates. Test binaries racing to create
+//! the same shared container attach to the container of the winner. Shared containers are managed
+//! like dynamic containers, and are therefore never removed. An attached container that is not yet
+//! started is started rather than replaced, and its wait condition is awaited before the test
+//! body is invoked.
+//!
+//! [DockerTest::with_shared_environment]: crate::DockerTest::with_shared_environment
+//! [DockerTest::environment_hash]: crate::DockerTest::environment_hash
+
+use crate::composition::Composition;
+use crate::dockertest::sanitize_name;
+use crate::utils::stable_hash;
+
+use bollard::{container::ListContainersOptions, Docker};
+use tracing::{event, Level};
+
+use std::collections::HashMap;
+
+/// The label holding the hash of the environment a container belongs to.
+pub(crate) const ENVIRONMENT_LABEL: &str = "dockertest.environment";
+/// The label holding the handle of a container within its environment.
+pub(crate) const HANDLE_LABEL: &str = "dockertest.handle";
+
+/// Label the containers of the environment with the `hash` of its definition.
+///
+/// Static containers are shared by the tests of different environments, and are left untouched.
+pub(crate) fn label(hash: &str, compositions: &mut [Composition]) {
+    for c in compositions.iter_mut().filter(|c| !c.is_static()) {
+        c.labels
+            .insert(ENVIRONMENT_LABEL.to_string(), hash.to_string());
+    }
+}
+
+/// Share the containers of the environment with the given `hash`, attaching to the containers of
+/// a matching environment if one has been started by another test binary.
+///
+/// Static containers are already shared, and are left untouched.
+pub(crate) async fn share(
+    client: &Docker,
+    namespace: &str,
+    hash: &str,
+    compositions: &mut [Composition],
+) {
+    let existing = discover(client, hash).await;
+    event!(
+        Level::DEBUG,
+        "sharing environment `{}`, attaching to {} existing containers",
+        hash,
+        existing.len()
+    );
+
+    for c in compositions.iter_mut().filter(|c| !c.is_static()) {
+        let handle = c.handle();
+        let name = existing.get(&handle).cloned().unwrap_or_else(|| {
+            format!(
+                "{}-{}-{}",
+                namespace,
+                sanitize_name(&handle.replace('/', "_")),
+                hash
+            )
+        });
+        c.share_environment(name);
+    }
+}
+
+/// Hash the definition of the environment, such that identical definitions yield the same hash
+/// across test binaries, see [DockerTest::environment_hash].
+///
+/// [DockerTest::environment_hash]: crate::DockerTest::environment_hash
+pub(crate) fn environment_hash(compositions: &[Composition]) -> String {
+    let mut definitions: Vec<String> = compositions
+        .iter()
+        .filter(|c| !c.is_static())
+        .map(|c| format!("{}|{}", c.handle(), c.definition()))
+        .collect();
+    definitions.sort();
+
+    stable_hash(&definitions.join("\n"))
+}
+
+/// Find the containers of the environment with the given hash, by handle.
+async fn discover(client: &Docker, hash: &str) -> HashMap<String, String> {
+    let label = format!("{}={}", ENVIRONMENT_LABEL, hash);
+    let options = ListContainersOptions {
+        all: true,
+        // Containers of the environment that are not shared lack the handle label.
+        filters: HashMap::from([("label", vec![label.as_str(), HANDLE_LABEL])]),
+        ..Default::default()
+    };
+
+    match client.list_containers(Some(options)).await {
+        Ok(containers) => containers
+            .into_iter()
+            .filter_map(|c| {
+                let handle = c.labels?.remove(HANDLE_LABEL)?;
+                let name = c.names?.into_iter().next()?;
+                Some((handle, name.trim_start_matches('/').to_string()))
+            })
+            .collect(),
+        Err(e) => {
+            event!(
+                Level::WARN,
+                "failed to discover the containers of shared environment `{}`: {}",
+                hash,
+                e
+            );
+            HashMap::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::environment_hash;
+    use crate::composition::Composition;
+    use crate::utils::stable_hash;
+
+    // Tests that the hash depends on the definition of the environment, not its order
+    #[test]
+    fn test_environment_hash() {
+        let mut postgres = Composition::with_repository("postgres");
+        postgres.env("POSTGRES_PASSWORD", "secret");
+        let redis = Composition::with_repository("redis");
+
+        let hash = environment_hash(&[postgres.clone(), redis.clone()]);
+        assert_eq!(hash, environment_hash(&[redis.clone(), postgres.clone()]));
+
+        postgres.env("POSTGRES_PASSWORD", "other");
+        assert_ne!(hash, environment_hash(&[postgres, redis]));
+    }
+
+    // Tests that the hash depends on the network aliases and secrets, whose values are not
+    // described in plain text
+    #[test]
+    fn test_environment_hash_aliases_and_secrets() {
+        let mut postgres = Composition::with_repository("postgres");
+        let hash = environment_hash(&[postgres.clone()]);
+
+        postgres.alias("db".to_string());
+        let aliased = environment_hash(&[postgres.clone()]);
+        assert_ne!(hash, aliased);
+
+        postgres.secret("password", "hunter2");
+        assert_ne!(aliased, environment_hash(&[postgres.clone()]));
+        assert!(!postgres.definition().contains("hunter2"));
+
+        let mut other = postgres.clone();
+        other.secret("password", "other");
+        assert_ne!(environment_hash(&[postgres]), environment_hash(&[other]));
+    }
+
+    // Tests that the hash is a truncated sha256 digest, independent of the toolchain
+    #[test]
+    fn test_stable_hash() {
+        assert_eq!(stable_hash("dockertest"), "00f0cb5f4f3798d8");
+    }
+}
@@ -0,0 +1,72 @@
+//! Readiness conditions of well-known official images, applied to the containers without a
+//! [WaitFor] through [DockerTest::with_smart_defaults].
+//!
+//! The containers of these images are reported as running long before they accept connections,
+//! e.g., while a database initializes its data directory through a temporary server that only
+//! listens on a unix socket. The conditions therefore probe the server over tcp from within the
+//! container, or await its readiness log line.
+//!
+//! [DockerTest::with_smart_defaults]: crate::DockerTest::with_smart_defaults
+
+use crate::waitfor::{ExecWait, MessageSource, MessageWait, WaitFor};
+
+/// How many seconds shall there be between each probe.
+const CHECK_INTERVAL: u64 = 1;
+
+/// How many seconds a container is awaited before erroring out, accounting for the
+/// initialization of databases on slow machines.
+const TIMEOUT: u64 = 120;
+
+/// The readiness condition of the official image of the repository, if recognized.
+pub(crate) fn wait_for(repository: &str) -> Option<Box<dyn WaitFor>> {
+    let name = repository.rsplit('/').next().unwrap_or(repository);
+    match name {
+        "postgres" => Some(exec(&["pg_isready", "-h", "127.0.0.1"])),
+        "mysql" | "mariadb" => Some(exec(&[
+            "sh",
+            "-c",
+            "mysqladmin ping -h 127.0.0.1 --silent || mariadb-admin ping -h 127.0.0.1 --silent",
+        ])),
+        // The temporary server initializing the database only listens on localhost.
+        "mongo" => Some(exec(&[
+            "sh",
+            "-c",
+            "mongosh --quiet --host \"$(hostname -i)\" --eval 'db.runCommand({ ping: 1 })' \
+             || mongo --quiet --host \"$(hostname -i)\" --eval 'db.runCommand({ ping: 1 })'",
+        ])),
+        "rabbitmq" => Some(exec(&[
+            "rabbitmq-diagnostics",
+            "-q",
+            "check_port_connectivity",
+        ])),
+        "redis" => Some(Box::new(MessageWait {
+            message: "Ready to accept connections".to_string(),
+            source: MessageSource::Stdout,
+            timeout: TIMEOUT as u16,
+        })),
+        _ => None,
+    }
+}
+
+fn exec(cmd: &[&str]) -> Box<dyn WaitFor> {
+    Box::new(ExecWait {
+        cmd: cmd.iter().map(|arg| arg.to_string()).collect(),
+        check_interval: CHECK_INTERVAL,
+        max_checks: TIMEOUT / CHECK_INTERVAL,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::wait_for;
+
+    // Tests that the official images are recognized, regardless of their registry
+    #[test]
+    fn test_wait_for() {
+        let wait = wait_for("library/postgres").unwrap();
+        assert!(format!("{:?}", wait).contains("pg_isready"));
+        assert!(wait_for("docker.io/library/mariadb").is_some());
+        assert!(wait_for("redis").is_some());
+        assert!(wait_for("hello-world").is_none());
+    }
+}
@@ -2,10 +2,16 @@
 
 use std::collections::HashMap;
 
+use futures::Future;
+
 use crate::{
-    composition::{Composition, StaticManagementPolicy},
+    composition::{
+        BindMountOptions, Composition, ExistingContainerPolicy, Isolation, StaticManagementPolicy,
+    },
+    container::RunningContainer,
+    reset::Resettable,
     waitfor::WaitFor,
-    Image, LogOptions, StartPolicy,
+    DockerTestError, Image, LogOptions, StartPolicy,
 };
 
 mod private {
@@ -59,6 +65,15 @@ macro_rules! impl_specify_container {
 
             /// Modify a single environment variable available for the [RunningContainer].
             ///
+            /// The value may reference other containers of the test through placeholders, which
+            /// are substituted before the container is created:
+            /// - `{handle:NAME}` is replaced by the generated container name of handle `NAME`.
+            /// - `{host_port:NAME:PORT}` is replaced by the host port mapped to the container
+            ///   port `PORT` of handle `NAME`, see [inject_host_port](Self::inject_host_port).
+            ///
+            /// A placeholder is escaped by doubling its opening brace, e.g., `{{handle:NAME}` is
+            /// passed to the container as `{handle:NAME}`.
+            ///
             /// A [replace_env] call will undo what has been configured individually with this
             /// method.
             ///
@@ -69,6 +84,48 @@ macro_rules! impl_specify_container {
                 self
             }
 
+            /// Mark the environment variables named `keys` as sensitive, e.g., credentials,
+            /// redacting their values from the diagnostics of dockertest: tracing events, startup
+            /// and validation errors, and the container logs forwarded by dockertest.
+            ///
+            /// The values remain visible to `docker inspect`, see
+            /// [modify_secret](Self::modify_secret) to hide them.
+            pub fn mark_env_sensitive<T: ToString>(&mut self, keys: &[T]) -> &mut Self {
+                self.composition.mark_env_sensitive(keys);
+                self
+            }
+
+            /// Pass every environment variable of the test process whose name starts with
+            /// `prefix` through to the [RunningContainer], e.g., `AWS_` for credentials or
+            /// `HTTP_PROXY` for proxy settings provided by CI.
+            ///
+            /// The variables are read when the container is created, and never replace the
+            /// environment variables configured on this container or the defaults of the test.
+            ///
+            /// [RunningContainer]: crate::container::RunningContainer
+            pub fn append_passthrough_env<T: ToString>(&mut self, prefix: T) -> &mut Self {
+                self.composition.passthrough_env(prefix);
+                self
+            }
+
+            /// Provide a secret to the [RunningContainer] as the file `/run/secrets/{name}`,
+            /// instead of through an environment variable that is visible to `docker inspect`.
+            ///
+            /// The file is written to the filesystem of the container before it is started, and
+            /// is readable by every user of the container. A mount at `/run/secrets` or `/run`,
+            /// which would hide the file, is rejected. The value is redacted from the container
+            /// logs forwarded by dockertest.
+            ///
+            /// [RunningContainer]: crate::container::RunningContainer
+            pub fn modify_secret<T: ToString, S: ToString>(
+                &mut self,
+                name: T,
+                value: S,
+            ) -> &mut Self {
+                self.composition.secret(name, value);
+                self
+            }
+
             /// Assign the full set of command vector entries for the [RunningContainer].
             ///
             /// This method replaces all existing command vector entries previously provided.
@@ -109,10 +166,11 @@ macro_rules! impl_specify_container {
             ///
             /// This mechanism is not recommended, as concurrent tests utilizing the same host port
             /// will fail since the port is already in use. If utilizing the host is needed, it is
-            /// recommended to use [set_publish_all_ports].
+            /// recommended to use [publish_port] or [set_publish_all_ports].
             ///
             /// This function can overwrite previously mapped ports, if invoked repeatedly.
             ///
+            /// [publish_port]: Self::publish_port
             /// [set_publish_all_ports]: Self::set_publish_all_ports
             // TODO: Add a replace_port_map that takes (exported, host) tuples
             // TODO: Guarantee that a modification of one already exported/host value is
@@ -122,6 +180,18 @@ macro_rules! impl_specify_container {
                 self
             }
 
+            /// Publish the exported tcp `port` of the container on a random free host port,
+            /// assigned by the docker daemon when the container starts.
+            ///
+            /// This avoids host port collisions between concurrent tests. The assigned host port
+            /// is found via the [RunningContainer::host_port] method.
+            ///
+            /// [RunningContainer::host_port]: crate::container::RunningContainer::host_port
+            pub fn publish_port(&mut self, port: u32) -> &mut Self {
+                self.composition.publish_port(port);
+                self
+            }
+
             /// Specify the privilege mode of the started container.
             ///
             /// This may be required for some containers to run correctly.
@@ -144,11 +214,162 @@ macro_rules! impl_specify_container {
                 self
             }
 
+            /// Specify whether the started container joins the user namespace of the host.
+            ///
+            /// A docker daemon remapping the users of containers through `userns-remap` rejects
+            /// privileged containers, unless they join the user namespace of the host, whose
+            /// users are then not remapped. This must be opted into explicitly for privileged
+            /// containers of such a daemon, as it grants the container the privileges of the
+            /// root user of the host.
+            pub fn host_user_namespace(&mut self, enabled: bool) -> &mut Self {
+                self.composition.userns_host = enabled;
+                self
+            }
+
+            /// Specify whether the started container joins the user namespace of the host, see
+            /// [host_user_namespace](Self::host_user_namespace).
+            pub fn set_host_user_namespace(mut self, enabled: bool) -> Self {
+                self.composition.userns_host = enabled;
+                self
+            }
+
+            /// Set the callback invoked with the [RunningContainer] right after its [WaitFor]
+            /// condition is fulfilled, before other containers may be ready.
+            ///
+            /// This allows wiring the container as part of its startup, e.g., creating the topics
+            /// of a Kafka broker, rather than at the top of every test body. A failing callback
+            /// fails the startup of the container. The ip address and host ports of the container
+            /// are only resolved once every container is ready, hence the callback interacts with
+            /// the container through the docker daemon, e.g., through [RunningContainer::exec].
+            ///
+            /// [RunningContainer]: crate::container::RunningContainer
+            /// [RunningContainer::exec]: crate::container::RunningContainer::exec
+            /// [WaitFor]: crate::waitfor::WaitFor
+            pub fn set_on_started<F, Fut>(mut self, callback: F) -> Self
+            where
+                F: Fn(RunningContainer) -> Fut + Send + Sync + 'static,
+                Fut: Future<Output = Result<(), DockerTestError>> + Send + 'static,
+            {
+                self.composition.on_started(callback);
+                self
+            }
+
+            /// Mark the container as expected to exit during the test, e.g., a one-shot job
+            /// migrating a database.
+            ///
+            /// The liveness check of [DockerTest::with_liveness_check] fails the test if any
+            /// other container exited or restarted, but only if an expected exit was
+            /// unsuccessful. Containers awaited through [ExitedWait] are expected to exit.
+            ///
+            /// [DockerTest::with_liveness_check]: crate::DockerTest::with_liveness_check
+            /// [ExitedWait]: crate::waitfor::ExitedWait
+            pub fn set_expect_exit(mut self) -> Self {
+                self.composition.expect_exit();
+                self
+            }
+
+            /// Apply well-known speed knobs of recognized database images, trading durability for
+            /// speed, as the data of the container is discarded along with it anyway.
+            ///
+            /// For `postgres`, `fsync`, `synchronous_commit` and `full_page_writes` are disabled.
+            /// For `mysql` and `mariadb`, the doublewrite buffer is skipped, and the logs are not
+            /// flushed on every commit. The data directory is mounted as a tmpfs, unless a volume
+            /// is mounted there. Other images are left as is.
+            pub fn set_ephemeral_performance_mode(mut self) -> Self {
+                self.composition.ephemeral_performance_mode();
+                self
+            }
+
+            /// Offset the clock of the started container by `offset` seconds, e.g., to test
+            /// certificate expiry, through [libfaketime].
+            ///
+            /// libfaketime of the host is mounted into the container and preloaded through
+            /// `LD_PRELOAD`, hence it must be built for the C library of the image. It is located
+            /// through `DOCKERTEST_FAKETIME_LIBRARY`, defaulting to the locations of the
+            /// distribution packages, e.g., `apt install libfaketime`. Monotonic clocks are not
+            /// faked. Statically linked executables are unaffected.
+            ///
+            /// The offset is read from a file within the container on every clock access, such
+            /// that [DockerOperations::set_faked_time] shifts the clock while the test runs.
+            ///
+            /// [libfaketime]: https://github.com/wolfcw/libfaketime
+            /// [DockerOperations::set_faked_time]: crate::DockerOperations::set_faked_time
+            pub fn set_faked_time(self, offset: i64) -> Self {
+                Self {
+                    composition: self.composition.with_faked_time(offset),
+                }
+            }
+
+            /// Create `replicas` containers of this specification, e.g., the brokers of a
+            /// cluster, to test clustering and load-balancing behavior.
+            ///
+            /// Each replica is identified by the handle suffixed with its index, counting from
+            /// zero, e.g., `kafka-0` and `kafka-1`, and all of them are retrieved through
+            /// [DockerOperations::replicas]. Every `{replica}` within the values of the
+            /// environment variables is replaced by the index of the replica. Network aliases are
+            /// shared by the replicas, such that the alias resolves to all of them.
+            ///
+            /// [DockerOperations::replicas]: crate::DockerOperations::replicas
+            pub fn set_replicas(self, replicas: usize) -> Self {
+                Self {
+                    composition: self.composition.with_replicas(replicas),
+                }
+            }
+
+            /// Set the hook mutating the raw bollard [Config] and [HostConfig] of the container
+            /// right before it is created, after dockertest has applied its own configuration.
+            ///
+            /// This is an escape hatch to set any option of the docker daemon that dockertest
+            /// does not wrap, without forking dockertest. The hook can override the configuration
+            /// of dockertest, e.g., the labels or network the container is cleaned up and reached
+            /// through, which may break the test environment.
+            ///
+            /// [Config]: bollard::container::Config
+            /// [HostConfig]: bollard::models::HostConfig
+            pub fn set_raw_config_hook<F>(self, hook: F) -> Self
+            where
+                F: Fn(&mut bollard::container::Config<String>, &mut bollard::models::HostConfig)
+                    + Send
+                    + Sync
+                    + 'static,
+            {
+                Self {
+                    composition: self.composition.with_raw_config_hook(hook),
+                }
+            }
+
+            /// Place the started container within the parent cgroup `path`, e.g., the cgroup of
+            /// a CI job, such that runaway containers are kept within the resource quota of the
+            /// job.
+            ///
+            /// The path is interpreted by the cgroup driver of the docker daemon, e.g.,
+            /// `/ci/job-42` with the `cgroupfs` driver, or `ci-job42.slice` with the `systemd`
+            /// driver. The `DOCKERTEST_CGROUP_PARENT` environment variable overrides the parent
+            /// cgroup of every container. Only supported for Linux containers.
+            pub fn set_cgroup_parent<T: ToString>(self, path: T) -> Self {
+                Self {
+                    composition: self.composition.with_cgroup_parent(path),
+                }
+            }
+
+            /// Specify the isolation technology of the started Windows container.
+            ///
+            /// This value defaults to the isolation configured by the docker daemon. Isolation is
+            /// only supported for Windows containers. See the corresponding [docker reference] on
+            /// this topic.
+            ///
+            /// [docker reference]: https://learn.microsoft.com/en-us/virtualization/windowscontainers/manage-containers/hyperv-container
+            pub fn set_isolation(mut self, isolation: Isolation) -> Self {
+                self.composition.isolation(isolation);
+                self
+            }
+
             /// Specify a string handle used to retrieve a reference to the [RunningContainer]
             /// within the test body.
             ///
             /// This value defaults to the repository name of the image used when constructing
-            /// this container specification.
+            /// this container specification. The handle is part of the container name, and may
+            /// only contain letters, digits, `_`, `.` and `-`, which is checked on validation.
             ///
             /// [RunningContainer]: crate::container::RunningContainer
             pub fn set_handle<T: ToString>(self, handle: T) -> Self {
@@ -182,6 +403,18 @@ macro_rules! impl_specify_container {
                 }
             }
 
+            /// Set the [Resettable] trait object resetting the state of this container before each
+            /// test body, e.g., of a container shared by all tests of the test binary.
+            ///
+            /// If not specified, the state of the container is never reset.
+            ///
+            /// [Resettable]: crate::reset::Resettable
+            pub fn set_reset(self, reset: Box<dyn Resettable>) -> Self {
+                Self {
+                    composition: self.composition.with_reset(reset),
+                }
+            }
+
             /// Specify how to handle logging from the container.
             ///
             /// If not specified, [LogAction::Forward], [LogPolicy::OnError] and
@@ -201,9 +434,12 @@ macro_rules! impl_specify_container {
             ///
             /// Named volumes can be shared between multiple containers. By specifying the same
             /// volume name across multiple container specifications, both container will be
-            /// given access to the same volume.
+            /// given access to the same volume. If volumes are declared through
+            /// [DockerTest::with_named_volume], the volume name must be one of them.
             ///
             /// * `path_in_container` must be an absolute path.
+            ///
+            /// [DockerTest::with_named_volume]: crate::DockerTest::with_named_volume
             // TODO: Add a set_ variant
             pub fn modify_named_volume<T: ToString, S: ToString>(
                 &mut self,
@@ -223,7 +459,8 @@ macro_rules! impl_specify_container {
             /// Use named volumes if you require shared data access between containers.
             ///
             /// * `host_path` can either point to a file or directory that must exist on the host.
-            /// * `path_in_container` must be an absolute path.
+            /// * `path_in_container` must be an absolute path, e.g., `/data`, or `C:\\data` for a
+            ///   Windows container.
             pub fn modify_bind_mount<T: ToString, S: ToString>(
                 &mut self,
                 host_path: T,
@@ -233,6 +470,56 @@ macro_rules! impl_specify_container {
                 self
             }
 
+            /// Add a bind mount with the provided options, e.g., to mount the host path
+            /// read-only, with a mount propagation such as `rshared` or `slave`, or with the
+            /// shared `z` SELinux relabeling instead of the private `Z` relabeling of
+            /// [modify_bind_mount](Self::modify_bind_mount).
+            ///
+            /// Only the read-only option applies to Windows containers.
+            pub fn modify_bind_mount_with<T: ToString, S: ToString>(
+                &mut self,
+                host_path: T,
+                path_in_container: S,
+                options: BindMountOptions,
+            ) -> &mut Self {
+                self.composition
+                    .bind_mount_with(host_path, path_in_container, options);
+                self
+            }
+
+            /// Set the user and group of the container that owns the host paths bind mounted
+            /// into it, with a rootless docker daemon or a daemon remapping the users of
+            /// containers.
+            ///
+            /// The users of the container are then mapped to unprivileged users of the host,
+            /// which are unable to write to the bind mounts. The owner of the bind mounted host
+            /// paths is recursively changed before the container is created, such that `uid` and
+            /// `gid` within the container own them. With other docker daemons, the bind mounts
+            /// are left as is.
+            ///
+            /// The owner of the host paths is changed permanently, it is not restored once the
+            /// test terminates. Read-only bind mounts are left as is.
+            pub fn modify_bind_mount_owner(&mut self, uid: u32, gid: u32) -> &mut Self {
+                self.composition.bind_mount_owner(uid, gid);
+                self
+            }
+
+            /// Add a named pipe of the Windows host to the [RunningContainer].
+            ///
+            /// Both pipes must be named as `\\.\pipe\{name}`, e.g., `\\.\pipe\docker_engine`
+            /// to use the docker daemon of the host from within the container. Named pipes are only
+            /// supported for Windows containers.
+            ///
+            /// [RunningContainer]: crate::container::RunningContainer
+            pub fn modify_named_pipe<T: ToString, S: ToString>(
+                &mut self,
+                host_pipe: T,
+                container_pipe: S,
+            ) -> &mut Self {
+                self.composition.named_pipe(host_pipe, container_pipe);
+                self
+            }
+
             /// Inject the full, generated container name identified by `handle` into this
             /// container specification environment.
             ///
@@ -254,6 +541,46 @@ macro_rules! impl_specify_container {
                 self.composition.inject_container_name(handle, env);
                 self
             }
+
+            /// Inject the first network alias of the container identified by `handle` into this
+            /// container specification environment.
+            ///
+            /// Unlike the generated container name, the alias is stable across test runs, such
+            /// that connection strings built from it remain readable in logs and debuggers. The
+            /// container identified by `handle` must have a network alias, see
+            /// [append_network_alias](Self::append_network_alias).
+            ///
+            /// Unlike the generated container name, the alias is not unique to the test. On a
+            /// network shared with other tests, i.e., any but
+            /// [Network::Isolated](crate::Network::Isolated), the alias resolves to the
+            /// containers of every test executing in parallel with the same alias.
+            pub fn inject_network_alias<T: ToString, E: ToString>(
+                &mut self,
+                handle: T,
+                env: E,
+            ) -> &mut Self {
+                self.composition.inject_network_alias(handle, env);
+                self
+            }
+
+            /// Inject the host port published for the exported tcp `port` of the container
+            /// identified by `handle` into this container specification environment.
+            ///
+            /// If the exported port of the referenced container is not explicitly mapped through
+            /// [modify_port_map](Self::modify_port_map), it is published on a host port
+            /// assigned by the docker daemon. This container is then only created once the
+            /// referenced container has started, regardless of its [StartPolicy].
+            ///
+            /// [StartPolicy]: crate::StartPolicy
+            pub fn inject_host_port<T: ToString, E: ToString>(
+                &mut self,
+                handle: T,
+                port: u32,
+                env: E,
+            ) -> &mut Self {
+                self.composition.inject_host_port(handle, port, env);
+                self
+            }
         }
     };
 }
@@ -331,6 +658,10 @@ impl_specify_container!(TestSuiteSpecification);
 ///
 /// This containers' lifecycle is managed entirely within a single dockertest test body run.
 /// It is created, started, and ensured exited all within the scope of the test body.
+///
+/// A specification may be cloned to use it as a template for several tests, or for several
+/// containers within a test. Each clone owns a copy of the configured [WaitFor].
+#[derive(Clone, Debug)]
 pub struct TestBodySpecification {
     composition: Composition,
 }
@@ -358,6 +689,33 @@ impl TestBodySpecification {
             composition: Composition::with_image(image),
         }
     }
+
+    /// Set the [ExistingContainerPolicy] of this container, which determines how to handle an
+    /// existing container with the same name, e.g., one left behind by an aborted test.
+    ///
+    /// Defaults to [removing](ExistingContainerPolicy::Remove) the existing container.
+    pub fn set_existing_container_policy(self, policy: ExistingContainerPolicy) -> Self {
+        Self {
+            composition: self.composition.with_existing_container_policy(policy),
+        }
+    }
+
+    /// Take this container from a pool of containers created ahead of time. **Experimental.**
+    ///
+    /// Creating a container is then replaced by taking a created, but not yet started, container
+    /// of an identical definition from the pool, which is refilled to `size` containers in the
+    /// background. The pool is shared by all tests, also of later test binaries, until the
+    /// pooled containers are removed, e.g., through
+    /// `docker container prune --filter label=dockertest.pool`. Containers that are not taken
+    /// within an hour are removed once a pool is refilled.
+    ///
+    /// The definition of the container must not depend on the test, hence pooled containers
+    /// cannot use named volumes, injections, env templates or secrets.
+    pub fn set_warm_pool(self, size: usize) -> Self {
+        Self {
+            composition: self.composition.with_warm_pool(size),
+        }
+    }
 }
 
 impl_specify_container!(TestBodySpecification);
@@ -404,3 +762,33 @@ impl DynamicSpecification {
 }
 
 impl_specify_container!(DynamicSpecification);
+
+#[cfg(test)]
+mod tests {
+    use crate::specification::{ContainerSpecification, TestBodySpecification};
+    use crate::waitfor::{MessageSource, MessageWait};
+
+    // Tests that a cloned specification retains its custom wait condition
+    #[test]
+    fn test_clone_retains_wait_for() {
+        let template = TestBodySpecification::with_repository("postgres").set_wait_for(Box::new(
+            MessageWait {
+                message: "ready to accept connections".to_string(),
+                source: MessageSource::Stderr,
+                timeout: 5,
+            },
+        ));
+
+        let first = template.clone().set_handle("first").into_composition();
+        let second = template.set_handle("second").into_composition();
+        for composition in [first, second].iter() {
+            assert!(
+                composition
+                    .to_string()
+                    .contains("ready to accept connections"),
+                "{}",
+                composition
+            );
+        }
+    }
+}
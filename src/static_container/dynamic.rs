@@ -1,8 +1,7 @@
-use super::{add_to_network, disconnect_container, running_container_from_composition};
+use super::{add_to_network, disconnect_container, start_static_container, startup_panicked};
 use crate::{
-    composition::Composition,
-    container::{CreatedContainer, StaticExternalContainer},
-    DockerTestError, Network, PendingContainer, RunningContainer,
+    composition::Composition, container::CreatedContainer, DockerTestError, Network,
+    PendingContainer, RunningContainer,
 };
 use bollard::{
     container::{InspectContainerOptions, RemoveContainerOptions},
@@ -26,11 +25,9 @@ pub struct DynamicContainer {
 }
 
 #[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
 enum DynamicStatus {
-    /// The container was running prior to test invocation.
-    /// For all these containers we essentially handle them the way we handle external containers.
-    RunningPrior(RunningContainer),
-    /// The container is in a running state and was not running prior to test invocation
+    /// The container is in a running state, and its wait condition has been awaited.
     Running(RunningContainer, PendingContainer),
     Pending(PendingContainer),
     Failed(DockerTestError, Option<String>),
@@ -50,23 +47,14 @@ impl DynamicContainers {
             match &container.status {
                 DynamicStatus::Pending(p) | DynamicStatus::Running(_, p) => {
                     match (network, network_setting) {
-                        (Some(n), Network::Isolated) => add_to_network(&p.id, n, client).await,
+                        (Some(n), Network::Isolated | Network::Shared) => {
+                            add_to_network(&p.id, n, client).await
+                        }
                         _ => Ok(()),
                     }?;
                     Ok(CreatedContainer::Pending(p.clone()))
                 }
                 DynamicStatus::Failed(e, _) => Err(e.clone()),
-                DynamicStatus::RunningPrior(c) => {
-                    match (network, network_setting) {
-                        (Some(n), Network::Isolated) => add_to_network(&c.id, n, client).await,
-                        _ => Ok(()),
-                    }?;
-
-                    Ok(CreatedContainer::StaticExternal(StaticExternalContainer {
-                        handle: c.handle.clone(),
-                        id: c.id().to_string(),
-                    }))
-                }
             }
         } else {
             let details = client
@@ -75,46 +63,56 @@ impl DynamicContainers {
 
             match details {
                 Ok(d) => {
-                    if let Some(container_state) = &d.state {
-                        if let Some(status) = container_state.status {
-                            if status != ContainerStateStatusEnum::RUNNING {
-                                let options = Some(RemoveContainerOptions {
-                                    force: true,
-                                    ..Default::default()
-                                });
-                                client
-                                    .remove_container(&composition.container_name, options)
-                                    .await
-                                    .map_err(|e| {
-                                        DockerTestError::Daemon(format!(
-                                            "failed to remove existing container: {}",
-                                            e
-                                        ))
-                                    })?;
-                            }
+                    let status = d.state.as_ref().and_then(|s| s.status);
+                    let id = match d.id {
+                        Some(id) => id,
+                        None => {
+                            return Err(DockerTestError::Daemon(
+                                "failed to retrieve container id for dynamic container".to_string(),
+                            ))
                         }
-                    }
-                    let running =
-                        running_container_from_composition(composition, client, d).await?;
-
-                    // Regardless of network mode the first to create a Dynamic container is
-                    // responsible for adding it to the network
-                    if let Some(n) = network {
-                        add_to_network(&running.id, n, client).await?;
-                    }
+                    };
 
-                    let external = StaticExternalContainer {
-                        handle: running.handle.clone(),
-                        id: running.id().to_string(),
+                    // A created container may be about to be started by another test binary,
+                    // and is started instead of removed, as starting it twice is harmless.
+                    let pending = match status {
+                        Some(ContainerStateStatusEnum::RUNNING)
+                        | Some(ContainerStateStatusEnum::CREATED) => {
+                            // Regardless of network mode the first to create a Dynamic container
+                            // is responsible for adding it to the network
+                            if let Some(n) = network {
+                                add_to_network(&id, n, client).await?;
+                            }
+                            composition.into_pending(client, id)
+                        }
+                        _ => {
+                            let options = Some(RemoveContainerOptions {
+                                force: true,
+                                ..Default::default()
+                            });
+                            client
+                                .remove_container(&composition.container_name, options)
+                                .await
+                                .map_err(|e| {
+                                    DockerTestError::Daemon(format!(
+                                        "failed to remove existing container: {}",
+                                        e
+                                    ))
+                                })?;
+                            self.create_dynamic_container(composition, client, network)
+                                .await?
+                        }
                     };
+
+                    // The existing container is started like a created one, such that its wait
+                    // condition is awaited before it is handed over.
                     map.insert(
-                        running.name.clone(),
+                        pending.name.clone(),
                         DynamicContainer {
-                            status: DynamicStatus::RunningPrior(running),
+                            status: DynamicStatus::Pending(pending.clone()),
                         },
                     );
-
-                    Ok(CreatedContainer::StaticExternal(external))
+                    Ok(CreatedContainer::Pending(pending))
                 }
                 Err(e) => match e {
                     bollard::errors::Error::DockerResponseServerError {
@@ -157,10 +155,18 @@ impl DynamicContainers {
 
         if let Some(existing) = map.get_mut(&container.name) {
             match &existing.status {
-                DynamicStatus::Running(r, _) | DynamicStatus::RunningPrior(r) => Ok(r.clone()),
+                DynamicStatus::Running(r, _) => Ok(r.clone()),
                 DynamicStatus::Pending(p) => {
-                    let cloned = p.clone();
-                    let running = cloned.start_internal().await;
+                    let running = match start_static_container(p.clone()).await {
+                        Ok(running) => running,
+                        Err(panic) => {
+                            existing.status = DynamicStatus::Failed(
+                                startup_panicked(container),
+                                Some(container.id.clone()),
+                            );
+                            std::panic::resume_unwind(panic);
+                        }
+                    };
                     match running {
                         Ok(r) => {
                             existing.status = DynamicStatus::Running(r.clone(), p.clone());
@@ -182,20 +188,6 @@ impl DynamicContainers {
         }
     }
 
-    pub async fn prior_running_containers(&self) -> Vec<RunningContainer> {
-        self.inner
-            .read()
-            .await
-            .values()
-            .filter_map(|d| match &d.status {
-                DynamicStatus::Running(_, _)
-                | DynamicStatus::Pending(_)
-                | DynamicStatus::Failed(_, _) => None,
-                DynamicStatus::RunningPrior(c) => Some(c.clone()),
-            })
-            .collect()
-    }
-
     pub async fn disconnect(
         &self,
         client: &Docker,
@@ -204,7 +196,7 @@ impl DynamicContainers {
         to_cleanup: &HashSet<&str>,
     ) {
         match network_mode {
-            Network::External(_) | Network::Singular => (),
+            Network::External(_) | Network::Singular | Network::Shared => (),
             Network::Isolated => {
                 let containers = self.inner.read().await;
                 for (id, _) in containers.iter() {
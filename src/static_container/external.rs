@@ -30,7 +30,8 @@ impl ExternalContainers {
         if let Some(running) = map.get(&composition.container_name) {
             match network_mode {
                 Network::Singular | Network::External(_) => (),
-                Network::Isolated => {
+                // The shared network may have been recreated since the container was included.
+                Network::Isolated | Network::Shared => {
                     if let Some(n) = network {
                         add_to_network(running.id(), n, client).await?;
                     }
@@ -54,8 +55,8 @@ impl ExternalContainers {
             match network_mode {
                 Network::External(_) => (),
                 // The first to include external containers are responsible for including them in
-                // the singular/isolated network
-                Network::Isolated | Network::Singular => {
+                // the singular/isolated/shared network
+                Network::Isolated | Network::Singular | Network::Shared => {
                     if let Some(n) = network {
                         add_to_network(running.id(), n, client).await?;
                     }
@@ -87,7 +88,7 @@ impl ExternalContainers {
         // is externally managed for the external container.
         // For singular network we perform the same behavior, we do not disconnect.
         match network_mode {
-            Network::Singular | Network::External(_) => (),
+            Network::Singular | Network::External(_) | Network::Shared => (),
             Network::Isolated => {
                 self.disconnect_impl(client, network, to_cleanup).await;
             }
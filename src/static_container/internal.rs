@@ -6,7 +6,10 @@ use tokio::sync::RwLock;
 
 use bollard::Docker;
 
-use super::{add_to_network, disconnect_container, remove_container};
+use super::{
+    add_to_network, disconnect_container, remove_container, start_static_container,
+    startup_panicked,
+};
 use crate::{
     composition::Composition, DockerTestError, Network, PendingContainer, RunningContainer,
 };
@@ -87,8 +90,16 @@ impl InternalContainers {
                 InternalStatus::Failed(e, _) => Err(e.clone()),
                 InternalStatus::Running(r, _) => Ok(r.clone()),
                 InternalStatus::Pending(p) => {
-                    let cloned = p.clone();
-                    let running = cloned.start_internal().await;
+                    let running = match start_static_container(p.clone()).await {
+                        Ok(running) => running,
+                        Err(panic) => {
+                            c.status = InternalStatus::Failed(
+                                startup_panicked(container),
+                                Some(container.id.clone()),
+                            );
+                            std::panic::resume_unwind(panic);
+                        }
+                    };
                     match running {
                         Ok(r) => {
                             c.status = InternalStatus::Running(r.clone(), p.clone());
@@ -116,8 +127,18 @@ impl InternalContainers {
         }
     }
 
-    pub async fn cleanup(&self, client: &Docker, network: &str, to_cleanup: &HashSet<&str>) {
-        self.disconnect(client, network, to_cleanup).await;
+    pub async fn cleanup(
+        &self,
+        client: &Docker,
+        network: &str,
+        network_mode: &Network,
+        to_cleanup: &HashSet<&str>,
+    ) {
+        // Other tests may still use the container on the shared network, which disconnects
+        // all containers once it is deleted.
+        if !matches!(network_mode, Network::Shared) {
+            self.disconnect(client, network, to_cleanup).await;
+        }
         let to_remove = self.decrement_completion_counters(to_cleanup).await;
         for to_cleanup in to_remove {
             remove_container(&to_cleanup, client).await;
@@ -144,10 +165,13 @@ impl InternalContainers {
         if let Some(c) = map.get_mut(&composition.container_name) {
             match &c.status {
                 InternalStatus::Pending(p) | InternalStatus::Running(_, p) => {
-                    // Only when the Isolated network mode is set do we need to add it to the
+                    // Only when the Isolated/Shared network mode is set do we need to add it to the
                     // network, as for External/Singular it will be added upon creation.
+                    // The shared network may have been recreated since the container was added.
                     match (network, network_setting) {
-                        (Some(n), Network::Isolated) => add_to_network(&p.id, n, client).await,
+                        (Some(n), Network::Isolated | Network::Shared) => {
+                            add_to_network(&p.id, n, client).await
+                        }
                         _ => Ok(()),
                     }?;
 
@@ -11,8 +11,11 @@ use bollard::{
     container::RemoveContainerOptions, models::ContainerInspectResponse,
     network::DisconnectNetworkOptions, Docker,
 };
+use futures::FutureExt;
 use lazy_static::lazy_static;
+use std::any::Any;
 use std::collections::HashSet;
+use std::panic::AssertUnwindSafe;
 use tracing::{event, Level};
 
 mod dynamic;
@@ -81,14 +84,7 @@ impl StaticContainers {
     }
 
     pub async fn external_containers(&self) -> Vec<RunningContainer> {
-        let mut external = self.external.containers().await;
-        // Dynamic containers that were running prior to test invocation are managed the same way
-        // as external containers
-        let mut dynamic_running_prior = self.dynamic.prior_running_containers().await;
-
-        external.append(&mut dynamic_running_prior);
-
-        external
+        self.external.containers().await
     }
 
     pub async fn start(
@@ -118,7 +114,9 @@ impl StaticContainers {
         to_cleanup: Vec<&str>,
     ) {
         let cleanup: HashSet<&str> = to_cleanup.into_iter().collect();
-        self.internal.cleanup(client, network, &cleanup).await;
+        self.internal
+            .cleanup(client, network, network_mode, &cleanup)
+            .await;
         self.dynamic
             .disconnect(client, network, network_mode, &cleanup)
             .await;
@@ -128,7 +126,7 @@ impl StaticContainers {
     }
 }
 
-async fn add_to_network(
+pub(crate) async fn add_to_network(
     container_id: &str,
     network: &str,
     client: &Docker,
@@ -171,6 +169,29 @@ async fn add_to_network(
     }
 }
 
+/// Start a static container on behalf of every test using it.
+///
+/// The starting test holds the lock of the store of static containers, shared by all tests of the
+/// test binary, while the container starts. Tokio locks are never poisoned, hence a panic while
+/// starting, e.g., within the wait condition of the container, would leave the container pending
+/// for the other tests. The panic is instead returned, for the caller to record the container as
+/// failed before propagating the panic.
+async fn start_static_container(
+    pending: PendingContainer,
+) -> Result<Result<RunningContainer, DockerTestError>, Box<dyn Any + Send>> {
+    AssertUnwindSafe(pending.start_internal())
+        .catch_unwind()
+        .await
+}
+
+/// The failure recorded for a static container whose startup panicked.
+fn startup_panicked(container: &PendingContainer) -> DockerTestError {
+    DockerTestError::Startup(format!(
+        "startup of static container `{}` panicked",
+        container.name
+    ))
+}
+
 async fn remove_container(id: &str, client: &Docker) {
     let remove_opts = Some(RemoveContainerOptions {
         force: true,
@@ -181,7 +202,7 @@ async fn remove_container(id: &str, client: &Docker) {
         event!(Level::ERROR, "failed to remove static container: {}", e);
     }
 }
-async fn disconnect_container(client: &Docker, container_id: &str, network: &str) {
+pub(crate) async fn disconnect_container(client: &Docker, container_id: &str, network: &str) {
     let opts = DisconnectNetworkOptions::<&str> {
         container: container_id,
         force: true,
@@ -203,13 +224,22 @@ async fn running_container_from_composition(
     if let Some(id) = container_details.id {
         Ok(RunningContainer {
             client: client.clone(),
+            redactions: composition.redactions(),
             id,
             name: composition.container_name.clone(),
             handle: composition.container_name,
             ip: std::net::Ipv4Addr::UNSPECIFIED,
             ports: HostPortMappings::default(),
+            network: String::new(),
+            on_network: false,
             is_static: true,
+            expect_exit: composition.expect_exit,
+            reused: false,
             log_options: composition.log_options,
+            details: Default::default(),
+            inspected: None,
+            env: Default::default(),
+            followed: Default::default(),
         })
     } else {
         Err(DockerTestError::Daemon(
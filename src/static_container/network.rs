@@ -1,12 +1,20 @@
 use std::{collections::HashMap, sync::Arc};
 
-use crate::{runner::add_self_to_network, DockerTestError};
+use crate::{
+    address_pool::AddressPool,
+    runner::{
+        add_self_to_network, create_network, create_network_with_fallback, delete_network,
+        network_creation_error,
+    },
+    utils::generate_random_string,
+    DockerTestError,
+};
 use bollard::{
-    network::{CreateNetworkOptions, ListNetworksOptions},
+    network::{DisconnectNetworkOptions, InspectNetworkOptions, ListNetworksOptions},
     Docker,
 };
 use lazy_static::lazy_static;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use tracing::{event, Level};
 
 static SINGULAR_NETWORK_NAME: &str = "dockertest";
@@ -19,6 +27,15 @@ lazy_static! {
 #[derive(Default)]
 pub struct ScopedNetworks {
     singular: Arc<RwLock<HashMap<String, SingularNetwork>>>,
+    shared: Arc<Mutex<HashMap<String, SharedNetwork>>>,
+}
+
+/// A network shared by all tests of a namespace, see [Network::Shared](crate::Network::Shared).
+#[derive(Debug)]
+pub struct SharedNetwork {
+    name: String,
+    /// The number of tests currently using the network.
+    users: usize,
 }
 
 #[derive(Debug)]
@@ -45,6 +62,7 @@ impl ScopedNetworks {
         client: &Docker,
         self_container: Option<&str>,
         namespace: &str,
+        address_pool: Option<&AddressPool>,
     ) -> Result<String, DockerTestError> {
         let mut networks = self.singular.write().await;
 
@@ -67,7 +85,7 @@ impl ScopedNetworks {
                 );
                 return Ok(id);
             } else {
-                match create_singular_network_impl(client, network_name).await {
+                match create_singular_network_impl(client, network_name, address_pool).await {
                     Ok(id) => Ok(id),
                     Err(e) => {
                         networks.insert(
@@ -102,18 +120,109 @@ impl ScopedNetworks {
     }
 }
 
+impl ScopedNetworks {
+    /// Retrieve the name of the shared network of the namespace, creating it if this is the
+    /// only test using it.
+    pub(crate) async fn acquire_shared_network(
+        &self,
+        client: &Docker,
+        self_container: Option<&str>,
+        namespace: &str,
+        address_pool: Option<&AddressPool>,
+    ) -> Result<String, DockerTestError> {
+        let mut networks = self.shared.lock().await;
+
+        if let Some(network) = networks.get_mut(namespace) {
+            network.users += 1;
+            return Ok(network.name.clone());
+        }
+
+        // A new name for each creation, as a previous network may still be in removal.
+        let name = format!(
+            "{}-shared-{}",
+            self.name(namespace),
+            generate_random_string(10)
+        );
+        create_network(client, &name, self_container, address_pool, false).await?;
+        networks.insert(
+            namespace.to_string(),
+            SharedNetwork {
+                name: name.clone(),
+                users: 1,
+            },
+        );
+
+        Ok(name)
+    }
+
+    /// Release the shared network of the namespace, deleting it if this was the last test
+    /// using it.
+    pub(crate) async fn release_shared_network(&self, client: &Docker, namespace: &str) {
+        // The lock is held during deletion, such that a test acquiring the network
+        // concurrently creates a new one.
+        let mut networks = self.shared.lock().await;
+
+        let network = match networks.get_mut(namespace) {
+            Some(network) => network,
+            None => return,
+        };
+        network.users -= 1;
+        if network.users > 0 {
+            return;
+        }
+
+        let name = network.name.clone();
+        networks.remove(namespace);
+        event!(Level::DEBUG, "deleting shared network `{}`", name);
+
+        // Static containers, and the container of dockertest itself, remain connected to the
+        // network until it is deleted.
+        disconnect_all_containers(client, &name).await;
+        delete_network(client, &name, None).await;
+    }
+}
+
+async fn disconnect_all_containers(client: &Docker, network_name: &str) {
+    let network = match client
+        .inspect_network(network_name, None::<InspectNetworkOptions<&str>>)
+        .await
+    {
+        Ok(network) => network,
+        Err(e) => {
+            event!(
+                Level::ERROR,
+                "unable to inspect shared network `{}`: {}",
+                network_name,
+                e
+            );
+            return;
+        }
+    };
+
+    for id in network.containers.unwrap_or_default().into_keys() {
+        let opts = DisconnectNetworkOptions::<&str> {
+            container: &id,
+            force: true,
+        };
+        if let Err(e) = client.disconnect_network(network_name, opts).await {
+            event!(
+                Level::ERROR,
+                "unable to disconnect container `{}` from shared network: {}",
+                id,
+                e
+            );
+        }
+    }
+}
+
 async fn create_singular_network_impl(
     client: &Docker,
     network_name: String,
+    address_pool: Option<&AddressPool>,
 ) -> Result<String, DockerTestError> {
-    let config = CreateNetworkOptions {
-        name: network_name.as_str(),
-        ..Default::default()
-    };
-
     event!(Level::TRACE, "creating singular network");
 
-    match client.create_network(config).await {
+    match create_network_with_fallback(client, &network_name, address_pool, false).await {
         Ok(resp) => match resp.id {
             Some(id) => Ok(id),
             None => Err(DockerTestError::Startup(
@@ -123,25 +232,25 @@ async fn create_singular_network_impl(
         Err(e) => {
             match e {
                 bollard::errors::Error::DockerResponseServerError {
-                    status_code,
-                    message,
+                    status_code: 409, ..
                 } => {
-                    if status_code == 409 {
-                        // We assume that we got a conflict due to multiple networks with the name
-                        // `dockertest`, and therefore assume that 'existing_dockertest_network' will
-                        // return the conflicting network.
-                        Ok(existing_dockertest_network(client, &network_name)
-                            .await?
-                            .unwrap())
-                    } else {
-                        Err(DockerTestError::Startup(format!(
-                            "failed to create singular network: {message}",
-                        )))
-                    }
+                    // We assume that we got a conflict due to multiple networks with the name
+                    // `dockertest`, and therefore assume that 'existing_dockertest_network' will
+                    // return the conflicting network.
+                    existing_dockertest_network(client, &network_name)
+                        .await?
+                        .ok_or_else(|| {
+                            DockerTestError::Startup(format!(
+                                "singular network `{}` conflicted on creation, but does not exist",
+                                network_name
+                            ))
+                        })
                 }
-                _ => Err(DockerTestError::Startup(format!(
-                    "failed to create singular network: {e}"
-                ))),
+                _ => Err(network_creation_error(
+                    "failed to create singular network",
+                    e,
+                    address_pool,
+                )),
             }
         }
     }
@@ -163,19 +272,23 @@ async fn existing_dockertest_network(
     let mut highest_timestamp: Option<String> = None;
     let mut highest_timestamp_id: Option<String> = None;
 
-    // We assume id is present on the returned networks
     for n in networks {
+        // Networks without an id cannot be used
+        let id = match n.id {
+            Some(id) => id,
+            None => continue,
+        };
         if let Some(name) = n.name {
             if name == network_name {
                 if let Some(timestamp) = n.created {
                     if let Some(compare_timestamp) = &highest_timestamp {
                         if timestamp.as_str() > compare_timestamp.as_str() {
                             highest_timestamp = Some(timestamp);
-                            highest_timestamp_id = Some(n.id.unwrap());
+                            highest_timestamp_id = Some(id);
                         }
                     } else {
                         highest_timestamp = Some(timestamp);
-                        highest_timestamp_id = Some(n.id.unwrap());
+                        highest_timestamp_id = Some(id);
                     }
                 }
             }
@@ -0,0 +1,168 @@
+//! A summary of every test environment of the process, printed to stderr as it exits, or by
+//! [finish_suite](crate::finish_suite), see [DockerTest::with_suite_summary].
+//!
+//! Every environment started by the process is accounted for, regardless of whether its test
+//! opted in, such that the summary reflects the overhead of the whole suite. Leaked resources are
+//! the containers and volumes that could not be removed during teardown.
+//!
+//! [DockerTest::with_suite_summary]: crate::DockerTest::with_suite_summary
+
+use crate::background;
+
+use lazy_static::lazy_static;
+
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+lazy_static! {
+    static ref SUMMARY: Mutex<SuiteSummary> = Mutex::new(SuiteSummary::default());
+}
+
+/// Whether any test of the process opted in to the summary, and it is not yet printed.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The accumulated statistics of the environments started by the process.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct SuiteSummary {
+    environments: usize,
+    containers: usize,
+    /// The time spent pulling images.
+    pull: Duration,
+    /// The time spent starting containers, excluding pulling their images.
+    start: Duration,
+    images: BTreeSet<String>,
+    leaked_containers: usize,
+    leaked_volumes: usize,
+}
+
+impl SuiteSummary {
+    fn render(&self) -> String {
+        let images: Vec<&str> = self.images.iter().map(|i| i.as_str()).collect();
+        format!(
+            "dockertest: started {} environments with {} containers\n  \
+             pulling images:      {:.1?}\n  \
+             starting containers: {:.1?}\n  \
+             images used:         {}\n  \
+             leaked resources:    {} containers, {} volumes\n",
+            self.environments,
+            self.containers,
+            self.pull,
+            self.start,
+            if images.is_empty() {
+                "none".to_string()
+            } else {
+                images.join(", ")
+            },
+            self.leaked_containers,
+            self.leaked_volumes
+        )
+    }
+}
+
+/// Print the summary once the process exits, or the suite finishes.
+pub(crate) fn enable() {
+    background::register_exit_hook();
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Account for a started environment.
+pub(crate) fn record_startup(images: &[String], pull: Duration, startup: Duration) {
+    let mut summary = SUMMARY.lock().unwrap_or_else(|e| e.into_inner());
+    summary.environments += 1;
+    summary.containers += images.len();
+    summary.pull += pull;
+    summary.start += startup.saturating_sub(pull);
+    summary.images.extend(images.iter().cloned());
+}
+
+/// Account for the containers and volumes that could not be removed during teardown.
+pub(crate) fn record_leaked(containers: usize, volumes: usize) {
+    let mut summary = SUMMARY.lock().unwrap_or_else(|e| e.into_inner());
+    summary.leaked_containers += containers;
+    summary.leaked_volumes += volumes;
+}
+
+/// Print the summary to stderr, if enabled and not yet printed.
+pub(crate) fn print() {
+    // Printing must not panic, as it is invoked as the process exits.
+    let _ = write_summary(&ENABLED, &SUMMARY, &mut std::io::stderr());
+}
+
+/// Write the summary to `out` if `enabled`, disabling it such that the summary is written once,
+/// as it is printed both by [finish_suite](crate::finish_suite) and as the process exits.
+fn write_summary<W: Write>(
+    enabled: &AtomicBool,
+    summary: &Mutex<SuiteSummary>,
+    out: &mut W,
+) -> std::io::Result<()> {
+    if enabled.swap(false, Ordering::Relaxed) {
+        let summary = summary.lock().unwrap_or_else(|e| e.into_inner());
+        out.write_all(summary.render().as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_summary, SuiteSummary};
+
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    // Tests that the summary lists the accumulated statistics of the suite
+    #[test]
+    fn test_render_summary() {
+        let summary = SuiteSummary {
+            environments: 2,
+            containers: 3,
+            pull: Duration::from_millis(1500),
+            start: Duration::from_secs(4),
+            images: vec!["redis:7".to_string(), "postgres:16".to_string()]
+                .into_iter()
+                .collect(),
+            leaked_containers: 1,
+            leaked_volumes: 0,
+        };
+        assert_eq!(
+            summary.render(),
+            "dockertest: started 2 environments with 3 containers\n  \
+             pulling images:      1.5s\n  \
+             starting containers: 4.0s\n  \
+             images used:         postgres:16, redis:7\n  \
+             leaked resources:    1 containers, 0 volumes\n"
+        );
+        assert!(SuiteSummary::default()
+            .render()
+            .contains("images used:         none"));
+    }
+
+    // Tests that the summary is only written once enabled, and at most once
+    #[test]
+    fn test_write_summary_once() {
+        let summary = Mutex::new(SuiteSummary {
+            environments: 1,
+            containers: 1,
+            ..Default::default()
+        });
+
+        let mut out = Vec::new();
+        write_summary(&AtomicBool::new(false), &summary, &mut out).unwrap();
+        assert!(out.is_empty(), "a disabled summary should not be written");
+
+        let enabled = AtomicBool::new(true);
+        write_summary(&enabled, &summary, &mut out).unwrap();
+        let written = String::from_utf8(out.clone()).unwrap();
+        assert!(
+            written.starts_with("dockertest: started 1 environments with 1 containers\n"),
+            "unexpected summary: {}",
+            written
+        );
+
+        write_summary(&enabled, &summary, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), written);
+    }
+}
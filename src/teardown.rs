@@ -0,0 +1,301 @@
+//! The final state of the containers of an environment, captured during teardown.
+
+use crate::composition::{Composition, StartPolicy};
+use crate::template::{self, Segment};
+
+use bollard::models::ContainerState;
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+/// The state of a container right before it was torn down.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContainerExit {
+    /// The handle of the container within the test body.
+    pub handle: String,
+    /// The generated docker name of the container.
+    pub name: String,
+    /// Whether the container was still running.
+    pub running: bool,
+    /// The exit code of the container, if it exited.
+    pub exit_code: Option<i64>,
+    /// Whether the container was killed as it ran out of memory.
+    pub oom_killed: bool,
+    /// The error reported by the docker daemon for the container, or the failure to inspect it.
+    pub error: Option<String>,
+    /// When the container exited, as reported by the docker daemon, if it exited.
+    pub finished_at: Option<String>,
+}
+
+impl ContainerExit {
+    /// Capture the exit information of the inspected state of a container.
+    pub(crate) fn from_state(handle: &str, name: &str, state: Option<ContainerState>) -> Self {
+        let state = state.unwrap_or_default();
+        let running = state.running == Some(true);
+        ContainerExit {
+            handle: handle.to_string(),
+            name: name.to_string(),
+            running,
+            exit_code: state.exit_code.filter(|_| !running),
+            oom_killed: state.oom_killed == Some(true),
+            error: state.error.filter(|e| !e.is_empty()),
+            // The daemon reports the zero time for containers that never exited.
+            finished_at: state
+                .finished_at
+                .filter(|t| !running && !t.starts_with("0001-01-01")),
+        }
+    }
+
+    /// Capture the failure to inspect the container.
+    pub(crate) fn from_error(handle: &str, name: &str, error: String) -> Self {
+        ContainerExit {
+            handle: handle.to_string(),
+            name: name.to_string(),
+            error: Some(format!("failed to inspect the container: {}", error)),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the container was still running, or exited successfully, without running out of
+    /// memory or reporting an error.
+    pub fn is_clean(&self) -> bool {
+        !self.oom_killed && self.error.is_none() && self.exit_code.unwrap_or(0) == 0
+    }
+}
+
+/// The final state of every container of the environment, captured during teardown before the
+/// containers are removed, and after they are stopped with
+/// [DockerTest::with_graceful_stop](crate::DockerTest::with_graceful_stop).
+///
+/// Returned by [DockerTest::run_with_report](crate::DockerTest::run_with_report) and
+/// [Runner::teardown](crate::Runner::teardown), and provided to the hook of
+/// [DockerTest::with_teardown_hook](crate::DockerTest::with_teardown_hook), such that
+/// post-mortem tooling can assert that the whole environment shut down cleanly.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TeardownReport {
+    /// The final state of each container.
+    pub containers: Vec<ContainerExit>,
+}
+
+impl TeardownReport {
+    /// The final state of the container identified by `handle`, if any.
+    pub fn container(&self, handle: &str) -> Option<&ContainerExit> {
+        self.containers.iter().find(|c| c.handle == handle)
+    }
+
+    /// Whether every container shut down cleanly, see [ContainerExit::is_clean].
+    pub fn is_clean(&self) -> bool {
+        self.containers.iter().all(ContainerExit::is_clean)
+    }
+
+    /// The containers that did not shut down cleanly.
+    pub fn unclean(&self) -> impl Iterator<Item = &ContainerExit> {
+        self.containers.iter().filter(|c| !c.is_clean())
+    }
+}
+
+/// Invoked with the [TeardownReport] once the environment is torn down.
+#[derive(Clone)]
+pub(crate) struct TeardownHook(Arc<dyn Fn(&TeardownReport) + Send + Sync>);
+
+impl TeardownHook {
+    pub(crate) fn new<F>(hook: F) -> Self
+    where
+        F: Fn(&TeardownReport) + Send + Sync + 'static,
+    {
+        TeardownHook(Arc::new(hook))
+    }
+
+    pub(crate) fn invoke(&self, report: &TeardownReport) {
+        (self.0)(report)
+    }
+}
+
+impl std::fmt::Debug for TeardownHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TeardownHook")
+    }
+}
+
+/// The stage each container is stopped in by an ordered teardown, keyed by handle. Containers
+/// are stopped in descending order of their stage, such that every container is stopped before
+/// the containers it depends on.
+///
+/// The stage reflects the order containers are started in, i.e., relaxed containers before
+/// strict containers in the order they were provided, before each group in order. A container
+/// also depends on the containers whose name, network alias or host port is injected into its
+/// environment, also through the `{handle:NAME}` and `{host_port:NAME:PORT}` placeholders of its
+/// environment variable values, see [template].
+pub(crate) fn stop_stages(compositions: &[Composition]) -> HashMap<String, usize> {
+    let groups: BTreeSet<(u32, &str)> = compositions
+        .iter()
+        .filter_map(|c| match c.start_policy() {
+            StartPolicy::Group(name, order) => Some((*order, name.as_str())),
+            _ => None,
+        })
+        .collect();
+    let strict = compositions
+        .iter()
+        .filter(|c| *c.start_policy() == StartPolicy::Strict)
+        .count();
+
+    let mut strict_position = 0;
+    let mut stages: HashMap<String, usize> = HashMap::new();
+    for c in compositions {
+        let stage = match c.start_policy() {
+            StartPolicy::Relaxed => 0,
+            StartPolicy::Strict => {
+                strict_position += 1;
+                strict_position
+            }
+            StartPolicy::Group(name, order) => {
+                // The group is always present
+                let position = groups
+                    .iter()
+                    .position(|g| *g == (*order, name.as_str()))
+                    .unwrap_or_default();
+                1 + strict + position
+            }
+        };
+        stages.insert(c.handle(), stage);
+    }
+
+    // Raise each container above its dependencies, bounded by the number of containers such that
+    // a cyclic dependency terminates.
+    for _ in 0..compositions.len() {
+        let mut changed = false;
+        for c in compositions {
+            let templated: Vec<String> = c
+                .env
+                .values()
+                .flat_map(|value| template::parse(value).unwrap_or_default())
+                .filter_map(|segment| match segment {
+                    Segment::Placeholder(placeholder) => Some(placeholder.handle().to_string()),
+                    Segment::Literal(_) => None,
+                })
+                .collect();
+            let dependencies = c
+                .inject_container_name_env
+                .iter()
+                .map(|(handle, _)| handle)
+                .chain(c.inject_network_alias_env.iter().map(|(handle, _)| handle))
+                .chain(c.inject_host_port_env.iter().map(|(handle, _, _)| handle))
+                .chain(templated.iter());
+            let required = dependencies
+                .filter_map(|handle| stages.get(handle))
+                .map(|stage| stage + 1)
+                .max()
+                .unwrap_or_default();
+            let stage = stages.entry(c.handle()).or_default();
+            if *stage < required {
+                *stage = required;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    stages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{stop_stages, ContainerExit, TeardownReport};
+    use crate::composition::{Composition, StartPolicy};
+
+    use bollard::models::ContainerState;
+
+    // Tests that the exit information is captured from the inspected state
+    #[test]
+    fn test_from_state() {
+        let exited = ContainerState {
+            running: Some(false),
+            exit_code: Some(3),
+            oom_killed: Some(false),
+            error: Some(String::new()),
+            finished_at: Some("2024-01-01T00:00:00Z".to_string()),
+            ..Default::default()
+        };
+        let exit = ContainerExit::from_state("db", "ns-db-id", Some(exited));
+        assert_eq!(exit.exit_code, Some(3));
+        assert_eq!(exit.error, None);
+        assert_eq!(exit.finished_at.as_deref(), Some("2024-01-01T00:00:00Z"));
+        assert!(!exit.is_clean());
+
+        let running = ContainerState {
+            running: Some(true),
+            exit_code: Some(0),
+            finished_at: Some("0001-01-01T00:00:00Z".to_string()),
+            ..Default::default()
+        };
+        let exit = ContainerExit::from_state("db", "ns-db-id", Some(running));
+        assert!(exit.running);
+        assert_eq!(exit.exit_code, None);
+        assert_eq!(exit.finished_at, None);
+        assert!(exit.is_clean());
+    }
+
+    // Tests that a single unclean container makes the environment unclean
+    #[test]
+    fn test_is_clean() {
+        let clean = ContainerExit {
+            handle: "clean".to_string(),
+            exit_code: Some(0),
+            ..Default::default()
+        };
+        let killed = ContainerExit {
+            handle: "killed".to_string(),
+            oom_killed: true,
+            ..Default::default()
+        };
+        let uninspected = ContainerExit::from_error("gone", "ns-gone-id", "not found".to_string());
+
+        let report = TeardownReport {
+            containers: vec![clean.clone()],
+        };
+        assert!(report.is_clean());
+
+        let report = TeardownReport {
+            containers: vec![clean, killed, uninspected],
+        };
+        assert!(!report.is_clean());
+        let unclean: Vec<&str> = report.unclean().map(|c| c.handle.as_str()).collect();
+        assert_eq!(unclean, ["killed", "gone"]);
+        assert!(report.container("clean").unwrap().is_clean());
+    }
+
+    // Tests that containers are staged after the containers started before them and after the
+    // containers injected into their environment
+    #[test]
+    fn test_stop_stages() {
+        let postgres = Composition::with_repository("postgres");
+        let mut app = Composition::with_repository("app");
+        app.inject_container_name("postgres", "DB_HOST");
+        let migrate =
+            Composition::with_repository("migrate").with_start_policy(StartPolicy::Strict);
+        let web = Composition::with_repository("web")
+            .with_start_policy(StartPolicy::Group("frontend".to_string(), 1));
+        let worker = Composition::with_repository("worker")
+            .with_start_policy(StartPolicy::Group("backend".to_string(), 0));
+        let mut proxy = Composition::with_repository("proxy");
+        proxy.env.insert(
+            "UPSTREAM".to_string(),
+            "http://localhost:{host_port:web:80}".to_string(),
+        );
+        let mut cache = Composition::with_repository("cache");
+        cache
+            .env
+            .insert("BACKEND".to_string(), "{handle:app}".to_string());
+
+        let stages = stop_stages(&[postgres, app, migrate, web, worker, proxy, cache]);
+        assert_eq!(stages["postgres"], 0);
+        assert_eq!(stages["app"], 1);
+        assert_eq!(stages["migrate"], 1);
+        assert_eq!(stages["worker"], 2);
+        assert_eq!(stages["web"], 3);
+        assert_eq!(stages["proxy"], 4);
+        assert_eq!(stages["cache"], 2);
+    }
+}
@@ -0,0 +1,248 @@
+//! Placeholder substitution within environment variable values.
+//!
+//! An environment variable value may reference other containers of the same test through
+//! placeholders, which are resolved once the referenced information is known:
+//! - `{handle:NAME}` resolves to the final container name of the container with handle `NAME`.
+//! - `{host_port:NAME:PORT}` resolves to the host port published for the container port `PORT`
+//!   of the container with handle `NAME`.
+//!
+//! A placeholder is escaped by doubling its opening brace, e.g., `{{handle:NAME}` is the literal
+//! `{handle:NAME}`. Any other use of braces is left untouched. The `{replica}` placeholder of
+//! replicated containers is substituted when the replicas are created, see
+//! [Composition::with_replicas](crate::composition::Composition::with_replicas).
+//!
+//! The placeholders are substituted in several passes, as host ports assigned by the daemon are
+//! only known once the referenced container has started. A rendered value is therefore still a
+//! template, with its literals escaped, until it is unescaped through [unescape] once it is passed
+//! to the container.
+
+/// A reference to another container within an environment variable value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Placeholder {
+    /// `{handle:NAME}`
+    ContainerName { handle: String },
+    /// `{host_port:NAME:PORT}`
+    HostPort { handle: String, port: u32 },
+}
+
+impl Placeholder {
+    /// The handle of the container referenced by this placeholder.
+    pub(crate) fn handle(&self) -> &str {
+        match self {
+            Placeholder::ContainerName { handle } => handle,
+            Placeholder::HostPort { handle, .. } => handle,
+        }
+    }
+}
+
+impl std::fmt::Display for Placeholder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Placeholder::ContainerName { handle } => {
+                write!(f, "{}{}}}", CONTAINER_NAME_PREFIX, handle)
+            }
+            Placeholder::HostPort { handle, port } => {
+                write!(f, "{}{}:{}}}", HOST_PORT_PREFIX, handle, port)
+            }
+        }
+    }
+}
+
+/// A parsed part of an environment variable value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+const CONTAINER_NAME_PREFIX: &str = "{handle:";
+const HOST_PORT_PREFIX: &str = "{host_port:";
+
+/// Split the value into its literal and placeholder segments.
+///
+/// The error describes the malformed placeholder.
+pub(crate) fn parse(value: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find('{') {
+        let candidate = &rest[start..];
+        if candidate.starts_with("{{") && is_placeholder(&candidate[1..]) {
+            // Only the escaping brace is skipped, the escaped placeholder is a literal.
+            literal.push_str(&rest[..=start]);
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        let prefix = if candidate.starts_with(CONTAINER_NAME_PREFIX) {
+            CONTAINER_NAME_PREFIX
+        } else if candidate.starts_with(HOST_PORT_PREFIX) {
+            HOST_PORT_PREFIX
+        } else {
+            literal.push_str(&rest[..=start]);
+            rest = &rest[start + 1..];
+            continue;
+        };
+
+        let end = candidate
+            .find('}')
+            .ok_or_else(|| format!("unterminated placeholder in `{}`", value))?;
+        let body = &candidate[prefix.len()..end];
+
+        let placeholder = if prefix == CONTAINER_NAME_PREFIX {
+            Placeholder::ContainerName {
+                handle: parse_handle(body, value)?,
+            }
+        } else {
+            let (handle, port) = body
+                .rsplit_once(':')
+                .ok_or_else(|| format!("missing port in placeholder of `{}`", value))?;
+            let port = port
+                .parse::<u32>()
+                .map_err(|_| format!("invalid port `{}` in placeholder of `{}`", port, value))?;
+            Placeholder::HostPort {
+                handle: parse_handle(handle, value)?,
+                port,
+            }
+        };
+
+        literal.push_str(&rest[..start]);
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(std::mem::take(&mut literal)));
+        }
+        segments.push(Segment::Placeholder(placeholder));
+        rest = &candidate[end + 1..];
+    }
+
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+
+    Ok(segments)
+}
+
+/// Whether the text starts with a placeholder.
+fn is_placeholder(text: &str) -> bool {
+    text.starts_with(CONTAINER_NAME_PREFIX) || text.starts_with(HOST_PORT_PREFIX)
+}
+
+/// Escape the placeholders within the literal, such that it is parsed as the same literal.
+fn escape(literal: &str) -> String {
+    literal
+        .replace(
+            CONTAINER_NAME_PREFIX,
+            &format!("{{{}", CONTAINER_NAME_PREFIX),
+        )
+        .replace(HOST_PORT_PREFIX, &format!("{{{}", HOST_PORT_PREFIX))
+}
+
+/// Remove the escaping of the placeholders within the rendered value, once every placeholder
+/// has been substituted.
+pub(crate) fn unescape(value: &str) -> String {
+    value
+        .replace(
+            &format!("{{{}", CONTAINER_NAME_PREFIX),
+            CONTAINER_NAME_PREFIX,
+        )
+        .replace(&format!("{{{}", HOST_PORT_PREFIX), HOST_PORT_PREFIX)
+}
+
+fn parse_handle(handle: &str, value: &str) -> Result<String, String> {
+    if handle.is_empty() {
+        Err(format!("missing handle in placeholder of `{}`", value))
+    } else {
+        Ok(handle.to_string())
+    }
+}
+
+/// Join the segments, resolving each placeholder through `resolve`, and escaping the literals.
+pub(crate) fn render<E, F>(segments: &[Segment], mut resolve: F) -> Result<String, E>
+where
+    F: FnMut(&Placeholder) -> Result<String, E>,
+{
+    let mut rendered = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Literal(literal) => rendered.push_str(&escape(literal)),
+            Segment::Placeholder(placeholder) => rendered.push_str(&resolve(placeholder)?),
+        }
+    }
+
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, render, unescape, Placeholder, Segment};
+
+    // Tests that both placeholder kinds are recognized and other braces are kept as is
+    #[test]
+    fn test_parse_placeholders() {
+        let segments = parse("postgres://{handle:db}:{host_port:db:5432}/{app}").unwrap();
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Literal("postgres://".to_string()),
+                Segment::Placeholder(Placeholder::ContainerName {
+                    handle: "db".to_string()
+                }),
+                Segment::Literal(":".to_string()),
+                Segment::Placeholder(Placeholder::HostPort {
+                    handle: "db".to_string(),
+                    port: 5432
+                }),
+                Segment::Literal("/{app}".to_string()),
+            ]
+        );
+    }
+
+    // Tests that malformed placeholders are rejected
+    #[test]
+    fn test_parse_malformed_placeholders() {
+        assert!(parse("{handle:db").is_err());
+        assert!(parse("{handle:}").is_err());
+        assert!(parse("{host_port:db}").is_err());
+        assert!(parse("{host_port:db:http}").is_err());
+    }
+
+    // Tests that rendering substitutes every placeholder
+    #[test]
+    fn test_render() {
+        let segments = parse("{handle:db}:{host_port:db:5432}").unwrap();
+        let rendered = render::<(), _>(&segments, |p| match p {
+            Placeholder::ContainerName { handle } => Ok(format!("ns-{}", handle)),
+            Placeholder::HostPort { port, .. } => Ok((port + 1).to_string()),
+        });
+        assert_eq!(rendered, Ok("ns-db:5433".to_string()));
+
+        // Placeholders rendered as themselves are left as is
+        let rendered = render::<(), _>(&segments, |p| Ok(p.to_string()));
+        assert_eq!(rendered, Ok("{handle:db}:{host_port:db:5432}".to_string()));
+    }
+
+    // Tests that escaped placeholders are literals, which survive rendering until unescaped
+    #[test]
+    fn test_escaped_placeholders() {
+        let segments = parse("{{handle:db}:{handle:db}/{{{host_port:db:5432}/{{app}}").unwrap();
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Literal("{handle:db}:".to_string()),
+                Segment::Placeholder(Placeholder::ContainerName {
+                    handle: "db".to_string()
+                }),
+                Segment::Literal("/{{host_port:db:5432}/{{app}}".to_string()),
+            ]
+        );
+
+        let rendered = render::<(), _>(&segments, |_| Ok("ns-db".to_string())).unwrap();
+        assert_eq!(rendered, "{{handle:db}:ns-db/{{{host_port:db:5432}/{{app}}");
+        assert_eq!(parse(&rendered).unwrap().len(), 1);
+        assert_eq!(
+            unescape(&rendered),
+            "{handle:db}:ns-db/{{host_port:db:5432}/{{app}}"
+        );
+    }
+}
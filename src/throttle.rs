@@ -0,0 +1,60 @@
+//! Process-wide bound on concurrent docker daemon operations.
+//!
+//! Parallel tests each pull, create and start their containers concurrently, which may overload
+//! the docker daemon. The `DOCKERTEST_MAX_DAEMON_OPERATIONS` environment variable bounds the
+//! number of such operations in flight across all tests of the process. It is unbounded if unset.
+
+use lazy_static::lazy_static;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tracing::{event, Level};
+
+const LIMIT_ENV: &str = "DOCKERTEST_MAX_DAEMON_OPERATIONS";
+
+lazy_static! {
+    /// Permits for daemon operations, if bounded.
+    static ref DAEMON_OPERATIONS: Option<Semaphore> = {
+        let limit = std::env::var(LIMIT_ENV).ok();
+        parse_limit(limit.as_deref()).map(|limit| {
+            event!(Level::DEBUG, "limiting concurrent daemon operations to {}", limit);
+            Semaphore::new(limit)
+        })
+    };
+}
+
+/// Wait until a daemon operation may be performed.
+///
+/// The operation must complete before the returned permit is dropped, and no other permit may
+/// be acquired while it is held.
+pub(crate) async fn daemon_operation() -> Option<SemaphorePermit<'static>> {
+    match DAEMON_OPERATIONS.as_ref() {
+        // The semaphore is never closed
+        Some(semaphore) => semaphore.acquire().await.ok(),
+        None => None,
+    }
+}
+
+fn parse_limit(value: Option<&str>) -> Option<usize> {
+    let value = value?;
+    match value.trim().parse::<usize>() {
+        Ok(limit) if limit > 0 => Some(limit),
+        _ => {
+            event!(Level::WARN, "unrecognized `{} = {:?}`", LIMIT_ENV, value);
+            event!(Level::DEBUG, "defaulting to unbounded daemon operations");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_limit;
+
+    // Tests that only positive limits bound the daemon operations
+    #[test]
+    fn test_parse_limit() {
+        assert_eq!(parse_limit(None), None);
+        assert_eq!(parse_limit(Some("8")), Some(8));
+        assert_eq!(parse_limit(Some("0")), None);
+        assert_eq!(parse_limit(Some("many")), None);
+    }
+}
@@ -0,0 +1,214 @@
+//! Shaping the network traffic of a running container, see [DockerOperations::shape_traffic].
+//!
+//! The traffic is shaped by the netem queueing discipline of the kernel, configured through `tc`
+//! by a helper container. The helper joins the network namespace of the shaped container with
+//! the `NET_ADMIN` capability, such that neither the shaped container nor its image require
+//! `tc` or additional privileges. The helper exits once the queueing discipline is configured,
+//! which outlives it along with the network namespace of the shaped container.
+//!
+//! [DockerOperations::shape_traffic]: crate::DockerOperations::shape_traffic
+
+use crate::container::{recent_logs, RunningContainer};
+use crate::image::{Image, Source};
+use crate::janitor;
+use crate::utils::generate_random_string;
+use crate::DockerTestError;
+
+use bollard::{
+    container::{Config, CreateContainerOptions, RemoveContainerOptions, WaitContainerOptions},
+    errors::Error,
+    models::HostConfig,
+    Docker,
+};
+use futures::StreamExt;
+use tracing::{event, Level};
+
+use std::time::Duration;
+
+/// The image providing `tc` to the helper container, a maintained multi-arch image shipping
+/// iproute2, pinned such that the helper does not change underneath the tests.
+const TC_IMAGE: &str = "nicolaka/netshoot";
+const TC_TAG: &str = "v0.13";
+
+/// The network traffic shaping applied to the egress of a container, through
+/// [DockerOperations::shape_traffic](crate::DockerOperations::shape_traffic).
+///
+/// The default shaping applies no latency, loss or rate limit, and thus removes earlier
+/// shaping. As the shaping applies to the egress of the container, the latency of a round trip
+/// to the container is increased by the latency once.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrafficShaping {
+    /// The latency added to every packet.
+    pub latency: Duration,
+    /// The random variation of the latency, in both directions.
+    pub jitter: Duration,
+    /// The percentage of packets dropped, from `0.0` to `100.0`.
+    pub loss_percent: f64,
+    /// The bandwidth limit in kilobits per second, if limited.
+    pub rate_kbit: Option<u32>,
+    /// The network interface to shape within the container, `eth0` if `None`.
+    ///
+    /// A container attached to several networks has one interface per network.
+    pub interface: Option<String>,
+}
+
+impl TrafficShaping {
+    /// The `tc` arguments configuring the netem queueing discipline.
+    fn tc_args(&self) -> Result<Vec<String>, String> {
+        if !(0.0..=100.0).contains(&self.loss_percent) {
+            return Err(format!(
+                "the packet loss must be a percentage, got {}",
+                self.loss_percent
+            ));
+        }
+        if self.rate_kbit == Some(0) {
+            return Err("the bandwidth limit must be positive".to_string());
+        }
+
+        let interface = self.interface.as_deref().unwrap_or("eth0");
+        let mut args: Vec<String> = ["qdisc", "replace", "dev", interface, "root", "netem"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        args.push("delay".to_string());
+        args.push(format!("{}us", self.latency.as_micros()));
+        if !self.jitter.is_zero() {
+            args.push(format!("{}us", self.jitter.as_micros()));
+        }
+        if self.loss_percent > 0.0 {
+            args.push("loss".to_string());
+            args.push(format!("{}%", self.loss_percent));
+        }
+        if let Some(rate) = self.rate_kbit {
+            args.push("rate".to_string());
+            args.push(format!("{}kbit", rate));
+        }
+        Ok(args)
+    }
+}
+
+/// Apply the shaping to the network traffic of the container through a helper container.
+pub(crate) async fn shape(
+    container: &RunningContainer,
+    shaping: &TrafficShaping,
+) -> Result<(), DockerTestError> {
+    let args = shaping.tc_args().map_err(|e| {
+        DockerTestError::TestBody(format!(
+            "invalid traffic shaping of container `{}`: {}",
+            container.handle, e
+        ))
+    })?;
+
+    let client = &container.client;
+    let name = format!("{}-tc-{}", container.name, generate_random_string(8));
+    event!(
+        Level::DEBUG,
+        "shaping traffic of container `{}`: tc {}",
+        container.handle,
+        args.join(" ")
+    );
+
+    let result = run_tc(client, &name, &container.id, args).await;
+    let options = RemoveContainerOptions {
+        force: true,
+        ..Default::default()
+    };
+    if let Err(e) = client.remove_container(&name, Some(options)).await {
+        event!(
+            Level::DEBUG,
+            "failed to remove traffic shaping container `{}`: {}",
+            name,
+            e
+        );
+    }
+
+    result.map_err(|e| {
+        DockerTestError::Daemon(format!(
+            "failed to shape traffic of container `{}`: {}",
+            container.handle, e
+        ))
+    })
+}
+
+/// Run `tc` with the arguments in the network namespace of the container `id`.
+async fn run_tc(client: &Docker, name: &str, id: &str, args: Vec<String>) -> Result<(), String> {
+    let image = Image::with_repository(TC_IMAGE).tag(TC_TAG);
+    image
+        .pull(client, &Source::DockerHub)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let options = CreateContainerOptions {
+        name,
+        platform: None,
+    };
+    let config = Config::<String> {
+        image: Some(image.reference()),
+        entrypoint: Some(vec!["tc".to_string()]),
+        cmd: Some(args),
+        labels: Some(janitor::session_labels()),
+        host_config: Some(HostConfig {
+            network_mode: Some(format!("container:{}", id)),
+            cap_add: Some(vec!["NET_ADMIN".to_string()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    client
+        .create_container(Some(options), config)
+        .await
+        .map_err(|e| e.to_string())?;
+    client
+        .start_container::<String>(name, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut wait = client.wait_container(name, None::<WaitContainerOptions<String>>);
+    let code = match wait.next().await {
+        Some(Ok(response)) => response.status_code,
+        Some(Err(Error::DockerContainerWaitError { code, .. })) => code,
+        Some(Err(e)) => return Err(e.to_string()),
+        None => return Err("the helper container did not exit".to_string()),
+    };
+    if code != 0 {
+        let logs = recent_logs(client, name).await.unwrap_or_default();
+        return Err(format!("tc exited with code {}: {}", code, logs.trim()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrafficShaping;
+
+    use std::time::Duration;
+
+    // Tests that the shaping is translated to the arguments of the netem queueing discipline
+    #[test]
+    fn test_tc_args() {
+        let shaping = TrafficShaping {
+            latency: Duration::from_millis(100),
+            jitter: Duration::from_millis(20),
+            loss_percent: 1.5,
+            rate_kbit: Some(512),
+            interface: None,
+        };
+        assert_eq!(
+            shaping.tc_args().unwrap().join(" "),
+            "qdisc replace dev eth0 root netem delay 100000us 20000us loss 1.5% rate 512kbit"
+        );
+
+        let args = TrafficShaping::default().tc_args().unwrap();
+        assert_eq!(
+            args.join(" "),
+            "qdisc replace dev eth0 root netem delay 0us"
+        );
+
+        let shaping = TrafficShaping {
+            loss_percent: 120.0,
+            ..Default::default()
+        };
+        assert!(shaping.tc_args().is_err());
+    }
+}
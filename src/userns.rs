@@ -0,0 +1,230 @@
+//! Compatibility with docker daemons executing containers within a user namespace, i.e., rootless
+//! daemons and daemons remapping the users of containers through `userns-remap`.
+//!
+//! The users of such containers are mapped to unprivileged users of the host, which are unable to
+//! write to host paths bind mounted into the container. The owner of such paths, see
+//! [Composition::bind_mount_owner], is therefore changed by a helper container before the
+//! container is created, and not restored once the test terminates:
+//! * A rootless daemon itself executes in a user namespace, owned by the user executing the test,
+//!   in which the users of the container are not remapped any further. The helper changes the
+//!   owner within that user namespace.
+//! * A daemon remapping the users of containers changes the owner to the host users the
+//!   container users are mapped to. These are probed through the user namespace of a helper, as
+//!   the mapping is not reported by the daemon. The owner is changed by a helper joining the user
+//!   namespace of the host.
+//!
+//! [Composition::bind_mount_owner]: crate::composition::Composition::bind_mount_owner
+
+use crate::container::recent_logs;
+use crate::daemon::DaemonInfo;
+use crate::image::{Image, Source};
+use crate::janitor;
+use crate::utils::generate_random_string;
+use crate::DockerTestError;
+
+use bollard::{
+    container::{Config, CreateContainerOptions, RemoveContainerOptions, WaitContainerOptions},
+    errors::Error,
+    models::HostConfig,
+    Docker,
+};
+use futures::StreamExt;
+use tracing::{event, Level};
+
+use std::convert::TryFrom;
+
+/// The image providing `chown` to the helper containers, pinned such that the helper does not
+/// change underneath the tests.
+const HELPER_IMAGE: &str = "busybox";
+const HELPER_TAG: &str = "1.36.1";
+
+/// The directory the paths are mounted within the helper container.
+const HELPER_MOUNT_DIR: &str = "/dockertest/chown";
+
+/// Change the owner of the host paths to the user and group of the container, as seen by the
+/// docker daemon.
+pub(crate) async fn chown(
+    client: &Docker,
+    daemon: &DaemonInfo,
+    handle: &str,
+    paths: &[String],
+    (uid, gid): (u32, u32),
+) -> Result<(), DockerTestError> {
+    let failed = |e: String| {
+        DockerTestError::Startup(format!(
+            "failed to change the owner of the bind mounts of container `{}` to {}:{}: {}",
+            handle, uid, gid, e
+        ))
+    };
+
+    let image = Image::with_repository(HELPER_IMAGE).tag(HELPER_TAG);
+    image
+        .pull(client, &Source::DockerHub)
+        .await
+        .map_err(|e| failed(e.to_string()))?;
+
+    let (owner, host_userns) = if daemon.is_userns_remapped() {
+        let maps = run_helper(
+            client,
+            &image,
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "cat /proc/self/uid_map; echo --; cat /proc/self/gid_map".to_string(),
+            ],
+            Vec::new(),
+            false,
+        )
+        .await
+        .map_err(failed)?;
+        let owner = match maps.split_once("--") {
+            Some((uid_map, gid_map)) => map_id(uid_map, uid).zip(map_id(gid_map, gid)),
+            None => None,
+        };
+        match owner {
+            Some(owner) => (owner, true),
+            None => {
+                return Err(failed(format!(
+                    "the user namespace of the container does not map {}:{}: {}",
+                    uid,
+                    gid,
+                    maps.trim()
+                )))
+            }
+        }
+    } else {
+        ((uid, gid), false)
+    };
+
+    let mut cmd = vec![
+        "chown".to_string(),
+        "-R".to_string(),
+        format!("{}:{}", owner.0, owner.1),
+    ];
+    let mut binds = Vec::new();
+    for (i, path) in paths.iter().enumerate() {
+        let target = format!("{}/{}", HELPER_MOUNT_DIR, i);
+        binds.push(format!("{}:{}", path, target));
+        cmd.push(target);
+    }
+    event!(
+        Level::DEBUG,
+        "changing the owner of the bind mounts of container `{}` to {}:{} on the host",
+        handle,
+        owner.0,
+        owner.1
+    );
+
+    run_helper(client, &image, cmd, binds, host_userns)
+        .await
+        .map(|_| ())
+        .map_err(failed)
+}
+
+/// Run the command in a helper container, returning its logs.
+async fn run_helper(
+    client: &Docker,
+    image: &Image,
+    cmd: Vec<String>,
+    binds: Vec<String>,
+    host_userns: bool,
+) -> Result<String, String> {
+    let name = format!("dockertest-userns-{}", generate_random_string(8));
+    let result = run_helper_container(client, &name, image, cmd, binds, host_userns).await;
+    let options = RemoveContainerOptions {
+        force: true,
+        ..Default::default()
+    };
+    if let Err(e) = client.remove_container(&name, Some(options)).await {
+        event!(
+            Level::DEBUG,
+            "failed to remove user namespace helper container `{}`: {}",
+            name,
+            e
+        );
+    }
+    result
+}
+
+async fn run_helper_container(
+    client: &Docker,
+    name: &str,
+    image: &Image,
+    cmd: Vec<String>,
+    binds: Vec<String>,
+    host_userns: bool,
+) -> Result<String, String> {
+    let options = CreateContainerOptions {
+        name,
+        platform: None,
+    };
+    let config = Config::<String> {
+        image: Some(image.reference()),
+        cmd: Some(cmd),
+        labels: Some(janitor::session_labels()),
+        host_config: Some(HostConfig {
+            binds: Some(binds),
+            userns_mode: host_userns.then(|| "host".to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    client
+        .create_container(Some(options), config)
+        .await
+        .map_err(|e| e.to_string())?;
+    client
+        .start_container::<String>(name, None)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut wait = client.wait_container(name, None::<WaitContainerOptions<String>>);
+    let code = match wait.next().await {
+        Some(Ok(response)) => response.status_code,
+        Some(Err(Error::DockerContainerWaitError { code, .. })) => code,
+        Some(Err(e)) => return Err(e.to_string()),
+        None => return Err("the helper container did not exit".to_string()),
+    };
+    let logs = recent_logs(client, name).await.unwrap_or_default();
+    if code != 0 {
+        return Err(format!("exited with code {}: {}", code, logs.trim()));
+    }
+
+    Ok(logs)
+}
+
+/// The id outside of the user namespace that `id` within it is mapped to, according to the
+/// `/proc/{pid}/uid_map` or `/proc/{pid}/gid_map` of a process within it.
+fn map_id(map: &str, id: u32) -> Option<u32> {
+    map.lines().find_map(|line| {
+        let mut fields = line.split_whitespace().map(|f| f.parse::<u64>().ok());
+        let inside = fields.next()??;
+        let outside = fields.next()??;
+        let count = fields.next()??;
+        let id = u64::from(id);
+        if (inside..inside + count).contains(&id) {
+            u32::try_from(outside + (id - inside)).ok()
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::map_id;
+
+    // Tests that ids are mapped through the ranges of the user namespace
+    #[test]
+    fn test_map_id() {
+        let map = "         0     100000      65536\n";
+        assert_eq!(map_id(map, 0), Some(100000));
+        assert_eq!(map_id(map, 999), Some(100999));
+        assert_eq!(map_id(map, 65536), None);
+
+        let map = "0 1000 1\n1 100000 65536\n";
+        assert_eq!(map_id(map, 0), Some(1000));
+        assert_eq!(map_id(map, 70), Some(100069));
+        assert_eq!(map_id("", 0), None);
+    }
+}
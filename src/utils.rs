@@ -4,7 +4,11 @@
 use crate::error::DockerTestError;
 
 use bollard::Docker;
+use lazy_static::lazy_static;
 use rand::{self, Rng};
+use sha2::{Digest, Sha256};
+
+use std::sync::Mutex;
 
 #[cfg(feature = "tls")]
 use std::env;
@@ -37,6 +41,74 @@ pub fn connect_with_local_or_tls_defaults() -> Result<Docker, DockerTestError> {
         .map_err(|e| DockerTestError::Daemon(format!("connection with locals defaults: {:?}", e)))
 }
 
+/// Hash the definition such that the hash is identical across processes and toolchains, as
+/// opposed to the hashers of the standard library. The hash is truncated to 16 hexadecimal
+/// digits, such that it fits within container names.
+pub(crate) fn stable_hash(definition: &str) -> String {
+    Sha256::digest(definition.as_bytes())
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// A timestamp reported by the docker daemon, e.g., `2024-01-01T00:00:00.123456789Z`, as the
+/// seconds and nanoseconds since the unix epoch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Timestamp {
+    pub(crate) seconds: i64,
+    pub(crate) nanos: u32,
+}
+
+impl Timestamp {
+    /// Parse the RFC 3339 timestamp in UTC reported by the docker daemon, with up to nanosecond
+    /// precision.
+    pub(crate) fn parse(timestamp: &str) -> Option<Timestamp> {
+        let (date, time) = timestamp.strip_suffix('Z')?.split_once('T')?;
+        let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+        if fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+        let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+        let mut time = time.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+        let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+        Some(Timestamp {
+            seconds: days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second,
+            nanos: format!("{:0<9}", fraction).parse().ok()?,
+        })
+    }
+}
+
+/// The number of days between the unix epoch and the date of the gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+lazy_static! {
+    /// The docker client shared by all tests of the process, established on first use.
+    static ref SHARED_CLIENT: Mutex<Option<Docker>> = Mutex::new(None);
+}
+
+/// Retrieve the docker client shared by all tests of the process, connecting it on first use.
+pub(crate) fn shared_client() -> Result<Docker, DockerTestError> {
+    let mut shared = SHARED_CLIENT.lock().unwrap_or_else(|e| e.into_inner());
+    match shared.as_ref() {
+        Some(client) => Ok(client.clone()),
+        None => {
+            let client = connect_with_local_or_tls_defaults()?;
+            *shared = Some(client.clone());
+            Ok(client)
+        }
+    }
+}
+
 #[doc(hidden)]
 pub fn generate_random_string(len: i32) -> String {
     let mut random_string = String::new();
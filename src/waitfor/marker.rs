@@ -0,0 +1,125 @@
+//! `WaitFor` implementation: `MarkerFileWait`.
+//!
+//! Images under test may signal readiness through a marker file instead of a log line or a
+//! listening port, which suits batch jobs and containers without any network service. The
+//! convention is that the image writes [MARKER_FILE] once initialized, e.g., as the last step of
+//! its entrypoint:
+//!
+//! ```sh
+//! ./initialize && touch /run/dockertest/ready && exec ./serve
+//! ```
+//!
+//! The directory of the marker is mounted as a tmpfs, such that the marker never persists
+//! across restarts of the container and is writable with a read-only root filesystem. The
+//! convention uses a directory dedicated to the marker, as the tmpfs hides any content of the
+//! image within the directory.
+
+use crate::container::{PendingContainer, RunningContainer};
+use crate::retry::is_not_found;
+use crate::waitfor::{async_trait, WaitFor};
+use crate::DockerTestError;
+
+use bollard::container::DownloadFromContainerOptions;
+use bollard::Docker;
+use futures::StreamExt;
+use tokio::time::{interval, Duration};
+
+/// The path of the marker file written by images once initialized, by convention.
+pub const MARKER_FILE: &str = "/run/dockertest/ready";
+
+/// The MarkerFileWait `WaitFor` implementation for containers.
+/// This variant will wait until the marker file exists within the container, see
+/// [MARKER_FILE] for the convention.
+///
+/// The presence of the marker is checked through the archive API of the docker daemon, such
+/// that the image requires no tooling, e.g., distroless images. The directory of the marker is
+/// mounted as a tmpfs, hiding any content of the image within it, such that a custom path should
+/// reside in a directory dedicated to the marker.
+#[derive(Clone, Debug)]
+pub struct MarkerFileWait {
+    /// The absolute path of the marker file within the container, e.g., [MARKER_FILE].
+    pub path: String,
+    /// How many seconds shall there be between each check for the marker.
+    pub check_interval: u64,
+    /// The number of checks to perform before erroring out.
+    pub max_checks: u64,
+}
+
+impl MarkerFileWait {
+    /// The directory of the marker, mounted as a tmpfs.
+    fn directory(&self) -> Option<&str> {
+        match self.path.rsplit_once('/') {
+            Some(("", _)) | None => None,
+            Some((directory, _)) => Some(directory),
+        }
+    }
+}
+
+#[async_trait]
+impl WaitFor for MarkerFileWait {
+    async fn wait_for_ready(
+        &self,
+        container: PendingContainer,
+    ) -> Result<RunningContainer, DockerTestError> {
+        let mut last = None;
+        let mut interval = interval(Duration::from_secs(self.check_interval));
+        for _ in 0..self.max_checks {
+            interval.tick().await;
+            match exists(&container.client, &container.name, &self.path).await {
+                Ok(true) => return Ok(container.into()),
+                Ok(false) => last = None,
+                Err(e) => last = Some(e.to_string()),
+            }
+        }
+
+        Err(DockerTestError::Startup(format!(
+            "container `{}` did not write the readiness marker `{}`{}",
+            container.handle,
+            self.path,
+            last.map(|e| format!(", last check failed: {}", e))
+                .unwrap_or_default()
+        )))
+    }
+
+    fn tmpfs(&self) -> Option<String> {
+        self.directory().map(|d| d.to_string())
+    }
+}
+
+/// Whether the path exists within the container, as told by the archive API.
+///
+/// The daemon also reports the presence of a path through `HEAD /containers/{id}/archive`,
+/// which the docker client does not expose. Only the start of the archive is read instead, which
+/// the daemon responds with once the path is found.
+async fn exists(client: &Docker, name: &str, path: &str) -> Result<bool, bollard::errors::Error> {
+    let options = DownloadFromContainerOptions { path };
+    let mut archive = client.download_from_container(name, Some(options));
+    match archive.next().await {
+        Some(Ok(_)) => Ok(true),
+        Some(Err(e)) if is_not_found(&e) => Ok(false),
+        Some(Err(e)) => Err(e),
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MarkerFileWait, MARKER_FILE};
+    use crate::waitfor::WaitFor;
+
+    // Tests that the directory of the marker is mounted as a tmpfs, except the root directory
+    #[test]
+    fn test_marker_file_tmpfs() {
+        let wait = |path: &str| MarkerFileWait {
+            path: path.to_string(),
+            check_interval: 1,
+            max_checks: 1,
+        };
+        assert_eq!(
+            wait(MARKER_FILE).tmpfs(),
+            Some("/run/dockertest".to_string())
+        );
+        assert_eq!(wait("/run/app/ready").tmpfs(), Some("/run/app".to_string()));
+        assert_eq!(wait("/ready").tmpfs(), None);
+    }
+}
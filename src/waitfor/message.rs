@@ -1,4 +1,5 @@
 use crate::container::{PendingContainer, RunningContainer};
+use crate::utils::Timestamp;
 use crate::waitfor::{async_trait, WaitFor};
 use crate::DockerTestError;
 
@@ -8,8 +9,6 @@ use bollard::{
 };
 use futures::stream::StreamExt;
 use serde::Serialize;
-use std::sync::atomic::{self, AtomicBool};
-use std::sync::Arc;
 use tokio::{time, time::Duration};
 use tracing::{event, Level};
 
@@ -25,6 +24,48 @@ pub struct MessageWait {
     pub timeout: u16,
 }
 
+/// The MessageSequenceWait `WaitFor` implementation for containers.
+/// This variant will wait until each message has appeared the requested number of times in the
+/// requested source, in order.
+///
+/// Occurrences of a message are only counted once all previous messages have appeared the
+/// requested number of times, e.g., to wait for a readiness line per worker followed by a line
+/// announcing that the service accepts requests.
+#[derive(Clone, Debug)]
+pub struct MessageSequenceWait {
+    /// The messages to be contained in source, in order.
+    pub messages: Vec<Occurrences>,
+    /// The source to listen for the messages.
+    pub source: MessageSource,
+    /// Number of seconds to wait for all messages. Times out with an error on expire.
+    pub timeout: u16,
+}
+
+/// A message expected to appear a number of times in the log stream.
+/// Used by `MessageSequenceWait`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Occurrences {
+    /// The message to be contained in source.
+    pub message: String,
+    /// The number of times the message must appear.
+    pub count: usize,
+}
+
+impl Occurrences {
+    /// The message must appear once.
+    pub fn once<T: ToString>(message: T) -> Occurrences {
+        Occurrences::times(message, 1)
+    }
+
+    /// The message must appear `count` times.
+    pub fn times<T: ToString>(message: T, count: usize) -> Occurrences {
+        Occurrences {
+            message: message.to_string(),
+            count,
+        }
+    }
+}
+
 /// The various sources to listen for a message on.
 /// Used by `MessageWait`.
 #[derive(Clone, Copy, Debug)]
@@ -41,36 +82,40 @@ impl WaitFor for MessageWait {
         &self,
         container: PendingContainer,
     ) -> Result<RunningContainer, DockerTestError> {
-        pending_container_wait_for_message(
-            container,
+        let client = container.client.clone();
+        let messages = [Occurrences::once(self.message.clone())];
+        wait_for_messages_since(
+            &client,
+            &container.id,
+            &container.handle,
             self.source,
-            self.message.clone(),
+            &messages,
             self.timeout,
+            container.logs_since,
         )
-        .await
+        .await?;
+        Ok(container.into())
     }
 }
 
-async fn pending_container_wait_for_message(
-    container: PendingContainer,
-    source: MessageSource,
-    msg: String,
-    timeout: u16,
-) -> Result<RunningContainer, DockerTestError> {
-    // Must unfortunately clone the client, since the PendingContainer will be consusumed.
-    let client = container.client.clone();
-    match wait_for_message(
-        &client,
-        &container.id,
-        &container.handle,
-        source,
-        msg,
-        timeout,
-    )
-    .await
-    {
-        Ok(_) => Ok(container.into()),
-        Err(e) => Err(e),
+#[async_trait]
+impl WaitFor for MessageSequenceWait {
+    async fn wait_for_ready(
+        &self,
+        container: PendingContainer,
+    ) -> Result<RunningContainer, DockerTestError> {
+        let client = container.client.clone();
+        wait_for_messages_since(
+            &client,
+            &container.id,
+            &container.handle,
+            self.source,
+            &self.messages,
+            self.timeout,
+            container.logs_since,
+        )
+        .await?;
+        Ok(container.into())
     }
 }
 
@@ -85,73 +130,220 @@ pub(crate) async fn wait_for_message<T>(
 where
     T: Into<String> + Serialize,
 {
-    // Construct LogOptions
+    let messages = [Occurrences::once(msg.into())];
+    wait_for_messages(client, container_id, handle, source, &messages, timeout).await
+}
+
+pub(crate) async fn wait_for_messages(
+    client: &Docker,
+    container_id: &str,
+    handle: &str,
+    source: MessageSource,
+    messages: &[Occurrences],
+    timeout: u16,
+) -> Result<(), DockerTestError> {
+    wait_for_messages_since(
+        client,
+        container_id,
+        handle,
+        source,
+        messages,
+        timeout,
+        None,
+    )
+    .await
+}
+
+/// Await the messages in the logs of the container, disregarding the logs written before `since`,
+/// e.g., by the container before it was restarted.
+async fn wait_for_messages_since(
+    client: &Docker,
+    container_id: &str,
+    handle: &str,
+    source: MessageSource,
+    messages: &[Occurrences],
+    timeout: u16,
+    since: Option<Timestamp>,
+) -> Result<(), DockerTestError> {
+    // Construct LogOptions, with the timestamps of the log lines to disregard those written
+    // within the second preceding `since`.
     let mut log_options = LogsOptions::<String> {
         follow: true,
+        since: since.map(|since| since.seconds).unwrap_or_default(),
+        timestamps: since.is_some(),
         ..Default::default()
     };
     match source {
         MessageSource::Stdout => log_options.stdout = true,
         MessageSource::Stderr => log_options.stderr = true,
     };
-    let log_options = Some(log_options);
-
-    // Construct remaining variables
-    let s1 = Arc::new(AtomicBool::new(false));
-    let s2 = s1.clone();
-    let msg_clone1: String = msg.into();
-    let msg_clone2: String = msg_clone1.clone();
 
     // Construct the stream
-    let stream = client.logs(container_id, log_options);
+    let mut stream = client.logs(container_id, Some(log_options));
+    let mut progress = Progress::default();
+    progress.observe(messages, "");
 
     // Work configuration
     let work_fut = async {
-        stream
-            .take_while(move |chunk| {
-                match chunk {
-                    Ok(chunk) => {
-                        // Extract the String from LogOutput variants
-                        let content = match chunk {
-                            LogOutput::StdErr { message } => Some(message),
-                            LogOutput::StdOut { message } => Some(message),
-                            LogOutput::StdIn { message: _ } => None,
-                            LogOutput::Console { message: _ } => None,
-                        };
-                        match content {
-                            Some(content)
-                                if String::from_utf8(content.to_vec())
-                                    .unwrap()
-                                    .contains(&msg_clone1) =>
-                            {
-                                s1.store(true, atomic::Ordering::SeqCst);
-                                futures::future::ready(false)
-                            }
-                            _ => futures::future::ready(true),
-                        }
-                    }
-                    Err(_) => futures::future::ready(false),
-                }
-            })
-            .collect::<Vec<_>>()
-            .await
+        while !progress.completed(messages) {
+            let chunk = match stream.next().await {
+                Some(Ok(chunk)) => chunk,
+                _ => break,
+            };
+            // Extract the String from LogOutput variants
+            let content = match chunk {
+                LogOutput::StdErr { message } => message,
+                LogOutput::StdOut { message } => message,
+                LogOutput::StdIn { message: _ } => continue,
+                LogOutput::Console { message: _ } => continue,
+            };
+            let content = String::from_utf8_lossy(&content);
+            let content = match since {
+                Some(since) => match written_since(&content, since) {
+                    Some(content) => content,
+                    None => continue,
+                },
+                None => &content,
+            };
+            progress.observe(messages, content);
+        }
     };
 
     match time::timeout(Duration::from_secs(timeout.into()), work_fut).await {
         Ok(_) => {
-            if s2.load(atomic::Ordering::SeqCst) {
+            if progress.completed(messages) {
                 Ok(())
             } else {
                 Err(DockerTestError::Startup(
-                   format!("container `{}` ended log stream (terminated) before waitfor message triggered: `{}`", handle, msg_clone2),
+                   format!("container `{}` ended log stream (terminated) before waitfor message triggered: {}", handle, progress.describe(messages)),
                 ))
             }
         }
         Err(_) => {
             event!(Level::WARN, "awaiting container message timed out");
-            Err(DockerTestError::Startup(
-                "awaiting container message timed out".to_string(),
-            ))
+            Err(DockerTestError::Startup(format!(
+                "awaiting container message timed out: {}",
+                progress.describe(messages)
+            )))
         }
     }
 }
+
+/// The log line without its timestamp, if written at or after `since`.
+fn written_since(line: &str, since: Timestamp) -> Option<&str> {
+    let (timestamp, rest) = line.split_once(' ')?;
+    match Timestamp::parse(timestamp) {
+        Some(written) if written < since => None,
+        Some(_) => Some(rest),
+        None => Some(line),
+    }
+}
+
+/// The progress through a sequence of expected messages.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Progress {
+    /// The index of the message currently awaited.
+    index: usize,
+    /// The number of times the current message has appeared.
+    seen: usize,
+}
+
+impl Progress {
+    /// Count the occurrences of the messages within the content, in order, returning whether all
+    /// messages have appeared. Messages that must appear zero times are skipped.
+    fn observe(&mut self, messages: &[Occurrences], content: &str) -> bool {
+        let mut rest = content;
+        while let Some(expected) = messages.get(self.index) {
+            if self.seen >= expected.count {
+                self.index += 1;
+                self.seen = 0;
+                continue;
+            }
+            match rest.find(&expected.message) {
+                Some(position) => {
+                    rest = &rest[position + expected.message.len()..];
+                    self.seen += 1;
+                }
+                None => break,
+            }
+        }
+        self.completed(messages)
+    }
+
+    /// Whether all messages have appeared.
+    fn completed(&self, messages: &[Occurrences]) -> bool {
+        self.index >= messages.len()
+    }
+
+    /// Describe the message awaited, for error messages.
+    fn describe(&self, messages: &[Occurrences]) -> String {
+        match messages.get(self.index) {
+            Some(expected) => format!(
+                "`{}` appeared {} of {} times",
+                expected.message, self.seen, expected.count
+            ),
+            None => "all messages appeared".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{written_since, Occurrences, Progress};
+    use crate::utils::Timestamp;
+
+    // Tests that log lines written before the container was last started are disregarded
+    #[test]
+    fn test_written_since() {
+        let since = Timestamp::parse("2024-03-01T12:00:00.5Z").unwrap();
+        assert_eq!(
+            since,
+            Timestamp {
+                seconds: 1709294400,
+                nanos: 500_000_000,
+            }
+        );
+
+        assert_eq!(
+            written_since("2024-03-01T12:00:00.400000000Z ready\n", since),
+            None
+        );
+        assert_eq!(
+            written_since("2024-03-01T12:00:00.500000000Z ready\n", since),
+            Some("ready\n")
+        );
+        assert_eq!(
+            written_since("2024-03-01T12:00:01.000000000Z ready\n", since),
+            Some("ready\n")
+        );
+        assert_eq!(Timestamp::parse("2024-03-01T12:00:00.5+01:00"), None);
+    }
+
+    // Tests that occurrences are counted in order, also within a single chunk
+    #[test]
+    fn test_progress_counts_occurrences_in_order() {
+        let messages = [
+            Occurrences::times("worker ready", 2),
+            Occurrences::once("listening"),
+        ];
+        let mut progress = Progress::default();
+
+        assert!(!progress.observe(&messages, "listening\nworker ready\n"));
+        assert!(!progress.observe(&messages, "listening\n"));
+        assert!(progress.observe(&messages, "worker ready\nlistening\n"));
+    }
+
+    // Tests that a message must appear the requested number of times
+    #[test]
+    fn test_progress_requires_count() {
+        let messages = [Occurrences::times("partition assigned", 3)];
+        let mut progress = Progress::default();
+
+        assert!(!progress.observe(&messages, "partition assigned\npartition assigned\n"));
+        assert_eq!(
+            progress.describe(&messages),
+            "`partition assigned` appeared 2 of 3 times"
+        );
+        assert!(progress.observe(&messages, "partition assigned\n"));
+    }
+}
@@ -7,16 +7,32 @@ use crate::DockerTestError;
 pub use async_trait::async_trait;
 use dyn_clone::DynClone;
 
+mod marker;
 mod message;
 mod nowait;
+mod poll;
+mod port;
+mod probe;
 mod status;
 
-pub(crate) use message::wait_for_message;
-pub use message::{MessageSource, MessageWait};
+pub use marker::{MarkerFileWait, MARKER_FILE};
+pub(crate) use message::{wait_for_message, wait_for_messages};
+pub use message::{MessageSequenceWait, MessageSource, MessageWait, Occurrences};
 pub use nowait::NoWait;
+pub use poll::poll_until;
+pub use port::HostPortWait;
+pub use probe::{ExecWait, HealthyWait};
 pub use status::{ExitedWait, RunningWait};
 
 /// Trait to wait for a container to be ready for service.
+///
+/// Implementations are cloned along with the container specification they are configured on,
+/// such that state is only shared between clones if the implementation shares it explicitly.
+///
+/// [WaitFor::wait_for_ready] is executed within a `wait` tracing span carrying the `handle` of
+/// the container, such that the time spent and events emitted by the implementation are
+/// attributed to the container. Tasks spawned by the implementation must instrument themselves
+/// with the current span to be included.
 #[async_trait]
 pub trait WaitFor: Send + Sync + DynClone + std::fmt::Debug {
     /// Method implementation should return a future that resolves once the condition
@@ -28,6 +44,18 @@ pub trait WaitFor: Send + Sync + DynClone + std::fmt::Debug {
         &self,
         container: PendingContainer,
     ) -> Result<RunningContainer, DockerTestError>;
+
+    /// Whether the container is expected to exit once the condition is fulfilled, see
+    /// [Composition::expect_exit](crate::composition::Composition::expect_exit).
+    fn expects_exit(&self) -> bool {
+        false
+    }
+
+    /// The directory within the container the condition requires to be mounted as a tmpfs, e.g.,
+    /// the directory of the marker awaited by [MarkerFileWait].
+    fn tmpfs(&self) -> Option<String> {
+        None
+    }
 }
 
 dyn_clone::clone_trait_object!(WaitFor);
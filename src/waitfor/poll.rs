@@ -0,0 +1,145 @@
+//! Building block for custom `WaitFor` implementations: `poll_until`.
+
+use crate::container::{PendingContainer, RunningContainer};
+use crate::DockerTestError;
+
+use futures::Future;
+use tokio::time::{timeout as within, Duration, Instant, MissedTickBehavior};
+
+/// Poll `predicate` every `interval` until it reports the container as ready, for at most
+/// `timeout`, such that a custom [WaitFor] implementation is reduced to its predicate:
+///
+/// ```no_run
+/// use dockertest::waitfor::{async_trait, poll_until, WaitFor};
+/// use dockertest::{DockerTestError, PendingContainer, RunningContainer};
+/// use std::time::Duration;
+///
+/// #[derive(Clone, Debug)]
+/// struct MigrationsApplied;
+///
+/// #[async_trait]
+/// impl WaitFor for MigrationsApplied {
+///     async fn wait_for_ready(
+///         &self,
+///         container: PendingContainer,
+///     ) -> Result<RunningContainer, DockerTestError> {
+///         let (interval, timeout) = (Duration::from_secs(1), Duration::from_secs(60));
+///         poll_until(container, interval, timeout, |c| async move {
+///             c.exec(&["test", "-e", "/var/lib/app/migrated"]).await.map(|_| true)
+///         })
+///         .await
+///     }
+/// }
+/// ```
+///
+/// The predicate is provided the container, and resolves to `Ok(true)` once the container is
+/// ready, or to `Ok(false)` or an error while it is not. Each invocation is bounded by the time
+/// remaining. Once the timeout elapses, the startup error names the container along with the
+/// error of the last invocation, if any.
+///
+/// The ip address and published ports of the container are resolved once all containers have
+/// started, and are therefore not yet available to the predicate, unless refreshed through
+/// [RunningContainer::refresh]. Probing the container from within, e.g., through
+/// [RunningContainer::exec], requires neither.
+///
+/// [WaitFor]: crate::waitfor::WaitFor
+pub async fn poll_until<F, Fut, E>(
+    container: PendingContainer,
+    interval: Duration,
+    timeout: Duration,
+    mut predicate: F,
+) -> Result<RunningContainer, DockerTestError>
+where
+    F: FnMut(RunningContainer) -> Fut + Send,
+    Fut: Future<Output = Result<bool, E>> + Send,
+    E: std::fmt::Display,
+{
+    let running: RunningContainer = container.into();
+    let deadline = Instant::now() + timeout;
+
+    let mut last = None;
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticks.tick().await;
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+
+        match within(remaining, predicate(running.clone())).await {
+            Ok(Ok(true)) => return Ok(running),
+            Ok(Ok(false)) => last = None,
+            Ok(Err(e)) => last = Some(e.to_string()),
+            Err(_) => last = Some("timed out".to_string()),
+        }
+    }
+
+    Err(DockerTestError::Startup(format!(
+        "container `{}` did not become ready within {:?}{}",
+        running.handle,
+        timeout,
+        last.map(|e| format!(", last check failed: {}", e))
+            .unwrap_or_default()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::poll_until;
+    use crate::container::PendingContainer;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn container() -> PendingContainer {
+        PendingContainer::fixture("dockertest-rs-hello", "hello")
+    }
+
+    // Tests that the predicate is polled until it reports the container as ready
+    #[tokio::test]
+    async fn test_poll_until_ready() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let counter = polls.clone();
+        let running = poll_until(
+            container(),
+            Duration::from_millis(1),
+            Duration::from_secs(10),
+            move |_| {
+                let polls = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                async move {
+                    match polls {
+                        1 => Err("connection refused"),
+                        2 => Ok(false),
+                        _ => Ok(true),
+                    }
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(running.handle(), "hello");
+        assert_eq!(polls.load(Ordering::SeqCst), 3);
+    }
+
+    // Tests that the startup error names the container along with the last failed check
+    #[tokio::test]
+    async fn test_poll_until_timeout() {
+        let err = poll_until(
+            container(),
+            Duration::from_millis(10),
+            Duration::from_millis(50),
+            |_| async { Err::<bool, _>("connection refused") },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "startup condition not fulfilled `container `hello` did not become ready within \
+             50ms, last check failed: connection refused`"
+        );
+    }
+}
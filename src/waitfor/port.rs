@@ -0,0 +1,212 @@
+//! `WaitFor` implementation: `HostPortWait`.
+
+use crate::container::{HostPortMappings, PendingContainer, RunningContainer};
+use crate::waitfor::{async_trait, WaitFor};
+use crate::DockerTestError;
+
+use bollard::container::InspectContainerOptions;
+use bollard::models::PortMap;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+use tokio::time::{interval, timeout, Duration};
+use tracing::{event, Level};
+
+use std::convert::TryFrom;
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+/// How long an accepted connection must be held open, unless the container sends data, for the
+/// port to be considered ready.
+const HOLD_PROBE: Duration = Duration::from_millis(250);
+
+/// The HostPortWait `WaitFor` implementation for containers.
+/// This variant will wait until a tcp connection is accepted on the host port published for the
+/// container port.
+///
+/// Unlike waiting on the container port, this is the readiness signal observed by the test
+/// process on macOS and Windows, where the ip address of the container is not reachable from the
+/// host. The container port must be published, e.g., through `modify_port_map`, `publish_port`
+/// or `set_publish_all_ports`. Ports published on all interfaces are reached through localhost.
+///
+/// The port proxy of some docker daemons, e.g., Docker Desktop, accepts connections before the
+/// process within the container listens on the port, and closes them right away. A connection is
+/// therefore only considered accepted once it is held open briefly, or the container sends data
+/// on it.
+#[derive(Clone, Debug)]
+pub struct HostPortWait {
+    /// The tcp port within the container published on the host.
+    pub port: u32,
+    /// How many seconds shall there be between each check for an accepted connection.
+    pub check_interval: u64,
+    /// The number of checks to perform before erroring out.
+    pub max_checks: u64,
+}
+
+#[async_trait]
+impl WaitFor for HostPortWait {
+    async fn wait_for_ready(
+        &self,
+        container: PendingContainer,
+    ) -> Result<RunningContainer, DockerTestError> {
+        let client = &container.client;
+        let check_interval = Duration::from_secs(self.check_interval);
+
+        let mut last_error = "the container was never inspected".to_string();
+        let mut interval = interval(check_interval);
+        for _ in 0..self.max_checks {
+            interval.tick().await;
+
+            let details = match client
+                .inspect_container(&container.name, None::<InspectContainerOptions>)
+                .await
+            {
+                Ok(details) => details,
+                Err(e) => {
+                    last_error = e.to_string();
+                    continue;
+                }
+            };
+            if matches!(&details.state, Some(state) if state.running == Some(false)) {
+                return Err(DockerTestError::Startup(format!(
+                    "container `{}` exited while waiting for its published port {}",
+                    container.handle, self.port
+                )));
+            }
+
+            let ports = details
+                .network_settings
+                .as_ref()
+                .and_then(|settings| settings.ports.clone())
+                .unwrap_or_default();
+            let addr = match published_addr(ports, self.port) {
+                Ok(Some(addr)) => addr,
+                Ok(None) => {
+                    last_error = format!("port {} is not published on the host", self.port);
+                    continue;
+                }
+                Err(e) => {
+                    last_error = e;
+                    continue;
+                }
+            };
+
+            // Bound the connection attempt, as filtered ports never refuse the connection.
+            match timeout(
+                check_interval.max(Duration::from_secs(1)),
+                TcpStream::connect(addr),
+            )
+            .await
+            {
+                Ok(Ok(mut stream)) => {
+                    if let Err(e) = held_open(&mut stream).await {
+                        last_error = format!("connection to {} {}", addr, e);
+                        continue;
+                    }
+                    event!(
+                        Level::DEBUG,
+                        "container `{}` accepted a connection on published port {}",
+                        container.handle,
+                        addr
+                    );
+                    let mut running: RunningContainer = container.into();
+                    // Spare the engine from inspecting the container again once started.
+                    running.inspected = Some(Box::new(details));
+                    return Ok(running);
+                }
+                Ok(Err(e)) => last_error = format!("failed to connect to {}: {}", addr, e),
+                Err(_) => last_error = format!("timed out connecting to {}", addr),
+            }
+        }
+
+        Err(DockerTestError::Startup(format!(
+            "container `{}` did not accept a connection on the host port published for port {}: {}",
+            container.handle, self.port, last_error
+        )))
+    }
+}
+
+/// Whether the connection is held open by the peer, rather than closed or reset right after it
+/// was accepted, e.g., by a port proxy without a process listening within the container.
+async fn held_open(stream: &mut TcpStream) -> Result<(), String> {
+    let mut buf = [0; 1];
+    match timeout(HOLD_PROBE, stream.read(&mut buf)).await {
+        // Still open without data, as with most client-first protocols.
+        Err(_) => Ok(()),
+        // The container sends data first, e.g., a greeting.
+        Ok(Ok(n)) if n > 0 => Ok(()),
+        Ok(Ok(_)) => Err("was closed right after it was accepted".to_string()),
+        Ok(Err(e)) => Err(format!("failed right after it was accepted: {}", e)),
+    }
+}
+
+/// The host address the container port is published on, if published.
+fn published_addr(ports: PortMap, port: u32) -> Result<Option<SocketAddrV4>, String> {
+    let mappings = HostPortMappings::try_from(ports).map_err(|e| e.to_string())?;
+    Ok(mappings.get_number(port).map(|(ip, host_port)| {
+        let ip = if ip.is_unspecified() {
+            Ipv4Addr::LOCALHOST
+        } else {
+            *ip
+        };
+        SocketAddrV4::new(ip, *host_port as u16)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{held_open, published_addr};
+
+    use bollard::models::{PortBinding, PortMap};
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::{TcpListener, TcpStream};
+
+    use std::net::{Ipv4Addr, SocketAddrV4};
+
+    // Tests that the published host port is resolved, reached through localhost if published on
+    // all interfaces
+    #[test]
+    fn test_published_addr() {
+        let mut ports = PortMap::new();
+        ports.insert(
+            "8080/tcp".to_string(),
+            Some(vec![
+                PortBinding {
+                    host_ip: Some("0.0.0.0".to_string()),
+                    host_port: Some("32768".to_string()),
+                },
+                PortBinding {
+                    host_ip: Some("::".to_string()),
+                    host_port: Some("32768".to_string()),
+                },
+            ]),
+        );
+        ports.insert("9090/tcp".to_string(), None);
+
+        assert_eq!(
+            published_addr(ports.clone(), 8080),
+            Ok(Some(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 32768)))
+        );
+        assert_eq!(published_addr(ports, 9090), Ok(None));
+    }
+
+    // Tests that a connection closed right after it was accepted is not considered accepted,
+    // unlike a connection held open or on which data is sent
+    #[tokio::test]
+    async fn test_held_open() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        drop(listener.accept().await.unwrap());
+        assert!(held_open(&mut stream).await.is_err());
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let (_held, _) = listener.accept().await.unwrap();
+        assert_eq!(held_open(&mut stream).await, Ok(()));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let (mut greeting, _) = listener.accept().await.unwrap();
+        greeting.write_all(b"hello").await.unwrap();
+        drop(greeting);
+        assert_eq!(held_open(&mut stream).await, Ok(()));
+    }
+}
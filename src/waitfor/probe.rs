@@ -0,0 +1,207 @@
+//! `WaitFor` implementations probing the readiness of the container: `ExecWait` and `HealthyWait`.
+//!
+//! The output of the last probe is included in the startup error once the probes ultimately fail,
+//! such that the reason the container never became ready is part of the test failure.
+
+use crate::container::{exec_output, ExecOutput, PendingContainer, RunningContainer};
+use crate::waitfor::{async_trait, WaitFor};
+use crate::DockerTestError;
+
+use bollard::{container::InspectContainerOptions, models::HealthStatusEnum};
+use tokio::time::{interval, Duration};
+
+/// The ExecWait `WaitFor` implementation for containers.
+/// This variant will execute the command within the container until it exits successfully, e.g.,
+/// `pg_isready`.
+///
+/// If the command never succeeds, the startup error includes the exit code, stdout and stderr of
+/// the last execution.
+#[derive(Clone, Debug)]
+pub struct ExecWait {
+    /// The command to execute within the container, along with its arguments.
+    pub cmd: Vec<String>,
+    /// How many seconds shall there be between each execution of the command.
+    pub check_interval: u64,
+    /// The number of executions to perform before erroring out.
+    pub max_checks: u64,
+}
+
+/// The HealthyWait `WaitFor` implementation for containers.
+/// This variant will wait until the healthcheck of the container reports it as healthy.
+///
+/// If the container never becomes healthy, the startup error includes the exit code and output of
+/// the last healthcheck probe. Containers without a healthcheck fail immediately.
+#[derive(Clone, Debug)]
+pub struct HealthyWait {
+    /// How many seconds shall there be between each check of the health.
+    pub check_interval: u64,
+    /// The number of checks to perform before erroring out.
+    pub max_checks: u64,
+}
+
+/// The result of a single probe of the container.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(super) struct ProbeResult {
+    exit_code: Option<i64>,
+    stdout: String,
+    stderr: String,
+}
+
+impl ProbeResult {
+    pub(super) fn is_success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+impl From<ExecOutput> for ProbeResult {
+    fn from(output: ExecOutput) -> Self {
+        ProbeResult {
+            exit_code: output.exit_code,
+            stdout: output.stdout,
+            stderr: output.stderr,
+        }
+    }
+}
+
+impl std::fmt::Display for ProbeResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.exit_code {
+            Some(code) => write!(f, "exit code {}", code)?,
+            None => write!(f, "unknown exit code")?,
+        }
+        for (stream, output) in [("stdout", &self.stdout), ("stderr", &self.stderr)] {
+            if !output.trim().is_empty() {
+                write!(f, ", {}: {}", stream, output.trim())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl WaitFor for ExecWait {
+    async fn wait_for_ready(
+        &self,
+        container: PendingContainer,
+    ) -> Result<RunningContainer, DockerTestError> {
+        if self.cmd.is_empty() {
+            return Err(DockerTestError::Startup(format!(
+                "exec waitfor of container `{}` has no command",
+                container.handle
+            )));
+        }
+
+        let mut last = None;
+        let mut interval = interval(Duration::from_secs(self.check_interval));
+        for _ in 0..self.max_checks {
+            interval.tick().await;
+            match exec_output(&container.client, &container.name, &self.cmd)
+                .await
+                .map(ProbeResult::from)
+            {
+                Ok(result) if result.is_success() => return Ok(container.into()),
+                Ok(result) => last = Some(result.to_string()),
+                Err(e) => last = Some(e.to_string()),
+            }
+        }
+
+        Err(DockerTestError::Startup(format!(
+            "container `{}` did not become ready, `{}` never succeeded, last probe: {}",
+            container.handle,
+            self.cmd.join(" "),
+            last.unwrap_or_else(|| "never executed".to_string())
+        )))
+    }
+}
+
+#[async_trait]
+impl WaitFor for HealthyWait {
+    async fn wait_for_ready(
+        &self,
+        container: PendingContainer,
+    ) -> Result<RunningContainer, DockerTestError> {
+        let mut last = None;
+        let mut interval = interval(Duration::from_secs(self.check_interval));
+        for _ in 0..self.max_checks {
+            interval.tick().await;
+            let details = match container
+                .client
+                .inspect_container(&container.name, None::<InspectContainerOptions>)
+                .await
+            {
+                Ok(details) => details,
+                Err(e) => {
+                    last = Some(e.to_string());
+                    continue;
+                }
+            };
+
+            let health = details.state.as_ref().and_then(|s| s.health.clone());
+            let status = health.as_ref().and_then(|h| h.status);
+            match status {
+                Some(HealthStatusEnum::HEALTHY) => {
+                    let mut running: RunningContainer = container.into();
+                    // Spare the engine from inspecting the container again once started.
+                    running.inspected = Some(Box::new(details));
+                    return Ok(running);
+                }
+                None | Some(HealthStatusEnum::EMPTY) | Some(HealthStatusEnum::NONE) => {
+                    return Err(DockerTestError::Startup(format!(
+                        "container `{}` does not have a healthcheck configured",
+                        container.handle
+                    )));
+                }
+                Some(status) => {
+                    let probe = health
+                        .and_then(|h| h.log)
+                        .and_then(|log| log.into_iter().last())
+                        .map(|result| ProbeResult {
+                            exit_code: result.exit_code,
+                            stdout: result.output.unwrap_or_default(),
+                            stderr: String::new(),
+                        });
+                    last = Some(match probe {
+                        Some(probe) => format!("{}, {}", status, probe),
+                        None => status.to_string(),
+                    });
+                }
+            }
+        }
+
+        Err(DockerTestError::Startup(format!(
+            "container `{}` did not become healthy, last probe: {}",
+            container.handle,
+            last.unwrap_or_else(|| "never executed".to_string())
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProbeResult;
+
+    // Tests that the last probe describes its exit code and non-empty output
+    #[test]
+    fn test_probe_result_display() {
+        let probe = ProbeResult {
+            exit_code: Some(2),
+            stdout: "/var/run/postgresql:5432 - no response\n".to_string(),
+            stderr: String::new(),
+        };
+        assert!(!probe.is_success());
+        assert_eq!(
+            probe.to_string(),
+            "exit code 2, stdout: /var/run/postgresql:5432 - no response"
+        );
+
+        let probe = ProbeResult {
+            exit_code: None,
+            stdout: String::new(),
+            stderr: "connection refused".to_string(),
+        };
+        assert_eq!(
+            probe.to_string(),
+            "unknown exit code, stderr: connection refused"
+        );
+    }
+}
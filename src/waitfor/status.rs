@@ -35,7 +35,7 @@ impl WaitFor for RunningWait {
         container: PendingContainer,
     ) -> Result<RunningContainer, DockerTestError> {
         wait_for_container_state(container, self.check_interval, self.max_checks, |state| {
-            state.running.unwrap()
+            state.running == Some(true)
         })
         .await
     }
@@ -48,10 +48,14 @@ impl WaitFor for ExitedWait {
         container: PendingContainer,
     ) -> Result<RunningContainer, DockerTestError> {
         wait_for_container_state(container, self.check_interval, self.max_checks, |state| {
-            !state.running.unwrap()
+            state.running == Some(false)
         })
         .await
     }
+
+    fn expects_exit(&self) -> bool {
+        true
+    }
 }
 
 async fn wait_for_container_state(
@@ -62,7 +66,7 @@ async fn wait_for_container_state(
 ) -> Result<RunningContainer, DockerTestError> {
     let client = &container.client;
 
-    let mut started = false;
+    let mut inspected = None;
     let mut num_checks = 0;
 
     // Periodically check container state in an interval.
@@ -76,27 +80,30 @@ async fn wait_for_container_state(
             break;
         }
 
-        started = if let Ok(c) = client
+        if let Ok(c) = client
             .inspect_container(&container.name, None::<InspectContainerOptions>)
             .await
         {
-            container_state_compare(&c.clone().state.unwrap())
-        } else {
-            false
-        };
-
-        if started {
-            break;
+            // Some daemons may omit the state, which is treated as not yet fulfilled
+            if matches!(&c.state, Some(state) if container_state_compare(state)) {
+                inspected = Some(c);
+                break;
+            }
         }
 
         num_checks += 1;
         interval.tick().await;
     }
 
-    match started {
-        false => Err(DockerTestError::Startup(
+    match inspected {
+        None => Err(DockerTestError::Startup(
             "status waitfor is not triggered".to_string(),
         )),
-        true => Ok(container.into()),
+        Some(details) => {
+            let mut running: RunningContainer = container.into();
+            // Spare the engine from inspecting the container again once started.
+            running.inspected = Some(Box::new(details));
+            Ok(running)
+        }
     }
 }
@@ -0,0 +1,299 @@
+//! An experimental pool of containers created ahead of time, see
+//! [TestBodySpecification::set_warm_pool].
+//!
+//! Containers with a warm pool are taken from a pool of created, but not yet started, containers
+//! of the same definition instead of being created by the test. The pool is refilled in the
+//! background, amortizing the creation of containers across the tests of a large test suite.
+//! Pooled containers are labeled with the hash of their definition, such that later test binaries
+//! take them as well. Containers that are not taken within an hour are removed once a pool is
+//! refilled, and may otherwise be removed through
+//! `docker container prune --filter label=dockertest.pool`.
+//!
+//! [TestBodySpecification::set_warm_pool]: crate::TestBodySpecification::set_warm_pool
+
+use crate::background::spawn_background;
+use crate::composition::Composition;
+use crate::janitor;
+use crate::utils::{generate_random_string, stable_hash};
+use crate::DockerTestError;
+
+use bollard::{
+    container::{ListContainersOptions, RemoveContainerOptions, RenameContainerOptions},
+    models::EndpointSettings,
+    network::{ConnectNetworkOptions, DisconnectNetworkOptions},
+    Docker,
+};
+use lazy_static::lazy_static;
+use tracing::{event, Level};
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The label holding the hash of the definition of a pooled container.
+pub(crate) const POOL_LABEL: &str = "dockertest.pool";
+
+/// The name prefix of containers that are still pooled. Taken containers are renamed.
+const POOL_PREFIX: &str = "dockertest-pool";
+
+/// The network pooled containers are created on, as the network of the test is not yet known.
+pub(crate) const POOL_NETWORK: &str = "bridge";
+
+/// How long a container may remain pooled before it is removed, e.g., as the definition of its
+/// pool changed such that no test takes it anymore.
+const POOL_EXPIRY: Duration = Duration::from_secs(60 * 60);
+
+lazy_static! {
+    /// The hashes of the pools currently being refilled by this process.
+    static ref REFILLING: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// Hash the definition of the container, including the id of its image, such that containers of
+/// identical definitions share a pool across test binaries.
+pub(crate) fn definition_hash(composition: &Composition) -> String {
+    stable_hash(&format!(
+        "{}|{}",
+        composition.image().retrieved_id(),
+        composition.definition()
+    ))
+}
+
+/// Take a container from the pool of the definition with the given hash, if any, renaming it to
+/// `name` and connecting it to the `network` of the test.
+///
+/// Pooled containers are renamed by their pool name, which fails if another test has already
+/// taken the container.
+pub(crate) async fn take(
+    client: &Docker,
+    hash: &str,
+    name: &str,
+    network: Option<&str>,
+    aliases: &[String],
+) -> Result<Option<String>, DockerTestError> {
+    for (pool_name, id) in pooled_containers(client, hash).await {
+        let options = RenameContainerOptions { name };
+        if let Err(e) = client.rename_container(&pool_name, options).await {
+            event!(
+                Level::DEBUG,
+                "pooled container `{}` was taken by another test: {}",
+                pool_name,
+                e
+            );
+            continue;
+        }
+        event!(
+            Level::DEBUG,
+            "took pooled container `{}` as `{}`",
+            pool_name,
+            name
+        );
+
+        if let Some(network) = network {
+            connect(client, &id, network, aliases).await?;
+        }
+        return Ok(Some(id));
+    }
+
+    Ok(None)
+}
+
+/// Refill the pool of the `composition` up to `size` containers in the background.
+pub(crate) fn refill(client: Docker, composition: Composition, hash: String, size: usize) {
+    if !REFILLING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(hash.clone())
+    {
+        return;
+    }
+
+    spawn_background(async move {
+        expire(&client).await;
+        let pooled = pooled_containers(&client, &hash).await.len();
+        event!(
+            Level::DEBUG,
+            "refilling pool `{}` with {} containers",
+            hash,
+            size.saturating_sub(pooled)
+        );
+        for _ in pooled..size {
+            let mut pooled = composition.clone();
+            pooled.container_name =
+                format!("{}-{}-{}", POOL_PREFIX, hash, generate_random_string(10));
+            pooled.labels.insert(POOL_LABEL.to_string(), hash.clone());
+            // Pooled containers outlive the test process.
+            pooled.labels.remove(janitor::SESSION_LABEL);
+            if let Err(e) = pooled.create_pooled(&client).await {
+                event!(Level::WARN, "failed to refill pool `{}`: {}", hash, e);
+                break;
+            }
+        }
+        REFILLING
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&hash);
+    });
+}
+
+/// The name and id of every container still pooled for the definition with the given hash.
+async fn pooled_containers(client: &Docker, hash: &str) -> Vec<(String, String)> {
+    let label = format!("{}={}", POOL_LABEL, hash);
+    let options = ListContainersOptions {
+        all: true,
+        filters: HashMap::from([("label", vec![label.as_str()]), ("status", vec!["created"])]),
+        ..Default::default()
+    };
+
+    match client.list_containers(Some(options)).await {
+        Ok(containers) => containers
+            .into_iter()
+            .filter_map(|c| {
+                let name = c.names?.into_iter().next()?;
+                let name = name.trim_start_matches('/').to_string();
+                name.starts_with(POOL_PREFIX).then_some((name, c.id?))
+            })
+            .collect(),
+        Err(e) => {
+            event!(
+                Level::WARN,
+                "failed to list the containers of pool `{}`: {}",
+                hash,
+                e
+            );
+            Vec::new()
+        }
+    }
+}
+
+/// Remove the containers of every pool that have been pooled for longer than [POOL_EXPIRY].
+///
+/// Containers are removed by their pool name, such that a container taken, and thereby renamed,
+/// by a test in the meantime is left untouched.
+async fn expire(client: &Docker) {
+    let options = ListContainersOptions {
+        all: true,
+        filters: HashMap::from([("label", vec![POOL_LABEL]), ("status", vec!["created"])]),
+        ..Default::default()
+    };
+    let containers = match client.list_containers(Some(options)).await {
+        Ok(containers) => containers,
+        Err(e) => {
+            event!(Level::WARN, "failed to list pooled containers: {}", e);
+            return;
+        }
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    for c in containers {
+        let name = match c.names.and_then(|names| names.into_iter().next()) {
+            Some(name) => name.trim_start_matches('/').to_string(),
+            None => continue,
+        };
+        let expired = matches!(c.created, Some(created)
+            if now - created > POOL_EXPIRY.as_secs() as i64);
+        if !expired || !name.starts_with(POOL_PREFIX) {
+            continue;
+        }
+
+        let options = Some(RemoveContainerOptions {
+            force: true,
+            ..Default::default()
+        });
+        match client.remove_container(&name, options).await {
+            Ok(_) => event!(Level::DEBUG, "removed expired pooled container `{}`", name),
+            Err(e) => event!(
+                Level::DEBUG,
+                "failed to remove expired pooled container `{}`: {}",
+                name,
+                e
+            ),
+        }
+    }
+}
+
+/// Move a taken container from the network of the pool onto the network of the test.
+async fn connect(
+    client: &Docker,
+    id: &str,
+    network: &str,
+    aliases: &[String],
+) -> Result<(), DockerTestError> {
+    let options = ConnectNetworkOptions {
+        container: id,
+        endpoint_config: EndpointSettings {
+            aliases: (!aliases.is_empty()).then(|| aliases.to_vec()),
+            ..Default::default()
+        },
+    };
+    client
+        .connect_network(network, options)
+        .await
+        .map_err(|e| {
+            DockerTestError::Startup(format!(
+                "failed to connect pooled container to network `{}`: {}",
+                network, e
+            ))
+        })?;
+
+    let options = DisconnectNetworkOptions {
+        container: id,
+        force: true,
+    };
+    if let Err(e) = client.disconnect_network(POOL_NETWORK, options).await {
+        event!(
+            Level::WARN,
+            "failed to disconnect pooled container from network `{}`: {}",
+            POOL_NETWORK,
+            e
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::definition_hash;
+    use crate::composition::Composition;
+
+    // Tests that the hash depends on the definition of the container, not its name
+    #[test]
+    fn test_definition_hash() {
+        let mut postgres = Composition::with_repository("postgres");
+        postgres.env("POSTGRES_PASSWORD", "secret");
+        let mut renamed = postgres.clone();
+        renamed.container_name = "ns-postgres-suffix".to_string();
+
+        let hash = definition_hash(&postgres);
+        assert_eq!(hash, definition_hash(&renamed));
+
+        postgres.env("POSTGRES_PASSWORD", "other");
+        assert_ne!(hash, definition_hash(&postgres));
+    }
+
+    // Tests that labels and log options are part of the definition, unlike the labels managed by
+    // dockertest
+    #[test]
+    fn test_definition_hash_labels_and_log_options() {
+        let postgres = Composition::with_repository("postgres");
+        let hash = definition_hash(&postgres);
+
+        let mut session = postgres.clone();
+        session
+            .labels
+            .insert("dockertest.session".to_string(), "other".to_string());
+        assert_eq!(hash, definition_hash(&session));
+
+        let mut labeled = postgres.clone();
+        labeled
+            .labels
+            .insert("team".to_string(), "storage".to_string());
+        assert_ne!(hash, definition_hash(&labeled));
+
+        let silent = postgres.with_log_options(None);
+        assert_ne!(hash, definition_hash(&silent));
+    }
+}
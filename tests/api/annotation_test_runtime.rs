@@ -14,3 +14,70 @@ async fn test_with_tokio_test() {
     })
     .await;
 }
+
+// Tests that an error returned by the test body fails the test executed within the runtime of
+// the caller
+#[tokio::test]
+#[should_panic(expected = "test body failure `invalid digit found in string`")]
+async fn test_result_with_tokio_test() {
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new().with_default_source(source);
+
+    let hello_world = TestBodySpecification::with_repository("hello-world");
+    test.provide_container(hello_world);
+
+    test.run_async_result(|ops| async move {
+        ops.handle("hello-world");
+        let _port: u16 = "http".parse()?;
+        Ok::<(), std::num::ParseIntError>(())
+    })
+    .await;
+}
+
+// Tests that the test can be executed within a provided runtime
+#[test]
+fn test_with_runtime_handle() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new()
+        .with_default_source(source)
+        .with_runtime_handle(runtime.handle().clone());
+
+    let repo = "hello-world";
+    let hello_world = TestBodySpecification::with_repository(repo);
+    test.provide_container(hello_world);
+
+    test.run(|ops| async move {
+        ops.handle("hello-world");
+    });
+}
+
+// Tests that multiple tests within the same runtime can share the docker client
+#[test]
+fn test_with_shared_client() {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+        .unwrap();
+
+    for _ in 0..2 {
+        let mut test = DockerTest::new()
+            .with_default_source(Source::DockerHub)
+            .with_runtime_handle(runtime.handle().clone())
+            .with_shared_client();
+
+        let repo = "hello-world";
+        let hello_world = TestBodySpecification::with_repository(repo);
+        test.provide_container(hello_world);
+
+        test.run(|ops| async move {
+            ops.handle("hello-world");
+        });
+    }
+}
@@ -0,0 +1,62 @@
+use dockertest::waitfor::{HealthyWait, RunningWait};
+use dockertest::{DockerTest, HealthStatus, Source, TestBodySpecification};
+
+// Tests that we can await a container to become healthy from the test body
+#[tokio::test]
+async fn test_assert_healthy() {
+    dockertest::selftest_images::ensure().await.unwrap();
+    let mut test = DockerTest::new();
+    let healthy = TestBodySpecification::with_repository("dockertest-rs/healthy").set_wait_for(
+        Box::new(RunningWait {
+            check_interval: 1,
+            max_checks: 10,
+        }),
+    );
+    test.provide_container(healthy);
+
+    test.run_async(|ops| async move {
+        let container = ops.handle("dockertest-rs/healthy");
+        container.assert_healthy(30).await;
+        assert_eq!(container.health().await.unwrap(), HealthStatus::Healthy);
+    })
+    .await;
+}
+
+// Tests that containers without a healthcheck report no health status
+#[tokio::test]
+async fn test_health_without_healthcheck() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    let sleep = TestBodySpecification::with_repository("luca3m/sleep")
+        .set_wait_for(Box::new(RunningWait {
+            check_interval: 1,
+            max_checks: 10,
+        }))
+        .set_handle("sleep");
+    test.provide_container(sleep);
+
+    test.run_async(|ops| async move {
+        let container = ops.handle("sleep");
+        assert_eq!(container.health().await.unwrap(), HealthStatus::None);
+    })
+    .await;
+}
+
+// Tests that the HealthyWait implementation waits for the healthcheck to pass
+#[tokio::test]
+async fn test_healthy_wait_for() {
+    dockertest::selftest_images::ensure().await.unwrap();
+    let mut test = DockerTest::new();
+    let healthy = TestBodySpecification::with_repository("dockertest-rs/healthy").set_wait_for(
+        Box::new(HealthyWait {
+            check_interval: 1,
+            max_checks: 30,
+        }),
+    );
+    test.provide_container(healthy);
+
+    test.run_async(|ops| async move {
+        let container = ops.handle("dockertest-rs/healthy");
+        assert_eq!(container.health().await.unwrap(), HealthStatus::Healthy);
+    })
+    .await;
+}
@@ -1,5 +1,12 @@
 use bollard::Docker;
-use dockertest::{utils::connect_with_local_or_tls_defaults, RunningContainer};
+use dockertest::waitfor::RunningWait;
+use dockertest::{
+    utils::connect_with_local_or_tls_defaults, DockerTest, RunningContainer, Source,
+    TestBodySpecification,
+};
+
+/// The repository of a container that sleeps until it is stopped.
+pub const SLEEP_REPOSITORY: &str = "luca3m/sleep";
 
 pub struct TestHelper {
     client: Docker,
@@ -34,4 +41,57 @@ impl TestHelper {
             .unwrap()
             .cmd
     }
+
+    pub async fn stop(&self, handle: &RunningContainer) {
+        self.client
+            .stop_container(handle.name(), None)
+            .await
+            .unwrap();
+    }
+
+    pub async fn networks(&self, handle: &RunningContainer) -> Vec<String> {
+        self.client
+            .inspect_container(handle.name(), None)
+            .await
+            .unwrap()
+            .network_settings
+            .unwrap()
+            .networks
+            .unwrap_or_default()
+            .into_keys()
+            .collect()
+    }
+
+    pub async fn container_exists(&self, name: &str) -> bool {
+        self.client.inspect_container(name, None).await.is_ok()
+    }
+
+    pub async fn network_exists(&self, network: &str) -> bool {
+        self.client
+            .inspect_network::<&str>(network, None)
+            .await
+            .is_ok()
+    }
+}
+
+/// Ensure that the `dockertest-rs/*` images used by the test are built, from a synchronous test.
+pub fn ensure_test_images() {
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(dockertest::selftest_images::ensure())
+        .expect("failed to build the test images");
+}
+
+/// A test of a single container of [SLEEP_REPOSITORY] pulled from Docker Hub, which is awaited
+/// until it is running.
+pub fn sleep_test() -> DockerTest {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    let sleep_container = TestBodySpecification::with_repository(SLEEP_REPOSITORY).set_wait_for(
+        Box::new(RunningWait {
+            max_checks: 10,
+            check_interval: 60,
+        }),
+    );
+    test.provide_container(sleep_container);
+    test
 }
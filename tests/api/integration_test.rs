@@ -1,10 +1,16 @@
 use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use dockertest::waitfor::RunningWait;
-use dockertest::{DockerTest, Source, TestBodySpecification};
+use dockertest::{
+    ConnectionKind, DockerTest, FsChangeKind, Network, ResourceUpdate, Source, StatsFormat,
+    TestBodySpecification,
+};
+use futures::StreamExt;
 use test_log::test;
 
-use crate::helper::TestHelper;
+use crate::helper::{ensure_test_images, sleep_test, TestHelper, SLEEP_REPOSITORY};
 
 #[test]
 fn test_run_with_no_failure() {
@@ -35,6 +41,358 @@ fn test_run_with_failure() {
     });
 }
 
+// Tests that a synchronous test body can be executed
+#[test]
+fn test_run_blocking_with_no_failure() {
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new().with_default_source(source);
+
+    let hello_world = TestBodySpecification::with_repository("hello-world");
+    test.provide_container(hello_world);
+
+    test.run_blocking(|ops| {
+        ops.handle("hello-world");
+    });
+}
+
+// Tests that a panic within a synchronous test body fails the test
+#[test]
+#[should_panic(expected = "blocking failure")]
+fn test_run_blocking_with_failure() {
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new().with_default_source(source);
+
+    let hello_world = TestBodySpecification::with_repository("hello-world");
+    test.provide_container(hello_world);
+
+    test.run_blocking(|_ops| {
+        panic!("blocking failure");
+    });
+}
+
+// Tests that a failure recorded through `fail_with` fails the test once the test body completes
+#[test]
+#[should_panic(expected = "test body failure `unexpected response: connection refused`")]
+fn test_run_with_recorded_failure() {
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new().with_default_source(source);
+
+    let hello_world = TestBodySpecification::with_repository("hello-world");
+    test.provide_container(hello_world);
+
+    test.run(|ops| async move {
+        let cause =
+            std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "connection refused");
+        ops.fail_with(Failure(cause));
+    });
+}
+
+#[derive(Debug)]
+struct Failure(std::io::Error);
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unexpected response")
+    }
+}
+
+impl std::error::Error for Failure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+// Tests that a test body returning `Ok` succeeds
+#[test]
+fn test_run_result_with_no_failure() {
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new().with_default_source(source);
+
+    let hello_world = TestBodySpecification::with_repository("hello-world");
+    test.provide_container(hello_world);
+
+    test.run_result(|ops| async move {
+        let port: u16 = "8080".parse()?;
+        ops.handle("hello-world");
+        assert_eq!(port, 8080);
+        Ok::<(), std::num::ParseIntError>(())
+    });
+}
+
+// Tests that an error returned by the test body fails the test
+#[test]
+#[should_panic(expected = "test body failure `invalid digit found in string`")]
+fn test_run_result_with_failure() {
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new().with_default_source(source);
+
+    let hello_world = TestBodySpecification::with_repository("hello-world");
+    test.provide_container(hello_world);
+
+    test.run_result(|_ops| async move {
+        let _port: u16 = "http".parse()?;
+        Ok::<(), std::num::ParseIntError>(())
+    });
+}
+
+// Tests that the docker daemon information is available to the test body
+#[test]
+fn test_daemon_info_is_available() {
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new().with_default_source(source);
+
+    let hello_world = TestBodySpecification::with_repository("hello-world");
+    test.provide_container(hello_world);
+
+    test.run(|ops| async move {
+        let daemon = ops.daemon_info();
+        assert!(!daemon.version().is_empty());
+        assert!(daemon.supports_api_version(1, 0));
+    });
+}
+
+// Tests that the environment report describes every started container
+#[test]
+fn test_report_describes_started_containers() {
+    let test = sleep_test();
+    let repo = SLEEP_REPOSITORY;
+
+    test.run(|ops| async move {
+        let handle = ops.handle(repo);
+        let report = ops.report();
+        assert!(report.contains(handle.name()));
+        assert!(report.contains(&handle.ip().to_string()));
+    });
+}
+
+// Tests that the processes running within a container can be listed
+#[test]
+fn test_processes_lists_container_processes() {
+    let test = sleep_test();
+    let repo = SLEEP_REPOSITORY;
+
+    test.run(|ops| async move {
+        let processes = ops.handle(repo).processes().await.unwrap();
+        assert!(processes
+            .iter()
+            .any(|p| p.command().is_some_and(|c| c.contains("sleep"))));
+        assert!(processes.iter().all(|p| p.pid().is_some()));
+    });
+}
+
+// Tests that the filesystem changes of a container are listed
+#[test]
+fn test_fs_changes_lists_added_paths() {
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new().with_default_source(source);
+
+    let sleep_container = TestBodySpecification::with_repository("alpine")
+        .replace_cmd(vec![
+            "/bin/sh".to_string(),
+            "-c".to_string(),
+            "touch /tmp/dockertest && sleep 3600".to_string(),
+        ])
+        .set_wait_for(Box::new(RunningWait {
+            max_checks: 10,
+            check_interval: 1,
+        }));
+    test.provide_container(sleep_container);
+
+    test.run(|ops| async move {
+        // Allow the command to create the file
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+        let changes = ops.handle("alpine").fs_changes().await.unwrap();
+        assert!(changes
+            .iter()
+            .any(|c| c.path() == "/tmp/dockertest" && c.kind() == FsChangeKind::Added));
+    });
+}
+
+// Tests that a test with background teardown returns once the test body completes, and that its
+// containers are removed once the pending teardowns are awaited
+#[test]
+fn test_background_teardown() {
+    let test_helper = TestHelper::new();
+    let name = Arc::new(Mutex::new(String::new()));
+
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new()
+        .with_default_source(source)
+        .with_background_teardown();
+
+    let repo = "hello-world";
+    let hello_world = TestBodySpecification::with_repository(repo);
+    test.provide_container(hello_world);
+
+    let body_name = name.clone();
+    test.run(|ops| async move {
+        *body_name.lock().unwrap() = ops.handle(repo).name().to_string();
+    });
+
+    dockertest::finish_suite(Duration::from_secs(60));
+    let name = name.lock().unwrap().clone();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    assert!(!runtime.block_on(test_helper.container_exists(&name)));
+}
+
+/// Set for the test process spawned by `test_suite_summary_at_exit`.
+const SUITE_SUMMARY_CHILD: &str = "DOCKERTEST_SUITE_SUMMARY_CHILD";
+
+// Starts an environment printing the suite summary, only within the test process spawned by
+// `test_suite_summary_at_exit`
+#[test]
+fn suite_summary_child() {
+    if std::env::var_os(SUITE_SUMMARY_CHILD).is_none() {
+        return;
+    }
+
+    let mut test = DockerTest::new()
+        .with_default_source(Source::DockerHub)
+        .with_suite_summary();
+    let repo = "hello-world";
+    test.provide_container(TestBodySpecification::with_repository(repo));
+    test.run(|ops| async move {
+        ops.handle(repo);
+    });
+}
+
+// Tests that the suite summary is printed as the test process exits
+#[test]
+fn test_suite_summary_at_exit() {
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args([
+            "--exact",
+            "integration_test::suite_summary_child",
+            "--nocapture",
+        ])
+        .env(SUITE_SUMMARY_CHILD, "1")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("dockertest: started 1 environments with 1 containers"),
+        "the summary should be printed at exit, got: {}",
+        stderr
+    );
+    assert!(
+        stderr.contains("images used:         hello-world"),
+        "the summary should list the images, got: {}",
+        stderr
+    );
+}
+
+// Tests that the shared network is deleted once the last test using it exits
+#[test]
+fn test_shared_network_is_deleted_after_last_test() {
+    let test_helper = TestHelper::new();
+    let networks = Arc::new(Mutex::new(Vec::new()));
+
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new()
+        .with_default_source(source)
+        .with_network(Network::Shared);
+
+    let repo = "hello-world";
+    let hello_world = TestBodySpecification::with_repository(repo);
+    test.provide_container(hello_world);
+
+    let body_networks = networks.clone();
+    let body_helper = TestHelper::new();
+    test.run(|ops| async move {
+        let container_networks = body_helper.networks(ops.handle(repo)).await;
+        *body_networks.lock().unwrap() = container_networks;
+    });
+
+    let networks = networks.lock().unwrap().clone();
+    assert_eq!(networks.len(), 1);
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    assert!(!runtime.block_on(test_helper.network_exists(&networks[0])));
+}
+
+// Tests that refreshing a container re-resolves its ip address after it has stopped
+#[test]
+fn test_refresh_resolves_current_ip() {
+    let test_helper = TestHelper::new();
+    let test = sleep_test();
+    let repo = SLEEP_REPOSITORY;
+
+    test.run(|ops| async move {
+        let mut container = ops.handle(repo).clone();
+        assert_ne!(container.ip(), &Ipv4Addr::UNSPECIFIED);
+
+        container.refresh().await.unwrap();
+        assert_eq!(container.ip(), ops.handle(repo).ip());
+
+        test_helper.stop(&container).await;
+        container.refresh().await.unwrap();
+        assert_eq!(container.ip(), &Ipv4Addr::UNSPECIFIED);
+        assert!(container.try_ip().is_err());
+    });
+}
+
+// Tests that the filesystem of a container can be exported
+#[test]
+fn test_export_writes_container_filesystem() {
+    let test = sleep_test();
+    let repo = SLEEP_REPOSITORY;
+
+    test.run(|ops| async move {
+        let path = std::env::temp_dir().join(format!("{}.tar", ops.handle(repo).name()));
+        ops.handle(repo).export(&path).await.unwrap();
+
+        let size = std::fs::metadata(&path).unwrap().len();
+        std::fs::remove_file(&path).unwrap();
+        assert!(size > 0, "exported archive should not be empty");
+    });
+}
+
+// Tests that the resource limits of a running container can be updated
+#[test]
+fn test_update_resources_of_running_container() {
+    let test = sleep_test();
+    let repo = SLEEP_REPOSITORY;
+
+    test.run(|ops| async move {
+        let update = ResourceUpdate::new()
+            .with_memory(64 * 1024 * 1024)
+            .with_memory_swap(-1)
+            .with_nano_cpus(500_000_000);
+        ops.handle(repo).update_resources(update).await.unwrap();
+    });
+}
+
+// Tests that the resource usage of a container is sampled and recorded
+#[test]
+fn test_stats_are_sampled_and_recorded() {
+    let test = sleep_test();
+    let repo = SLEEP_REPOSITORY;
+
+    test.run(|ops| async move {
+        let container = ops.handle(repo);
+        let samples: Vec<_> = container
+            .stats_stream(std::time::Duration::from_secs(1))
+            .take(2)
+            .collect()
+            .await;
+        assert_eq!(samples.len(), 2);
+        assert!(samples.iter().all(|s| s.as_ref().unwrap().memory_usage > 0));
+
+        let path = std::env::temp_dir().join(format!("{}.csv", container.name()));
+        let recorder =
+            container.record_stats(std::time::Duration::from_secs(1), &path, StatsFormat::Csv);
+        tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+        let written = recorder.stop().await.unwrap();
+
+        let recorded = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(written > 0);
+        assert_eq!(recorded.lines().count(), written + 1);
+    });
+}
+
 // Tests that we can retrieve the handle of a container by providing the repository as the key
 #[test]
 fn test_resolve_handle_with_repository_as_key() {
@@ -85,11 +443,10 @@ fn test_resolve_handle_with_user_provided_container_name_as_key() {
     });
 }
 
-// Tests that we fail to retrieve a handle for a container when multiple containers have the same
-// user provided container name
+// Tests that validation fails when multiple containers have the same user provided container name
 #[test]
 #[should_panic(
-    expected = "test body failure `handle 'this_is_a_container_name' defined multiple times`"
+    expected = "container name `this_is_a_container_name` is provided by multiple containers"
 )]
 fn test_resolve_handle_with_identical_user_provided_container_name() {
     let source = Source::DockerHub;
@@ -171,6 +528,29 @@ fn test_host_port_returns_correct_host_port_when_using_port_mapping() {
     });
 }
 
+#[test]
+fn test_host_port_returns_daemon_assigned_host_port_of_published_port() {
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new().with_default_source(source);
+
+    let repo = "luca3m/sleep";
+    let mut composition =
+        TestBodySpecification::with_repository(repo).set_wait_for(Box::new(RunningWait {
+            max_checks: 10,
+            check_interval: 60,
+        }));
+    composition.publish_port(7900);
+    test.provide_container(composition);
+
+    test.run(|ops| async move {
+        let handle = ops.handle(repo);
+        let ports = handle.host_port(7900).unwrap();
+
+        assert_ne!(0, ports.1);
+        assert_eq!(Ipv4Addr::LOCALHOST, ports.0);
+    });
+}
+
 #[test]
 fn test_host_port_returns_the_last_port_mapping_if_multiple_mappings_applied_to_same_container_port(
 ) {
@@ -214,6 +594,7 @@ fn test_host_port_returns_none_if_the_port_is_not_mapped() {
 
 #[test]
 fn test_host_port_returns_ports_exposed_by_publish_all() {
+    ensure_test_images();
     let source = Source::DockerHub;
     let mut test = DockerTest::new().with_default_source(source);
 
@@ -338,10 +719,9 @@ fn test_replace_cmd_replaces_command() {
     });
 }
 
-// TODO: return a reasonable error message
 #[test]
 #[should_panic(
-    expected = "processing error condition ``Composition::create()` invoked without populating its image through `Image::pull()``"
+    expected = "docker pull this_does_not_exist:latest  # container `this_does_not_exist`"
 )]
 fn test_non_existing_local_image_fails() {
     let source = Source::DockerHub;
@@ -355,3 +735,137 @@ fn test_non_existing_local_image_fails() {
         panic!();
     });
 }
+
+#[test]
+fn test_raw_config_hook_mutates_config_before_creation() {
+    let test_helper = TestHelper::new();
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+
+    let repo = "hello-world";
+    let hello_world =
+        TestBodySpecification::with_repository(repo).set_raw_config_hook(|config, host_config| {
+            config
+                .env
+                .get_or_insert_with(Vec::new)
+                .push("RAW_HOOK=applied".to_string());
+            host_config.init = Some(true);
+        });
+    test.provide_container(hello_world);
+
+    test.run(|ops| async move {
+        let handle = ops.handle(repo);
+        assert_eq!(
+            test_helper.env_value(handle, "RAW_HOOK").await.unwrap(),
+            "applied"
+        );
+    });
+}
+
+// Tests that containers are stopped after the containers depending on them, through injected
+// names and placeholders of their environment
+#[test]
+fn test_ordered_teardown_stops_dependent_containers() {
+    let report = Arc::new(Mutex::new(None));
+    let captured = report.clone();
+    // The sleeping containers ignore the stop signal, and are killed once the timeout expires.
+    let mut test = DockerTest::new()
+        .with_default_source(Source::DockerHub)
+        .with_ordered_teardown()
+        .with_graceful_stop(1)
+        .with_teardown_hook(move |report| *captured.lock().unwrap() = Some(report.clone()));
+
+    let sleeper = |handle: &str| {
+        TestBodySpecification::with_repository("busybox")
+            .set_handle(handle)
+            .replace_cmd(vec!["sleep".to_string(), "60".to_string()])
+            .set_wait_for(Box::new(RunningWait {
+                check_interval: 1,
+                max_checks: 10,
+            }))
+    };
+    let db = sleeper("db");
+    let mut app = sleeper("app");
+    app.inject_container_name("db", "DB_HOST");
+    let mut proxy = sleeper("proxy");
+    proxy.modify_env("UPSTREAM", "{handle:app}");
+    test.provide_container(proxy)
+        .provide_container(app)
+        .provide_container(db);
+
+    test.run(|ops| async move {
+        let db = ops.handle("db").name().to_string();
+        let host = ops
+            .handle("app")
+            .exec(&["printenv", "DB_HOST"])
+            .await
+            .unwrap();
+        assert_eq!(host.trim(), db);
+    });
+
+    let report = report.lock().unwrap().take().unwrap();
+    let finished_at = |handle: &str| {
+        let exit = report.container(handle).unwrap();
+        comparable_timestamp(exit.finished_at.as_deref().unwrap())
+    };
+    assert!(finished_at("proxy") < finished_at("app"));
+    assert!(finished_at("app") < finished_at("db"));
+}
+
+// The timestamp reported by the docker daemon, with trailing zeros of the fractional seconds, which
+// the daemon omits, such that timestamps compare in order.
+fn comparable_timestamp(timestamp: &str) -> String {
+    let timestamp = timestamp.trim_end_matches('Z');
+    let (seconds, fraction) = timestamp.split_once('.').unwrap_or((timestamp, ""));
+    format!("{}.{:0<9}", seconds, fraction)
+}
+
+#[test]
+fn test_connection_string_from_environment() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+
+    let mut redis =
+        TestBodySpecification::with_repository("redis").set_wait_for(Box::new(RunningWait {
+            check_interval: 1,
+            max_checks: 10,
+        }));
+    redis.modify_env("REDIS_PASSWORD", "hunter 2");
+    test.provide_container(redis);
+
+    test.run(|ops| async move {
+        let redis = ops.handle("redis");
+        assert_eq!(
+            redis.connection_string(ConnectionKind::Redis).unwrap(),
+            format!(
+                "redis://:hunter%202@{}",
+                redis.reachable_addr(6379).unwrap()
+            )
+        );
+    });
+}
+
+#[test]
+fn test_discover_running_environment() {
+    let mut test = DockerTest::new()
+        .with_default_source(Source::DockerHub)
+        .with_namespace("discovered");
+
+    let sleep = vec!["sleep".to_string(), "60".to_string()];
+    let app = TestBodySpecification::with_repository("busybox")
+        .replace_cmd(sleep)
+        .set_wait_for(Box::new(RunningWait {
+            check_interval: 1,
+            max_checks: 10,
+        }));
+    test.provide_container(app);
+
+    test.run(|ops| async move {
+        let name = ops.handle("busybox").name().to_string();
+        let environments = dockertest::discover().await.unwrap();
+        let environment = environments
+            .iter()
+            .find(|e| e.containers().iter().any(|c| c.name() == name))
+            .expect("the environment of the test is discovered");
+        assert_eq!(environment.namespace(), "discovered");
+        assert!(environment.containers()[0].is_running());
+    });
+}
@@ -2,11 +2,15 @@
 #![deny(rust_2018_idioms)]
 
 mod annotation_test_runtime;
+mod health;
 mod helper;
 mod integration_test;
 mod message;
 mod multiple_containers;
 mod privileged_container;
 mod readme;
+mod reset;
+mod runner;
+mod secrets;
 mod static_containers;
 mod waitfor;
@@ -1,9 +1,12 @@
-use dockertest::waitfor::MessageSource;
+use dockertest::waitfor::{MessageSource, Occurrences};
 use dockertest::{DockerTest, TestBodySpecification};
 use test_log::test;
 
+use crate::helper::ensure_test_images;
+
 #[test]
 fn test_assert_message_in_test_body_succeeds() {
+    ensure_test_images();
     let mut test = DockerTest::new();
     let composition = TestBodySpecification::with_repository("dockertest-rs/hello");
     test.provide_container(composition);
@@ -19,6 +22,7 @@ fn test_assert_message_in_test_body_succeeds() {
 #[test]
 #[should_panic]
 fn test_assert_message_in_test_body_panics_not_present() {
+    ensure_test_images();
     let mut test = DockerTest::new();
     let composition = TestBodySpecification::with_repository("dockertest-rs/hello");
     test.provide_container(composition);
@@ -30,3 +34,43 @@ fn test_assert_message_in_test_body_panics_not_present() {
             .await;
     });
 }
+
+#[test]
+#[should_panic]
+fn test_assert_message_sequence_in_test_body_panics_on_count() {
+    ensure_test_images();
+    let mut test = DockerTest::new();
+    let composition = TestBodySpecification::with_repository("dockertest-rs/hello");
+    test.provide_container(composition);
+
+    test.run(|ops| async move {
+        let hello = ops.handle("dockertest-rs/hello");
+        hello
+            .assert_message_sequence(
+                &[Occurrences::times("hello dockertest-rs", 2)],
+                MessageSource::Stdout,
+                1,
+            )
+            .await;
+    });
+}
+
+#[test]
+fn test_expect_logs_in_test_body_succeeds() {
+    ensure_test_images();
+    let mut test = DockerTest::new();
+    let composition = TestBodySpecification::with_repository("dockertest-rs/hello");
+    test.provide_container(composition);
+
+    test.run(|ops| async move {
+        let hello = ops.handle("dockertest-rs/hello");
+        hello
+            .assert_message("hello dockertest-rs", MessageSource::Stdout, 5)
+            .await;
+        hello
+            .expect_logs()
+            .await
+            .contains("hello dockertest-rs")
+            .not_contains("ERROR");
+    });
+}
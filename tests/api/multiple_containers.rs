@@ -1,9 +1,12 @@
 use dockertest::waitfor::{MessageSource, MessageWait};
-use dockertest::{DockerTest, StartPolicy, TestBodySpecification};
+use dockertest::{DockerTest, Source, StartPolicy, TestBodySpecification};
 use test_log::test;
 
+use crate::helper::{ensure_test_images, TestHelper};
+
 #[test]
 fn test_inject_container_name_ip_through_env_communication() {
+    ensure_test_images();
     let mut test = DockerTest::new();
 
     let recv = TestBodySpecification::with_repository("dockertest-rs/coop_recv")
@@ -32,3 +35,152 @@ fn test_inject_container_name_ip_through_env_communication() {
             .await;
     });
 }
+
+// Tests that the network hostname of a container is the name injected into other containers
+#[test]
+fn test_network_hostname_matches_injected_container_name() {
+    ensure_test_images();
+    let test_helper = TestHelper::new();
+    let mut test = DockerTest::new();
+
+    let recv = TestBodySpecification::with_repository("dockertest-rs/coop_recv")
+        .set_start_policy(StartPolicy::Strict)
+        .set_wait_for(Box::new(MessageWait {
+            message: "recv started".to_string(),
+            source: MessageSource::Stdout,
+            timeout: 10,
+        }))
+        .set_handle("recv");
+
+    let mut send = TestBodySpecification::with_repository("dockertest-rs/coop_send")
+        .set_start_policy(StartPolicy::Strict)
+        .set_wait_for(Box::new(MessageWait {
+            message: "send success".to_string(),
+            source: MessageSource::Stdout,
+            timeout: 60,
+        }));
+    send.inject_container_name("recv", "SEND_TO_IP");
+
+    test.provide_container(recv).provide_container(send);
+
+    test.run(|ops| async move {
+        let recv = ops.handle("recv");
+        let send = ops.handle("dockertest-rs/coop_send");
+        assert_eq!(
+            test_helper.env_value(send, "SEND_TO_IP").await.unwrap(),
+            recv.network_hostname()
+        );
+    });
+}
+
+// Tests that the network hostname of a container with a network alias is the alias
+#[test]
+fn test_network_hostname_is_network_alias() {
+    ensure_test_images();
+    let mut test = DockerTest::new();
+
+    let recv = TestBodySpecification::with_repository("dockertest-rs/coop_recv")
+        .set_wait_for(Box::new(MessageWait {
+            message: "recv started".to_string(),
+            source: MessageSource::Stdout,
+            timeout: 10,
+        }))
+        .set_handle("recv")
+        .replace_network_alias(vec!["receiver".to_string()]);
+    test.provide_container(recv);
+
+    test.run(|ops| async move {
+        assert_eq!(ops.handle("recv").network_hostname(), "receiver");
+    });
+}
+
+// Tests that relaxed containers start with a bounded start concurrency
+#[test]
+fn test_relaxed_containers_with_start_concurrency() {
+    let mut test = DockerTest::new()
+        .with_default_source(Source::DockerHub)
+        .with_start_concurrency(1);
+
+    for handle in ["first", "second", "third"] {
+        let hello_world = TestBodySpecification::with_repository("hello-world")
+            .set_start_policy(StartPolicy::Relaxed)
+            .set_handle(handle);
+        test.provide_container(hello_world);
+    }
+
+    test.run(|ops| async move {
+        ops.handle("first");
+        ops.handle("second");
+        ops.handle("third");
+    });
+}
+
+// Tests that a start group starts once every container of the previous group is ready
+#[test]
+fn test_start_groups_start_in_order() {
+    ensure_test_images();
+    let mut test = DockerTest::new();
+
+    let recv = TestBodySpecification::with_repository("dockertest-rs/coop_recv")
+        .set_start_policy(StartPolicy::Group("infrastructure".to_string(), 0))
+        .set_wait_for(Box::new(MessageWait {
+            message: "recv started".to_string(),
+            source: MessageSource::Stdout,
+            timeout: 10,
+        }))
+        .set_handle("recv");
+
+    let mut send = TestBodySpecification::with_repository("dockertest-rs/coop_send")
+        .set_start_policy(StartPolicy::Group("application".to_string(), 1))
+        .set_wait_for(Box::new(MessageWait {
+            message: "send success".to_string(),
+            source: MessageSource::Stdout,
+            timeout: 60,
+        }));
+    send.inject_container_name("recv", "SEND_TO_IP");
+
+    // The application group is provided first, but started last.
+    test.provide_container(send).provide_container(recv);
+
+    test.run(|ops| async move {
+        let recv = ops.handle("recv");
+        recv.assert_message("coop send message to container", MessageSource::Stdout, 5)
+            .await;
+    });
+}
+
+// Tests that containers are iterated in the order provided, regardless of their start order
+#[test]
+fn test_containers_in_provided_order() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+
+    for handle in ["broker-c", "broker-a", "broker-b"] {
+        let hello_world = TestBodySpecification::with_repository("hello-world")
+            .set_start_policy(StartPolicy::Relaxed)
+            .set_handle(handle);
+        test.provide_container(hello_world);
+    }
+
+    test.run(|ops| async move {
+        let handles: Vec<&str> = ops.containers().map(|c| c.handle()).collect();
+        assert_eq!(handles, vec!["broker-c", "broker-a", "broker-b"]);
+        assert_eq!(ops.container(1).handle(), "broker-a");
+        let indexed: Vec<&str> = (0..3).map(|i| ops.container(i).handle()).collect();
+        assert_eq!(indexed, handles);
+    });
+}
+
+// Tests that retrieving a container beyond the running containers fails the test
+#[test]
+#[should_panic]
+fn test_container_index_out_of_range() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    test.provide_container(
+        TestBodySpecification::with_repository("hello-world")
+            .set_start_policy(StartPolicy::Relaxed),
+    );
+
+    test.run(|ops| async move {
+        ops.container(1);
+    });
+}
@@ -2,6 +2,7 @@ use dockertest::{DockerTest, TestBodySpecification};
 
 #[tokio::test]
 async fn test_privileged_container() {
+    dockertest::selftest_images::ensure().await.unwrap();
     let mut test = DockerTest::new();
     let mut hello_world = TestBodySpecification::with_repository("dockertest-rs/hello-privileged");
 
@@ -0,0 +1,34 @@
+use dockertest::reset::RedisReset;
+use dockertest::{DockerTest, Source, TestSuiteSpecification};
+
+// A test sharing the redis container of this test binary, which is reset before the test body.
+fn redis_test() -> DockerTest {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    let redis = TestSuiteSpecification::with_repository("redis")
+        .set_handle("resettable-redis")
+        .set_reset(Box::new(RedisReset {}));
+    test.provide_container(redis);
+    test
+}
+
+// Tests that the state written by a test is reset before the next test body
+#[tokio::test]
+async fn test_redis_reset_between_test_bodies() {
+    redis_test()
+        .run_async(|ops| async move {
+            let redis = ops.handle("resettable-redis");
+            redis
+                .exec(&["redis-cli", "SET", "key", "value"])
+                .await
+                .unwrap();
+        })
+        .await;
+
+    redis_test()
+        .run_async(|ops| async move {
+            let redis = ops.handle("resettable-redis");
+            let value = redis.exec(&["redis-cli", "GET", "key"]).await.unwrap();
+            assert_eq!(value.trim(), "", "the key should have been reset");
+        })
+        .await;
+}
@@ -0,0 +1,482 @@
+use dockertest::utils::{connect_with_local_or_tls_defaults, generate_random_string};
+use dockertest::waitfor::{ExitedWait, MessageSource, MessageWait, RunningWait};
+use dockertest::{
+    DockerTest, DockerTestError, ExistingContainerPolicy, LogAction, LogOptions, LogPolicy,
+    LogSource, Runner, Source, StartPolicy, TestBodySpecification, TrafficShaping,
+};
+
+use bollard::container::{ListContainersOptions, RemoveContainerOptions};
+use bollard::Docker;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Tests that the environment lifetime can be managed through the Runner
+#[tokio::test]
+async fn test_runner_setup_and_teardown() {
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new().with_default_source(source);
+
+    let hello_world = TestBodySpecification::with_repository("hello-world");
+    test.provide_container(hello_world);
+
+    let mut runner = Runner::try_new(test).await.unwrap();
+    let ops = runner.setup().await.unwrap();
+    ops.handle("hello-world");
+
+    // The environment can only be set up once
+    assert!(runner.setup().await.is_err());
+
+    runner.teardown(false).await;
+}
+
+// A test whose container name is identical across instances.
+fn fixed_name_test(namespace: &str, policy: ExistingContainerPolicy) -> DockerTest {
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new()
+        .with_default_source(source)
+        .with_namespace(namespace)
+        .with_id_generator(|| "fixed".to_string());
+
+    let hello_world =
+        TestBodySpecification::with_repository("hello-world").set_existing_container_policy(policy);
+    test.provide_container(hello_world);
+    test
+}
+
+// Tests that an existing container with the same name fails the setup with the Fail policy
+#[tokio::test]
+async fn test_existing_container_policy_fail() {
+    let namespace = "dockertest-rs-existing-fail";
+    let test = fixed_name_test(namespace, ExistingContainerPolicy::Remove);
+    let mut existing = Runner::try_new(test).await.unwrap();
+    existing.setup().await.unwrap();
+
+    let test = fixed_name_test(namespace, ExistingContainerPolicy::Fail);
+    let mut runner = Runner::try_new(test).await.unwrap();
+    let result = runner.setup().await;
+    runner.teardown(false).await;
+    existing.teardown(false).await;
+
+    let err = result.err().expect("setup should fail").to_string();
+    assert!(err.contains("already exists"), "{}", err);
+}
+
+// Tests that an existing container with the same name is used, and left behind, with the Reuse
+// policy
+#[tokio::test]
+async fn test_existing_container_policy_reuse() {
+    let namespace = "dockertest-rs-existing-reuse";
+    let test = fixed_name_test(namespace, ExistingContainerPolicy::Remove);
+    let mut existing = Runner::try_new(test).await.unwrap();
+    let existing_id = existing
+        .setup()
+        .await
+        .unwrap()
+        .handle("hello-world")
+        .id()
+        .to_string();
+
+    let test = fixed_name_test(namespace, ExistingContainerPolicy::Reuse);
+    let mut runner = Runner::try_new(test).await.unwrap();
+    let reused_id = runner
+        .setup()
+        .await
+        .unwrap()
+        .handle("hello-world")
+        .id()
+        .to_string();
+    runner.teardown(false).await;
+
+    // The reused container outlives the test reusing it
+    let client = connect_with_local_or_tls_defaults().unwrap();
+    let outlived = client.inspect_container(&reused_id, None).await.is_ok();
+    existing.teardown(false).await;
+
+    assert_eq!(existing_id, reused_id);
+    assert!(outlived, "the reused container should not be removed");
+}
+
+// A test whose container is taken from a warm pool unique to this test.
+fn pooled_test(pool: &str) -> DockerTest {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    let mut hello_world = TestBodySpecification::with_repository("hello-world").set_warm_pool(1);
+    hello_world.modify_env("POOL", pool);
+    test.provide_container(hello_world);
+    test
+}
+
+// The ids of the containers still pooled with the given environment.
+async fn pooled_containers(client: &Docker, pool: &str) -> Vec<String> {
+    let env = format!("POOL={}", pool);
+    let options = ListContainersOptions {
+        all: true,
+        filters: HashMap::from([
+            ("label", vec!["dockertest.pool"]),
+            ("status", vec!["created"]),
+        ]),
+        ..Default::default()
+    };
+    let mut pooled = Vec::new();
+    for c in client.list_containers(Some(options)).await.unwrap() {
+        let id = c.id.unwrap();
+        let details = client.inspect_container(&id, None).await.unwrap();
+        if details
+            .config
+            .unwrap()
+            .env
+            .unwrap_or_default()
+            .contains(&env)
+        {
+            pooled.push(id);
+        }
+    }
+    pooled
+}
+
+// Tests that a container is taken from its warm pool once the pool is refilled
+#[tokio::test]
+async fn test_warm_pool_provides_created_container() {
+    let client = connect_with_local_or_tls_defaults().unwrap();
+    let pool = generate_random_string(20);
+
+    let mut runner = Runner::try_new(pooled_test(&pool)).await.unwrap();
+    runner.setup().await.unwrap();
+    runner.teardown(false).await;
+
+    let mut pooled = Vec::new();
+    for _ in 0..30 {
+        pooled = pooled_containers(&client, &pool).await;
+        if !pooled.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    assert_eq!(pooled.len(), 1, "the pool should be refilled");
+
+    let mut runner = Runner::try_new(pooled_test(&pool)).await.unwrap();
+    let ops = runner.setup().await.unwrap();
+    assert_eq!(ops.handle("hello-world").id(), pooled[0]);
+    runner.teardown(false).await;
+
+    // Remove the containers of the pool refilled by the last test.
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    for id in pooled_containers(&client, &pool).await {
+        let options = RemoveContainerOptions {
+            force: true,
+            ..Default::default()
+        };
+        client.remove_container(&id, Some(options)).await.unwrap();
+    }
+}
+
+// An alpine container running the given shell script.
+fn alpine(handle: &str, script: &str) -> TestBodySpecification {
+    TestBodySpecification::with_repository("alpine")
+        .replace_cmd(vec![
+            "/bin/sh".to_string(),
+            "-c".to_string(),
+            script.to_string(),
+        ])
+        .set_start_policy(StartPolicy::Strict)
+        .set_handle(handle)
+}
+
+// Tests that a container dying after becoming ready fails the liveness check with its logs
+#[tokio::test]
+async fn test_liveness_check_fails_on_died_container() {
+    let mut test = DockerTest::new()
+        .with_default_source(Source::DockerHub)
+        .with_liveness_check();
+
+    let crashing = alpine("crashing", "echo crashing && sleep 1 && exit 3").set_wait_for(Box::new(
+        RunningWait {
+            max_checks: 10,
+            check_interval: 1,
+        },
+    ));
+    let slow =
+        alpine("slow", "sleep 3 && echo ready && sleep 3600").set_wait_for(Box::new(MessageWait {
+            message: "ready".to_string(),
+            source: MessageSource::Stdout,
+            timeout: 10,
+        }));
+    test.provide_container(crashing).provide_container(slow);
+
+    let mut runner = Runner::try_new(test).await.unwrap();
+    let result = runner.setup().await;
+    assert!(
+        matches!(result, Err(DockerTestError::Startup(ref msg)) if msg.contains("`crashing` died") && msg.contains("exit code 3") && msg.contains("crashing\n")),
+        "the liveness check should fail on the crashed container, got: {:?}",
+        result.map(|_| ())
+    );
+}
+
+// Tests that the started callback wires the container before the test body
+#[tokio::test]
+async fn test_on_started_callback() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    let broker = alpine("broker", "echo ready && sleep 3600")
+        .set_wait_for(Box::new(MessageWait {
+            message: "ready".to_string(),
+            source: MessageSource::Stdout,
+            timeout: 10,
+        }))
+        .set_on_started(|container| async move {
+            container.exec(&["touch", "/tmp/topic"]).await.map(|_| ())
+        });
+    test.provide_container(broker);
+
+    let mut runner = Runner::try_new(test).await.unwrap();
+    let ops = runner.setup().await.unwrap();
+    let broker = ops.handle("broker");
+    assert!(broker.exec(&["test", "-f", "/tmp/topic"]).await.is_ok());
+
+    runner.teardown(false).await;
+}
+
+// Tests that a failing started callback fails the startup of the container
+#[tokio::test]
+async fn test_on_started_callback_fails_startup() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    let broker = alpine("broker", "echo ready && sleep 3600")
+        .set_wait_for(Box::new(MessageWait {
+            message: "ready".to_string(),
+            source: MessageSource::Stdout,
+            timeout: 10,
+        }))
+        .set_on_started(|_container| async move {
+            Err(DockerTestError::Startup("no topics".to_string()))
+        });
+    test.provide_container(broker);
+
+    let mut runner = Runner::try_new(test).await.unwrap();
+    let result = runner.setup().await;
+    assert!(
+        matches!(result, Err(DockerTestError::Startup(ref msg)) if msg.contains("callback of container `broker`") && msg.contains("no topics")),
+        "the failing callback should fail the startup, got: {:?}",
+        result.map(|_| ())
+    );
+}
+
+// Tests that the teardown reports the final state of every container without stopping it
+#[tokio::test]
+async fn test_teardown_report() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    let running =
+        alpine("running", "echo ready && sleep 3600").set_wait_for(Box::new(MessageWait {
+            message: "ready".to_string(),
+            source: MessageSource::Stdout,
+            timeout: 10,
+        }));
+    let failed = alpine("failed", "exit 3").set_wait_for(Box::new(ExitedWait {
+        max_checks: 10,
+        check_interval: 1,
+    }));
+    test.provide_container(running).provide_container(failed);
+
+    let mut runner = Runner::try_new(test).await.unwrap();
+    runner.setup().await.unwrap();
+    let report = runner.teardown(false).await;
+
+    let running = report.container("running").unwrap();
+    assert!(running.running);
+    assert!(running.is_clean());
+    let failed = report.container("failed").unwrap();
+    assert_eq!(failed.exit_code, Some(3));
+    assert!(failed.finished_at.is_some());
+    assert!(!report.is_clean());
+}
+
+// Tests that the teardown reports how the containers shut down once stopped gracefully
+#[tokio::test]
+async fn test_teardown_report_graceful_stop() {
+    let mut test = DockerTest::new()
+        .with_default_source(Source::DockerHub)
+        .with_graceful_stop(5);
+    let graceful = alpine(
+        "graceful",
+        "trap 'exit 0' TERM; echo ready; sleep 3600 & wait",
+    )
+    .set_wait_for(Box::new(MessageWait {
+        message: "ready".to_string(),
+        source: MessageSource::Stdout,
+        timeout: 10,
+    }));
+    test.provide_container(graceful);
+
+    let mut runner = Runner::try_new(test).await.unwrap();
+    runner.setup().await.unwrap();
+    let report = runner.teardown(false).await;
+
+    let graceful = report.container("graceful").unwrap();
+    assert!(!graceful.running);
+    assert_eq!(graceful.exit_code, Some(0));
+    assert!(report.is_clean());
+}
+
+// Tests that the teardown report is returned by the test run
+#[test]
+fn test_run_with_report() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    let failed = alpine("failed", "exit 3").set_wait_for(Box::new(ExitedWait {
+        max_checks: 10,
+        check_interval: 1,
+    }));
+    test.provide_container(failed);
+
+    let report = test.run_with_report(|_ops| async {});
+
+    assert_eq!(report.container("failed").unwrap().exit_code, Some(3));
+    assert!(!report.is_clean());
+}
+
+// Tests that the traffic of a container is shaped through a helper container
+#[tokio::test]
+async fn test_shape_traffic() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    let server = alpine("server", "echo ready && sleep 3600").set_wait_for(Box::new(MessageWait {
+        message: "ready".to_string(),
+        source: MessageSource::Stdout,
+        timeout: 10,
+    }));
+    test.provide_container(server);
+
+    let mut runner = Runner::try_new(test).await.unwrap();
+    let ops = runner.setup().await.unwrap();
+
+    let shaping = TrafficShaping {
+        latency: Duration::from_millis(200),
+        loss_percent: 1.0,
+        ..Default::default()
+    };
+    let shaped = ops.shape_traffic("server", shaping).await;
+    let missing = TrafficShaping {
+        interface: Some("missing0".to_string()),
+        ..Default::default()
+    };
+    let failed = ops.shape_traffic("server", missing).await;
+    runner.teardown(false).await;
+
+    shaped.unwrap();
+    assert!(
+        matches!(failed, Err(DockerTestError::Daemon(ref msg)) if msg.contains("failed to shape traffic of container `server`")),
+        "shaping a missing interface should fail, got: {:?}",
+        failed
+    );
+}
+
+// Tests that followed logs are written to the file while the container runs
+#[tokio::test]
+async fn test_follow_logs_to_file() {
+    let dir = std::env::temp_dir().join(format!("dockertest-follow-{}", generate_random_string(8)));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    let chatty = alpine(
+        "chatty",
+        "echo first && sleep 1 && echo second && sleep 3600",
+    )
+    .set_wait_for(Box::new(MessageWait {
+        message: "first".to_string(),
+        source: MessageSource::Stdout,
+        timeout: 10,
+    }))
+    .set_log_options(Some(LogOptions {
+        action: LogAction::ForwardToFile {
+            path: dir.display().to_string(),
+        },
+        policy: LogPolicy::Follow,
+        source: LogSource::StdOut,
+    }));
+    test.provide_container(chatty);
+
+    let mut runner = Runner::try_new(test).await.unwrap();
+    let ops = runner.setup().await.unwrap();
+    let path = dir.join(ops.handle("chatty").name());
+
+    let mut logs = String::new();
+    for _ in 0..30 {
+        logs = std::fs::read_to_string(&path).unwrap_or_default();
+        if logs.contains("second") {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    runner.teardown(false).await;
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(logs, "first\nsecond\n");
+}
+
+// A liveness checked test of a one-shot job and a server becoming ready after the job exited.
+fn job_test(expect_exit: bool) -> DockerTest {
+    let mut test = DockerTest::new()
+        .with_default_source(Source::DockerHub)
+        .with_liveness_check();
+    let mut job = alpine("job", "echo migrated").set_start_policy(StartPolicy::Relaxed);
+    if expect_exit {
+        job = job.set_expect_exit();
+    }
+    let server = alpine("server", "sleep 2 && echo ready && sleep 3600").set_wait_for(Box::new(
+        MessageWait {
+            message: "ready".to_string(),
+            source: MessageSource::Stdout,
+            timeout: 10,
+        },
+    ));
+    test.provide_container(job).provide_container(server);
+    test
+}
+
+// Tests that only the exit of containers not expected to exit fails the liveness check
+#[tokio::test]
+async fn test_liveness_check_expected_exit() {
+    let mut runner = Runner::try_new(job_test(true)).await.unwrap();
+    let result = runner.setup().await;
+    runner.teardown(false).await;
+    assert!(
+        result.is_ok(),
+        "the expected exit should pass: {:?}",
+        result.map(|_| ())
+    );
+
+    let mut runner = Runner::try_new(job_test(false)).await.unwrap();
+    let result = runner.setup().await;
+    assert!(
+        matches!(result, Err(DockerTestError::Startup(ref msg)) if msg.contains("`job` died") && msg.contains("exit code 0")),
+        "the unexpected exit should fail the liveness check, got: {:?}",
+        result.map(|_| ())
+    );
+}
+
+// Tests that tests holding the same exclusive lock are serialized, and the wait is reported
+#[tokio::test]
+async fn test_exclusive_lock_serializes_tests() {
+    let lock = format!("dockertest-rs-{}", generate_random_string(10));
+    let locked_test = |lock: &str| {
+        let mut test = DockerTest::new()
+            .with_default_source(Source::DockerHub)
+            .with_exclusive_lock(lock);
+        test.provide_container(TestBodySpecification::with_repository("hello-world"));
+        test
+    };
+
+    let mut holder = Runner::try_new(locked_test(&lock)).await.unwrap();
+    holder.setup().await.unwrap();
+
+    let mut contender = Runner::try_new(locked_test(&lock)).await.unwrap();
+    let contended = tokio::spawn(async move {
+        let ops = contender.setup().await.unwrap();
+        let report: serde_json::Value = serde_json::from_str(ops.report()).unwrap();
+        contender.teardown(false).await;
+        report
+    });
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    assert!(!contended.is_finished(), "the lock should still be held");
+
+    holder.teardown(false).await;
+    let report = contended.await.unwrap();
+    assert!(report["lock_wait_ms"].as_u64().unwrap() >= 2000);
+    assert!(report["startup_ms"].is_u64());
+}
@@ -0,0 +1,31 @@
+use dockertest::utils::connect_with_local_or_tls_defaults;
+use dockertest::{DockerTest, Source, TestBodySpecification};
+
+use bollard::container::InspectContainerOptions;
+
+// Tests that a secret is provided as a file, and not through the environment of the container
+#[tokio::test]
+async fn test_secret_provided_as_file() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    let mut redis = TestBodySpecification::with_repository("redis");
+    redis.modify_secret("token", "hunter2");
+    test.provide_container(redis);
+
+    test.run_async(|ops| async move {
+        let redis = ops.handle("redis");
+        let token = redis.exec(&["cat", "/run/secrets/token"]).await.unwrap();
+        assert_eq!(token, "hunter2");
+
+        let client = connect_with_local_or_tls_defaults().unwrap();
+        let details = client
+            .inspect_container(redis.id(), None::<InspectContainerOptions>)
+            .await
+            .unwrap();
+        let inspected = serde_json::to_string(&details).unwrap();
+        assert!(
+            !inspected.contains("hunter2"),
+            "the secret should not be visible to inspect"
+        );
+    })
+    .await;
+}
@@ -2,8 +2,10 @@
 
 use dockertest::{
     utils::{connect_with_local_or_tls_defaults, generate_random_string},
-    DockerTest, DynamicSpecification, ExternalSpecification, Network, Source,
-    TestBodySpecification, TestSuiteSpecification,
+    waitfor::{async_trait, WaitFor},
+    DockerTest, DockerTestError, DynamicSpecification, ExternalSpecification, Network,
+    PendingContainer, Runner, RunningContainer, Source, StartPolicy, TestBodySpecification,
+    TestSuiteSpecification,
 };
 
 use bollard::container::{Config, CreateContainerOptions, StartContainerOptions};
@@ -34,6 +36,7 @@ fn test_static_containers_runs() {
 
 #[tokio::test]
 async fn test_external_static_container_handle_resolves_correctly_mixed_with_others() {
+    dockertest::selftest_images::ensure().await.unwrap();
     let source = Source::DockerHub;
     let mut test = DockerTest::new().with_default_source(source);
     let repo = "hello-world";
@@ -151,6 +154,76 @@ fn test_multiple_internal_containers_with_singular_network() {
     });
 }
 
+// Tests that a panicking test body does not affect the other tests using the static container
+#[test]
+fn test_static_container_isolated_from_panicking_test() {
+    let repo = "hello-world".to_string();
+    let handle = "isolated-from-panic";
+
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    test.provide_container(TestSuiteSpecification::with_repository(&repo).set_handle(handle));
+    let panicking = std::thread::spawn(move || {
+        test.run(|_ops| async move {
+            panic!("test body failure");
+        });
+    });
+    assert!(panicking.join().is_err());
+
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    test.provide_container(TestSuiteSpecification::with_repository(&repo).set_handle(handle));
+    test.run(|ops| async move {
+        ops.handle(handle);
+    });
+}
+
+// Tests that a panicking wait condition of a static container fails the other tests using the
+// static container with an error, instead of leaving them waiting on it
+#[test]
+fn test_static_container_isolated_from_panicking_wait_for() {
+    let repo = "hello-world".to_string();
+    let handle = "isolated-from-panicking-wait-for";
+
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    test.provide_container(
+        TestSuiteSpecification::with_repository(&repo)
+            .set_handle(handle)
+            .set_start_policy(StartPolicy::Relaxed)
+            .set_wait_for(Box::new(PanicWait {})),
+    );
+    let panicking = std::thread::spawn(move || {
+        test.run(|_ops| async move {});
+    });
+    assert!(panicking.join().is_err());
+
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+    test.provide_container(TestSuiteSpecification::with_repository(&repo).set_handle(handle));
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let result = runtime.block_on(async {
+        let mut runner = Runner::try_new(test).await?;
+        let result = runner.setup().await.map(|_| ());
+        runner.teardown(result.is_err()).await;
+        result
+    });
+    assert!(
+        matches!(&result, Err(e) if e.to_string().contains("panicked")),
+        "the static container should be recorded as failed, got: {:?}",
+        result
+    );
+}
+
+#[derive(Clone, Debug)]
+struct PanicWait {}
+
+#[async_trait]
+impl WaitFor for PanicWait {
+    async fn wait_for_ready(
+        &self,
+        _container: PendingContainer,
+    ) -> Result<RunningContainer, DockerTestError> {
+        panic!("this PanicWait shall panic");
+    }
+}
+
 #[derive(Debug)]
 struct ContainerName {
     name: Arc<Mutex<Option<String>>>,
@@ -1,6 +1,7 @@
 use dockertest::utils::connect_with_local_or_tls_defaults;
 use dockertest::waitfor::{
-    async_trait, ExitedWait, MessageSource, MessageWait, RunningWait, WaitFor,
+    async_trait, ExecWait, ExitedWait, HostPortWait, MarkerFileWait, MessageSequenceWait,
+    MessageSource, MessageWait, Occurrences, RunningWait, WaitFor, MARKER_FILE,
 };
 use dockertest::{
     DockerTest, DockerTestError, PendingContainer, RunningContainer, Source, StartPolicy,
@@ -11,6 +12,10 @@ use bollard::container::InspectContainerOptions;
 use futures::future::TryFutureExt;
 use test_log::test;
 
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
 #[derive(Clone, Debug)]
 struct FailWait {}
 
@@ -26,6 +31,26 @@ impl WaitFor for FailWait {
     }
 }
 
+/// Sleeps before failing, recording whether it completed.
+#[derive(Clone, Debug)]
+struct SlowWait {
+    completed: Arc<AtomicBool>,
+}
+
+#[async_trait]
+impl WaitFor for SlowWait {
+    async fn wait_for_ready(
+        &self,
+        _container: PendingContainer,
+    ) -> Result<RunningContainer, DockerTestError> {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        self.completed.store(true, Ordering::SeqCst);
+        Err(DockerTestError::Processing(
+            "this SlowWait shall fail".to_string(),
+        ))
+    }
+}
+
 /// Returns whether the container is in a running state.
 pub async fn is_running(id: String) -> Result<bool, DockerTestError> {
     let client = connect_with_local_or_tls_defaults()?;
@@ -152,6 +177,195 @@ fn test_message_wait_for_success_on_stdout() {
     });
 }
 
+// Tests that the HostPortWait implementation waits for the published host port to accept
+// connections.
+#[test]
+fn test_host_port_wait_for() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+
+    let mut server = TestBodySpecification::with_repository("busybox")
+        .replace_cmd(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "sleep 2 && httpd -f -p 8080".to_string(),
+        ])
+        .set_wait_for(Box::new(HostPortWait {
+            port: 8080,
+            check_interval: 1,
+            max_checks: 30,
+        }));
+    server.publish_port(8080);
+    test.provide_container(server);
+
+    test.run(|ops| async move {
+        let (ip, port) = *ops.handle("busybox").host_port(8080).unwrap();
+        tokio::net::TcpStream::connect((ip, port as u16))
+            .await
+            .expect("published port should accept connections once ready");
+    });
+}
+
+// Tests that the ExecWait implementation waits for the command to succeed within the container.
+#[test]
+fn test_exec_wait_for() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+
+    let probe = TestBodySpecification::with_repository("busybox")
+        .replace_cmd(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "sleep 2 && touch /ready && sleep 60".to_string(),
+        ])
+        .set_wait_for(Box::new(ExecWait {
+            cmd: vec!["test".to_string(), "-f".to_string(), "/ready".to_string()],
+            check_interval: 1,
+            max_checks: 30,
+        }));
+    test.provide_container(probe);
+
+    test.run(|ops| async move {
+        ops.handle("busybox")
+            .exec(&["test", "-f", "/ready"])
+            .await
+            .unwrap();
+    });
+}
+
+// Tests that the MarkerFileWait implementation waits for the marker written by the container.
+#[test]
+fn test_marker_file_wait_for() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+
+    let marker = TestBodySpecification::with_repository("busybox")
+        .replace_cmd(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            format!("sleep 2 && touch {} && sleep 60", MARKER_FILE),
+        ])
+        .set_wait_for(Box::new(MarkerFileWait {
+            path: MARKER_FILE.to_string(),
+            check_interval: 1,
+            max_checks: 30,
+        }));
+    test.provide_container(marker);
+
+    test.run(|ops| async move {
+        let mounts = ops
+            .handle("busybox")
+            .exec(&["grep", " /tmp ", "/proc/mounts"])
+            .await
+            .unwrap();
+        assert!(mounts.contains("tmpfs"), "{}", mounts);
+    });
+}
+
+// Tests that the WaitFor implementations are reusable within the test body, awaiting the container
+// once restarted.
+#[test]
+fn test_wait_for_in_test_body() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+
+    let probe = TestBodySpecification::with_repository("busybox")
+        .replace_cmd(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "sleep 2 && touch /ready && sleep 60".to_string(),
+        ])
+        .set_wait_for(Box::new(RunningWait {
+            check_interval: 1,
+            max_checks: 10,
+        }));
+    test.provide_container(probe);
+
+    test.run(|ops| async move {
+        // The marker of the run preceding the restart must not satisfy the wait.
+        ops.handle("busybox")
+            .exec(&["rm", "-f", "/ready"])
+            .await
+            .unwrap();
+        let client = connect_with_local_or_tls_defaults().unwrap();
+        client
+            .restart_container(ops.handle("busybox").name(), None)
+            .await
+            .unwrap();
+
+        let wait = ExecWait {
+            cmd: vec!["test".to_string(), "-f".to_string(), "/ready".to_string()],
+            check_interval: 1,
+            max_checks: 30,
+        };
+        let container = ops.wait_for("busybox", wait).await.unwrap();
+        assert_eq!(container.handle(), "busybox");
+    });
+}
+
+// Tests that the MessageWait implementation only considers the logs of the current run of a
+// container awaited within the test body.
+#[test]
+fn test_message_wait_for_in_test_body_after_restart() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+
+    let probe = TestBodySpecification::with_repository("busybox")
+        .replace_cmd(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "if [ -f /restarted ]; then echo second run; else touch /restarted; echo first run; fi; \
+             sleep 60"
+                .to_string(),
+        ])
+        .set_wait_for(Box::new(MessageWait {
+            message: "first run".to_string(),
+            source: MessageSource::Stdout,
+            timeout: 10,
+        }));
+    test.provide_container(probe);
+
+    test.run(|ops| async move {
+        let client = connect_with_local_or_tls_defaults().unwrap();
+        client
+            .restart_container(ops.handle("busybox").name(), None)
+            .await
+            .unwrap();
+
+        let stale = MessageWait {
+            message: "first run".to_string(),
+            source: MessageSource::Stdout,
+            timeout: 3,
+        };
+        assert!(ops.wait_for("busybox", stale).await.is_err());
+
+        let current = MessageWait {
+            message: "second run".to_string(),
+            source: MessageSource::Stdout,
+            timeout: 10,
+        };
+        ops.wait_for("busybox", current).await.unwrap();
+    });
+}
+
+// Tests that the output of the last failed probe of the ExecWait implementation is part of the
+// failure.
+#[test]
+#[should_panic(expected = "exit code 3, stderr: connection refused")]
+fn test_exec_wait_for_failure_includes_output() {
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+
+    let probe = TestBodySpecification::with_repository("busybox")
+        .replace_cmd(vec!["sleep".to_string(), "60".to_string()])
+        .set_wait_for(Box::new(ExecWait {
+            cmd: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo connection refused >&2; exit 3".to_string(),
+            ],
+            check_interval: 1,
+            max_checks: 2,
+        }));
+    test.provide_container(probe);
+
+    test.run(|_| async {});
+}
+
 // Tests that the MessageWait implementation fails test when message does not occur.
 #[test]
 #[should_panic]
@@ -173,3 +387,97 @@ fn test_message_wait_for_not_found_on_stream() {
         ops.handle("hello-world");
     });
 }
+
+// Tests that the MessageSequenceWait implementation waits for messages to occur in order
+#[test]
+fn test_message_sequence_wait_for_success_on_stdout() {
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new().with_default_source(source);
+
+    let repo = "hello-world";
+    let hello_container =
+        TestBodySpecification::with_repository(repo).set_wait_for(Box::new(MessageSequenceWait {
+            messages: vec![
+                Occurrences::once("Hello from Docker!"),
+                Occurrences::times("The Docker", 2),
+                Occurrences::once("Share images"),
+            ],
+            source: MessageSource::Stdout,
+            timeout: 5,
+        }));
+
+    test.provide_container(hello_container);
+
+    test.run(|ops| async move {
+        ops.handle("hello-world");
+    });
+}
+
+// Tests that the MessageSequenceWait implementation fails test when messages occur out of order.
+#[test]
+#[should_panic]
+fn test_message_sequence_wait_for_out_of_order() {
+    let source = Source::DockerHub;
+    let mut test = DockerTest::new().with_default_source(source);
+
+    let repo = "hello-world";
+    let hello_container =
+        TestBodySpecification::with_repository(repo).set_wait_for(Box::new(MessageSequenceWait {
+            messages: vec![
+                Occurrences::once("Share images"),
+                Occurrences::once("Hello from Docker!"),
+            ],
+            source: MessageSource::Stdout,
+            timeout: 5,
+        }));
+
+    test.provide_container(hello_container);
+
+    test.run(|ops| async move {
+        ops.handle("hello-world");
+    });
+}
+
+// Tests that relaxed containers still starting are stopped once a strict container fails to start.
+#[tokio::test(flavor = "multi_thread")]
+async fn test_relaxed_containers_stopped_on_strict_failure() {
+    let completed = Arc::new(AtomicBool::new(false));
+    let mut test = DockerTest::new().with_default_source(Source::DockerHub);
+
+    let relaxed = TestBodySpecification::with_repository("luca3m/sleep")
+        .set_start_policy(StartPolicy::Relaxed)
+        .set_wait_for(Box::new(SlowWait {
+            completed: completed.clone(),
+        }));
+    let strict = TestBodySpecification::with_repository("hello-world")
+        .set_start_policy(StartPolicy::Strict)
+        .set_wait_for(Box::new(FailWait {}));
+    test.provide_container(relaxed).provide_container(strict);
+
+    // The test runtime outlives the environment, and would keep executing the relaxed startup.
+    let result = tokio::spawn(test.run_async(|_ops| async {})).await;
+    assert!(result.is_err(), "strict container should fail to start");
+
+    tokio::time::sleep(Duration::from_secs(6)).await;
+    assert!(
+        !completed.load(Ordering::SeqCst),
+        "relaxed container startup continued after the startup was abandoned"
+    );
+}
+
+// Tests that containers of recognized images await their readiness without a configured WaitFor.
+#[test]
+fn test_smart_defaults_wait_for() {
+    let mut test = DockerTest::new()
+        .with_default_source(Source::DockerHub)
+        .with_smart_defaults(true);
+
+    test.provide_container(TestBodySpecification::with_repository("redis"));
+
+    test.run(|ops| async move {
+        ops.handle("redis")
+            .expect_logs()
+            .await
+            .contains("Ready to accept connections");
+    });
+}